@@ -0,0 +1,47 @@
+//! CAN identifier assignment by message priority.
+//!
+//! CAN arbitration is won by the lowest identifier, so the identifier carries the priority of the
+//! message in its top three bits and the sending node in the low eight:
+//!
+//! | Priority    | IDs             | Messages                                  |
+//! |-------------|-----------------|-------------------------------------------|
+//! | `Command`   | `0x000`-`0x0FF` | Commands, e.g. deployment or power down   |
+//! | `State`     | `0x100`-`0x1FF` | Flight state changes                      |
+//! | `Sensor`    | `0x200`-`0x2FF` | Sensor data                               |
+//! | `Bulk`      | `0x300`-`0x3FF` | Logs and anything else                    |
+//!
+//! `0x400`-`0x7FF` is left for diagnostics such as the CAN echo test, which should never delay
+//! flight traffic.
+
+use messages::{Data, Message};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, defmt::Format)]
+pub enum CanPriority {
+    Command = 0,
+    State = 1,
+    Sensor = 2,
+    Bulk = 3,
+}
+
+impl CanPriority {
+    /// The priority a message is sent with.
+    pub fn of(message: &Message) -> Self {
+        match message.data {
+            Data::Command(_) => CanPriority::Command,
+            Data::State(_) => CanPriority::State,
+            Data::Sensor(_) => CanPriority::Sensor,
+            #[allow(unreachable_patterns)]
+            _ => CanPriority::Bulk,
+        }
+    }
+
+    /// The standard CAN identifier for a message of this priority sent by `node`.
+    pub fn can_id(self, node: u16) -> u16 {
+        (self as u16) << 8 | (node & 0xFF)
+    }
+}
+
+/// The standard CAN identifier to send `message` from `node` with.
+pub fn can_id_for(message: &Message, node: u16) -> u16 {
+    CanPriority::of(message).can_id(node)
+}
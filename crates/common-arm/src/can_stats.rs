@@ -0,0 +1,65 @@
+//! Frame counters of a CAN bus, for debugging intermittent bus issues.
+//!
+//! A frame that fails to decode or to send is otherwise only logged, so a bus that drops or
+//! corrupts the odd frame looks healthy from the ground. The counters wrap around rather than
+//! saturate, so only differences between two reports are meaningful.
+
+use messages::Message;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, defmt::Format)]
+pub struct CanStats {
+    /// Messages handed to the peripheral for sending, a fragmented one counting once.
+    pub tx_frames: u32,
+    /// Messages that couldn't be sent, e.g. because they didn't fit a frame or no mailbox was
+    /// free.
+    pub tx_errors: u32,
+    /// Frames read from the receive FIFO, including the ones that failed to decode.
+    pub rx_frames: u32,
+    /// Messages decoded, a fragmented one counting once.
+    pub rx_messages: u32,
+    /// Received frames that weren't a valid postcard `Message`.
+    pub decode_errors: u32,
+    /// Bus-off recoveries started.
+    pub bus_off_recoveries: u32,
+}
+
+impl CanStats {
+    pub const fn new() -> Self {
+        Self {
+            tx_frames: 0,
+            tx_errors: 0,
+            rx_frames: 0,
+            rx_messages: 0,
+            decode_errors: 0,
+            bus_off_recoveries: 0,
+        }
+    }
+
+    /// Counts the outcome of sending a message.
+    pub fn record_tx<T, E>(&mut self, result: &Result<T, E>) {
+        match result {
+            Ok(_) => self.tx_frames = self.tx_frames.wrapping_add(1),
+            Err(_) => self.tx_errors = self.tx_errors.wrapping_add(1),
+        }
+    }
+
+    /// Counts a received frame, call for every frame read whether or not it gets decoded.
+    pub fn record_rx(&mut self) {
+        self.rx_frames = self.rx_frames.wrapping_add(1);
+    }
+
+    /// Decodes a received frame, or a reassembled message, as a `Message` and counts the outcome.
+    pub fn decode(&mut self, payload: &[u8]) -> Result<Message, postcard::Error> {
+        let result = postcard::from_bytes::<Message>(payload);
+        match result {
+            Ok(_) => self.rx_messages = self.rx_messages.wrapping_add(1),
+            Err(_) => self.decode_errors = self.decode_errors.wrapping_add(1),
+        }
+        result
+    }
+
+    pub fn record_bus_off_recovery(&mut self) -> u32 {
+        self.bus_off_recoveries = self.bus_off_recoveries.wrapping_add(1);
+        self.bus_off_recoveries
+    }
+}
@@ -0,0 +1,49 @@
+//! Decides when to emit a value that changes rarely, such as the flight state.
+//!
+//! Sending on a fixed period wastes bandwidth while nothing happens and delays a change by up to
+//! a whole period. Instead the value is emitted as soon as it is marked changed, and otherwise
+//! only once per keep-alive interval so the receiver knows the sender is still there.
+
+#[derive(Clone)]
+pub struct ChangeEmitter {
+    /// Longest time between two emissions of an unchanged value, in ms.
+    keepalive_ms: u64,
+    pending: bool,
+    last_sent_ms: Option<u64>,
+}
+
+impl ChangeEmitter {
+    pub fn new(keepalive_ms: u64) -> Self {
+        Self {
+            keepalive_ms,
+            pending: false,
+            last_sent_ms: None,
+        }
+    }
+
+    /// Marks the value as changed, the next [`Self::poll`] emits it.
+    pub fn changed(&mut self) {
+        self.pending = true;
+    }
+
+    /// Returns true if the value should be sent now, and records that it was.
+    pub fn poll(&mut self, now_ms: u64) -> bool {
+        let due = self.pending
+            || self
+                .last_sent_ms
+                .map_or(true, |last| now_ms.saturating_sub(last) >= self.keepalive_ms);
+        if due {
+            self.pending = false;
+            self.last_sent_ms = Some(now_ms);
+        }
+        due
+    }
+
+    pub fn set_keepalive(&mut self, keepalive_ms: u64) {
+        self.keepalive_ms = keepalive_ms;
+    }
+
+    pub fn get_keepalive(&self) -> u64 {
+        self.keepalive_ms
+    }
+}
@@ -0,0 +1,215 @@
+//! Delta compression of radio samples against the previous sample of the same sensor.
+//!
+//! Consecutive samples of slowly-changing sensors (GPS position, time) encode to nearly the same
+//! bytes. A delta frame carries the XOR of a sample's encoding with the previous one, with runs of
+//! zero bytes collapsed, which is much smaller than the sample itself. Every
+//! `keyframe_interval` samples, and whenever the encoded length changes, a keyframe carries the
+//! sample in full. A decoder that misses a frame drops deltas until the next keyframe rather than
+//! applying them to the wrong base.
+//!
+//! A frame is [`DELTA_MAGIC`], the stream (sensor) id, [`KEYFRAME`] or [`DELTA`], a sequence
+//! number and the body. In a delta body a zero byte is followed by the length of the zero run it
+//! stands for, any other byte is literal.
+
+use crate::{HydraError, PayloadTooLarge};
+
+/// Marks a payload as a delta codec frame rather than a plain postcard message.
+pub const DELTA_MAGIC: [u8; 2] = [0xDE, 0x1A];
+pub const KEYFRAME: u8 = 0;
+pub const DELTA: u8 = 1;
+const HEADER_LEN: usize = DELTA_MAGIC.len() + 3;
+
+/// Encodes one stream of samples of at most `N` bytes.
+pub struct DeltaEncoder<const N: usize> {
+    keyframe_interval: u8,
+    prev: [u8; N],
+    /// Length of the previous sample, `None` before the first one.
+    prev_len: Option<usize>,
+    since_keyframe: u8,
+    seq: u8,
+}
+
+impl<const N: usize> DeltaEncoder<N> {
+    pub const fn new(keyframe_interval: u8) -> Self {
+        Self {
+            keyframe_interval,
+            prev: [0; N],
+            prev_len: None,
+            since_keyframe: 0,
+            seq: 0,
+        }
+    }
+
+    /// Encodes `sample` as a frame of `stream` into `out` and returns the frame length.
+    pub fn encode(
+        &mut self,
+        stream: u8,
+        sample: &[u8],
+        out: &mut [u8],
+    ) -> Result<usize, HydraError> {
+        if sample.len() > N || out.len() < HEADER_LEN {
+            return Err(PayloadTooLarge {
+                len: sample.len(),
+                max: N.min(out.len().saturating_sub(HEADER_LEN)),
+            }
+            .into());
+        }
+        let keyframe_due =
+            self.prev_len != Some(sample.len()) || self.since_keyframe >= self.keyframe_interval;
+        let delta_len = if keyframe_due {
+            None
+        } else {
+            encode_delta(&self.prev[..sample.len()], sample, &mut out[HEADER_LEN..])
+        };
+        let (kind, body_len) = match delta_len {
+            Some(len) => {
+                self.since_keyframe += 1;
+                (DELTA, len)
+            }
+            None => {
+                let body =
+                    out.get_mut(HEADER_LEN..HEADER_LEN + sample.len())
+                        .ok_or(PayloadTooLarge {
+                            len: HEADER_LEN + sample.len(),
+                            max: out.len(),
+                        })?;
+                body.copy_from_slice(sample);
+                self.since_keyframe = 0;
+                (KEYFRAME, sample.len())
+            }
+        };
+
+        out[..DELTA_MAGIC.len()].copy_from_slice(&DELTA_MAGIC);
+        out[DELTA_MAGIC.len()..HEADER_LEN].copy_from_slice(&[stream, kind, self.seq]);
+        self.seq = self.seq.wrapping_add(1);
+        self.prev[..sample.len()].copy_from_slice(sample);
+        self.prev_len = Some(sample.len());
+        Ok(HEADER_LEN + body_len)
+    }
+
+    /// Makes the next sample a keyframe, e.g. when the ground has just connected.
+    pub fn force_keyframe(&mut self) {
+        self.since_keyframe = self.keyframe_interval;
+    }
+}
+
+/// Decodes one stream of samples of at most `N` bytes.
+pub struct DeltaDecoder<const N: usize> {
+    prev: [u8; N],
+    /// Length of the last decoded sample, `None` until a keyframe arrives or after a lost frame.
+    prev_len: Option<usize>,
+    last_seq: u8,
+}
+
+impl<const N: usize> DeltaDecoder<N> {
+    pub fn new() -> Self {
+        Self {
+            prev: [0; N],
+            prev_len: None,
+            last_seq: 0,
+        }
+    }
+
+    /// Decodes a frame into `out` and returns the sample length. Returns `None` for a frame that
+    /// can't be decoded, including deltas after a lost frame until the next keyframe.
+    pub fn decode(&mut self, frame: &[u8], out: &mut [u8]) -> Option<usize> {
+        if !is_delta_frame(frame) || frame.len() < HEADER_LEN {
+            return None;
+        }
+        let (kind, seq) = (frame[DELTA_MAGIC.len() + 1], frame[DELTA_MAGIC.len() + 2]);
+        let body = &frame[HEADER_LEN..];
+        let len = match kind {
+            KEYFRAME if body.len() <= N && body.len() <= out.len() => {
+                out[..body.len()].copy_from_slice(body);
+                Some(body.len())
+            }
+            DELTA => self
+                .prev_len
+                .filter(|len| seq == self.last_seq.wrapping_add(1) && *len <= out.len())
+                .and_then(|len| decode_delta(&self.prev[..len], body, &mut out[..len])),
+            _ => None,
+        };
+        self.prev_len = len;
+        self.last_seq = seq;
+        let len = len?;
+        self.prev[..len].copy_from_slice(&out[..len]);
+        Some(len)
+    }
+}
+
+impl<const N: usize> Default for DeltaDecoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns true if the payload is a delta codec frame.
+pub fn is_delta_frame(payload: &[u8]) -> bool {
+    payload.starts_with(&DELTA_MAGIC)
+}
+
+/// The stream id of a delta codec frame, to pick its decoder.
+pub fn delta_stream(frame: &[u8]) -> Option<u8> {
+    if !is_delta_frame(frame) {
+        return None;
+    }
+    frame.get(DELTA_MAGIC.len()).copied()
+}
+
+/// Writes the zero-run encoded XOR of `prev` and `sample` to `out`. Returns `None` if it doesn't
+/// fit or wouldn't be smaller than the sample.
+fn encode_delta(prev: &[u8], sample: &[u8], out: &mut [u8]) -> Option<usize> {
+    let limit = out.len().min(sample.len().saturating_sub(1));
+    let out = &mut out[..limit];
+    let mut len = 0;
+    let mut zeros: u8 = 0;
+    for (p, s) in prev.iter().zip(sample) {
+        let byte = p ^ s;
+        if byte == 0 && zeros < u8::MAX {
+            zeros += 1;
+            continue;
+        }
+        if zeros > 0 {
+            push(out, &mut len, &[0, zeros])?;
+            zeros = 0;
+        }
+        if byte == 0 {
+            zeros = 1;
+        } else {
+            push(out, &mut len, &[byte])?;
+        }
+    }
+    if zeros > 0 {
+        push(out, &mut len, &[0, zeros])?;
+    }
+    Some(len)
+}
+
+fn push(out: &mut [u8], len: &mut usize, bytes: &[u8]) -> Option<()> {
+    out.get_mut(*len..*len + bytes.len())?
+        .copy_from_slice(bytes);
+    *len += bytes.len();
+    Some(())
+}
+
+/// Reverses [`encode_delta`], `out` must be exactly the sample length.
+fn decode_delta(prev: &[u8], body: &[u8], out: &mut [u8]) -> Option<usize> {
+    let mut pos = 0;
+    let mut bytes = body.iter();
+    while let Some(&byte) = bytes.next() {
+        let run = if byte == 0 {
+            usize::from(*bytes.next()?)
+        } else {
+            1
+        };
+        let end = pos + run;
+        if end > out.len() {
+            return None;
+        }
+        for (o, p) in out[pos..end].iter_mut().zip(&prev[pos..end]) {
+            *o = p ^ byte;
+        }
+        pos = end;
+    }
+    (pos == out.len()).then_some(pos)
+}
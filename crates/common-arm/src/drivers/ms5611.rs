@@ -1,7 +1,11 @@
 //! Driver for the MS5611 Barometric Pressure Sensor
+//!
+//! Also drives the pin- and protocol-compatible MS5607, which only differs in the compensation
+//! scaling, see [`Variant`]. The sensor can be on SPI or I2C, see [`Ms5611Interface`].
 use embedded_hal::{
     blocking::{
         delay::DelayUs,
+        i2c,
         spi::{Transfer, Write},
     },
     digital::v2::OutputPin,
@@ -24,14 +28,14 @@ mod command {
     pub const CONVERT_D2_OSR_2048: u8 = 0x56;
     pub const CONVERT_D2_OSR_4096: u8 = 0x58;
     // PROM Read
-    // pub const PROM_READ_ADDR_0: u8 = 0xA0; // Reserved
+    pub const PROM_READ_ADDR_0: u8 = 0xA0; // Reserved, covered by the CRC
     pub const PROM_READ_ADDR_1: u8 = 0xA2; // C1 Pressure sensitivity (SENST1)
     pub const PROM_READ_ADDR_2: u8 = 0xA4; // C2 Pressure offset (OFFT1)
     pub const PROM_READ_ADDR_3: u8 = 0xA6; // C3 Temperature coefficient of pressure sensitivity (TCS)
     pub const PROM_READ_ADDR_4: u8 = 0xA8; // C4 Temperature coefficient of pressure offset (TCO)
     pub const PROM_READ_ADDR_5: u8 = 0xAA; // C5 Reference temperature (TREF)
     pub const PROM_READ_ADDR_6: u8 = 0xAC; // C6 Temperature coefficient of the temperature (TEMPSENS)
-                                           // pub const PROM_READ_ADDR_7: u8 = 0xAE; // C7 Serial Code & CRC
+    pub const PROM_READ_ADDR_7: u8 = 0xAE; // C7 Serial Code & CRC
 }
 
 /// Oversampling Ratio (OSR) options
@@ -74,7 +78,7 @@ impl OversamplingRatio {
     }
 
     /// Get the maximum conversion time in microseconds (based on datasheet max values)
-    fn conversion_time_us(self) -> u32 {
+    pub fn conversion_time_us(self) -> u32 {
         match self {
             OversamplingRatio::Osr256 => 600,
             OversamplingRatio::Osr512 => 1_170,
@@ -85,76 +89,371 @@ impl OversamplingRatio {
     }
 }
 
+/// Sensor populated on the board. Both share the commands and PROM layout, only the scaling of the
+/// compensation formulas differs.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Variant {
+    /// MS5611-01BA03
+    #[default]
+    Ms5611,
+    /// MS5607-02BA03
+    Ms5607,
+}
+
 /// Calibration Coefficients read from PROM
 #[derive(Debug, Clone, Copy)]
-struct CalibrationCoefficients {
+pub struct CalibrationCoefficients {
     /// C1: Pressure sensitivity (SENST1)
-    c1_sens_t1: u16,
+    pub c1_sens_t1: u16,
     /// C2: Pressure offset (OFFT1)
-    c2_off_t1: u16,
+    pub c2_off_t1: u16,
     /// C3: Temperature coefficient of pressure sensitivity (TCS)
-    c3_tcs: u16,
+    pub c3_tcs: u16,
     /// C4: Temperature coefficient of pressure offset (TCO)
-    c4_tco: u16,
+    pub c4_tco: u16,
     /// C5: Reference temperature (TREF)
-    c5_t_ref: u16,
+    pub c5_t_ref: u16,
     /// C6: Temperature coefficient of the temperature (TEMPSENS)
-    c6_temp_sens: u16,
-    // C7 (Serial/CRC) is read but maybe only used for CRC check, not stored here yet
+    pub c6_temp_sens: u16,
+    // C7 (Serial/CRC) is only used for the CRC check, not stored here
+}
+
+impl CalibrationCoefficients {
+    /// Takes the coefficients C1-C6 out of the eight PROM words.
+    pub fn from_prom(prom: &[u16; 8]) -> Self {
+        Self {
+            c1_sens_t1: prom[1],
+            c2_off_t1: prom[2],
+            c3_tcs: prom[3],
+            c4_tco: prom[4],
+            c5_t_ref: prom[5],
+            c6_temp_sens: prom[6],
+        }
+    }
+
+    /// Returns false if all six coefficients are 0x0000 or all are 0xFFFF, as read from a
+    /// disconnected or mis-wired sensor.
+    pub fn is_plausible(&self) -> bool {
+        let words = [
+            self.c1_sens_t1,
+            self.c2_off_t1,
+            self.c3_tcs,
+            self.c4_tco,
+            self.c5_t_ref,
+            self.c6_temp_sens,
+        ];
+        !words.iter().all(|word| *word == 0x0000) && !words.iter().all(|word| *word == 0xFFFF)
+    }
+}
+
+/// Computes the 4-bit CRC over the eight PROM words, as in application note AN520.
+/// The CRC field itself, the low 4 bits of word 7, is excluded from the calculation.
+pub fn crc4(prom: &[u16; 8]) -> u8 {
+    let mut words = *prom;
+    words[7] &= 0xFF00;
+    let mut remainder: u16 = 0;
+    for byte in 0..16 {
+        let word = words[byte >> 1];
+        remainder ^= if byte % 2 == 1 {
+            word & 0x00FF
+        } else {
+            word >> 8
+        };
+        for _ in 0..8 {
+            remainder = if remainder & 0x8000 != 0 {
+                (remainder << 1) ^ 0x3000
+            } else {
+                remainder << 1
+            };
+        }
+    }
+    ((remainder >> 12) & 0x000F) as u8
+}
+
+/// Returns true if the CRC field of the PROM matches its contents. An all-zero PROM, as read
+/// with MISO stuck low, has a matching CRC and is rejected separately.
+pub fn prom_crc_valid(prom: &[u16; 8]) -> bool {
+    prom.iter().any(|word| *word != 0) && crc4(prom) == (prom[7] & 0x000F) as u8
+}
+
+/// Raw counts and compensated values from a single acquisition cycle
+#[derive(Debug, Clone, Copy)]
+pub struct Ms5611Reading {
+    /// Raw pressure (D1)
+    pub d1_raw: u32,
+    /// Raw temperature (D2)
+    pub d2_raw: u32,
+    pub temperature_c: f32,
+    pub pressure_mbar: f32,
+}
+
+impl Ms5611Reading {
+    /// Pressure in kPa, the unit returned by [`Ms5611::read_pressure_temperature`]
+    pub fn pressure_kpa(&self) -> f32 {
+        self.pressure_mbar / 10.0
+    }
 }
 
 /// MS5611 Driver Error
 #[derive(Debug)]
-pub enum Error<SPIE, CSE> {
-    /// SPI communication error
-    Spi(SPIE),
-    /// Chip Select pin error
-    Cs(CSE),
-    /// CRC check failed on PROM data (Not yet implemented)
+pub enum Error<E> {
+    /// Error from the bus the sensor is on, see [`Ms5611Interface`]
+    Interface(E),
+    /// CRC check failed on PROM data
     CrcError,
+    /// The coefficients are all 0x0000 or all 0xFFFF, the sensor is most likely not connected
+    InvalidCoefficients,
     /// Calculation resulted in an invalid value (e.g. NaN or Infinity)
     /// This might indicate issues with raw data or coefficients.
     CalculationFault,
+    /// The pressure filter alpha is outside (0, 1]
+    InvalidFilterAlpha,
 }
 
-/// MS5611 Driver
-pub struct Ms5611<SPI, CS, DELAY> {
+/// Error of the SPI interface
+#[derive(Debug)]
+pub enum SpiError<SPIE, CSE> {
+    /// SPI communication error
+    Spi(SPIE),
+    /// Chip Select pin error
+    Cs(CSE),
+}
+
+/// Bus the sensor is connected to. The commands are the same on SPI and I2C, only how they are
+/// framed differs.
+pub trait Ms5611Interface {
+    type Error;
+
+    /// Sends the Reset command.
+    fn reset(&mut self) -> Result<(), Self::Error>;
+    /// Reads the 16-bit PROM word at the given PROM read command.
+    fn read_prom(&mut self, address_command: u8) -> Result<u16, Self::Error>;
+    /// Sends a conversion command.
+    fn start_conversion(&mut self, command: u8) -> Result<(), Self::Error>;
+    /// Reads the 24-bit result of the last conversion, 0 if it hasn't finished.
+    fn read_adc(&mut self) -> Result<u32, Self::Error>;
+}
+
+/// Sensor on an SPI bus with its own chip select pin.
+pub struct SpiInterface<SPI, CS> {
     spi: SPI,
     cs: CS,
-    delay: DELAY,
-    coefficients: CalibrationCoefficients,
+}
+
+impl<SPI, CS, SPIE, CSE> SpiInterface<SPI, CS>
+where
+    SPI: Transfer<u8, Error = SPIE> + Write<u8, Error = SPIE>,
+    CS: OutputPin<Error = CSE>,
+{
+    pub fn new(spi: SPI, mut cs: CS) -> Result<Self, SpiError<SPIE, CSE>> {
+        // Ensure CS is high initially
+        cs.set_high().map_err(SpiError::Cs)?;
+        Ok(Self { spi, cs })
+    }
 }
 
 // Helper macro for handling CS pin toggling
 macro_rules! with_cs {
     ($self:expr, $block:expr) => {{
         // Set CS low to start transaction
-        $self.cs.set_low().map_err(Error::Cs)?;
+        $self.cs.set_low().map_err(SpiError::Cs)?;
         // Small delay might be needed depending on SPI speed and device, though often not.
         // $self.delay.delay_us(1);
         let result = $block;
         // Set CS high to end transaction
-        $self.cs.set_high().map_err(Error::Cs)?;
+        $self.cs.set_high().map_err(SpiError::Cs)?;
         result
     }};
 }
 
-impl<SPI, CS, DELAY, SPIE, CSE> Ms5611<SPI, CS, DELAY>
+impl<SPI, CS, SPIE, CSE> Ms5611Interface for SpiInterface<SPI, CS>
+where
+    SPI: Transfer<u8, Error = SPIE> + Write<u8, Error = SPIE>,
+    CS: OutputPin<Error = CSE>,
+{
+    type Error = SpiError<SPIE, CSE>;
+
+    fn reset(&mut self) -> Result<(), Self::Error> {
+        with_cs!(self, {
+            self.spi.write(&[command::RESET]).map_err(SpiError::Spi)
+        })
+    }
+
+    fn read_prom(&mut self, address_command: u8) -> Result<u16, Self::Error> {
+        with_cs!(self, {
+            // 1. Send PROM read command for the specific address
+            //    We only write the command, ignore anything read back during this byte.
+            self.spi.write(&[address_command]).map_err(SpiError::Spi)?;
+
+            // 2. Immediately transfer two dummy bytes (e.g., 0x00) to clock out
+            //    the 16-bit result from the sensor.
+            let mut buffer = [0u8; 2]; // Buffer to receive the 2 bytes
+            self.spi.transfer(&mut buffer).map_err(SpiError::Spi)?;
+
+            // 3. Construct the u16 result from the received bytes.
+            Ok(u16::from_be_bytes([buffer[0], buffer[1]]))
+        })
+    }
+
+    fn start_conversion(&mut self, command: u8) -> Result<(), Self::Error> {
+        with_cs!(self, { self.spi.write(&[command]).map_err(SpiError::Spi) })
+    }
+
+    fn read_adc(&mut self) -> Result<u32, Self::Error> {
+        with_cs!(self, {
+            // Send ADC Read command (0x00) to clock out the data
+            let mut buffer = [command::ADC_READ, 0x00, 0x00, 0x00]; // Send read cmd, receive 3 bytes
+            self.spi.transfer(&mut buffer).map_err(SpiError::Spi)?;
+            Ok(u32::from_be_bytes([0, buffer[1], buffer[2], buffer[3]])) // Pad to 4 bytes for u32
+        })
+    }
+}
+
+/// I2C address with the CSB pin high
+pub const I2C_ADDRESS_CSB_HIGH: u8 = 0x77;
+/// I2C address with the CSB pin low
+pub const I2C_ADDRESS_CSB_LOW: u8 = 0x76;
+
+/// Sensor on an I2C bus. Each command is its own write, and results are read in a separate
+/// transaction after it.
+pub struct I2cInterface<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C> I2cInterface<I2C> {
+    /// `address` is [`I2C_ADDRESS_CSB_HIGH`] or [`I2C_ADDRESS_CSB_LOW`] depending on the CSB pin.
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        Self { i2c, address }
+    }
+}
+
+impl<I2C, E> Ms5611Interface for I2cInterface<I2C>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>,
+{
+    type Error = E;
+
+    fn reset(&mut self) -> Result<(), E> {
+        self.i2c.write(self.address, &[command::RESET])
+    }
+
+    fn read_prom(&mut self, address_command: u8) -> Result<u16, E> {
+        self.i2c.write(self.address, &[address_command])?;
+        let mut buffer = [0u8; 2];
+        self.i2c.read(self.address, &mut buffer)?;
+        Ok(u16::from_be_bytes(buffer))
+    }
+
+    fn start_conversion(&mut self, command: u8) -> Result<(), E> {
+        self.i2c.write(self.address, &[command])
+    }
+
+    fn read_adc(&mut self) -> Result<u32, E> {
+        self.i2c.write(self.address, &[command::ADC_READ])?;
+        let mut buffer = [0u8; 3];
+        self.i2c.read(self.address, &mut buffer)?;
+        Ok(u32::from_be_bytes([0, buffer[0], buffer[1], buffer[2]]))
+    }
+}
+
+/// Standard sea level pressure in mbar, the default altitude reference
+pub const STANDARD_SEA_LEVEL_MBAR: f32 = 1013.25;
+
+/// Temperature lapse rate of the standard atmosphere in K/m
+const LAPSE_RATE: f32 = 0.0065;
+
+/// Default range of temperatures in C trusted for the temperature-corrected altitude, the
+/// operating range of the sensor
+pub const DEFAULT_CORRECTION_TEMPERATURE_RANGE: (f32, f32) = (-40.0, 85.0);
+
+/// MS5611 Driver
+pub struct Ms5611<IFACE, DELAY> {
+    interface: IFACE,
+    delay: DELAY,
+    coefficients: CalibrationCoefficients,
+    variant: Variant,
+    /// Pressure in mbar that [`Ms5611::altitude_m`] measures altitude from
+    sea_level_mbar: f32,
+    /// Temperatures in C outside this range fall back to the standard atmosphere altitude
+    correction_temperature_range: (f32, f32),
+    /// Weight of a new reading in the pressure EMA, 1.0 disables filtering
+    filter_alpha: f32,
+    /// Filtered pressure in kPa, `None` until the first reading
+    filtered_pressure_kpa: Option<f32>,
+    /// Unfiltered pressure in kPa of the last reading
+    last_raw_pressure_kpa: Option<f32>,
+}
+
+impl<SPI, CS, DELAY, SPIE, CSE> Ms5611<SpiInterface<SPI, CS>, DELAY>
 where
     SPI: Transfer<u8, Error = SPIE> + Write<u8, Error = SPIE>,
     CS: OutputPin<Error = CSE>,
     DELAY: DelayUs<u32>,
+{
+    /// Creates a driver for a sensor on SPI, see [`Self::with_interface`].
+    pub fn new(
+        spi: SPI,
+        cs: CS,
+        delay: DELAY,
+        variant: Variant,
+    ) -> Result<Self, Error<SpiError<SPIE, CSE>>> {
+        let interface = SpiInterface::new(spi, cs).map_err(Error::Interface)?;
+        Self::with_interface(interface, delay, variant)
+    }
+
+    /// Creates a driver for a sensor on SPI with known coefficients, see
+    /// [`Self::with_interface_and_coefficients`].
+    pub fn with_coefficients(
+        spi: SPI,
+        cs: CS,
+        delay: DELAY,
+        coefficients: CalibrationCoefficients,
+        variant: Variant,
+    ) -> Self {
+        Self::with_interface_and_coefficients(
+            SpiInterface { spi, cs },
+            delay,
+            coefficients,
+            variant,
+        )
+    }
+}
+
+impl<I2C, DELAY, E> Ms5611<I2cInterface<I2C>, DELAY>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>,
+    DELAY: DelayUs<u32>,
+{
+    /// Creates a driver for a sensor on I2C at `address`, see [`Self::with_interface`].
+    pub fn new_i2c(
+        i2c: I2C,
+        address: u8,
+        delay: DELAY,
+        variant: Variant,
+    ) -> Result<Self, Error<E>> {
+        Self::with_interface(I2cInterface::new(i2c, address), delay, variant)
+    }
+}
+
+impl<IFACE, DELAY> Ms5611<IFACE, DELAY>
+where
+    IFACE: Ms5611Interface,
+    DELAY: DelayUs<u32>,
 {
     /// Creates a new MS5611 driver instance.
     /// Performs a reset, waits, and reads calibration coefficients from the PROM.
-    pub fn new(spi: SPI, mut cs: CS, mut delay: DELAY) -> Result<Self, Error<SPIE, CSE>> {
-        // Ensure CS is high initially
-        cs.set_high().map_err(Error::Cs)?;
+    /// Returns `Error::InvalidCoefficients` if the PROM reads as all zeros or all ones, and
+    /// `Error::CrcError` if the PROM contents don't match their CRC.
+    pub fn with_interface(
+        interface: IFACE,
+        mut delay: DELAY,
+        variant: Variant,
+    ) -> Result<Self, Error<IFACE::Error>> {
         delay.delay_us(100); // Small delay after power-up before reset
 
         let mut sensor = Self {
-            spi,
-            cs,
+            interface,
             delay,
             // Placeholder coefficients, will be overwritten
             coefficients: CalibrationCoefficients {
@@ -165,6 +464,12 @@ where
                 c5_t_ref: 0,
                 c6_temp_sens: 0,
             },
+            variant,
+            sea_level_mbar: STANDARD_SEA_LEVEL_MBAR,
+            correction_temperature_range: DEFAULT_CORRECTION_TEMPERATURE_RANGE,
+            filter_alpha: 1.0,
+            filtered_pressure_kpa: None,
+            last_raw_pressure_kpa: None,
         };
 
         sensor.reset()?;
@@ -173,61 +478,166 @@ where
 
         sensor.coefficients = sensor.read_coefficients()?;
 
-        // Optional: Implement CRC check here using PROM word 7 (0xAE)
-
         Ok(sensor)
     }
 
-    /// Sends the Reset command to the sensor.
-    fn reset(&mut self) -> Result<(), Error<SPIE, CSE>> {
-        with_cs!(self, {
-            self.spi.write(&[command::RESET]).map_err(Error::Spi)
-        })
+    /// Creates a driver with known coefficients instead of reading them from the PROM. Nothing is
+    /// sent to the sensor. Meant for running test vectors, such as the datasheet example, through
+    /// [`Self::calculate_compensated_values`] without hardware.
+    pub fn with_interface_and_coefficients(
+        interface: IFACE,
+        delay: DELAY,
+        coefficients: CalibrationCoefficients,
+        variant: Variant,
+    ) -> Self {
+        Self {
+            interface,
+            delay,
+            coefficients,
+            variant,
+            sea_level_mbar: STANDARD_SEA_LEVEL_MBAR,
+            correction_temperature_range: DEFAULT_CORRECTION_TEMPERATURE_RANGE,
+            filter_alpha: 1.0,
+            filtered_pressure_kpa: None,
+            last_raw_pressure_kpa: None,
+        }
     }
 
-    /// Reads a 16-bit word from the specified PROM address.
-    fn read_prom_word(&mut self, address_command: u8) -> Result<u16, Error<SPIE, CSE>> {
-        with_cs!(self, {
-            // 1. Send PROM read command for the specific address
-            //    We only write the command, ignore anything read back during this byte.
-            self.spi.write(&[address_command]).map_err(Error::Spi)?;
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
 
-            // 2. Immediately transfer two dummy bytes (e.g., 0x00) to clock out
-            //    the 16-bit result from the sensor.
-            let mut buffer = [0u8; 2]; // Buffer to receive the 2 bytes
-            self.spi.transfer(&mut buffer).map_err(Error::Spi)?;
+    /// Converts a pressure in mbar to an altitude in meters above the sea level reference,
+    /// using the international barometric formula.
+    pub fn altitude_m(&self, pressure_mbar: f32) -> f32 {
+        44330.0 * (1.0 - libm::powf(pressure_mbar / self.sea_level_mbar, 1.0 / 5.255))
+    }
 
-            // 3. Construct the u16 result from the received bytes.
-            Ok(u16::from_be_bytes([buffer[0], buffer[1]]))
-        })
+    /// Converts a pressure in mbar to an altitude in meters using the hypsometric equation with the
+    /// measured temperature instead of the standard atmosphere. On a hot or cold day this is
+    /// closer to the true altitude than [`Self::altitude_m`]. Temperatures outside the configured
+    /// range, e.g. from a faulty reading, fall back to [`Self::altitude_m`].
+    pub fn altitude_temperature_corrected_m(&self, pressure_mbar: f32, temperature_c: f32) -> f32 {
+        let (min_c, max_c) = self.correction_temperature_range;
+        if !(min_c..=max_c).contains(&temperature_c) {
+            return self.altitude_m(pressure_mbar);
+        }
+        let ratio = libm::powf(self.sea_level_mbar / pressure_mbar, 1.0 / 5.255);
+        (ratio - 1.0) * (temperature_c + 273.15) / LAPSE_RATE
     }
 
-    /// Reads all calibration coefficients (C1-C6) from the PROM.
-    fn read_coefficients(&mut self) -> Result<CalibrationCoefficients, Error<SPIE, CSE>> {
+    /// Sets the range of temperatures in C used for the temperature-corrected altitude.
+    /// Defaults to [`DEFAULT_CORRECTION_TEMPERATURE_RANGE`].
+    pub fn set_correction_temperature_range(&mut self, min_c: f32, max_c: f32) {
+        self.correction_temperature_range = (min_c, max_c);
+    }
+
+    /// Sets the weight of each new reading in the exponential moving average applied to the
+    /// pressure by [`Self::read_pressure_temperature`] and [`Self::filter_pressure`]. Lower values
+    /// smooth more but lag further behind. 1.0, the default, disables filtering. Returns
+    /// `Error::InvalidFilterAlpha`, leaving the filter unchanged, if `alpha` is outside (0, 1].
+    pub fn set_filter_alpha(&mut self, alpha: f32) -> Result<(), Error<IFACE::Error>> {
+        if !(alpha > 0.0 && alpha <= 1.0) {
+            return Err(Error::InvalidFilterAlpha);
+        }
+        self.filter_alpha = alpha;
+        Ok(())
+    }
+
+    /// Feeds an unfiltered pressure in kPa through the moving average and returns the filtered
+    /// pressure. The first reading starts the average. Meant for callers that run the
+    /// conversions themselves with [`Self::calculate_compensated_values`].
+    pub fn filter_pressure(&mut self, pressure_kpa: f32) -> f32 {
+        self.last_raw_pressure_kpa = Some(pressure_kpa);
+        let filtered = match self.filtered_pressure_kpa {
+            Some(previous) => previous + self.filter_alpha * (pressure_kpa - previous),
+            None => pressure_kpa,
+        };
+        self.filtered_pressure_kpa = Some(filtered);
+        filtered
+    }
+
+    /// Unfiltered pressure in kPa of the last reading, `None` before the first.
+    pub fn last_raw_pressure(&self) -> Option<f32> {
+        self.last_raw_pressure_kpa
+    }
+
+    /// Sets the pressure in mbar that altitude is measured from, e.g. the local QNH.
+    /// Defaults to [`STANDARD_SEA_LEVEL_MBAR`].
+    pub fn set_sea_level_pressure(&mut self, sea_level_mbar: f32) {
+        self.sea_level_mbar = sea_level_mbar;
+    }
+
+    /// Returns the pressure in mbar that altitude is measured from.
+    pub fn sea_level_pressure(&self) -> f32 {
+        self.sea_level_mbar
+    }
+
+    /// Sends the Reset command to the sensor.
+    fn reset(&mut self) -> Result<(), Error<IFACE::Error>> {
+        self.interface.reset().map_err(Error::Interface)
+    }
+
+    /// Reads a 16-bit word from the specified PROM address.
+    fn read_prom_word(&mut self, address_command: u8) -> Result<u16, Error<IFACE::Error>> {
+        self.interface
+            .read_prom(address_command)
+            .map_err(Error::Interface)
+    }
+
+    /// Reads all calibration coefficients (C1-C6) from the PROM and checks them against the CRC.
+    fn read_coefficients(&mut self) -> Result<CalibrationCoefficients, Error<IFACE::Error>> {
         // Note: PROM address 0 (0xA0) is reserved, address 7 (0xAE) is CRC/Serial
-        Ok(CalibrationCoefficients {
-            c1_sens_t1: self.read_prom_word(command::PROM_READ_ADDR_1)?,
-            c2_off_t1: self.read_prom_word(command::PROM_READ_ADDR_2)?,
-            c3_tcs: self.read_prom_word(command::PROM_READ_ADDR_3)?,
-            c4_tco: self.read_prom_word(command::PROM_READ_ADDR_4)?,
-            c5_t_ref: self.read_prom_word(command::PROM_READ_ADDR_5)?,
-            c6_temp_sens: self.read_prom_word(command::PROM_READ_ADDR_6)?,
-        })
+        let prom = [
+            self.read_prom_word(command::PROM_READ_ADDR_0)?,
+            self.read_prom_word(command::PROM_READ_ADDR_1)?,
+            self.read_prom_word(command::PROM_READ_ADDR_2)?,
+            self.read_prom_word(command::PROM_READ_ADDR_3)?,
+            self.read_prom_word(command::PROM_READ_ADDR_4)?,
+            self.read_prom_word(command::PROM_READ_ADDR_5)?,
+            self.read_prom_word(command::PROM_READ_ADDR_6)?,
+            self.read_prom_word(command::PROM_READ_ADDR_7)?,
+        ];
+        let coefficients = CalibrationCoefficients::from_prom(&prom);
+        // Checked first, a stuck bus is a wiring fault rather than corrupted data.
+        if !coefficients.is_plausible() {
+            return Err(Error::InvalidCoefficients);
+        }
+        if !prom_crc_valid(&prom) {
+            return Err(Error::CrcError);
+        }
+        Ok(coefficients)
     }
 
     /// Sends a conversion command (Pressure or Temperature).
-    fn start_conversion(&mut self, command: u8) -> Result<(), Error<SPIE, CSE>> {
-        with_cs!(self, { self.spi.write(&[command]).map_err(Error::Spi) })
+    fn start_conversion(&mut self, command: u8) -> Result<(), Error<IFACE::Error>> {
+        self.interface
+            .start_conversion(command)
+            .map_err(Error::Interface)
+    }
+
+    /// Starts a pressure (D1) conversion without waiting for it. Read the result with
+    /// [`Self::read_adc_raw`] once [`OversamplingRatio::conversion_time_us`] has passed.
+    pub fn start_pressure_conversion(
+        &mut self,
+        osr: OversamplingRatio,
+    ) -> Result<(), Error<IFACE::Error>> {
+        self.start_conversion(osr.pressure_command())
+    }
+
+    /// Starts a temperature (D2) conversion without waiting for it. Read the result with
+    /// [`Self::read_adc_raw`] once [`OversamplingRatio::conversion_time_us`] has passed.
+    pub fn start_temperature_conversion(
+        &mut self,
+        osr: OversamplingRatio,
+    ) -> Result<(), Error<IFACE::Error>> {
+        self.start_conversion(osr.temperature_command())
     }
 
     /// Reads the 24-bit raw ADC result from the sensor.
-    pub fn read_adc_raw(&mut self) -> Result<u32, Error<SPIE, CSE>> {
-        with_cs!(self, {
-            // Send ADC Read command (0x00) to clock out the data
-            let mut buffer = [command::ADC_READ, 0x00, 0x00, 0x00]; // Send read cmd, receive 3 bytes
-            self.spi.transfer(&mut buffer).map_err(Error::Spi)?;
-            Ok(u32::from_be_bytes([0, buffer[1], buffer[2], buffer[3]])) // Pad to 4 bytes for u32
-        })
+    /// Reads 0 if the conversion hasn't finished yet.
+    pub fn read_adc_raw(&mut self) -> Result<u32, Error<IFACE::Error>> {
+        self.interface.read_adc().map_err(Error::Interface)
     }
 
     /// Reads the raw temperature value (D2).
@@ -235,43 +645,69 @@ where
     pub fn read_raw_temperature(
         &mut self,
         osr: OversamplingRatio,
-    ) -> Result<u32, Error<SPIE, CSE>> {
-        self.start_conversion(osr.temperature_command())?;
+    ) -> Result<u32, Error<IFACE::Error>> {
+        self.start_temperature_conversion(osr)?;
         self.delay.delay_us(osr.conversion_time_us());
         self.read_adc_raw()
     }
 
     /// Reads the raw pressure value (D1).
     /// Starts conversion, waits, and reads the ADC.
-    pub fn read_raw_pressure(&mut self, osr: OversamplingRatio) -> Result<u32, Error<SPIE, CSE>> {
-        self.start_conversion(osr.pressure_command())?;
+    pub fn read_raw_pressure(
+        &mut self,
+        osr: OversamplingRatio,
+    ) -> Result<u32, Error<IFACE::Error>> {
+        self.start_pressure_conversion(osr)?;
         self.delay.delay_us(osr.conversion_time_us());
         self.read_adc_raw()
     }
 
     /// Performs a full temperature and pressure reading cycle and returns compensated values.
     /// Reads temperature (D2), then pressure (D1), then performs calculations.
+    /// Blocks for both conversions, async callers should use the start/read calls instead.
+    /// The pressure is filtered if a filter alpha is set, see [`Self::set_filter_alpha`].
     ///
     /// Returns `(temperature_celsius, pressure_kpa)`
     pub fn read_pressure_temperature(
         &mut self,
         osr: OversamplingRatio,
-    ) -> Result<(f32, f32), Error<SPIE, CSE>> {
+    ) -> Result<(f32, f32), Error<IFACE::Error>> {
+        let reading = self.read_full(osr)?;
+        Ok((
+            reading.temperature_c,
+            self.filter_pressure(reading.pressure_kpa()),
+        ))
+    }
+
+    /// Same reading cycle as [`Self::read_pressure_temperature`], also returning the raw D1/D2
+    /// counts the compensated values came from, e.g. to log them for checking the coefficients
+    /// after a flight. The pressure is never filtered.
+    pub fn read_full(
+        &mut self,
+        osr: OversamplingRatio,
+    ) -> Result<Ms5611Reading, Error<IFACE::Error>> {
         let d2_raw = self.read_raw_temperature(osr)?;
         let d1_raw = self.read_raw_pressure(osr)?;
 
-        self.calculate_compensated_values(d1_raw, d2_raw)
+        let (temperature_c, pressure_kpa) = self.calculate_compensated_values(d1_raw, d2_raw)?;
+        Ok(Ms5611Reading {
+            d1_raw,
+            d2_raw,
+            temperature_c,
+            pressure_mbar: pressure_kpa * 10.0,
+        })
     }
 
     /// Calculates compensated temperature and pressure using raw ADC values and PROM coefficients.
-    /// Implements the 1st and 2nd order compensation formulas from the datasheet.
+    /// Implements the 1st and 2nd order compensation formulas from the datasheet of the
+    /// [`Variant`].
     ///
     /// Returns `(temperature_celsius, pressure_kpa)`
-    fn calculate_compensated_values(
+    pub fn calculate_compensated_values(
         &self,
         d1_raw: u32, // Raw Pressure
         d2_raw: u32, // Raw Temperature
-    ) -> Result<(f32, f32), Error<SPIE, CSE>> {
+    ) -> Result<(f32, f32), Error<IFACE::Error>> {
         let c = &self.coefficients;
 
         // Cast coefficients to i64 for intermediate calculations to prevent overflow
@@ -291,11 +727,20 @@ where
         // TEMP = 2000 + dT * C6 / 2^23  (Result in 0.01 degC)
         let temp_i32 = (2000 + ((dt * c6) >> 23)) as i32; // Cast to i32 for checks
 
-        // OFF = C2 * 2^16 + (C4 * dT) / 2^7
-        let off = (c2 << 16) + ((c4 * dt) >> 7);
-
-        // SENS = C1 * 2^15 + (C3 * dT) / 2^8
-        let sens = (c1 << 15) + ((c3 * dt) >> 8);
+        let (off, sens) = match self.variant {
+            Variant::Ms5611 => (
+                // OFF = C2 * 2^16 + (C4 * dT) / 2^7
+                (c2 << 16) + ((c4 * dt) >> 7),
+                // SENS = C1 * 2^15 + (C3 * dT) / 2^8
+                (c1 << 15) + ((c3 * dt) >> 8),
+            ),
+            Variant::Ms5607 => (
+                // OFF = C2 * 2^17 + (C4 * dT) / 2^6
+                (c2 << 17) + ((c4 * dt) >> 6),
+                // SENS = C1 * 2^16 + (C3 * dT) / 2^7
+                (c1 << 16) + ((c3 * dt) >> 7),
+            ),
+        };
 
         // --- Second Order Temperature Compensation ---
         let mut temp = temp_i32 as i64; // Use i64 for further calculations
@@ -307,19 +752,40 @@ where
             // T2 = dT^2 / 2^31
             t2 = (dt * dt) >> 31;
 
-            // OFF2 = 5 * (TEMP - 2000)^2 / 2
             let temp_diff_sq = (temp - 2000) * (temp - 2000);
-            off2 = 5 * temp_diff_sq >> 1; // Divide by 2
-
-            // SENS2 = 5 * (TEMP - 2000)^2 / 4
-            sens2 = 5 * temp_diff_sq >> 2; // Divide by 4
+            match self.variant {
+                Variant::Ms5611 => {
+                    // OFF2 = 5 * (TEMP - 2000)^2 / 2
+                    off2 = 5 * temp_diff_sq >> 1; // Divide by 2
+
+                    // SENS2 = 5 * (TEMP - 2000)^2 / 4
+                    sens2 = 5 * temp_diff_sq >> 2; // Divide by 4
+                }
+                Variant::Ms5607 => {
+                    // OFF2 = 61 * (TEMP - 2000)^2 / 2^4
+                    off2 = 61 * temp_diff_sq >> 4;
+
+                    // SENS2 = 2 * (TEMP - 2000)^2
+                    sens2 = 2 * temp_diff_sq;
+                }
+            }
 
             if temp < -1500 {
-                // OFF2 = OFF2 + 7 * (TEMP + 1500)^2
                 let temp_low_diff_sq = (temp + 1500) * (temp + 1500);
-                off2 += 7 * temp_low_diff_sq;
-                // SENS2 = SENS2 + 11 * (TEMP + 1500)^2 / 2
-                sens2 += 11 * temp_low_diff_sq >> 1; // Divide by 2
+                match self.variant {
+                    Variant::Ms5611 => {
+                        // OFF2 = OFF2 + 7 * (TEMP + 1500)^2
+                        off2 += 7 * temp_low_diff_sq;
+                        // SENS2 = SENS2 + 11 * (TEMP + 1500)^2 / 2
+                        sens2 += 11 * temp_low_diff_sq >> 1; // Divide by 2
+                    }
+                    Variant::Ms5607 => {
+                        // OFF2 = OFF2 + 15 * (TEMP + 1500)^2
+                        off2 += 15 * temp_low_diff_sq;
+                        // SENS2 = SENS2 + 8 * (TEMP + 1500)^2
+                        sens2 += 8 * temp_low_diff_sq;
+                    }
+                }
             }
         }
 
@@ -8,19 +8,24 @@ use cortex_m::interrupt::Mutex;
 use defmt::error;
 use heapless::HistoryBuffer;
 
+/// Number of errors kept by an [`ErrorManager`] unless a different length is given.
+pub const DEFAULT_ERROR_HISTORY_LEN: usize = 8;
+
 /// Central error management for HYDRA. A single instance of this should be created for each board.
-pub struct ErrorManager {
+///
+/// The last `N` errors are kept. Boards short on RAM can shrink this, debug setups can grow it.
+pub struct ErrorManager<const N: usize = DEFAULT_ERROR_HISTORY_LEN> {
     has_error: AtomicBool,
-    error_history: Mutex<RefCell<HistoryBuffer<HydraError, 8>>>,
+    error_history: Mutex<RefCell<HistoryBuffer<HydraError, N>>>,
 }
 
-impl Default for ErrorManager {
+impl<const N: usize> Default for ErrorManager<N> {
     fn default() -> Self {
         ErrorManager::new()
     }
 }
 
-impl ErrorManager {
+impl<const N: usize> ErrorManager<N> {
     pub fn new() -> Self {
         ErrorManager {
             has_error: false.into(),
@@ -57,4 +62,9 @@ impl ErrorManager {
     pub fn has_error(&self) -> bool {
         self.has_error.load(Relaxed)
     }
+
+    /// Returns how many errors are currently kept, at most `N`.
+    pub fn error_history_len(&self) -> usize {
+        interrupt::free(|cs| self.error_history.borrow(cs).borrow().len())
+    }
 }
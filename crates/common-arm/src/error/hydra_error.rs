@@ -22,11 +22,21 @@ pub enum HydraErrorType {
     /// Error from the SD card library.
     SdCardError(sd::Error<sd::SdMmcError>),
     /// Error from the Baro driver.
-    BaroError(ms5611::Error<stm32h7xx_hal::spi::Error, core::convert::Infallible>),
+    BaroError(ms5611::Error<ms5611::SpiError<stm32h7xx_hal::spi::Error, Infallible>>),
     /// Error from the Mavlink library.
     MavlinkError(messages::mavlink::error::MessageWriteError),
     MavlinkReadError(messages::mavlink::error::MessageReadError),
     NbError(NbError<Infallible>),
+    /// A serialized payload did not fit in the frame it was meant to be sent in.
+    PayloadTooLarge(PayloadTooLarge),
+    /// A versioned payload is older than the oldest version that can still be decoded.
+    IncompatibleSchema(IncompatibleSchema),
+    /// More CAN IDs were requested than the hardware filters can hold.
+    TooManyFilterIds(TooManyFilterIds),
+    /// A CAN peripheral went bus-off and was restarted.
+    CanBusOff(CanBusOff),
+    /// A message sent in CAN internal loopback didn't come back unchanged.
+    CanSelfTestFailed(CanSelfTestFailed),
 }
 
 impl defmt::Format for HydraErrorType {
@@ -56,10 +66,80 @@ impl defmt::Format for HydraErrorType {
             HydraErrorType::BaroError(_) => {
                 write!(f, "Baro error!");
             }
+            HydraErrorType::PayloadTooLarge(e) => {
+                write!(f, "Payload of {} bytes exceeds the {} byte limit", e.len, e.max);
+            }
+            HydraErrorType::IncompatibleSchema(e) => {
+                write!(
+                    f,
+                    "Schema version {} is older than the oldest compatible version {}",
+                    e.version, e.oldest_compatible
+                );
+            }
+            HydraErrorType::TooManyFilterIds(e) => {
+                write!(
+                    f,
+                    "{} CAN IDs requested, the filters hold at most {}",
+                    e.requested, e.max
+                );
+            }
+            HydraErrorType::CanBusOff(e) => {
+                write!(
+                    f,
+                    "CAN {} bus went bus-off, recovery attempt {}",
+                    e.bus, e.recoveries
+                );
+            }
+            HydraErrorType::CanSelfTestFailed(e) => {
+                write!(f, "CAN {} bus loopback self test failed", e.bus);
+            }
         }
     }
 }
 
+/// Raised when a payload is larger than the frame it is being sent in.
+#[derive(Debug, Clone, Copy)]
+pub struct PayloadTooLarge {
+    /// Length of the rejected payload in bytes.
+    pub len: usize,
+    /// Maximum length the frame can carry in bytes.
+    pub max: usize,
+}
+
+/// Raised when a versioned payload can no longer be decoded, see `Schema`.
+#[derive(Debug, Clone, Copy)]
+pub struct IncompatibleSchema {
+    /// Version the payload was written with.
+    pub version: u8,
+    /// Oldest version the decoder accepts.
+    pub oldest_compatible: u8,
+}
+
+/// Raised when a CAN ID filter is asked to accept more IDs than it has room for.
+#[derive(Debug, Clone, Copy)]
+pub struct TooManyFilterIds {
+    /// Number of IDs requested.
+    pub requested: usize,
+    /// Number of IDs the filters can hold.
+    pub max: usize,
+}
+
+/// Raised when a CAN peripheral stopped after too many transmit errors and a recovery was started.
+#[derive(Debug, Clone, Copy)]
+pub struct CanBusOff {
+    /// Name of the bus, e.g. "data".
+    pub bus: &'static str,
+    /// Number of recoveries started on this bus since boot, including this one.
+    pub recoveries: u32,
+}
+
+/// Raised when a CAN peripheral doesn't return a message sent in internal loopback unchanged.
+#[derive(Debug, Clone, Copy)]
+pub struct CanSelfTestFailed {
+    /// Name of the bus, e.g. "data".
+    pub bus: &'static str,
+}
+
 /// Standard HYDRA error. This type should be used as the return type for most functions that can
 /// fail and that returns a `Result`.
 #[derive(Format)]
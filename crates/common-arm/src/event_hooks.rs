@@ -0,0 +1,57 @@
+//! Bounded list of user hooks run after a data update.
+//!
+//! Hooks are plain function pointers so registering one needs no allocation. Each hook sees the
+//! updated state and checks its own condition, e.g. "altitude above X and descending". Hooks run
+//! synchronously in the context of the update, so they must be short and must not block. To do
+//! real work, spawn a task from the hook.
+
+use defmt::warn;
+use heapless::Vec;
+
+/// A hook called with the state after each update.
+pub type EventHook<T> = fn(&T);
+
+#[derive(Clone)]
+pub struct EventHooks<T, const N: usize> {
+    hooks: Vec<EventHook<T>, N>,
+}
+
+impl<T, const N: usize> EventHooks<T, N> {
+    pub fn new() -> Self {
+        Self { hooks: Vec::new() }
+    }
+
+    /// Registers a hook. Returns false if all `N` slots are taken.
+    pub fn register(&mut self, hook: EventHook<T>) -> bool {
+        if self.hooks.push(hook).is_err() {
+            warn!("No free event hook slot, hook not registered");
+            return false;
+        }
+        true
+    }
+
+    /// Calls every registered hook with `state`, in registration order.
+    pub fn run(&self, state: &T) {
+        for hook in &self.hooks {
+            hook(state);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.hooks.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.hooks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+}
+
+impl<T, const N: usize> Default for EventHooks<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,92 @@
+//! Records of the flight index, a small CSV file on the SD card listing every flight session.
+//!
+//! A recovery tool reads the index to find the flights on a card without scanning every data
+//! file. Each session gets a record at boot and another with the summary at landing or shutdown.
+//! The file is only ever appended to and every record is exactly [`RECORD_LEN`] bytes, so a reset
+//! mid-write can damage at most the last record. Readers take the last record of each session.
+//! Missing values are written as `-`.
+
+use core::fmt::Write;
+use heapless::String;
+
+/// Name of the index file on the card.
+pub const INDEX_FILE_NAME: &str = "flights.csv";
+
+/// Length of every record including the trailing newline.
+pub const RECORD_LEN: usize = 64;
+
+/// First line of a new index file, padded like a record.
+pub const INDEX_HEADER: &[u8; RECORD_LEN] =
+    b"session,boot_s,reset,file,duration_s,peak_alt_m,boost_clip     \n";
+
+/// Longest reset reason kept, longer ones are truncated.
+const MAX_RESET_LEN: usize = 16;
+/// Longest file name kept, enough for an 8.3 name.
+const MAX_FILE_LEN: usize = 12;
+
+#[derive(Clone, Copy)]
+pub struct FlightRecord<'a> {
+    /// Counts boots, so the boot and summary records of a session can be matched.
+    pub session: u32,
+    /// RTC time at boot, in s since the epoch.
+    pub boot_s: u64,
+    pub reset_reason: &'a str,
+    /// Data file written during the session.
+    pub file_name: &'a str,
+    /// Time from boot to landing or shutdown, `None` in the boot record.
+    pub duration_s: Option<u32>,
+    /// Highest altitude above the pad, `None` in the boot record.
+    pub peak_altitude_m: Option<f32>,
+    /// Whether the accelerometer saturated during boost, `None` in the boot record. Written as
+    /// `1` or `0`.
+    pub boost_accel_clipped: Option<bool>,
+}
+
+impl FlightRecord<'_> {
+    /// Encodes the record as one padded CSV line.
+    pub fn encode(&self) -> [u8; RECORD_LEN] {
+        let mut line: String<RECORD_LEN> = String::new();
+        // Every field is bounded, so the line always fits before the newline.
+        write!(
+            line,
+            "{},{},{},{},",
+            self.session,
+            self.boot_s,
+            truncate(self.reset_reason, MAX_RESET_LEN),
+            truncate(self.file_name, MAX_FILE_LEN),
+        )
+        .ok();
+        match self.duration_s {
+            Some(duration_s) => write!(line, "{},", duration_s).ok(),
+            None => line.push_str("-,").ok(),
+        };
+        match self.peak_altitude_m {
+            Some(peak) => write!(line, "{:.1},", peak).ok(),
+            None => line.push_str("-,").ok(),
+        };
+        match self.boost_accel_clipped {
+            Some(clipped) => line.push(if clipped { '1' } else { '0' }).ok(),
+            None => line.push('-').ok(),
+        };
+
+        let mut record = [b' '; RECORD_LEN];
+        let len = line.len().min(RECORD_LEN - 1);
+        record[..len].copy_from_slice(&line.as_bytes()[..len]);
+        record[RECORD_LEN - 1] = b'\n';
+        record
+    }
+
+    /// Session of an encoded record, `None` for the header or a damaged record.
+    pub fn session_of(record: &[u8]) -> Option<u32> {
+        let end = record.iter().position(|b| *b == b',')?;
+        core::str::from_utf8(&record[..end]).ok()?.parse().ok()
+    }
+}
+
+fn truncate(s: &str, max: usize) -> &str {
+    let mut end = s.len().min(max);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
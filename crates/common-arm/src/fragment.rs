@@ -0,0 +1,224 @@
+//! Splitting messages too long for a single frame across several frames.
+//!
+//! Used for CAN FD frames of 64 bytes and radio payloads of 255 bytes alike. Each fragment starts
+//! with [`FRAGMENT_MAGIC`] and a header byte holding the sequence number in its low seven bits and
+//! [`LAST_FRAGMENT`] on the final fragment. The first fragment then gives the length of the whole
+//! message as a little endian `u16`, so padding the link adds after the last fragment, such as
+//! the rounding of CAN FD frame lengths or the zero padding of a MAVLink payload, is dropped.
+//! Messages that fit a single frame are sent as they are, so receivers check [`is_fragment`]
+//! first and decode a plain postcard message otherwise.
+//!
+//! Fragments of one message are sent back to back, so the receiver tells interleaved messages
+//! apart by the sender alone. A fragment out of sequence discards the message it belongs to, there
+//! are no retransmissions.
+
+use crate::{HydraError, PayloadTooLarge};
+
+/// Marks a frame as a fragment rather than a single postcard message.
+pub const FRAGMENT_MAGIC: [u8; 2] = [0xF7, 0xA6];
+/// Set in the header byte of the final fragment of a message.
+pub const LAST_FRAGMENT: u8 = 0x80;
+const SEQUENCE_MASK: u8 = 0x7F;
+/// Bytes each fragment spends on the magic and the header byte.
+pub const FRAGMENT_HEADER_LEN: usize = FRAGMENT_MAGIC.len() + 1;
+/// Bytes the first fragment additionally spends on the message length.
+const LENGTH_LEN: usize = 2;
+
+/// Longest message that can be sent in frames of `frame_len` bytes.
+pub const fn max_fragmented_len(frame_len: usize) -> usize {
+    let data_len = frame_len.saturating_sub(FRAGMENT_HEADER_LEN);
+    let len = (SEQUENCE_MASK as usize + 1) * data_len;
+    let len = len.saturating_sub(LENGTH_LEN);
+    if len < u16::MAX as usize {
+        len
+    } else {
+        u16::MAX as usize
+    }
+}
+
+/// Returns true if `frame` is a fragment of a longer message.
+pub fn is_fragment(frame: &[u8]) -> bool {
+    frame.len() > FRAGMENT_HEADER_LEN && frame[..FRAGMENT_MAGIC.len()] == FRAGMENT_MAGIC
+}
+
+/// Cuts a message into fragments, one frame at a time.
+pub struct Fragmenter<'a> {
+    message: &'a [u8],
+    /// Bytes of `message` already written.
+    written: usize,
+    frame_len: usize,
+    sequence: u8,
+}
+
+impl<'a> Fragmenter<'a> {
+    /// Fragments `message` into frames of at most `frame_len` bytes.
+    pub fn new(message: &'a [u8], frame_len: usize) -> Result<Self, HydraError> {
+        let max = max_fragmented_len(frame_len);
+        if message.len() > max || frame_len <= FRAGMENT_HEADER_LEN + LENGTH_LEN {
+            return Err(PayloadTooLarge {
+                len: message.len(),
+                max,
+            }
+            .into());
+        }
+        Ok(Self {
+            message,
+            written: 0,
+            frame_len,
+            sequence: 0,
+        })
+    }
+
+    /// Writes the next fragment into `frame`, which must hold the `frame_len` given to
+    /// [`Self::new`], and returns its length. Returns `None` once the whole message was written.
+    pub fn next_frame(&mut self, frame: &mut [u8]) -> Option<usize> {
+        if self.written == self.message.len() && self.sequence > 0 {
+            return None;
+        }
+        let mut len = FRAGMENT_HEADER_LEN;
+        if self.sequence == 0 {
+            let total = self.message.len() as u16;
+            frame[len..len + LENGTH_LEN].copy_from_slice(&total.to_le_bytes());
+            len += LENGTH_LEN;
+        }
+        let data_len = (self.message.len() - self.written).min(self.frame_len - len);
+        let data = &self.message[self.written..self.written + data_len];
+        frame[len..len + data_len].copy_from_slice(data);
+        len += data_len;
+        self.written += data_len;
+
+        let mut header = self.sequence & SEQUENCE_MASK;
+        if self.written == self.message.len() {
+            header |= LAST_FRAGMENT;
+        }
+        frame[..FRAGMENT_MAGIC.len()].copy_from_slice(&FRAGMENT_MAGIC);
+        frame[FRAGMENT_MAGIC.len()] = header;
+        self.sequence = self.sequence.wrapping_add(1);
+        Some(len)
+    }
+}
+
+/// A message being put back together from the fragments of one sender.
+struct Partial<const N: usize> {
+    source: Option<u8>,
+    next_sequence: u8,
+    /// Length of the whole message, from the first fragment.
+    total: usize,
+    len: usize,
+    /// Value of `Reassembler::activity` when the last fragment arrived, to pick a slot to evict.
+    last_activity: u32,
+    buf: [u8; N],
+}
+
+/// Reassembles fragmented messages of up to `N` bytes from up to `SOURCES` senders at once.
+pub struct Reassembler<const SOURCES: usize, const N: usize> {
+    partials: [Partial<N>; SOURCES],
+    activity: u32,
+    dropped: u32,
+}
+
+impl<const SOURCES: usize, const N: usize> Reassembler<SOURCES, N> {
+    pub fn new() -> Self {
+        Self {
+            partials: core::array::from_fn(|_| Partial {
+                source: None,
+                next_sequence: 0,
+                total: 0,
+                len: 0,
+                last_activity: 0,
+                buf: [0; N],
+            }),
+            activity: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Adds a fragment received from `source`, e.g. the sending node. Returns the whole message
+    /// once its last fragment arrived.
+    ///
+    /// A fragment that doesn't follow the previous one from the same sender, or a message longer
+    /// than `N`, is discarded and counted in [`Self::dropped`]. A first fragment with all slots
+    /// taken evicts the message that was idle the longest, whose sender most likely reset halfway
+    /// through it.
+    pub fn push(&mut self, source: u8, frame: &[u8]) -> Option<&[u8]> {
+        if !is_fragment(frame) {
+            return None;
+        }
+        let header = frame[FRAGMENT_MAGIC.len()];
+        let sequence = header & SEQUENCE_MASK;
+        let mut data = &frame[FRAGMENT_HEADER_LEN..];
+        self.activity = self.activity.wrapping_add(1);
+
+        let index = match self.partials.iter().position(|p| p.source == Some(source)) {
+            Some(index) => index,
+            None if sequence != 0 => {
+                // The start of this message was missed.
+                self.dropped = self.dropped.wrapping_add(1);
+                return None;
+            }
+            None => self.free_slot(),
+        };
+        let partial = &mut self.partials[index];
+        if sequence == 0 {
+            if partial.source.is_some() {
+                // The sender started over before finishing the previous message.
+                self.dropped = self.dropped.wrapping_add(1);
+            }
+            if data.len() < LENGTH_LEN {
+                partial.source = None;
+                self.dropped = self.dropped.wrapping_add(1);
+                return None;
+            }
+            partial.source = Some(source);
+            partial.next_sequence = 0;
+            partial.total = u16::from_le_bytes([data[0], data[1]]) as usize;
+            partial.len = 0;
+            data = &data[LENGTH_LEN..];
+        }
+        if sequence != partial.next_sequence || partial.total > N {
+            partial.source = None;
+            self.dropped = self.dropped.wrapping_add(1);
+            return None;
+        }
+        // Anything past the announced length is padding.
+        let data = &data[..data.len().min(partial.total - partial.len)];
+        partial.buf[partial.len..partial.len + data.len()].copy_from_slice(data);
+        partial.len += data.len();
+        partial.next_sequence = partial.next_sequence.wrapping_add(1);
+        partial.last_activity = self.activity;
+        if header & LAST_FRAGMENT == 0 {
+            return None;
+        }
+        partial.source = None;
+        if partial.len != partial.total {
+            self.dropped = self.dropped.wrapping_add(1);
+            return None;
+        }
+        Some(&partial.buf[..partial.len])
+    }
+
+    /// Index of a free slot, or of the one idle the longest, which is then discarded.
+    fn free_slot(&mut self) -> usize {
+        if let Some(index) = self.partials.iter().position(|p| p.source.is_none()) {
+            return index;
+        }
+        let activity = self.activity;
+        let index = (0..SOURCES)
+            .max_by_key(|&i| activity.wrapping_sub(self.partials[i].last_activity))
+            .unwrap_or(0);
+        self.partials[index].source = None;
+        self.dropped = self.dropped.wrapping_add(1);
+        index
+    }
+
+    /// Number of messages discarded because of missing or out of sequence fragments.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+}
+
+impl<const SOURCES: usize, const N: usize> Default for Reassembler<SOURCES, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,48 @@
+//! Microsecond timestamps from a free-running 32-bit cycle counter, such as the Cortex-M DWT
+//! `CYCCNT`.
+//!
+//! Message timestamps come from the RTC, which is far too coarse to tell apart IMU samples taken
+//! a few ms apart. The cycle counter has the resolution but wraps (every ~21 s at 200 MHz), so
+//! each reading is extended to 64 bits from the previous one. The clock must therefore be read at
+//! least once per wrap. Stamps are strictly increasing, two stamps taken within the same
+//! microsecond differ by one.
+
+#[derive(Clone)]
+pub struct HighResClock {
+    cycles_per_us: u32,
+    last_cycles: Option<u32>,
+    /// Cycles counted since the first reading, extended past the 32-bit wrap.
+    elapsed_cycles: u64,
+    last_us: Option<u64>,
+}
+
+impl HighResClock {
+    /// `cycles_per_us` is the counter frequency in MHz, e.g. 200 for the core clock at 200 MHz.
+    pub fn new(cycles_per_us: u32) -> Self {
+        Self {
+            cycles_per_us: cycles_per_us.max(1),
+            last_cycles: None,
+            elapsed_cycles: 0,
+            last_us: None,
+        }
+    }
+
+    /// Returns microseconds since the first call, given the current counter value.
+    pub fn stamp_us(&mut self, cycles: u32) -> u64 {
+        if let Some(last) = self.last_cycles {
+            self.elapsed_cycles += u64::from(cycles.wrapping_sub(last));
+        }
+        self.last_cycles = Some(cycles);
+        let mut us = self.elapsed_cycles / u64::from(self.cycles_per_us);
+        if let Some(last_us) = self.last_us {
+            us = us.max(last_us + 1);
+        }
+        self.last_us = Some(us);
+        us
+    }
+
+    /// The last stamp handed out.
+    pub fn last_us(&self) -> Option<u64> {
+        self.last_us
+    }
+}
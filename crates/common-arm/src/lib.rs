@@ -6,14 +6,64 @@
 //! here.
 //!
 
+mod can_priority;
+mod can_stats;
+mod change_emitter;
+mod delta_codec;
 pub mod drivers;
 mod error;
+mod event_hooks;
+mod flight_index;
+mod fragment;
+mod high_res_clock;
+mod link_monitor;
+mod log_rate;
 mod logging;
+mod mav_identity;
+mod peak_tracker;
+mod pretrigger_buffer;
+mod radio_batch;
 mod sd_manager;
+mod stack;
+mod telemetry_detail;
+mod time_alignment;
+mod token_bucket;
+mod update_times;
+mod verbosity;
+mod versioned;
 
-pub use crate::error::error_manager::ErrorManager;
-pub use crate::error::hydra_error::{ErrorContextTrait, HydraError, SpawnError};
+pub use crate::can_priority::{can_id_for, CanPriority};
+pub use crate::can_stats::CanStats;
+pub use crate::change_emitter::ChangeEmitter;
+pub use crate::delta_codec::{
+    delta_stream, is_delta_frame, DeltaDecoder, DeltaEncoder, DELTA, DELTA_MAGIC, KEYFRAME,
+};
+pub use crate::error::error_manager::{ErrorManager, DEFAULT_ERROR_HISTORY_LEN};
+pub use crate::error::hydra_error::{
+    CanBusOff, CanSelfTestFailed, ErrorContextTrait, HydraError, IncompatibleSchema,
+    PayloadTooLarge, SpawnError, TooManyFilterIds,
+};
+pub use crate::event_hooks::{EventHook, EventHooks};
+pub use crate::flight_index::{FlightRecord, INDEX_FILE_NAME, INDEX_HEADER, RECORD_LEN};
+pub use crate::fragment::{
+    is_fragment, max_fragmented_len, Fragmenter, Reassembler, FRAGMENT_HEADER_LEN, FRAGMENT_MAGIC,
+    LAST_FRAGMENT,
+};
+pub use crate::high_res_clock::HighResClock;
+pub use crate::link_monitor::LinkMonitor;
+pub use crate::log_rate::LogRate;
 pub use crate::logging::HydraLogging;
-pub use crate::sd_manager::SdManager;
+pub use crate::mav_identity::{MavIdentity, DEFAULT_MAV_COMPONENT_ID, DEFAULT_MAV_SYSTEM_ID};
+pub use crate::peak_tracker::PeakTracker;
+pub use crate::pretrigger_buffer::{PretriggerBuffer, EVENT_FILE_NAME};
+pub use crate::radio_batch::{is_batch, unbatch, Batch, Unbatch, BATCH_MAGIC};
+pub use crate::sd_manager::{SdManager, LOG_FILE_NAME};
+pub use crate::stack::{paint_stack, stack_high_water, stack_size};
+pub use crate::telemetry_detail::{TelemetryDetail, TelemetryDetailSelector};
+pub use crate::time_alignment::{AlignedSignal, EvaluationClock};
+pub use crate::token_bucket::TokenBucket;
+pub use crate::update_times::UpdateTimes;
+pub use crate::verbosity::{LogForwarding, Verbosity, VerbositySettings, VERBOSITY_MAGIC};
+pub use crate::versioned::Schema;
 
 use defmt_rtt as _; // global logger
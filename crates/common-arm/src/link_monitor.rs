@@ -0,0 +1,66 @@
+//! Radio link supervision.
+//!
+//! Nothing safety-critical may wait on the ground, so once no frame has been received from the
+//! ground for `timeout_ms` the link is lost and the firmware commits to autonomous recovery. The
+//! autonomous flag latches: a link that comes back mid-flight is reported, but doesn't hand
+//! control back to ground input.
+
+use defmt::{info, warn};
+
+#[derive(Clone)]
+pub struct LinkMonitor {
+    /// Time without a received frame after which the link is lost, in ms.
+    timeout_ms: u64,
+    /// Time of the first update, the timeout before any frame counts from it.
+    started_ms: Option<u64>,
+    link_lost: bool,
+    autonomous: bool,
+}
+
+impl LinkMonitor {
+    pub fn new(timeout_ms: u64) -> Self {
+        Self {
+            timeout_ms,
+            started_ms: None,
+            link_lost: false,
+            autonomous: false,
+        }
+    }
+
+    /// Re-evaluates the link from the time the last frame was received, `None` if none was.
+    /// Returns true once autonomous mode is committed. Call periodically.
+    pub fn update(&mut self, last_receive_ms: Option<u64>, now_ms: u64) -> bool {
+        let started_ms = *self.started_ms.get_or_insert(now_ms);
+        let since = last_receive_ms.unwrap_or(started_ms).max(started_ms);
+        let lost = now_ms.saturating_sub(since) > self.timeout_ms;
+        if lost && !self.link_lost {
+            warn!("Radio link lost for {} ms", self.timeout_ms);
+            if !self.autonomous {
+                warn!("Committing to autonomous recovery");
+                self.autonomous = true;
+            }
+        } else if !lost && self.link_lost {
+            info!("Radio link restored, staying autonomous");
+        }
+        self.link_lost = lost;
+        self.autonomous
+    }
+
+    /// State as of the last call to [`Self::update`].
+    pub fn is_link_lost(&self) -> bool {
+        self.link_lost
+    }
+
+    /// Set once the link has been lost, ground input is no longer waited on.
+    pub fn is_autonomous(&self) -> bool {
+        self.autonomous
+    }
+
+    pub fn set_timeout(&mut self, timeout_ms: u64) {
+        self.timeout_ms = timeout_ms;
+    }
+
+    pub fn get_timeout(&self) -> u64 {
+        self.timeout_ms
+    }
+}
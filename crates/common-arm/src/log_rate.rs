@@ -0,0 +1,40 @@
+//! Time-based rate limit for a logging sink.
+//!
+//! Each sink (radio, SD card) keeps its own `LogRate`, so the SD card can take every sample while
+//! the radio only gets a subset of the same stream.
+
+#[derive(Clone)]
+pub struct LogRate {
+    /// Minimum time between logged samples in ms, 0 logs every sample.
+    period_ms: u64,
+    last_ms: Option<u64>,
+}
+
+impl LogRate {
+    pub fn new(period_ms: u64) -> Self {
+        Self {
+            period_ms,
+            last_ms: None,
+        }
+    }
+
+    /// Returns true if a sample arriving now should be logged, and counts it as logged.
+    pub fn due(&mut self, now_ms: u64) -> bool {
+        let due = match self.last_ms {
+            Some(last) => now_ms.saturating_sub(last) >= self.period_ms,
+            None => true,
+        };
+        if due {
+            self.last_ms = Some(now_ms);
+        }
+        due
+    }
+
+    pub fn set_period(&mut self, period_ms: u64) {
+        self.period_ms = period_ms;
+    }
+
+    pub fn get_period(&self) -> u64 {
+        self.period_ms
+    }
+}
@@ -1,6 +1,10 @@
+use crate::LogForwarding;
+use core::sync::atomic::{AtomicU8, Ordering};
 use messages::{Event, Log, LogLevel};
 
 static mut GROUND_STATION_CALLBACK: Option<fn(Log)> = None;
+/// [`LogForwarding`] as a `u8`, so it can change at any time without a lock.
+static FORWARDING: AtomicU8 = AtomicU8::new(LogForwarding::All as u8);
 
 /// Log an informational message. This message will be logged using defmt, and if configured, sent
 /// to the ground station. As arguments, it takes an event from the [`Event`] enum, along with its
@@ -46,10 +50,23 @@ impl HydraLogging {
         unsafe { GROUND_STATION_CALLBACK = Some(cb) }
     }
 
+    /// Set the lowest level of the messages sent to the ground station. Every message is still
+    /// logged with defmt.
+    pub fn set_forwarding(forwarding: LogForwarding) {
+        FORWARDING.store(forwarding as u8, Ordering::Relaxed);
+    }
+
+    pub fn forwarding() -> LogForwarding {
+        LogForwarding::from_u8(FORWARDING.load(Ordering::Relaxed))
+    }
+
     /// Log a message using the callback set in [`HydraLogging::set_ground_station_callback`].
     /// While this function can be called directly, usually the [`hinfo`] and similar macros would
     /// be used instead.
     pub fn log(level: LogLevel, event: Event) {
+        if !Self::forwarding().forwards(&level) {
+            return;
+        }
         // SAFETY:
         // Since the static mut should only be written once during init and never after, reading
         // this variable is fine.
@@ -0,0 +1,52 @@
+//! MAVLink identity of this board on the radio link.
+//!
+//! Every MAVLink frame names the system and component that sent it, and the ground station keeps
+//! a link per pair. Two vehicles sending as the same pair to a shared receiver look like a single
+//! vehicle with garbled sequence numbers, so each one flown together needs its own system ID.
+
+use messages::mavlink::MavHeader;
+
+pub const DEFAULT_MAV_SYSTEM_ID: u8 = 1;
+pub const DEFAULT_MAV_COMPONENT_ID: u8 = 1;
+
+/// Builds the header of each frame sent, numbering the frames.
+#[derive(Clone)]
+pub struct MavIdentity {
+    system_id: u8,
+    component_id: u8,
+    sequence: u8,
+}
+
+impl MavIdentity {
+    pub fn new(system_id: u8, component_id: u8) -> Self {
+        Self {
+            system_id,
+            component_id,
+            sequence: 0,
+        }
+    }
+
+    /// Header for the next frame, with the sequence number advanced. It wraps after 255 frames.
+    pub fn next_header(&mut self) -> MavHeader {
+        self.sequence = self.sequence.wrapping_add(1);
+        MavHeader {
+            system_id: self.system_id,
+            component_id: self.component_id,
+            sequence: self.sequence,
+        }
+    }
+
+    pub fn system_id(&self) -> u8 {
+        self.system_id
+    }
+
+    pub fn component_id(&self) -> u8 {
+        self.component_id
+    }
+}
+
+impl Default for MavIdentity {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAV_SYSTEM_ID, DEFAULT_MAV_COMPONENT_ID)
+    }
+}
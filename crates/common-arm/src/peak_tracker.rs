@@ -0,0 +1,39 @@
+//! Largest value seen since the last reset, such as the peak altitude of a flight.
+//!
+//! The ground station could reconstruct the peak from the altitude stream, but only from the
+//! samples that made it over the radio. Tracking it on board catches every sample.
+
+#[derive(Clone)]
+pub struct PeakTracker {
+    peak: Option<f32>,
+}
+
+impl PeakTracker {
+    pub fn new() -> Self {
+        Self { peak: None }
+    }
+
+    /// Feeds a sample and returns the peak so far. Samples that aren't finite are ignored.
+    pub fn update(&mut self, value: f32) -> Option<f32> {
+        if value.is_finite() {
+            self.peak = Some(self.peak.map_or(value, |peak| peak.max(value)));
+        }
+        self.peak
+    }
+
+    /// Largest sample since the last reset, `None` if there was none.
+    pub fn peak(&self) -> Option<f32> {
+        self.peak
+    }
+
+    /// Forgets the peak, e.g. when the vehicle is re-armed on the ground.
+    pub fn reset(&mut self) {
+        self.peak = None;
+    }
+}
+
+impl Default for PeakTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
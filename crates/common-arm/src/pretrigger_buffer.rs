@@ -0,0 +1,113 @@
+//! Circular buffer that keeps the moments before an event for the SD log.
+//!
+//! Logging every sample at full rate all the time fills the card and the bus, but the moments
+//! around apogee, a deploy or an anomaly are worth having at full rate. The buffer always holds
+//! the last `pretrigger_ms` of samples. When an event triggers, those samples and everything
+//! pushed in the following `posttrigger_ms` are handed out by [`PretriggerBuffer::drain`] to be
+//! committed to SD. Another trigger during a capture extends it.
+//!
+//! Events are committed to [`EVENT_FILE_NAME`], see `SdManager::append`.
+
+use defmt::{info, warn};
+use heapless::Deque;
+
+/// File on the SD card that event captures are appended to.
+pub const EVENT_FILE_NAME: &str = "events.bin";
+
+#[derive(Clone)]
+pub struct PretriggerBuffer<T, const N: usize> {
+    /// Time kept before a trigger, in ms.
+    pretrigger_ms: u64,
+    /// Time captured after a trigger, in ms.
+    posttrigger_ms: u64,
+    samples: Deque<(u64, T), N>,
+    /// End of the running capture, `None` while idle.
+    capture_until_ms: Option<u64>,
+    /// Samples dropped during a capture because the buffer wasn't drained in time.
+    overflowed: u32,
+}
+
+impl<T, const N: usize> PretriggerBuffer<T, N> {
+    pub fn new(pretrigger_ms: u64, posttrigger_ms: u64) -> Self {
+        Self {
+            pretrigger_ms,
+            posttrigger_ms,
+            samples: Deque::new(),
+            capture_until_ms: None,
+            overflowed: 0,
+        }
+    }
+
+    /// Adds a sample. While idle, samples older than the pre-trigger window are discarded.
+    pub fn push(&mut self, sample: T, now_ms: u64) {
+        let capturing = self.is_capturing(now_ms);
+        if !capturing {
+            while let Some((at_ms, _)) = self.samples.front() {
+                if now_ms.saturating_sub(*at_ms) <= self.pretrigger_ms {
+                    break;
+                }
+                self.samples.pop_front();
+            }
+        }
+        if self.samples.is_full() {
+            self.samples.pop_front();
+            if capturing {
+                self.overflowed = self.overflowed.saturating_add(1);
+            }
+        }
+        self.samples.push_back((now_ms, sample)).ok();
+    }
+
+    /// Starts a capture, or extends the running one.
+    pub fn trigger(&mut self, now_ms: u64) {
+        if !self.is_capturing(now_ms) {
+            info!(
+                "Event capture started with {} pre-trigger samples",
+                self.samples.len()
+            );
+        }
+        self.capture_until_ms = Some(now_ms + self.posttrigger_ms);
+    }
+
+    /// Returns true from a trigger until the post-trigger window has run out.
+    pub fn is_capturing(&self, now_ms: u64) -> bool {
+        self.capture_until_ms.is_some_and(|until| now_ms <= until)
+    }
+
+    /// Hands out the samples to commit, oldest first. Yields nothing while idle, except what is
+    /// left over from a capture that just ended.
+    pub fn drain(&mut self, now_ms: u64) -> impl Iterator<Item = T> + '_ {
+        let commit = self.capture_until_ms.is_some();
+        if commit && !self.is_capturing(now_ms) {
+            if self.overflowed > 0 {
+                warn!("Event capture lost {} samples", self.overflowed);
+            }
+            self.capture_until_ms = None;
+            self.overflowed = 0;
+        }
+        core::iter::from_fn(move || {
+            if commit {
+                self.samples.pop_front().map(|(_, sample)| sample)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Number of samples currently held.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn set_pretrigger(&mut self, pretrigger_ms: u64) {
+        self.pretrigger_ms = pretrigger_ms;
+    }
+
+    pub fn set_posttrigger(&mut self, posttrigger_ms: u64) {
+        self.posttrigger_ms = posttrigger_ms;
+    }
+}
@@ -0,0 +1,113 @@
+//! Packing several postcard messages into a single radio payload.
+//!
+//! Each MAVLink frame carries a fixed framing overhead, so small messages that are ready at the
+//! same time are cheaper to send together. A batch payload starts with [`BATCH_MAGIC`], followed
+//! by each message as a length byte and its postcard encoding. A zero length byte, such as the
+//! zero padding of a fixed-size MAVLink payload, ends the batch. Receivers check
+//! [`is_batch`] first and decode a plain postcard message otherwise.
+
+use crate::HydraError;
+use messages::Message;
+
+/// Marks a payload as a batch rather than a single postcard message.
+pub const BATCH_MAGIC: [u8; 2] = [0xBA, 0x7C];
+
+/// A batch being built in a buffer of `N` bytes, at most 256.
+pub struct Batch<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    count: usize,
+}
+
+impl<const N: usize> Batch<N> {
+    pub fn new() -> Self {
+        let mut buf = [0u8; N];
+        buf[..BATCH_MAGIC.len()].copy_from_slice(&BATCH_MAGIC);
+        Self {
+            buf,
+            len: BATCH_MAGIC.len(),
+            count: 0,
+        }
+    }
+
+    /// Appends a message. Returns `Ok(false)`, leaving the batch unchanged, if it doesn't fit.
+    pub fn push(&mut self, message: &Message) -> Result<bool, HydraError> {
+        if self.len + 1 >= N {
+            return Ok(false);
+        }
+        let encoded_len = match postcard::to_slice(message, &mut self.buf[self.len + 1..]) {
+            Ok(encoded) => encoded.len(),
+            Err(postcard::Error::SerializeBufferFull) => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        self.buf[self.len] = encoded_len as u8;
+        self.len += 1 + encoded_len;
+        self.count += 1;
+        Ok(true)
+    }
+
+    /// Number of messages in the batch.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The encoded batch, ready to be sent as a payload.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    pub fn clear(&mut self) {
+        self.buf[BATCH_MAGIC.len()..].fill(0);
+        self.len = BATCH_MAGIC.len();
+        self.count = 0;
+    }
+}
+
+impl<const N: usize> Default for Batch<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns true if the payload is a batch.
+pub fn is_batch(payload: &[u8]) -> bool {
+    payload.starts_with(&BATCH_MAGIC)
+}
+
+/// Decodes the messages of a batch payload in order. Yields nothing if the payload isn't a batch.
+pub fn unbatch(payload: &[u8]) -> Unbatch<'_> {
+    let rest = if is_batch(payload) {
+        &payload[BATCH_MAGIC.len()..]
+    } else {
+        &[]
+    };
+    Unbatch { rest }
+}
+
+pub struct Unbatch<'a> {
+    rest: &'a [u8],
+}
+
+impl Iterator for Unbatch<'_> {
+    type Item = Result<Message, HydraError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&len, rest) = self.rest.split_first()?;
+        let len = len as usize;
+        if len == 0 {
+            self.rest = &[];
+            return None;
+        }
+        if len > rest.len() {
+            self.rest = &[];
+            return Some(Err(postcard::Error::DeserializeUnexpectedEnd.into()));
+        }
+        let (encoded, rest) = rest.split_at(len);
+        self.rest = rest;
+        Some(postcard::from_bytes(encoded).map_err(HydraError::from))
+    }
+}
@@ -1,10 +1,15 @@
+use crate::flight_index::{FlightRecord, INDEX_FILE_NAME, INDEX_HEADER, RECORD_LEN};
 use core::{fmt::Debug, marker::PhantomData};
 use defmt::info;
 use defmt::panic;
 use embedded_hal as hal;
 use embedded_sdmmc as sd;
+use hal::blocking::delay::DelayMs;
 use hal::spi::FullDuplex;
 
+/// Name of the log file opened on the card.
+pub const LOG_FILE_NAME: &str = "lc24.txt";
+
 /// Time source for `[SdInterface]`. It doesn't return any useful information for now, and will
 /// always return an arbitrary time.
 pub struct TimeSink {
@@ -41,8 +46,10 @@ where
 {
     pub sd_controller: sd::Controller<sd::SdMmcSpi<SPI, CS>, TimeSink>,
     pub volume: sd::Volume,
-    pub root_directory: sd::Directory,
+    /// `None` after a [`Self::reinit`] that failed before the root directory was reopened.
+    pub root_directory: Option<sd::Directory>,
     pub file: Option<sd::File>,
+    ready: bool,
 }
 
 impl<SPI, CS> SdManager<SPI, CS>
@@ -81,7 +88,7 @@ where
         let file = sd_cont.open_file_in_dir(
             &mut volume,
             &root_directory,
-            "lc24.txt",
+            LOG_FILE_NAME,
             sd::Mode::ReadWriteCreateOrTruncate,
         );
         let file = match file {
@@ -94,10 +101,125 @@ where
         SdManager {
             sd_controller: sd_cont,
             volume,
-            root_directory,
+            root_directory: Some(root_directory),
             file: Some(file),
+            ready: true,
+        }
+    }
+
+    /// Like [`SdManager::new`], but retries card initialization up to `attempts` times with
+    /// `retry_delay_ms` between attempts, and returns an error instead of panicking. Slow cards
+    /// and cards that are still settling after insertion often need more than one attempt.
+    pub fn new_with_retry<D: DelayMs<u32>>(
+        spi: SPI,
+        cs: CS,
+        attempts: u8,
+        retry_delay_ms: u32,
+        delay: &mut D,
+    ) -> Result<Self, sd::Error<sd::SdMmcError>> {
+        let time_sink: TimeSink = TimeSink::new();
+        info!("Initializing SD card");
+        let mut sd_cont = sd::Controller::new(sd::SdMmcSpi::new(spi, cs), time_sink);
+        Self::init_card(sd_cont.device(), attempts, retry_delay_ms, delay)
+            .map_err(sd::Error::DeviceError)?;
+        let (volume, root_directory, file) =
+            Self::open_log(&mut sd_cont, sd::Mode::ReadWriteCreateOrTruncate)?;
+
+        Ok(SdManager {
+            sd_controller: sd_cont,
+            volume,
+            root_directory: Some(root_directory),
+            file: Some(file),
+            ready: true,
+        })
+    }
+
+    /// Initializes the card again and reopens the volume, the root directory and the log file,
+    /// e.g. after the card was reseated mid-session. A reseated card may hold a different
+    /// filesystem, so nothing opened on the old one is reused. The log file is appended to rather
+    /// than truncated. The manager is marked not ready if this fails.
+    pub fn reinit(&mut self) -> Result<(), sd::Error<sd::SdMmcError>> {
+        self.ready = false;
+        // The card may already be gone, in which case there is nothing left to flush. Closing
+        // only releases the handles, the controller refuses to open them again otherwise.
+        if let Some(file) = self.file.take() {
+            self.close_file(file).ok();
+        }
+        if let Some(root_directory) = self.root_directory.take() {
+            self.sd_controller.close_dir(&self.volume, root_directory);
+        }
+        self.sd_controller
+            .device()
+            .init()
+            .map_err(sd::Error::DeviceError)?;
+        let (volume, root_directory, file) =
+            Self::open_log(&mut self.sd_controller, sd::Mode::ReadWriteCreateOrAppend)?;
+        self.volume = volume;
+        self.root_directory = Some(root_directory);
+        self.file = Some(file);
+        self.ready = true;
+        Ok(())
+    }
+
+    /// Returns true if the card is initialized and the log file is open. Cleared when a write to
+    /// the log file fails, until a [`Self::reinit`] succeeds.
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    /// Initializes the card, up to `attempts` times with `retry_delay_ms` between attempts.
+    /// Returns the attempt that succeeded.
+    pub fn init_card<D: DelayMs<u32>>(
+        device: &mut sd::SdMmcSpi<SPI, CS>,
+        attempts: u8,
+        retry_delay_ms: u32,
+        delay: &mut D,
+    ) -> Result<u8, sd::SdMmcError> {
+        let mut attempt = 1;
+        loop {
+            match device.init() {
+                Ok(_) => {
+                    info!("SD card ready after {} attempt(s)", attempt);
+                    return Ok(attempt);
+                }
+                Err(e) if attempt >= attempts => return Err(e),
+                Err(_) => {
+                    info!("SD card init attempt {} failed, retrying", attempt);
+                    delay.delay_ms(retry_delay_ms);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    fn open_log(
+        sd_cont: &mut sd::Controller<sd::SdMmcSpi<SPI, CS>, TimeSink>,
+        mode: sd::Mode,
+    ) -> Result<(sd::Volume, sd::Directory, sd::File), sd::Error<sd::SdMmcError>> {
+        let mut volume = sd_cont.get_volume(sd::VolumeIdx(0))?;
+        let root_directory = sd_cont.open_root_dir(&volume)?;
+        match sd_cont.open_file_in_dir(&mut volume, &root_directory, LOG_FILE_NAME, mode) {
+            Ok(file) => Ok((volume, root_directory, file)),
+            Err(e) => {
+                sd_cont.close_dir(&volume, root_directory);
+                Err(e)
+            }
         }
     }
+
+    /// Opens a file in the root directory, failing while [`Self::reinit`] hasn't reopened it.
+    fn open_in_root(
+        &mut self,
+        file_name: &str,
+        mode: sd::Mode,
+    ) -> Result<sd::File, sd::Error<sd::SdMmcError>> {
+        let root_directory = self
+            .root_directory
+            .as_ref()
+            .ok_or(sd::Error::NoSuchVolume)?;
+        self.sd_controller
+            .open_file_in_dir(&mut self.volume, root_directory, file_name, mode)
+    }
     pub fn write(
         &mut self,
         file: &mut sd::File,
@@ -105,6 +227,40 @@ where
     ) -> Result<usize, sd::Error<sd::SdMmcError>> {
         self.sd_controller.write(&mut self.volume, file, buffer)
     }
+    /// Appends `buffer` to the log file. Does nothing and returns 0 while no log file is open,
+    /// e.g. after a failed [`Self::reinit`].
+    pub fn write_log(&mut self, buffer: &[u8]) -> Result<usize, sd::Error<sd::SdMmcError>> {
+        let Some(mut file) = self.file.take() else {
+            return Ok(0);
+        };
+        let result = self.write(&mut file, buffer);
+        if result.is_err() {
+            self.ready = false;
+        }
+        self.file = Some(file);
+        result
+    }
+    /// Commits everything written to the log file so far to the card. embedded_sdmmc only writes
+    /// the file length to the directory entry when the file is closed, so the log file is closed
+    /// and opened again for appending. The manager is marked not ready if this fails.
+    pub fn flush_log(&mut self) -> Result<(), sd::Error<sd::SdMmcError>> {
+        let Some(file) = self.file.take() else {
+            return Ok(());
+        };
+        let reopened = self
+            .close_file(file)
+            .and_then(|_| self.open_in_root(LOG_FILE_NAME, sd::Mode::ReadWriteCreateOrAppend));
+        match reopened {
+            Ok(file) => {
+                self.file = Some(file);
+                Ok(())
+            }
+            Err(e) => {
+                self.ready = false;
+                Err(e)
+            }
+        }
+    }
     pub fn write_str(
         &mut self,
         file: &mut sd::File,
@@ -114,12 +270,53 @@ where
         self.sd_controller.write(&mut self.volume, file, buffer)
     }
     pub fn open_file(&mut self, file_name: &str) -> Result<sd::File, sd::Error<sd::SdMmcError>> {
-        self.sd_controller.open_file_in_dir(
-            &mut self.volume,
-            &self.root_directory,
-            file_name,
-            sd::Mode::ReadWriteCreateOrTruncate,
-        )
+        self.open_in_root(file_name, sd::Mode::ReadWriteCreateOrTruncate)
+    }
+    /// Appends a record to the flight index, creating the index with its header if needed. The
+    /// index is closed again right away so a reset can't leave it open.
+    pub fn append_index(&mut self, record: &FlightRecord) -> Result<(), sd::Error<sd::SdMmcError>> {
+        let mut file = self.open_in_root(INDEX_FILE_NAME, sd::Mode::ReadWriteCreateOrAppend)?;
+        let result = if file.length() == 0 {
+            self.write(&mut file, INDEX_HEADER)
+        } else {
+            Ok(0)
+        }
+        .and_then(|_| self.write(&mut file, &record.encode()));
+        self.close_file(file)?;
+        result.map(|_| ())
+    }
+    /// Session number for this boot, one more than the session of the last record in the flight
+    /// index. Sessions start at 1 on a card without an index.
+    pub fn next_session(&mut self) -> Result<u32, sd::Error<sd::SdMmcError>> {
+        let mut file = match self.open_in_root(INDEX_FILE_NAME, sd::Mode::ReadOnly) {
+            Ok(file) => file,
+            Err(sd::Error::FileNotFound) => return Ok(1),
+            Err(e) => return Err(e),
+        };
+        // The last whole record, a reset mid-write may have left a partial one after it.
+        let whole_records = file.length() / RECORD_LEN as u32;
+        let mut record = [0u8; RECORD_LEN];
+        let result = match whole_records.checked_sub(1) {
+            Some(last) if file.seek_from_start(last * RECORD_LEN as u32).is_ok() => self
+                .sd_controller
+                .read(&self.volume, &mut file, &mut record)
+                .map(|len| FlightRecord::session_of(&record[..len])),
+            _ => Ok(None),
+        };
+        self.close_file(file)?;
+        Ok(result?.map_or(1, |session| session.wrapping_add(1)))
+    }
+    /// Appends to a file, creating it if needed, and closes it again right away. Used to commit
+    /// event captures to `EVENT_FILE_NAME`.
+    pub fn append(
+        &mut self,
+        file_name: &str,
+        buffer: &[u8],
+    ) -> Result<usize, sd::Error<sd::SdMmcError>> {
+        let mut file = self.open_in_root(file_name, sd::Mode::ReadWriteCreateOrAppend)?;
+        let result = self.write(&mut file, buffer);
+        self.close_file(file)?;
+        result
     }
     pub fn close_current_file(&mut self) -> Result<(), sd::Error<sd::SdMmcError>> {
         if let Some(file) = self.file.take() {
@@ -131,8 +328,9 @@ where
         self.sd_controller.close_file(&self.volume, file)
     }
     pub fn close(mut self) {
-        self.sd_controller
-            .close_dir(&self.volume, self.root_directory);
+        if let Some(root_directory) = self.root_directory {
+            self.sd_controller.close_dir(&self.volume, root_directory);
+        }
     }
 }
 
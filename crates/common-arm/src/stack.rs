@@ -0,0 +1,55 @@
+//! Stack high-water mark by painting.
+//!
+//! The stack grows down from `_stack_start` towards the end of the statics (`__sheap`, there is
+//! no heap). `paint_stack` fills the unused part with a pattern early in init, and the deepest
+//! the stack has been since is the lowest word that no longer holds it. A frame that happens to
+//! leave the pattern in place reads as unused, so the mark can come out low by a few words but
+//! never high.
+
+use core::ptr::addr_of;
+
+const PAINT: u32 = 0xC0DE_5AFE;
+/// Bytes left unpainted just below the stack pointer, for the frames of the painting loop itself.
+const PAINT_MARGIN: usize = 256;
+
+extern "C" {
+    static __sheap: u32;
+    static _stack_start: u32;
+}
+
+fn stack_limit() -> usize {
+    // SAFETY: only the address of the linker symbol is taken.
+    unsafe { addr_of!(__sheap) as usize }
+}
+
+fn stack_top() -> usize {
+    // SAFETY: only the address of the linker symbol is taken.
+    unsafe { addr_of!(_stack_start) as usize }
+}
+
+/// Paints the stack below the current frame. Call once, first thing in init.
+pub fn paint_stack() {
+    let end = cortex_m::register::msp::read() as usize - PAINT_MARGIN;
+    let mut word = stack_limit();
+    while word < end {
+        // SAFETY: the words between the statics and the stack pointer belong to nobody yet.
+        unsafe { (word as *mut u32).write_volatile(PAINT) };
+        word += 4;
+    }
+}
+
+/// Bytes of RAM set aside for the stack.
+pub fn stack_size() -> usize {
+    stack_top() - stack_limit()
+}
+
+/// Deepest the stack has been since `paint_stack`, in bytes. Without painting it reads as the
+/// whole stack.
+pub fn stack_high_water() -> usize {
+    let mut word = stack_limit();
+    // SAFETY: reads stay within the stack region, which is always mapped.
+    while word < stack_top() && unsafe { (word as *const u32).read_volatile() } == PAINT {
+        word += 4;
+    }
+    stack_top() - word
+}
@@ -0,0 +1,74 @@
+//! Selection of how much telemetry the downlink carries.
+//!
+//! During boost the radio should carry a few streams at a high rate, while coast and descent leave
+//! bandwidth for every sensor with its full status fields. In [`TelemetryDetail::Compact`] only
+//! the streams in the compact set are sent, packed together into shared frames. In
+//! [`TelemetryDetail::Detailed`] every stream is sent as its own message. The level follows the
+//! flight phase unless the ground forces one.
+//!
+//! Streams are identified by their index, at most 32, such as `SensorKind as usize` on phoenix.
+
+use defmt::info;
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum TelemetryDetail {
+    Compact,
+    Detailed,
+}
+
+#[derive(Clone)]
+pub struct TelemetryDetailSelector {
+    /// Level picked for the current flight phase.
+    phase: TelemetryDetail,
+    /// Level forced from the ground, overrides the phase.
+    forced: Option<TelemetryDetail>,
+    /// Bit `i` is set if stream `i` is sent in compact mode.
+    compact_streams: u32,
+}
+
+impl TelemetryDetailSelector {
+    pub fn new(phase: TelemetryDetail, compact_streams: &[usize]) -> Self {
+        Self {
+            phase,
+            forced: None,
+            compact_streams: compact_streams
+                .iter()
+                .filter(|stream| **stream < 32)
+                .fold(0, |mask, stream| mask | 1 << stream),
+        }
+    }
+
+    /// Sets the level for the current flight phase. Has no visible effect while a level is forced.
+    pub fn set_phase_detail(&mut self, detail: TelemetryDetail) {
+        if detail != self.phase && self.forced.is_none() {
+            info!("Telemetry detail is now {}", detail);
+        }
+        self.phase = detail;
+    }
+
+    /// Forces a level regardless of the flight phase, or returns control to the phase with `None`.
+    pub fn force(&mut self, detail: Option<TelemetryDetail>) {
+        if detail != self.forced {
+            info!("Telemetry detail forced to {}", detail);
+        }
+        self.forced = detail;
+    }
+
+    pub fn current(&self) -> TelemetryDetail {
+        self.forced.unwrap_or(self.phase)
+    }
+
+    /// Returns true if `stream` is sent at the current level.
+    pub fn includes(&self, stream: usize) -> bool {
+        match self.current() {
+            TelemetryDetail::Detailed => true,
+            TelemetryDetail::Compact => stream < 32 && self.compact_streams & 1 << stream != 0,
+        }
+    }
+
+    /// Returns true if the streams are packed into shared frames rather than sent one message
+    /// each.
+    pub fn is_packed(&self) -> bool {
+        self.current() == TelemetryDetail::Compact
+    }
+}
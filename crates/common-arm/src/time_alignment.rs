@@ -0,0 +1,105 @@
+//! Alignment of sensors sampled at different rates to one evaluation time.
+//!
+//! The baro, the IMU and the GPS each report on their own cadence, so the latest value of each
+//! was taken at a different moment. Fusing them as they are mixes values from different times,
+//! which matters most while the vehicle is moving fast. Each [`AlignedSignal`] keeps the last two
+//! samples of one source with the time it was taken, and estimates its value at any instant by
+//! interpolating between them, or extrapolating past the newest one. An [`EvaluationClock`] picks
+//! the instants at which the aligned values are fused, at a fixed rate.
+
+/// One source's recent samples, in whatever unit it reports.
+#[derive(Clone, Copy)]
+pub struct AlignedSignal {
+    previous: Option<(u64, f32)>,
+    latest: Option<(u64, f32)>,
+    /// How far past the newest sample the value may be extrapolated, in ms.
+    max_extrapolation_ms: u64,
+}
+
+impl AlignedSignal {
+    pub fn new(max_extrapolation_ms: u64) -> Self {
+        Self {
+            previous: None,
+            latest: None,
+            max_extrapolation_ms,
+        }
+    }
+
+    /// Adds a sample taken at `at_ms`. Samples older than the newest one are ignored, a sample at
+    /// the same time replaces it.
+    pub fn push(&mut self, value: f32, at_ms: u64) {
+        if !value.is_finite() {
+            return;
+        }
+        match self.latest {
+            Some((latest_ms, _)) if at_ms < latest_ms => {}
+            Some((latest_ms, _)) if at_ms == latest_ms => self.latest = Some((at_ms, value)),
+            _ => {
+                self.previous = self.latest;
+                self.latest = Some((at_ms, value));
+            }
+        }
+    }
+
+    /// Value of the signal at `at_ms`, or `None` if there is no sample yet or `at_ms` is further
+    /// past the newest sample than the extrapolation limit. With a single sample the value is
+    /// held.
+    pub fn value_at(&self, at_ms: u64) -> Option<f32> {
+        let (latest_ms, latest) = self.latest?;
+        if at_ms.saturating_sub(latest_ms) > self.max_extrapolation_ms {
+            return None;
+        }
+        let Some((previous_ms, previous)) = self.previous else {
+            return Some(latest);
+        };
+        let span = (latest_ms - previous_ms) as f32;
+        let offset = at_ms as f32 - latest_ms as f32;
+        Some(latest + (latest - previous) * offset / span)
+    }
+
+    /// Time the newest sample was taken, in ms.
+    pub fn last_sample_ms(&self) -> Option<u64> {
+        self.latest.map(|(at_ms, _)| at_ms)
+    }
+
+    pub fn set_max_extrapolation(&mut self, max_extrapolation_ms: u64) {
+        self.max_extrapolation_ms = max_extrapolation_ms;
+    }
+}
+
+/// Fixed-rate schedule of the instants at which aligned values are fused.
+#[derive(Clone, Copy)]
+pub struct EvaluationClock {
+    period_ms: u64,
+    next_ms: u64,
+}
+
+impl EvaluationClock {
+    pub fn new(period_ms: u64) -> Self {
+        Self {
+            period_ms: period_ms.max(1),
+            next_ms: 0,
+        }
+    }
+
+    /// Returns the instant to evaluate at if one is due, the most recent one if several were
+    /// missed. Instants fall on multiples of the period.
+    pub fn poll(&mut self, now_ms: u64) -> Option<u64> {
+        if now_ms < self.next_ms {
+            return None;
+        }
+        let at_ms = now_ms - now_ms % self.period_ms;
+        self.next_ms = at_ms + self.period_ms;
+        Some(at_ms)
+    }
+
+    /// Time between evaluations, in ms.
+    pub fn period_ms(&self) -> u64 {
+        self.period_ms
+    }
+
+    /// Sets the evaluation rate. Takes effect after the next evaluation.
+    pub fn set_period(&mut self, period_ms: u64) {
+        self.period_ms = period_ms.max(1);
+    }
+}
@@ -0,0 +1,73 @@
+//! Token bucket rate limiter for outbound messages.
+//!
+//! A bug or a burst of sensor data can produce messages faster than a link can carry them.
+//! Rather than letting them queue up and starve other tasks, each message takes a token, tokens
+//! refill at `rate_per_s` up to `burst`, and a message that finds the bucket empty is dropped and
+//! counted. Critical messages always pass and don't take a token.
+
+use defmt::warn;
+
+/// Tokens are counted in thousandths so that a refill over a few ms isn't rounded away.
+const MILLI: u64 = 1000;
+
+#[derive(Clone)]
+pub struct TokenBucket {
+    rate_per_s: u32,
+    burst: u32,
+    /// Available tokens, in thousandths.
+    tokens: u64,
+    last_refill_ms: Option<u64>,
+    dropped: u32,
+}
+
+impl TokenBucket {
+    /// Starts with a full bucket.
+    pub fn new(rate_per_s: u32, burst: u32) -> Self {
+        Self {
+            rate_per_s,
+            burst,
+            tokens: u64::from(burst) * MILLI,
+            last_refill_ms: None,
+            dropped: 0,
+        }
+    }
+
+    /// Returns true if a message may be sent now. Critical messages always may.
+    pub fn allow(&mut self, now_ms: u64, critical: bool) -> bool {
+        self.refill(now_ms);
+        if critical {
+            return true;
+        }
+        if self.tokens >= MILLI {
+            self.tokens -= MILLI;
+            return true;
+        }
+        if self.dropped == 0 {
+            warn!(
+                "Outbound rate above {} messages/s, dropping",
+                self.rate_per_s
+            );
+        }
+        self.dropped = self.dropped.saturating_add(1);
+        false
+    }
+
+    fn refill(&mut self, now_ms: u64) {
+        if let Some(last) = self.last_refill_ms {
+            let refill = now_ms.saturating_sub(last) * u64::from(self.rate_per_s);
+            self.tokens = (self.tokens + refill).min(u64::from(self.burst) * MILLI);
+        }
+        self.last_refill_ms = Some(now_ms);
+    }
+
+    /// Number of messages dropped so far.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    pub fn set_rate(&mut self, rate_per_s: u32, burst: u32) {
+        self.rate_per_s = rate_per_s;
+        self.burst = burst;
+        self.tokens = self.tokens.min(u64::from(burst) * MILLI);
+    }
+}
@@ -0,0 +1,46 @@
+//! Time of the last update of each of `N` data sources.
+//!
+//! A held value from a source that stopped reporting minutes ago looks just as current as a new
+//! one, so the time of each update is recorded next to it. A source that never reported and one
+//! that stopped are told apart by [`UpdateTimes::last`], both count as stale.
+
+#[derive(Clone)]
+pub struct UpdateTimes<const N: usize> {
+    /// Time of the last update of each source, in ms since boot.
+    last_ms: [Option<u64>; N],
+}
+
+impl<const N: usize> UpdateTimes<N> {
+    pub fn new() -> Self {
+        Self { last_ms: [None; N] }
+    }
+
+    /// Records an update of `source`. Sources past `N` are ignored.
+    pub fn record(&mut self, source: usize, now_ms: u64) {
+        if let Some(last) = self.last_ms.get_mut(source) {
+            *last = Some(now_ms);
+        }
+    }
+
+    /// Time of the last update of `source`, `None` if it never reported.
+    pub fn last(&self, source: usize) -> Option<u64> {
+        self.last_ms.get(source).copied().flatten()
+    }
+
+    /// Time since the last update of `source`, `None` if it never reported.
+    pub fn age_ms(&self, source: usize, now_ms: u64) -> Option<u64> {
+        self.last(source).map(|last| now_ms.saturating_sub(last))
+    }
+
+    /// Returns true if `source` never reported or its last update is older than `max_age_ms`.
+    pub fn is_stale(&self, source: usize, max_age_ms: u64, now_ms: u64) -> bool {
+        self.age_ms(source, now_ms)
+            .map_or(true, |age| age > max_age_ms)
+    }
+}
+
+impl<const N: usize> Default for UpdateTimes<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,125 @@
+//! A single ground-settable verbosity for everything the firmware logs and transmits.
+//!
+//! Rather than the crew setting the CAN receive logging, the radio telemetry and the log
+//! forwarding one by one, a [`Verbosity`] sets all of them consistently:
+//!
+//! | Level     | CAN rx logging | Telemetry           | Radio rate | Forwarded logs |
+//! |-----------|----------------|---------------------|------------|----------------|
+//! | `Quiet`   | off            | compact             | slow       | errors         |
+//! | `Normal`  | on             | follows the phase   | slow       | warnings       |
+//! | `Verbose` | on             | detailed            | slow       | all            |
+//! | `Debug`   | on             | detailed            | fast       | all            |
+//!
+//! The individual controls still work afterwards, the next verbosity command overrides them again.
+//! The ground sends the level on the command bus as [`VERBOSITY_MAGIC`] followed by the level
+//! byte, see [`Verbosity::command`]. Receivers check [`Verbosity::from_command`] before decoding a
+//! postcard message.
+
+use crate::TelemetryDetail;
+use messages::LogLevel;
+
+/// Marks a command frame as a verbosity change rather than a postcard message.
+pub const VERBOSITY_MAGIC: [u8; 2] = [0x7E, 0xB0];
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+    Debug,
+}
+
+/// Lowest level of the log messages forwarded to the ground station.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum LogForwarding {
+    Errors,
+    Warnings,
+    All,
+}
+
+/// Individual outputs a [`Verbosity`] level enables.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct VerbositySettings {
+    /// Log every message received on the CAN command bus with defmt.
+    pub log_can_rx: bool,
+    /// Telemetry detail forced on the radio, `None` to let the flight phase pick it.
+    pub telemetry: Option<TelemetryDetail>,
+    /// Send radio sensor updates at the fast rate.
+    pub radio_fast: bool,
+    pub forwarding: LogForwarding,
+}
+
+impl Verbosity {
+    pub fn settings(self) -> VerbositySettings {
+        match self {
+            Verbosity::Quiet => VerbositySettings {
+                log_can_rx: false,
+                telemetry: Some(TelemetryDetail::Compact),
+                radio_fast: false,
+                forwarding: LogForwarding::Errors,
+            },
+            Verbosity::Normal => VerbositySettings {
+                log_can_rx: true,
+                telemetry: None,
+                radio_fast: false,
+                forwarding: LogForwarding::Warnings,
+            },
+            Verbosity::Verbose => VerbositySettings {
+                log_can_rx: true,
+                telemetry: Some(TelemetryDetail::Detailed),
+                radio_fast: false,
+                forwarding: LogForwarding::All,
+            },
+            Verbosity::Debug => VerbositySettings {
+                log_can_rx: true,
+                telemetry: Some(TelemetryDetail::Detailed),
+                radio_fast: true,
+                forwarding: LogForwarding::All,
+            },
+        }
+    }
+
+    pub fn from_u8(level: u8) -> Option<Self> {
+        match level {
+            0 => Some(Verbosity::Quiet),
+            1 => Some(Verbosity::Normal),
+            2 => Some(Verbosity::Verbose),
+            3 => Some(Verbosity::Debug),
+            _ => None,
+        }
+    }
+
+    /// Command frame setting this level.
+    pub fn command(self) -> [u8; 3] {
+        [VERBOSITY_MAGIC[0], VERBOSITY_MAGIC[1], self as u8]
+    }
+
+    /// Level set by a command frame, `None` if the frame isn't a verbosity command.
+    pub fn from_command(frame: &[u8]) -> Option<Self> {
+        match frame {
+            [m0, m1, level, ..] if [*m0, *m1] == VERBOSITY_MAGIC => Self::from_u8(*level),
+            _ => None,
+        }
+    }
+}
+
+impl LogForwarding {
+    /// Returns true if a message at `level` is forwarded.
+    pub fn forwards(self, level: &LogLevel) -> bool {
+        match level {
+            LogLevel::Error => true,
+            LogLevel::Warning => self != LogForwarding::Errors,
+            LogLevel::Info => self == LogForwarding::All,
+            #[allow(unreachable_patterns)]
+            _ => self == LogForwarding::All,
+        }
+    }
+
+    pub fn from_u8(forwarding: u8) -> Self {
+        match forwarding {
+            0 => LogForwarding::Errors,
+            1 => LogForwarding::Warnings,
+            _ => LogForwarding::All,
+        }
+    }
+}
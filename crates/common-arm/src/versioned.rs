@@ -0,0 +1,84 @@
+//! Versioned postcard payloads that stay readable across schema changes.
+//!
+//! postcard isn't self-describing, so a payload from a peer or an SD log written by older firmware
+//! fails to decode as soon as a field is added. A [`Schema`] prefixes each payload with its version
+//! and decodes every version from `oldest_compatible` on with the same code path:
+//!
+//! - A payload from an older version is shorter. It is padded with zeros, and postcard decodes
+//!   zeros as `None`, `false`, `0` or the first enum variant, so the missing fields default.
+//! - A payload from a newer version carries fields this firmware doesn't know, they are ignored.
+//!
+//! This only holds if fields are appended at the end of the top-level struct and new fields are
+//! `Option`s, or default sensibly from zero. Any other change, such as removing, reordering or
+//! retyping a field, breaks compatibility and must raise `oldest_compatible` to the new version.
+
+use crate::{HydraError, IncompatibleSchema, PayloadTooLarge};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Version and compatibility range of one message type.
+#[derive(Clone, Copy, Debug)]
+pub struct Schema {
+    /// Version written by this firmware.
+    pub version: u8,
+    /// Oldest version that can still be decoded.
+    pub oldest_compatible: u8,
+}
+
+impl Schema {
+    pub const fn new(version: u8, oldest_compatible: u8) -> Self {
+        Self {
+            version,
+            oldest_compatible,
+        }
+    }
+
+    /// Writes the version followed by the postcard encoding of `value` into `buf`, and returns
+    /// the written part.
+    pub fn encode<'a, T: Serialize>(
+        &self,
+        value: &T,
+        buf: &'a mut [u8],
+    ) -> Result<&'a mut [u8], HydraError> {
+        let Some((version, rest)) = buf.split_first_mut() else {
+            return Err(postcard::Error::SerializeBufferFull.into());
+        };
+        *version = self.version;
+        let len = postcard::to_slice(value, rest)?.len();
+        Ok(&mut buf[..len + 1])
+    }
+
+    /// Decodes a payload written by [`Self::encode`] of any compatible version. `scratch` holds
+    /// the zero-padded copy of an older payload and must be at least as long as the current
+    /// encoding of `T`.
+    pub fn decode<T: DeserializeOwned>(
+        &self,
+        bytes: &[u8],
+        scratch: &mut [u8],
+    ) -> Result<T, HydraError> {
+        let Some((&version, payload)) = bytes.split_first() else {
+            return Err(postcard::Error::DeserializeUnexpectedEnd.into());
+        };
+        if version < self.oldest_compatible {
+            return Err(IncompatibleSchema {
+                version,
+                oldest_compatible: self.oldest_compatible,
+            }
+            .into());
+        }
+        // Trailing fields from a newer version are left unread.
+        match postcard::take_from_bytes(payload) {
+            Err(postcard::Error::DeserializeUnexpectedEnd) if version < self.version => {}
+            result => return Ok(result?.0),
+        }
+        if payload.len() > scratch.len() {
+            return Err(PayloadTooLarge {
+                len: payload.len(),
+                max: scratch.len(),
+            }
+            .into());
+        }
+        scratch[..payload.len()].copy_from_slice(payload);
+        scratch[payload.len()..].fill(0);
+        Ok(postcard::take_from_bytes(scratch)?.0)
+    }
+}
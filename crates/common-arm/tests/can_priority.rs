@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+mod common;
+
+use common::state_message;
+use common_arm::{can_id_for, CanPriority};
+use panic_probe as _;
+
+const NODE: u16 = 0x12;
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_matches_message_priority() {
+        let message = state_message();
+        assert!(CanPriority::of(&message) == CanPriority::State);
+        assert_eq!(can_id_for(&message, NODE), 0x112);
+    }
+
+    #[test]
+    fn higher_priority_gets_lower_id() {
+        let ids = [
+            CanPriority::Command.can_id(NODE),
+            CanPriority::State.can_id(NODE),
+            CanPriority::Sensor.can_id(NODE),
+            CanPriority::Bulk.can_id(NODE),
+        ];
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        // Any node's command beats any node's sensor data.
+        assert!(CanPriority::Command.can_id(0xFF) < CanPriority::Sensor.can_id(0));
+    }
+
+    #[test]
+    fn identical_boards_with_different_nodes_do_not_collide() {
+        // What two CAN managers configured with different node IDs put in the frame header.
+        let message = state_message();
+        let first = can_id_for(&message, NODE);
+        let second = can_id_for(&message, NODE + 1);
+        assert!(first != second);
+        assert_eq!(first & 0xFF, NODE);
+        assert_eq!(second & 0xFF, NODE + 1);
+        assert_eq!(first >> 8, second >> 8);
+    }
+
+    #[test]
+    fn ids_fit_a_standard_frame() {
+        assert!(CanPriority::Bulk.can_id(0xFFFF) <= 0x7FF);
+    }
+}
@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+mod common;
+
+use common::state_message;
+use common_arm::{CanStats, HydraError, PayloadTooLarge};
+use panic_probe as _;
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn good_and_bad_frames_are_counted() {
+        let mut buf = [0u8; 64];
+        let len = postcard::to_slice(&state_message(), &mut buf)
+            .unwrap()
+            .len();
+        let good = &buf[..len];
+        let truncated = &buf[..len - 1];
+        let empty: &[u8] = &[];
+
+        let mut stats = CanStats::new();
+        let mut decoded = 0;
+        // As a process_data loop sees them.
+        for frame in [good, truncated, good, empty, good] {
+            stats.record_rx();
+            if stats.decode(frame).is_ok() {
+                decoded += 1;
+            }
+        }
+        assert_eq!(decoded, 3);
+        assert_eq!(stats.rx_messages, 3);
+        assert_eq!(stats.rx_frames, 5);
+        assert_eq!(stats.decode_errors, 2);
+        assert_eq!(stats.tx_frames, 0);
+    }
+
+    #[test]
+    fn transmit_outcomes_are_counted() {
+        let mut stats = CanStats::new();
+        stats.record_tx::<(), HydraError>(&Ok(()));
+        stats.record_tx::<(), HydraError>(&Ok(()));
+        stats.record_tx::<(), HydraError>(&Err(PayloadTooLarge { len: 80, max: 64 }.into()));
+        assert_eq!(stats.tx_frames, 2);
+        assert_eq!(stats.tx_errors, 1);
+        assert_eq!(stats.record_bus_off_recovery(), 1);
+        assert!(
+            stats
+                == CanStats {
+                    tx_frames: 2,
+                    tx_errors: 1,
+                    rx_frames: 0,
+                    rx_messages: 0,
+                    decode_errors: 0,
+                    bus_off_recoveries: 1,
+                }
+        );
+    }
+}
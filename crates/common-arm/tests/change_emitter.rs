@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+use common_arm::ChangeEmitter;
+use panic_probe as _;
+
+const KEEPALIVE_MS: u64 = 5000;
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn change_is_sent_immediately() {
+        let mut emitter = ChangeEmitter::new(KEEPALIVE_MS);
+        assert!(emitter.poll(0));
+        assert!(!emitter.poll(100));
+        emitter.changed();
+        assert!(emitter.poll(101));
+        assert!(!emitter.poll(102));
+    }
+
+    #[test]
+    fn unchanged_value_only_at_keepalive() {
+        let mut emitter = ChangeEmitter::new(KEEPALIVE_MS);
+        assert!(emitter.poll(0));
+        let sends = (1..=2 * KEEPALIVE_MS)
+            .step_by(50)
+            .filter(|now| emitter.poll(*now))
+            .count();
+        assert_eq!(sends, 2);
+    }
+
+    #[test]
+    fn keepalive_restarts_after_a_change() {
+        let mut emitter = ChangeEmitter::new(KEEPALIVE_MS);
+        emitter.poll(0);
+        emitter.changed();
+        assert!(emitter.poll(4000));
+        assert!(!emitter.poll(KEEPALIVE_MS));
+        assert!(emitter.poll(4000 + KEEPALIVE_MS));
+    }
+}
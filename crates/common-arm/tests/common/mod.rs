@@ -0,0 +1,27 @@
+//! Fixtures shared by the message tests.
+
+// Each test binary uses only some of these.
+#![allow(dead_code)]
+
+use chrono::NaiveDate;
+use messages::node::Node;
+use messages::state::{State, StateData};
+use messages::{FormattedNaiveDateTime, Message};
+
+/// A small state message.
+pub fn state_message() -> Message {
+    state_message_at(0)
+}
+
+/// A small state message, distinguished by its timestamp.
+pub fn state_message_at(second: u32) -> Message {
+    let timestamp = NaiveDate::from_ymd_opt(2001, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, second)
+        .unwrap();
+    Message::new(
+        FormattedNaiveDateTime(timestamp),
+        Node::TemperatureBoard,
+        State::new(StateData::Initializing),
+    )
+}
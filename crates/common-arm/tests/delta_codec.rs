@@ -0,0 +1,82 @@
+#![no_std]
+#![no_main]
+
+use common_arm::{delta_stream, DeltaDecoder, DeltaEncoder, DELTA, KEYFRAME};
+use panic_probe as _;
+
+const STREAM: u8 = 11;
+const KEYFRAME_INTERVAL: u8 = 4;
+
+/// A sample whose bytes barely change between calls, like an encoded GPS position.
+fn sample(i: u8) -> [u8; 40] {
+    let mut sample = [0x55; 40];
+    sample[10] = i;
+    sample[30] = i / 2;
+    sample
+}
+
+/// The frame kind, after the magic and stream id.
+fn kind(frame: &[u8]) -> u8 {
+    frame[3]
+}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyframe_then_deltas_round_trip() {
+        let mut encoder: DeltaEncoder<64> = DeltaEncoder::new(KEYFRAME_INTERVAL);
+        let mut decoder: DeltaDecoder<64> = DeltaDecoder::new();
+        let mut frame = [0u8; 80];
+        let mut decoded = [0u8; 64];
+        for i in 0..10 {
+            let sent = sample(i);
+            let len = encoder.encode(STREAM, &sent, &mut frame).unwrap();
+            let expected_kind = if i % (KEYFRAME_INTERVAL + 1) == 0 {
+                KEYFRAME
+            } else {
+                DELTA
+            };
+            assert_eq!(kind(&frame), expected_kind);
+            if expected_kind == DELTA {
+                assert!(len < sent.len());
+            }
+            assert_eq!(delta_stream(&frame[..len]), Some(STREAM));
+            let decoded_len = decoder.decode(&frame[..len], &mut decoded).unwrap();
+            assert_eq!(&decoded[..decoded_len], &sent[..]);
+        }
+    }
+
+    #[test]
+    fn lost_frame_waits_for_keyframe() {
+        let mut encoder: DeltaEncoder<64> = DeltaEncoder::new(KEYFRAME_INTERVAL);
+        let mut decoder: DeltaDecoder<64> = DeltaDecoder::new();
+        let mut frame = [0u8; 80];
+        let mut decoded = [0u8; 64];
+
+        let len = encoder.encode(STREAM, &sample(0), &mut frame).unwrap();
+        assert!(decoder.decode(&frame[..len], &mut decoded).is_some());
+        // Frame 1 is lost.
+        encoder.encode(STREAM, &sample(1), &mut frame).unwrap();
+        let len = encoder.encode(STREAM, &sample(2), &mut frame).unwrap();
+        assert!(decoder.decode(&frame[..len], &mut decoded).is_none());
+
+        encoder.force_keyframe();
+        let len = encoder.encode(STREAM, &sample(3), &mut frame).unwrap();
+        assert_eq!(kind(&frame), KEYFRAME);
+        let decoded_len = decoder.decode(&frame[..len], &mut decoded).unwrap();
+        assert_eq!(&decoded[..decoded_len], &sample(3)[..]);
+    }
+
+    #[test]
+    fn length_change_sends_a_keyframe() {
+        let mut encoder: DeltaEncoder<64> = DeltaEncoder::new(KEYFRAME_INTERVAL);
+        let mut frame = [0u8; 80];
+        encoder.encode(STREAM, &sample(0), &mut frame).unwrap();
+        encoder
+            .encode(STREAM, &sample(0)[..39], &mut frame)
+            .unwrap();
+        assert_eq!(kind(&frame), KEYFRAME);
+    }
+}
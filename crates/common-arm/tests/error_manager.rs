@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+use common_arm::{ErrorManager, HydraError, PayloadTooLarge};
+use panic_probe as _;
+
+fn raise<const N: usize>(em: &ErrorManager<N>, count: usize) {
+    for len in 0..count {
+        em.handle(Err(HydraError::from(PayloadTooLarge { len, max: 0 })));
+    }
+}
+
+/// Runs the same checks against a manager with the given history length.
+fn check_history<const N: usize>() {
+    let em: ErrorManager<N> = ErrorManager::new();
+    assert!(!em.has_error());
+    assert_eq!(em.error_history_len(), 0);
+
+    raise(&em, 1);
+    assert!(em.has_error());
+    assert_eq!(em.error_history_len(), 1);
+
+    raise(&em, 2 * N);
+    assert_eq!(em.error_history_len(), N);
+}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_history() {
+        check_history::<2>();
+    }
+
+    #[test]
+    fn large_history() {
+        check_history::<32>();
+    }
+}
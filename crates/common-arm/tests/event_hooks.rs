@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+use common_arm::EventHooks;
+use core::sync::atomic::{AtomicU32, Ordering};
+use panic_probe as _;
+
+struct State {
+    altitude: f32,
+    velocity: f32,
+}
+
+static FIRED: AtomicU32 = AtomicU32::new(0);
+
+/// Fires when above 1000 m and descending.
+fn descending_above_1000m(state: &State) {
+    if state.altitude > 1000.0 && state.velocity < 0.0 {
+        FIRED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn noop(_: &State) {}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hook_fires_on_matching_condition() {
+        FIRED.store(0, Ordering::Relaxed);
+        let mut hooks: EventHooks<State, 2> = EventHooks::new();
+        assert!(hooks.register(descending_above_1000m));
+
+        hooks.run(&State {
+            altitude: 500.0,
+            velocity: -10.0,
+        });
+        hooks.run(&State {
+            altitude: 1500.0,
+            velocity: 50.0,
+        });
+        assert_eq!(FIRED.load(Ordering::Relaxed), 0);
+
+        hooks.run(&State {
+            altitude: 1500.0,
+            velocity: -5.0,
+        });
+        assert_eq!(FIRED.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn registrations_are_bounded() {
+        let mut hooks: EventHooks<State, 2> = EventHooks::new();
+        assert!(hooks.register(noop));
+        assert!(hooks.register(noop));
+        assert!(!hooks.register(noop));
+        assert_eq!(hooks.len(), 2);
+    }
+}
@@ -0,0 +1,73 @@
+#![no_std]
+#![no_main]
+
+use common_arm::{FlightRecord, INDEX_HEADER, RECORD_LEN};
+use panic_probe as _;
+
+fn boot_record() -> FlightRecord<'static> {
+    FlightRecord {
+        session: 7,
+        boot_s: 1_700_000_000,
+        reset_reason: "PowerOnReset",
+        file_name: "lc24.txt",
+        duration_s: None,
+        peak_altitude_m: None,
+        boost_accel_clipped: None,
+    }
+}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boot_record_is_padded_csv() {
+        let record = boot_record().encode();
+        let expected = b"7,1700000000,PowerOnReset,lc24.txt,-,-,-";
+        assert_eq!(&record[..expected.len()], expected);
+        assert!(record[expected.len()..RECORD_LEN - 1]
+            .iter()
+            .all(|b| *b == b' '));
+        assert_eq!(record[RECORD_LEN - 1], b'\n');
+    }
+
+    #[test]
+    fn summary_record_has_duration_and_peak() {
+        let record = FlightRecord {
+            duration_s: Some(1234),
+            peak_altitude_m: Some(3048.4),
+            boost_accel_clipped: Some(true),
+            ..boot_record()
+        }
+        .encode();
+        let expected = b"7,1700000000,PowerOnReset,lc24.txt,1234,3048.4,1";
+        assert_eq!(&record[..expected.len()], expected);
+    }
+
+    #[test]
+    fn long_fields_are_truncated() {
+        let record = FlightRecord {
+            reset_reason: "AVeryLongResetReasonName",
+            file_name: "averylongname.txt",
+            ..boot_record()
+        }
+        .encode();
+        let expected = b"7,1700000000,AVeryLongResetRe,averylongnam,-,-,-";
+        assert_eq!(&record[..expected.len()], expected);
+        assert_eq!(record[RECORD_LEN - 1], b'\n');
+    }
+
+    #[test]
+    fn session_is_read_back() {
+        assert_eq!(FlightRecord::session_of(&boot_record().encode()), Some(7));
+        assert_eq!(FlightRecord::session_of(INDEX_HEADER), None);
+        // A reset mid-write leaves a partial record.
+        assert_eq!(FlightRecord::session_of(b"7"), None);
+    }
+
+    #[test]
+    fn header_is_one_record() {
+        assert_eq!(INDEX_HEADER.len(), RECORD_LEN);
+        assert_eq!(INDEX_HEADER[RECORD_LEN - 1], b'\n');
+    }
+}
@@ -0,0 +1,121 @@
+#![no_std]
+#![no_main]
+
+use common_arm::{is_fragment, max_fragmented_len, Fragmenter, Reassembler, LAST_FRAGMENT};
+use panic_probe as _;
+
+/// CAN FD frame, and a message just long enough for three of them: the first fragment carries
+/// 59 bytes after the header and the length, the others 61.
+const CAN_FRAME_LEN: usize = 64;
+const MESSAGE_LEN: usize = 59 + 61 + 10;
+/// POSTCARD_MESSAGE payload, always sent zero padded to its full length.
+const RADIO_FRAME_LEN: usize = 255;
+const RADIO_MESSAGE_LEN: usize = 600;
+const NODE_A: u8 = 0x01;
+const NODE_B: u8 = 0x02;
+
+fn message<const N: usize>(seed: u8) -> [u8; N] {
+    core::array::from_fn(|i| (i as u8).wrapping_mul(7).wrapping_add(seed))
+}
+
+/// Fragments `message` into three frames of `L` bytes as the sender would put them on the link.
+fn fragments<const L: usize>(message: &[u8]) -> ([[u8; L]; 3], [usize; 3]) {
+    let mut frames = [[0u8; L]; 3];
+    let mut lens = [0; 3];
+    let mut fragmenter = Fragmenter::new(message, L).unwrap();
+    for (frame, len) in frames.iter_mut().zip(lens.iter_mut()) {
+        *len = fragmenter.next_frame(frame).unwrap();
+    }
+    assert!(fragmenter.next_frame(&mut [0; L]).is_none());
+    (frames, lens)
+}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn three_fragments_round_trip() {
+        let sent = message::<MESSAGE_LEN>(0);
+        let (frames, lens) = fragments::<CAN_FRAME_LEN>(&sent);
+        assert_eq!(lens, [CAN_FRAME_LEN, CAN_FRAME_LEN, 13]);
+        assert!(frames.iter().all(|frame| is_fragment(frame)));
+        assert_eq!(frames[2][2] & LAST_FRAGMENT, LAST_FRAGMENT);
+
+        let mut reassembler = Reassembler::<2, 256>::new();
+        assert!(reassembler.push(NODE_A, &frames[0][..lens[0]]).is_none());
+        assert!(reassembler.push(NODE_A, &frames[1][..lens[1]]).is_none());
+        let received = reassembler.push(NODE_A, &frames[2][..lens[2]]).unwrap();
+        assert_eq!(received, &sent[..]);
+        assert_eq!(reassembler.dropped(), 0);
+    }
+
+    #[test]
+    fn padded_radio_frames_round_trip() {
+        let sent = message::<RADIO_MESSAGE_LEN>(3);
+        let (frames, lens) = fragments::<RADIO_FRAME_LEN>(&sent);
+        // 250 bytes in the first fragment, 252 in the second, and the 98 left after a header.
+        assert_eq!(lens[2], 3 + 98);
+
+        // The receiver gets every frame zero padded to the full payload.
+        let mut reassembler = Reassembler::<1, 1024>::new();
+        assert!(reassembler.push(NODE_A, &frames[0]).is_none());
+        assert!(reassembler.push(NODE_A, &frames[1]).is_none());
+        assert_eq!(reassembler.push(NODE_A, &frames[2]).unwrap(), &sent[..]);
+    }
+
+    #[test]
+    fn interleaved_senders_are_kept_apart() {
+        let (a, a_lens) = fragments::<CAN_FRAME_LEN>(&message::<MESSAGE_LEN>(0));
+        let (b, b_lens) = fragments::<CAN_FRAME_LEN>(&message::<MESSAGE_LEN>(100));
+
+        let mut reassembler = Reassembler::<2, 256>::new();
+        for i in 0..2 {
+            assert!(reassembler.push(NODE_A, &a[i][..a_lens[i]]).is_none());
+            assert!(reassembler.push(NODE_B, &b[i][..b_lens[i]]).is_none());
+        }
+        assert_eq!(
+            reassembler.push(NODE_B, &b[2][..b_lens[2]]).unwrap(),
+            &message::<MESSAGE_LEN>(100)[..]
+        );
+        assert_eq!(
+            reassembler.push(NODE_A, &a[2][..a_lens[2]]).unwrap(),
+            &message::<MESSAGE_LEN>(0)[..]
+        );
+    }
+
+    #[test]
+    fn missing_fragment_drops_the_message() {
+        let (frames, lens) = fragments::<CAN_FRAME_LEN>(&message::<MESSAGE_LEN>(0));
+
+        let mut reassembler = Reassembler::<2, 256>::new();
+        reassembler.push(NODE_A, &frames[0][..lens[0]]);
+        assert!(reassembler.push(NODE_A, &frames[2][..lens[2]]).is_none());
+        assert_eq!(reassembler.dropped(), 1);
+
+        // The next message goes through again.
+        for i in 0..2 {
+            reassembler.push(NODE_A, &frames[i][..lens[i]]);
+        }
+        assert!(reassembler.push(NODE_A, &frames[2][..lens[2]]).is_some());
+    }
+
+    #[test]
+    fn message_longer_than_the_buffer_is_dropped() {
+        let (frames, lens) = fragments::<CAN_FRAME_LEN>(&message::<MESSAGE_LEN>(0));
+
+        let mut reassembler = Reassembler::<1, 100>::new();
+        for i in 0..3 {
+            assert!(reassembler.push(NODE_A, &frames[i][..lens[i]]).is_none());
+        }
+        assert!(reassembler.dropped() > 0);
+    }
+
+    #[test]
+    fn too_long_to_fragment_is_an_error() {
+        let max = max_fragmented_len(CAN_FRAME_LEN);
+        assert_eq!(max, 128 * 61 - 2);
+        assert!(Fragmenter::new(&[0; 128 * 61], CAN_FRAME_LEN).is_err());
+        assert!(Fragmenter::new(&[0; 128 * 61 - 2], CAN_FRAME_LEN).is_ok());
+    }
+}
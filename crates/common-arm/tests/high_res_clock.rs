@@ -0,0 +1,34 @@
+#![no_std]
+#![no_main]
+
+use common_arm::HighResClock;
+use panic_probe as _;
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rapid_stamps_are_distinct() {
+        let mut clock = HighResClock::new(200);
+        let first = clock.stamp_us(1000);
+        let second = clock.stamp_us(1010);
+        assert!(second > first);
+    }
+
+    #[test]
+    fn microsecond_resolution() {
+        let mut clock = HighResClock::new(200);
+        let first = clock.stamp_us(0);
+        let second = clock.stamp_us(200 * 1500);
+        assert_eq!(second - first, 1500);
+    }
+
+    #[test]
+    fn counter_wrap_is_extended() {
+        let mut clock = HighResClock::new(200);
+        let first = clock.stamp_us(u32::MAX - 199);
+        let second = clock.stamp_us(200 * 9);
+        assert_eq!(second - first, 10);
+    }
+}
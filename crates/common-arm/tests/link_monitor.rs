@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+use common_arm::LinkMonitor;
+use panic_probe as _;
+
+const TIMEOUT_MS: u64 = 10_000;
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_loss_flips_autonomous_after_timeout() {
+        let mut link = LinkMonitor::new(TIMEOUT_MS);
+        assert!(!link.update(Some(0), 0));
+        assert!(!link.update(Some(1000), 1000 + TIMEOUT_MS));
+        assert!(!link.is_link_lost());
+        assert!(link.update(Some(1000), 1001 + TIMEOUT_MS));
+        assert!(link.is_link_lost());
+        assert!(link.is_autonomous());
+    }
+
+    #[test]
+    fn autonomous_latches_when_link_returns() {
+        let mut link = LinkMonitor::new(TIMEOUT_MS);
+        link.update(None, 0);
+        assert!(link.update(None, TIMEOUT_MS + 1));
+        assert!(link.update(Some(TIMEOUT_MS + 2), TIMEOUT_MS + 3));
+        assert!(!link.is_link_lost());
+        assert!(link.is_autonomous());
+    }
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+use common_arm::LogRate;
+use panic_probe as _;
+
+/// Counts how many samples a sink logs out of one second of 100 Hz samples.
+fn logged(rate: &mut LogRate) -> u32 {
+    (0..1000).step_by(10).filter(|&t| rate.due(t)).count() as u32
+}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sd_logs_more_than_the_radio() {
+        let mut sd = LogRate::new(0);
+        let mut radio = LogRate::new(250);
+        let sd_samples = logged(&mut sd);
+        let radio_samples = logged(&mut radio);
+        assert_eq!(sd_samples, 100);
+        assert_eq!(radio_samples, 4);
+    }
+
+    #[test]
+    fn period_between_sample_intervals() {
+        let mut sd = LogRate::new(25);
+        assert_eq!(logged(&mut sd), 34);
+    }
+}
@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+use common_arm::{MavIdentity, DEFAULT_MAV_COMPONENT_ID, DEFAULT_MAV_SYSTEM_ID};
+use panic_probe as _;
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_carries_the_configured_ids() {
+        let mut identity = MavIdentity::new(42, 7);
+        let header = identity.next_header();
+        assert_eq!(header.system_id, 42);
+        assert_eq!(header.component_id, 7);
+        assert_eq!(
+            identity.next_header().sequence,
+            header.sequence.wrapping_add(1)
+        );
+    }
+
+    #[test]
+    fn default_is_one_one() {
+        let header = MavIdentity::default().next_header();
+        assert_eq!(header.system_id, DEFAULT_MAV_SYSTEM_ID);
+        assert_eq!(header.component_id, DEFAULT_MAV_COMPONENT_ID);
+        assert_eq!((DEFAULT_MAV_SYSTEM_ID, DEFAULT_MAV_COMPONENT_ID), (1, 1));
+    }
+
+    #[test]
+    fn sequence_wraps() {
+        let mut identity = MavIdentity::default();
+        for expected in 1..=255u8 {
+            assert_eq!(identity.next_header().sequence, expected);
+        }
+        assert_eq!(identity.next_header().sequence, 0);
+    }
+}
@@ -0,0 +1,437 @@
+#![no_std]
+#![no_main]
+
+use common_arm::drivers::ms5611::{
+    crc4, prom_crc_valid, CalibrationCoefficients, Error, Ms5611, OversamplingRatio, SpiInterface,
+    Variant, I2C_ADDRESS_CSB_HIGH,
+};
+use embedded_hal::blocking::delay::DelayUs;
+use embedded_hal::blocking::i2c;
+use embedded_hal::blocking::spi::{Transfer, Write};
+use embedded_hal::digital::v2::OutputPin;
+use panic_probe as _;
+
+/// Stand-in for the bus, pin and delay. The compensation never touches them.
+struct NoHardware;
+
+impl Transfer<u8> for NoHardware {
+    type Error = ();
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], ()> {
+        Ok(words)
+    }
+}
+
+impl Write<u8> for NoHardware {
+    type Error = ();
+    fn write(&mut self, _words: &[u8]) -> Result<(), ()> {
+        Ok(())
+    }
+}
+
+impl OutputPin for NoHardware {
+    type Error = ();
+    fn set_low(&mut self) -> Result<(), ()> {
+        Ok(())
+    }
+    fn set_high(&mut self) -> Result<(), ()> {
+        Ok(())
+    }
+}
+
+impl DelayUs<u32> for NoHardware {
+    fn delay_us(&mut self, _us: u32) {}
+}
+
+/// Bus with MISO stuck at one level, every byte read is the given value.
+struct StuckBus(u8);
+
+impl Transfer<u8> for StuckBus {
+    type Error = ();
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], ()> {
+        words.fill(self.0);
+        Ok(words)
+    }
+}
+
+impl Write<u8> for StuckBus {
+    type Error = ();
+    fn write(&mut self, _words: &[u8]) -> Result<(), ()> {
+        Ok(())
+    }
+}
+
+/// Bus that answers each ADC read with the raw value of the last conversion started.
+struct ScriptedAdc {
+    d1: u32,
+    d2: u32,
+    last_command: u8,
+}
+
+impl Transfer<u8> for ScriptedAdc {
+    type Error = ();
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], ()> {
+        let raw = match self.last_command {
+            0x40..=0x48 => self.d1,
+            0x50..=0x58 => self.d2,
+            _ => 0,
+        };
+        words[1..4].copy_from_slice(&raw.to_be_bytes()[1..]);
+        Ok(words)
+    }
+}
+
+impl Write<u8> for ScriptedAdc {
+    type Error = ();
+    fn write(&mut self, words: &[u8]) -> Result<(), ()> {
+        self.last_command = words[0];
+        Ok(())
+    }
+}
+
+/// I2C bus answering PROM reads from `prom` and ADC reads like [`ScriptedAdc`].
+struct ScriptedI2c {
+    prom: [u16; 8],
+    d1: u32,
+    d2: u32,
+    last_command: u8,
+}
+
+impl i2c::Write for ScriptedI2c {
+    type Error = ();
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+        assert_eq!(address, I2C_ADDRESS_CSB_HIGH);
+        // The ADC read command doesn't start a conversion.
+        if bytes[0] != 0x00 {
+            self.last_command = bytes[0];
+        }
+        Ok(())
+    }
+}
+
+impl i2c::Read for ScriptedI2c {
+    type Error = ();
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), ()> {
+        assert_eq!(address, I2C_ADDRESS_CSB_HIGH);
+        match self.last_command {
+            0xA0..=0xAE => {
+                let word = self.prom[usize::from(self.last_command - 0xA0) / 2];
+                buffer.copy_from_slice(&word.to_be_bytes());
+            }
+            0x40..=0x48 => buffer.copy_from_slice(&self.d1.to_be_bytes()[1..]),
+            0x50..=0x58 => buffer.copy_from_slice(&self.d2.to_be_bytes()[1..]),
+            _ => buffer.fill(0),
+        }
+        Ok(())
+    }
+}
+
+/// Coefficients of the worked example in the MS5611-01BA03 datasheet.
+const DATASHEET_COEFFICIENTS: CalibrationCoefficients = CalibrationCoefficients {
+    c1_sens_t1: 40127,
+    c2_off_t1: 36924,
+    c3_tcs: 23317,
+    c4_tco: 23282,
+    c5_t_ref: 33464,
+    c6_temp_sens: 28312,
+};
+
+/// Coefficients of the worked example in the MS5607-02BA03 datasheet.
+const MS5607_DATASHEET_COEFFICIENTS: CalibrationCoefficients = CalibrationCoefficients {
+    c1_sens_t1: 46372,
+    c2_off_t1: 43981,
+    c3_tcs: 29059,
+    c4_tco: 27842,
+    c5_t_ref: 31553,
+    c6_temp_sens: 28165,
+};
+
+/// PROM holding the datasheet coefficients, with serial code 0x450 and its CRC of 8 in word 7.
+const GOOD_PROM: [u16; 8] = [0, 40127, 36924, 23317, 23282, 33464, 28312, 0x4508];
+
+/// D1 of the datasheet example.
+const DATASHEET_D1: u32 = 9085466;
+
+fn driver() -> Ms5611<SpiInterface<NoHardware, NoHardware>, NoHardware> {
+    Ms5611::with_coefficients(
+        NoHardware,
+        NoHardware,
+        NoHardware,
+        DATASHEET_COEFFICIENTS,
+        Variant::Ms5611,
+    )
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 1e-4);
+}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn datasheet_example() {
+        // TEMP = 2007 (20.07 C), P = 100009 (1000.09 mbar).
+        let (temp, pressure) = driver()
+            .calculate_compensated_values(DATASHEET_D1, 8569150)
+            .unwrap();
+        assert_close(temp, 20.07);
+        assert_close(pressure, 100.009);
+    }
+
+    #[test]
+    fn ms5607_datasheet_example() {
+        // TEMP = 2000 (20.00 C), P = 110002 (1100.02 mbar).
+        let baro = Ms5611::with_coefficients(
+            NoHardware,
+            NoHardware,
+            NoHardware,
+            MS5607_DATASHEET_COEFFICIENTS,
+            Variant::Ms5607,
+        );
+        let (temp, pressure) = baro.calculate_compensated_values(6465444, 8077636).unwrap();
+        assert_close(temp, 20.0);
+        assert_close(pressure, 110.002);
+    }
+
+    #[test]
+    fn ms5607_uses_its_own_second_order_scaling() {
+        // dT = -1577568, first order TEMP = -3297, T2 = 1158.
+        let baro = Ms5611::with_coefficients(
+            NoHardware,
+            NoHardware,
+            NoHardware,
+            MS5607_DATASHEET_COEFFICIENTS,
+            Variant::Ms5607,
+        );
+        let (temp, pressure) = baro.calculate_compensated_values(6465444, 6500000).unwrap();
+        assert_close(temp, -44.55);
+        assert_close(pressure, 94.282);
+    }
+
+    #[test]
+    fn below_20_c_uses_second_order() {
+        // dT = -566784, first order TEMP = 87, T2 = 149.
+        let (temp, pressure) = driver()
+            .calculate_compensated_values(DATASHEET_D1, 8000000)
+            .unwrap();
+        assert_close(temp, -0.62);
+        assert_close(pressure, 95.989);
+    }
+
+    #[test]
+    fn below_minus_15_c_uses_low_temperature_branch() {
+        // dT = -1566784, first order TEMP = -3288, T2 = 1143.
+        let (temp, pressure) = driver()
+            .calculate_compensated_values(DATASHEET_D1, 7000000)
+            .unwrap();
+        assert_close(temp, -44.31);
+        assert_close(pressure, 85.693);
+    }
+    #[test]
+    fn good_prom_passes_crc() {
+        assert_eq!(crc4(&GOOD_PROM), 8);
+        assert!(prom_crc_valid(&GOOD_PROM));
+        let coefficients = CalibrationCoefficients::from_prom(&GOOD_PROM);
+        assert_eq!(coefficients.c1_sens_t1, DATASHEET_COEFFICIENTS.c1_sens_t1);
+        assert_eq!(
+            coefficients.c6_temp_sens,
+            DATASHEET_COEFFICIENTS.c6_temp_sens
+        );
+    }
+
+    #[test]
+    fn corrupted_prom_fails_crc() {
+        let mut prom = GOOD_PROM;
+        prom[3] ^= 0x0001;
+        assert!(!prom_crc_valid(&prom));
+    }
+
+    #[test]
+    fn stuck_bus_fails_crc() {
+        assert!(!prom_crc_valid(&[0; 8]));
+        assert!(!prom_crc_valid(&[0xFFFF; 8]));
+    }
+
+    #[test]
+    fn new_rejects_an_all_zero_prom() {
+        // Every transfer reads back zeros, as with MISO stuck low.
+        assert!(matches!(
+            Ms5611::new(StuckBus(0x00), NoHardware, NoHardware, Variant::Ms5611),
+            Err(Error::InvalidCoefficients)
+        ));
+    }
+
+    #[test]
+    fn new_rejects_an_all_ones_prom() {
+        // Every transfer reads back ones, as with MISO floating high.
+        assert!(matches!(
+            Ms5611::new(StuckBus(0xFF), NoHardware, NoHardware, Variant::Ms5611),
+            Err(Error::InvalidCoefficients)
+        ));
+    }
+
+    #[test]
+    fn datasheet_coefficients_are_plausible() {
+        assert!(DATASHEET_COEFFICIENTS.is_plausible());
+        assert!(!CalibrationCoefficients::from_prom(&[0; 8]).is_plausible());
+        assert!(!CalibrationCoefficients::from_prom(&[0xFFFF; 8]).is_plausible());
+    }
+
+    #[test]
+    fn altitude_matches_the_standard_atmosphere() {
+        let baro = driver();
+        // Standard atmosphere pressures in mbar at 0, 1000 and 2000 m.
+        for (pressure, altitude) in [(1013.25, 0.0), (898.76, 1000.0), (795.01, 2000.0)] {
+            assert!((baro.altitude_m(pressure) - altitude).abs() < 1.0);
+        }
+    }
+
+    #[test]
+    fn altitude_follows_the_sea_level_reference() {
+        let mut baro = driver();
+        baro.set_sea_level_pressure(1000.0);
+        assert!(baro.altitude_m(1000.0).abs() < 1e-3);
+        assert!(baro.altitude_m(1013.25) < -100.0);
+    }
+    #[test]
+    fn temperature_corrected_altitude_matches_at_standard_temperature() {
+        // The standard atmosphere is 8.5 C at 1000 m.
+        let baro = driver();
+        let altitude = baro.altitude_temperature_corrected_m(898.76, 8.5);
+        assert!((altitude - baro.altitude_m(898.76)).abs() < 1.0);
+    }
+
+    #[test]
+    fn temperature_corrected_altitude_on_a_hot_day() {
+        // Warm air is less dense, so the same pressure drop spans more height. 20 C above
+        // standard is about 7% higher.
+        let baro = driver();
+        let altitude = baro.altitude_temperature_corrected_m(898.76, 28.5);
+        assert!((altitude - 1071.0).abs() < 1.0);
+        assert!(altitude > baro.altitude_m(898.76) + 50.0);
+    }
+
+    #[test]
+    fn out_of_range_temperature_falls_back_to_standard() {
+        let mut baro = driver();
+        baro.set_correction_temperature_range(-20.0, 50.0);
+        let altitude = baro.altitude_temperature_corrected_m(898.76, 60.0);
+        assert_eq!(altitude, baro.altitude_m(898.76));
+    }
+    #[test]
+    fn read_reports_pressure_in_kpa() {
+        // Regression test for the unit of the full read path, 1000.09 mbar is 100.009 kPa.
+        let adc = ScriptedAdc {
+            d1: DATASHEET_D1,
+            d2: 8569150,
+            last_command: 0,
+        };
+        let mut baro = Ms5611::with_coefficients(
+            adc,
+            NoHardware,
+            NoHardware,
+            DATASHEET_COEFFICIENTS,
+            Variant::Ms5611,
+        );
+        let (temp, pressure) = baro
+            .read_pressure_temperature(OversamplingRatio::Osr4096)
+            .unwrap();
+        assert_close(temp, 20.07);
+        assert_close(pressure, 100.009);
+    }
+    #[test]
+    fn read_full_returns_raw_and_compensated_values() {
+        let adc = ScriptedAdc {
+            d1: DATASHEET_D1,
+            d2: 8569150,
+            last_command: 0,
+        };
+        let mut baro = Ms5611::with_coefficients(
+            adc,
+            NoHardware,
+            NoHardware,
+            DATASHEET_COEFFICIENTS,
+            Variant::Ms5611,
+        );
+        let reading = baro.read_full(OversamplingRatio::Osr256).unwrap();
+        assert_eq!(reading.d1_raw, DATASHEET_D1);
+        assert_eq!(reading.d2_raw, 8569150);
+        assert_close(reading.temperature_c, 20.07);
+        assert!((reading.pressure_mbar - 1000.09).abs() < 1e-3);
+    }
+
+    #[test]
+    fn filter_alpha_must_be_in_range() {
+        let mut baro = driver();
+        assert!(matches!(
+            baro.set_filter_alpha(0.0),
+            Err(Error::InvalidFilterAlpha)
+        ));
+        assert!(matches!(
+            baro.set_filter_alpha(1.5),
+            Err(Error::InvalidFilterAlpha)
+        ));
+        assert!(matches!(
+            baro.set_filter_alpha(f32::NAN),
+            Err(Error::InvalidFilterAlpha)
+        ));
+        assert!(baro.set_filter_alpha(1.0).is_ok());
+        assert!(baro.set_filter_alpha(0.25).is_ok());
+    }
+
+    #[test]
+    fn filter_smooths_pressure_and_keeps_the_raw_value() {
+        let mut baro = driver();
+        assert_eq!(baro.filter_pressure(100.0), 100.0);
+        baro.set_filter_alpha(0.25).unwrap();
+        assert_eq!(baro.last_raw_pressure(), Some(100.0));
+        assert_close(baro.filter_pressure(104.0), 101.0);
+        assert_close(baro.filter_pressure(104.0), 101.75);
+        assert_eq!(baro.last_raw_pressure(), Some(104.0));
+
+        // An alpha of 1 follows the readings exactly.
+        baro.set_filter_alpha(1.0).unwrap();
+        assert_eq!(baro.filter_pressure(90.0), 90.0);
+    }
+
+    #[test]
+    fn read_returns_the_filtered_pressure() {
+        let adc = ScriptedAdc {
+            d1: DATASHEET_D1,
+            d2: 8569150,
+            last_command: 0,
+        };
+        let mut baro = Ms5611::with_coefficients(
+            adc,
+            NoHardware,
+            NoHardware,
+            DATASHEET_COEFFICIENTS,
+            Variant::Ms5611,
+        );
+        baro.set_filter_alpha(0.5).unwrap();
+        baro.filter_pressure(100.0);
+        let (_, pressure) = baro
+            .read_pressure_temperature(OversamplingRatio::Osr256)
+            .unwrap();
+        assert_close(pressure, 100.0045);
+        assert_close(baro.last_raw_pressure().unwrap(), 100.009);
+    }
+
+    #[test]
+    fn i2c_sensor_reads_prom_and_adc() {
+        let i2c = ScriptedI2c {
+            prom: GOOD_PROM,
+            d1: DATASHEET_D1,
+            d2: 8569150,
+            last_command: 0,
+        };
+        let mut baro =
+            Ms5611::new_i2c(i2c, I2C_ADDRESS_CSB_HIGH, NoHardware, Variant::Ms5611).unwrap();
+        let (temp, pressure) = baro
+            .read_pressure_temperature(OversamplingRatio::Osr4096)
+            .unwrap();
+        assert_close(temp, 20.07);
+        assert_close(pressure, 100.009);
+    }
+}
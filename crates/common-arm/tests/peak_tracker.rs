@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+use common_arm::PeakTracker;
+use panic_probe as _;
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peak_is_kept_through_the_descent() {
+        let mut tracker = PeakTracker::new();
+        assert_eq!(tracker.peak(), None);
+
+        // Climb to 1200 m at 20 m/s, then descend at 10 m/s, sampled once a second.
+        for t in 0..=60u16 {
+            tracker.update(f32::from(t) * 20.0);
+        }
+        for t in 1..=120u16 {
+            assert_eq!(tracker.update(1200.0 - f32::from(t) * 10.0), Some(1200.0));
+        }
+        assert_eq!(tracker.peak(), Some(1200.0));
+    }
+
+    #[test]
+    fn invalid_samples_are_ignored() {
+        let mut tracker = PeakTracker::new();
+        assert_eq!(tracker.update(f32::NAN), None);
+        tracker.update(-5.0);
+        assert_eq!(tracker.update(f32::INFINITY), Some(-5.0));
+    }
+
+    #[test]
+    fn reset_forgets_the_peak() {
+        let mut tracker = PeakTracker::new();
+        tracker.update(500.0);
+        tracker.reset();
+        assert_eq!(tracker.peak(), None);
+        assert_eq!(tracker.update(10.0), Some(10.0));
+    }
+}
@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+use common_arm::PretriggerBuffer;
+use panic_probe as _;
+
+const PRETRIGGER_MS: u64 = 100;
+const POSTTRIGGER_MS: u64 = 50;
+const PERIOD_MS: u64 = 10;
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_pretrigger_window_is_kept_while_idle() {
+        let mut buffer: PretriggerBuffer<u64, 64> =
+            PretriggerBuffer::new(PRETRIGGER_MS, POSTTRIGGER_MS);
+        for t in (0..=500).step_by(PERIOD_MS as usize) {
+            buffer.push(t, t);
+        }
+        // 400 ms to 500 ms.
+        assert_eq!(buffer.len(), 11);
+        assert_eq!(buffer.drain(500).count(), 0);
+    }
+
+    #[test]
+    fn trigger_flushes_the_pretrigger_window_and_what_follows() {
+        let mut buffer: PretriggerBuffer<u64, 64> =
+            PretriggerBuffer::new(PRETRIGGER_MS, POSTTRIGGER_MS);
+        for t in (0..=500).step_by(PERIOD_MS as usize) {
+            buffer.push(t, t);
+        }
+        buffer.trigger(500);
+
+        let mut expected = 400;
+        for sample in buffer.drain(500) {
+            assert_eq!(sample, expected);
+            expected += PERIOD_MS;
+        }
+        assert_eq!(expected, 510);
+
+        // Post-trigger samples are committed as they arrive, then the capture ends.
+        for t in (510..=600).step_by(PERIOD_MS as usize) {
+            buffer.push(t, t);
+        }
+        for sample in buffer.drain(600) {
+            assert_eq!(sample, expected);
+            expected += PERIOD_MS;
+        }
+        assert_eq!(expected, 610);
+        assert!(!buffer.is_capturing(600));
+
+        buffer.push(610, 610);
+        assert_eq!(buffer.drain(610).count(), 0);
+    }
+
+    #[test]
+    fn retrigger_extends_the_capture() {
+        let mut buffer: PretriggerBuffer<u64, 64> =
+            PretriggerBuffer::new(PRETRIGGER_MS, POSTTRIGGER_MS);
+        buffer.trigger(0);
+        buffer.trigger(40);
+        assert!(buffer.is_capturing(80));
+        assert!(!buffer.is_capturing(100));
+    }
+}
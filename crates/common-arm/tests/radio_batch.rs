@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+mod common;
+
+use common::state_message_at;
+use common_arm::{is_batch, unbatch, Batch};
+use messages::Message;
+use panic_probe as _;
+
+/// Compares messages by their encoding.
+fn same(a: &Message, b: &Message) -> bool {
+    let mut a_buf = [0u8; 64];
+    let mut b_buf = [0u8; 64];
+    postcard::to_slice(a, &mut a_buf).unwrap() == postcard::to_slice(b, &mut b_buf).unwrap()
+}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_of_three_round_trips() {
+        let sent = [
+            state_message_at(1),
+            state_message_at(2),
+            state_message_at(3),
+        ];
+        let mut batch: Batch<255> = Batch::new();
+        for m in &sent {
+            assert!(batch.push(m).unwrap());
+        }
+        assert_eq!(batch.count(), 3);
+
+        // Zero padded like a MAVLink POSTCARD_MESSAGE payload.
+        let mut payload = [0u8; 255];
+        payload[..batch.as_bytes().len()].copy_from_slice(batch.as_bytes());
+        assert!(is_batch(&payload));
+
+        let mut received = unbatch(&payload);
+        for m in &sent {
+            assert!(same(&received.next().unwrap().unwrap(), m));
+        }
+        assert!(received.next().is_none());
+    }
+
+    #[test]
+    fn full_batch_refuses_more() {
+        let mut batch: Batch<32> = Batch::new();
+        let mut pushed = 0;
+        while batch.push(&state_message_at(1)).unwrap() {
+            pushed += 1;
+        }
+        assert_eq!(batch.count(), pushed);
+        assert!(unbatch(batch.as_bytes()).all(|m| m.is_ok()));
+    }
+
+    #[test]
+    fn single_message_is_not_a_batch() {
+        let mut buf = [0u8; 64];
+        let single = postcard::to_slice(&state_message_at(1), &mut buf).unwrap();
+        assert!(!is_batch(single));
+        assert!(unbatch(single).next().is_none());
+    }
+}
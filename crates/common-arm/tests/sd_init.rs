@@ -0,0 +1,125 @@
+#![no_std]
+#![no_main]
+
+use core::convert::Infallible;
+use core::sync::atomic::{AtomicU32, Ordering};
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::spi::FullDuplex;
+use heapless::{Deque, Vec};
+use panic_probe as _;
+
+const RETRY_MS: u32 = 200;
+/// The card only answers once this much time has passed since power up, so the first two
+/// attempts fail.
+const SETTLE_MS: u32 = 2 * RETRY_MS;
+
+/// Time spent in `RetryDelay`, the only clock the mock card sees.
+static ELAPSED_MS: AtomicU32 = AtomicU32::new(0);
+
+struct RetryDelay;
+
+impl DelayMs<u32> for RetryDelay {
+    fn delay_ms(&mut self, ms: u32) {
+        ELAPSED_MS.fetch_add(ms, Ordering::Relaxed);
+    }
+}
+
+struct Pin;
+
+impl OutputPin for Pin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// An SDHC card on the SPI bus, answering just the commands of the SPI mode initialization.
+/// Until it has settled it doesn't drive the bus at all, as if it weren't there.
+#[derive(Default)]
+struct MockCard {
+    command: Vec<u8, 6>,
+    response: Deque<u8, 8>,
+    /// The byte just sent was part of a command, so nothing is clocked back yet.
+    in_command: bool,
+}
+
+impl MockCard {
+    fn respond(&mut self, command: u8) {
+        let response: &[u8] = match command {
+            // GO_IDLE_STATE, CRC_ON_OFF and APP_CMD: idle.
+            0 | 59 | 55 => &[0x01],
+            // SEND_IF_COND: idle, then the echoed voltage range and check pattern.
+            8 => &[0x01, 0x00, 0x00, 0x01, 0xAA],
+            // SD_SEND_OP_COND: ready.
+            41 => &[0x00],
+            // READ_OCR: ready, powered up and high capacity.
+            58 => &[0x00, 0xC0, 0xFF, 0x80, 0x00],
+            _ => &[0x04],
+        };
+        self.response.clear();
+        for byte in response {
+            self.response.push_back(*byte).unwrap();
+        }
+    }
+}
+
+impl FullDuplex<u8> for MockCard {
+    type Error = Infallible;
+
+    fn send(&mut self, byte: u8) -> nb::Result<(), Infallible> {
+        self.in_command = false;
+        if ELAPSED_MS.load(Ordering::Relaxed) < SETTLE_MS {
+            return Ok(());
+        }
+        // A command starts with its start and transmission bits, 0b01.
+        if self.command.is_empty() && byte & 0xC0 != 0x40 {
+            return Ok(());
+        }
+        self.command.push(byte).unwrap();
+        self.in_command = true;
+        if self.command.is_full() {
+            self.respond(self.command[0] & 0x3F);
+            self.command.clear();
+        }
+        Ok(())
+    }
+
+    fn read(&mut self) -> nb::Result<u8, Infallible> {
+        if self.in_command {
+            return Ok(0xFF);
+        }
+        Ok(self.response.pop_front().unwrap_or(0xFF))
+    }
+}
+
+type Sd = common_arm::SdManager<MockCard, Pin>;
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+    use embedded_sdmmc::SdMmcSpi;
+
+    #[test]
+    fn init_succeeds_on_the_third_attempt() {
+        ELAPSED_MS.store(0, Ordering::Relaxed);
+        let mut device = SdMmcSpi::new(MockCard::default(), Pin);
+        let attempt = Sd::init_card(&mut device, 3, RETRY_MS, &mut RetryDelay);
+        assert_eq!(attempt.ok(), Some(3));
+        // Waited between attempts, not after the last one.
+        assert_eq!(ELAPSED_MS.load(Ordering::Relaxed), 2 * RETRY_MS);
+    }
+
+    #[test]
+    fn init_gives_up_after_the_last_attempt() {
+        ELAPSED_MS.store(0, Ordering::Relaxed);
+        let mut device = SdMmcSpi::new(MockCard::default(), Pin);
+        assert!(Sd::init_card(&mut device, 2, RETRY_MS, &mut RetryDelay).is_err());
+        assert_eq!(ELAPSED_MS.load(Ordering::Relaxed), RETRY_MS);
+    }
+}
@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+use common_arm::{paint_stack, stack_high_water, stack_size};
+use panic_probe as _;
+
+/// Bytes of stack the deep call below is sure to touch.
+const DEEP_FRAME: usize = 4096;
+
+#[inline(never)]
+fn deep_call() {
+    let frame = core::hint::black_box([0x55u8; DEEP_FRAME]);
+    core::hint::black_box(&frame);
+}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deeper_call_raises_the_high_water_mark() {
+        paint_stack();
+        let before = stack_high_water();
+        deep_call();
+        let after = stack_high_water();
+
+        assert!(after >= before + DEEP_FRAME / 2);
+        assert!(after < stack_size());
+    }
+}
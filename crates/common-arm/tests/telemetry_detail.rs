@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+use common_arm::{TelemetryDetail, TelemetryDetailSelector};
+use panic_probe as _;
+
+const STREAMS: usize = 6;
+const COMPACT: [usize; 2] = [1, 4];
+
+fn included(selector: &TelemetryDetailSelector) -> [bool; STREAMS] {
+    core::array::from_fn(|stream| selector.includes(stream))
+}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_sends_only_the_compact_set_packed() {
+        let selector = TelemetryDetailSelector::new(TelemetryDetail::Compact, &COMPACT);
+        assert_eq!(
+            included(&selector),
+            [false, true, false, false, true, false]
+        );
+        assert!(selector.is_packed());
+    }
+
+    #[test]
+    fn detailed_sends_every_stream_on_its_own() {
+        let selector = TelemetryDetailSelector::new(TelemetryDetail::Detailed, &COMPACT);
+        assert_eq!(included(&selector), [true; STREAMS]);
+        assert!(!selector.is_packed());
+    }
+
+    #[test]
+    fn phase_change_switches_the_output() {
+        let mut selector = TelemetryDetailSelector::new(TelemetryDetail::Compact, &COMPACT);
+        assert!(!selector.includes(0));
+        selector.set_phase_detail(TelemetryDetail::Detailed);
+        assert!(selector.includes(0));
+        assert!(!selector.is_packed());
+    }
+
+    #[test]
+    fn forced_level_overrides_the_phase() {
+        let mut selector = TelemetryDetailSelector::new(TelemetryDetail::Compact, &COMPACT);
+        selector.force(Some(TelemetryDetail::Detailed));
+        selector.set_phase_detail(TelemetryDetail::Compact);
+        assert!(selector.current() == TelemetryDetail::Detailed);
+
+        selector.force(None);
+        assert!(selector.current() == TelemetryDetail::Compact);
+    }
+}
@@ -0,0 +1,86 @@
+#![no_std]
+#![no_main]
+
+use common_arm::{AlignedSignal, EvaluationClock};
+use panic_probe as _;
+
+const EVALUATION_PERIOD_MS: u64 = 20;
+
+/// Altitude climbing at 100 m/s, in m.
+fn altitude(at_ms: u64) -> f32 {
+    at_ms as f32 * 0.1
+}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sources_at_different_rates_agree_at_the_evaluation_instant() {
+        // A slow source every 100 ms from 30 ms and a fast one every 5 ms from 2 ms, both
+        // measuring the same altitude.
+        let mut slow = AlignedSignal::new(100);
+        let mut fast = AlignedSignal::new(10);
+        let mut clock = EvaluationClock::new(EVALUATION_PERIOD_MS);
+        let mut evaluations = 0;
+        for now_ms in 0..=1000 {
+            if now_ms % 100 == 30 {
+                slow.push(altitude(now_ms), now_ms);
+            }
+            if now_ms % 5 == 2 {
+                fast.push(altitude(now_ms), now_ms);
+            }
+            // With a single sample a source is only held, start once both have two.
+            if now_ms < 130 {
+                continue;
+            }
+            if let Some(at_ms) = clock.poll(now_ms) {
+                assert_eq!(at_ms % EVALUATION_PERIOD_MS, 0);
+                if let (Some(slow), Some(fast)) = (slow.value_at(at_ms), fast.value_at(at_ms)) {
+                    assert!((slow - altitude(at_ms)).abs() < 1e-3);
+                    assert!((fast - altitude(at_ms)).abs() < 1e-3);
+                    evaluations += 1;
+                }
+            }
+        }
+        assert!(evaluations > 40);
+    }
+
+    #[test]
+    fn value_is_interpolated_between_samples() {
+        let mut signal = AlignedSignal::new(100);
+        signal.push(10.0, 100);
+        signal.push(20.0, 200);
+        assert_eq!(signal.value_at(150), Some(15.0));
+        assert_eq!(signal.value_at(250), Some(25.0));
+    }
+
+    #[test]
+    fn stale_source_is_not_extrapolated() {
+        let mut signal = AlignedSignal::new(50);
+        assert_eq!(signal.value_at(0), None);
+        signal.push(1.0, 100);
+        assert_eq!(signal.value_at(150), Some(1.0));
+        assert_eq!(signal.value_at(151), None);
+    }
+
+    #[test]
+    fn out_of_order_samples_are_ignored() {
+        let mut signal = AlignedSignal::new(100);
+        signal.push(10.0, 100);
+        signal.push(20.0, 200);
+        signal.push(0.0, 150);
+        assert_eq!(signal.last_sample_ms(), Some(200));
+        assert_eq!(signal.value_at(200), Some(20.0));
+    }
+
+    #[test]
+    fn clock_skips_missed_instants() {
+        let mut clock = EvaluationClock::new(EVALUATION_PERIOD_MS);
+        assert_eq!(clock.poll(5), Some(0));
+        assert_eq!(clock.poll(15), None);
+        assert_eq!(clock.poll(73), Some(60));
+        assert_eq!(clock.poll(79), None);
+        assert_eq!(clock.poll(80), Some(80));
+    }
+}
@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+use common_arm::TokenBucket;
+use panic_probe as _;
+
+const RATE_PER_S: u32 = 10;
+const BURST: u32 = 5;
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_excess_is_dropped_and_counted() {
+        let mut bucket = TokenBucket::new(RATE_PER_S, BURST);
+        let sent = (0..20).filter(|_| bucket.allow(0, false)).count();
+        assert_eq!(sent, BURST as usize);
+        assert_eq!(bucket.dropped(), 20 - BURST);
+    }
+
+    #[test]
+    fn critical_messages_bypass() {
+        let mut bucket = TokenBucket::new(RATE_PER_S, BURST);
+        while bucket.allow(0, false) {}
+        assert!((0..10).all(|_| bucket.allow(0, true)));
+        assert_eq!(bucket.dropped(), 1);
+    }
+
+    #[test]
+    fn tokens_refill_at_rate() {
+        let mut bucket = TokenBucket::new(RATE_PER_S, BURST);
+        while bucket.allow(0, false) {}
+        // 10 messages/s is one every 100 ms.
+        assert!(!bucket.allow(99, false));
+        assert!(bucket.allow(100, false));
+        assert!(!bucket.allow(100, false));
+        // Never more than the burst, however long the bucket sat idle.
+        let sent = (0..20).filter(|_| bucket.allow(60_000, false)).count();
+        assert_eq!(sent, BURST as usize);
+    }
+}
@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+
+use common_arm::UpdateTimes;
+use panic_probe as _;
+
+const GPS: usize = 0;
+const BARO: usize = 1;
+const MAX_AGE_MS: u64 = 1000;
+
+/// Stands in for the monotonic clock, advanced by hand.
+struct MockClock {
+    now_ms: u64,
+}
+
+impl MockClock {
+    fn advance(&mut self, ms: u64) -> u64 {
+        self.now_ms += ms;
+        self.now_ms
+    }
+}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_reported_is_stale() {
+        let times = UpdateTimes::<2>::new();
+        assert!(times.is_stale(GPS, MAX_AGE_MS, 0));
+        assert_eq!(times.last(GPS), None);
+        assert_eq!(times.age_ms(GPS, 0), None);
+    }
+
+    #[test]
+    fn source_goes_stale_once_it_stops() {
+        let mut clock = MockClock { now_ms: 5000 };
+        let mut times = UpdateTimes::<2>::new();
+        times.record(GPS, clock.now_ms);
+        times.record(BARO, clock.now_ms);
+
+        let now_ms = clock.advance(MAX_AGE_MS);
+        assert!(!times.is_stale(GPS, MAX_AGE_MS, now_ms));
+        times.record(BARO, now_ms);
+
+        // The GPS stopped responding, the baro keeps reporting.
+        let now_ms = clock.advance(1);
+        assert!(times.is_stale(GPS, MAX_AGE_MS, now_ms));
+        assert!(!times.is_stale(BARO, MAX_AGE_MS, now_ms));
+        assert_eq!(times.age_ms(GPS, now_ms), Some(MAX_AGE_MS + 1));
+        // Unlike a source that never reported, the last update is still known.
+        assert_eq!(times.last(GPS), Some(5000));
+
+        times.record(GPS, now_ms);
+        assert!(!times.is_stale(GPS, MAX_AGE_MS, now_ms));
+    }
+
+    #[test]
+    fn unknown_source_is_ignored() {
+        let mut times = UpdateTimes::<2>::new();
+        times.record(2, 0);
+        assert!(times.is_stale(2, MAX_AGE_MS, 0));
+    }
+}
@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+use common_arm::{LogForwarding, TelemetryDetail, Verbosity, VerbositySettings};
+use messages::LogLevel;
+use panic_probe as _;
+
+const LEVELS: [Verbosity; 4] = [
+    Verbosity::Quiet,
+    Verbosity::Normal,
+    Verbosity::Verbose,
+    Verbosity::Debug,
+];
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_level_enables_its_outputs() {
+        let expected = [
+            VerbositySettings {
+                log_can_rx: false,
+                telemetry: Some(TelemetryDetail::Compact),
+                radio_fast: false,
+                forwarding: LogForwarding::Errors,
+            },
+            VerbositySettings {
+                log_can_rx: true,
+                telemetry: None,
+                radio_fast: false,
+                forwarding: LogForwarding::Warnings,
+            },
+            VerbositySettings {
+                log_can_rx: true,
+                telemetry: Some(TelemetryDetail::Detailed),
+                radio_fast: false,
+                forwarding: LogForwarding::All,
+            },
+            VerbositySettings {
+                log_can_rx: true,
+                telemetry: Some(TelemetryDetail::Detailed),
+                radio_fast: true,
+                forwarding: LogForwarding::All,
+            },
+        ];
+        for (level, expected) in LEVELS.iter().zip(expected) {
+            assert!(level.settings() == expected);
+        }
+    }
+
+    #[test]
+    fn forwarding_filters_by_level() {
+        let forwarded = |forwarding: LogForwarding| {
+            [LogLevel::Info, LogLevel::Warning, LogLevel::Error]
+                .map(|level| forwarding.forwards(&level))
+        };
+        assert_eq!(forwarded(LogForwarding::Errors), [false, false, true]);
+        assert_eq!(forwarded(LogForwarding::Warnings), [false, true, true]);
+        assert_eq!(forwarded(LogForwarding::All), [true, true, true]);
+    }
+
+    #[test]
+    fn command_round_trips() {
+        for level in LEVELS {
+            assert!(Verbosity::from_command(&level.command()) == Some(level));
+        }
+        assert!(Verbosity::from_command(&[0x7E, 0xB0, 4]).is_none());
+        assert!(Verbosity::from_command(&[0x00, 0xB0, 1]).is_none());
+        assert!(Verbosity::from_command(&[0x7E]).is_none());
+    }
+}
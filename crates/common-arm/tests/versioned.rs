@@ -0,0 +1,115 @@
+#![no_std]
+#![no_main]
+
+use common_arm::Schema;
+use panic_probe as _;
+use serde::{Deserialize, Serialize};
+
+/// First version of a message.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct StatusV1 {
+    sequence: u32,
+    altitude: f32,
+}
+
+/// Second version, with two fields appended.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct StatusV2 {
+    sequence: u32,
+    altitude: f32,
+    velocity: Option<f32>,
+    armed: bool,
+}
+
+const V1: Schema = Schema::new(1, 1);
+const V2: Schema = Schema::new(2, 1);
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_and_new_payloads_decode_with_the_same_code() {
+        let old = StatusV1 {
+            sequence: 7,
+            altitude: 120.5,
+        };
+        let new = StatusV2 {
+            sequence: 8,
+            altitude: 130.0,
+            velocity: Some(12.5),
+            armed: true,
+        };
+        let mut old_buf = [0u8; 32];
+        let mut new_buf = [0u8; 32];
+        let old_bytes = V1.encode(&old, &mut old_buf).unwrap();
+        let new_bytes = V2.encode(&new, &mut new_buf).unwrap();
+
+        let mut scratch = [0u8; 32];
+        for (bytes, expected) in [
+            (
+                &old_bytes[..],
+                StatusV2 {
+                    sequence: 7,
+                    altitude: 120.5,
+                    velocity: None,
+                    armed: false,
+                },
+            ),
+            (&new_bytes[..], new),
+        ] {
+            let decoded: StatusV2 = V2.decode(bytes, &mut scratch).unwrap();
+            assert_eq!(decoded, expected);
+        }
+    }
+
+    #[test]
+    fn newer_fields_are_ignored_by_older_firmware() {
+        let new = StatusV2 {
+            sequence: 8,
+            altitude: 130.0,
+            velocity: Some(12.5),
+            armed: true,
+        };
+        let mut buf = [0u8; 32];
+        let bytes = V2.encode(&new, &mut buf).unwrap();
+        let mut scratch = [0u8; 32];
+        let decoded: StatusV1 = V1.decode(bytes, &mut scratch).unwrap();
+        assert_eq!(
+            decoded,
+            StatusV1 {
+                sequence: 8,
+                altitude: 130.0
+            }
+        );
+    }
+
+    #[test]
+    fn versions_before_a_breaking_change_are_rejected() {
+        let old = StatusV1 {
+            sequence: 7,
+            altitude: 120.5,
+        };
+        let mut buf = [0u8; 32];
+        let bytes = V1.encode(&old, &mut buf).unwrap();
+        let mut scratch = [0u8; 32];
+        let breaking = Schema::new(3, 3);
+        assert!(breaking.decode::<StatusV2>(bytes, &mut scratch).is_err());
+    }
+
+    #[test]
+    fn truncated_current_payload_is_an_error() {
+        let new = StatusV2 {
+            sequence: 8,
+            altitude: 130.0,
+            velocity: Some(12.5),
+            armed: true,
+        };
+        let mut buf = [0u8; 32];
+        let len = V2.encode(&new, &mut buf).unwrap().len();
+        let mut scratch = [0u8; 32];
+        assert!(V2
+            .decode::<StatusV2>(&buf[..len - 1], &mut scratch)
+            .is_err());
+    }
+}
@@ -0,0 +1,258 @@
+#![no_std]
+
+//! Madgwick's gradient descent orientation filter, shared by phoenix and the host tests in
+//! `madgwick-test`.
+//!
+//! This follows `madgwick::Marg` step for step, but `Marg` fixes its gain and sample period when
+//! it is created and keeps its quaternion private, so neither could change between updates
+//! without resetting the orientation. Here the gain can follow an [`AdaptiveGain`] and each
+//! update can integrate over the period measured from the IMU time stamps.
+
+/// Parameters for adapting the filter gain to how well the filter has converged
+/// The innovation is the angle in rad between the measured gravity direction and the one predicted by the quaternion;
+/// it is averaged and the gain scales linearly from 'beta_min' at zero to 'beta_max' at 'full_scale_innovation'
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AdaptiveGain {
+    pub beta_min: f32, // gain once converged, low so accelerometer noise barely moves the orientation
+    pub beta_max: f32, // gain at startup and after a disturbance, high so the orientation converges quickly
+    pub full_scale_innovation: f32, // averaged innovation in rad at and above which 'beta_max' is used
+    pub smoothing: f32, // weight of each new innovation in the average, in (0, 1]; lower rides through short vibrations
+}
+
+impl AdaptiveGain {
+    /// Gain for an averaged innovation in rad
+    pub fn beta_for(&self, innovation: f32) -> f32 {
+        let scale = (innovation / self.full_scale_innovation).clamp(0.0, 1.0);
+        self.beta_min + (self.beta_max - self.beta_min) * scale
+    }
+}
+
+impl Default for AdaptiveGain {
+    fn default() -> Self {
+        Self {
+            beta_min: 0.033,
+            beta_max: 0.5,
+            full_scale_innovation: 0.2, // ~11 deg
+            smoothing: 0.05,
+        }
+    }
+}
+
+/// Madgwick's gradient descent orientation filter using the accelerometer, gyroscope and optionally the magnetometer
+pub struct MadgwickFilter {
+    quat: (f32, f32, f32, f32),
+    beta: f32, // gain used for the next update
+    sample_period: f32, // time in seconds the next update integrates over
+}
+
+impl MadgwickFilter {
+    /// Starts from the identity quaternion
+    pub fn new(beta: f32, sample_period: f32) -> Self {
+        Self {
+            quat: (1.0, 0.0, 0.0, 0.0),
+            beta,
+            sample_period,
+        }
+    }
+
+    /// Integrates a gyro sample in rad/s, corrected towards the accelerometer and magnetometer directions
+    /// A zero 'accel' skips the correction and a zero 'mag' leaves the heading to the gyro
+    pub fn update(&mut self, gyro: [f32; 3], accel: [f32; 3], mag: [f32; 3]) -> (f32, f32, f32, f32) {
+        let (q0, q1, q2, q3) = self.quat;
+        let [gx, gy, gz] = gyro;
+
+        // Rate of change of the quaternion from the gyro
+        let mut q_dot = [
+            0.5 * (-q1 * gx - q2 * gy - q3 * gz),
+            0.5 * (q0 * gx + q2 * gz - q3 * gy),
+            0.5 * (q0 * gy - q1 * gz + q3 * gx),
+            0.5 * (q0 * gz + q1 * gy - q2 * gx),
+        ];
+
+        let accel_norm = libm::sqrtf(accel.iter().map(|a| a * a).sum());
+        if accel_norm > 0.0 {
+            let [ax, ay, az] = accel.map(|a| a / accel_norm);
+
+            // Gradient of the error between the measured and predicted gravity direction
+            let mut step = [
+                4.0 * q0 * q2 * q2 + 2.0 * q2 * ax + 4.0 * q0 * q1 * q1 - 2.0 * q1 * ay,
+                4.0 * q1 * q3 * q3 - 2.0 * q3 * ax + 4.0 * q0 * q0 * q1 - 2.0 * q0 * ay - 4.0 * q1
+                    + 8.0 * q1 * q1 * q1 + 8.0 * q1 * q2 * q2 + 4.0 * q1 * az,
+                4.0 * q0 * q0 * q2 + 2.0 * q0 * ax + 4.0 * q2 * q3 * q3 - 2.0 * q3 * ay - 4.0 * q2
+                    + 8.0 * q2 * q1 * q1 + 8.0 * q2 * q2 * q2 + 4.0 * q2 * az,
+                4.0 * q1 * q1 * q3 - 2.0 * q1 * ax + 4.0 * q2 * q2 * q3 - 2.0 * q2 * ay,
+            ];
+
+            // With a magnetometer, also correct the heading towards the measured field
+            let mag_norm = libm::sqrtf(mag.iter().map(|m| m * m).sum());
+            if mag_norm > 0.0 {
+                let [mx, my, mz] = mag.map(|m| m / mag_norm);
+
+                // Direction of the field in the earth frame, only its horizontal magnitude and
+                // vertical component are kept so the tilt doesn't leak into the heading
+                let hx = mx * (q0 * q0 + q1 * q1 - q2 * q2 - q3 * q3)
+                    + 2.0 * my * (q1 * q2 - q0 * q3)
+                    + 2.0 * mz * (q1 * q3 + q0 * q2);
+                let hy = 2.0 * mx * (q1 * q2 + q0 * q3)
+                    + my * (q0 * q0 - q1 * q1 + q2 * q2 - q3 * q3)
+                    + 2.0 * mz * (q2 * q3 - q0 * q1);
+                let bx = libm::sqrtf(hx * hx + hy * hy);
+                let bz = 2.0 * mx * (q1 * q3 - q0 * q2)
+                    + 2.0 * my * (q2 * q3 + q0 * q1)
+                    + mz * (q0 * q0 - q1 * q1 - q2 * q2 + q3 * q3);
+
+                // Error between the measured and predicted field, and its Jacobian
+                let error = [
+                    2.0 * bx * (0.5 - q2 * q2 - q3 * q3) + 2.0 * bz * (q1 * q3 - q0 * q2) - mx,
+                    2.0 * bx * (q1 * q2 - q0 * q3) + 2.0 * bz * (q0 * q1 + q2 * q3) - my,
+                    2.0 * bx * (q0 * q2 + q1 * q3) + 2.0 * bz * (0.5 - q1 * q1 - q2 * q2) - mz,
+                ];
+                let jacobian = [
+                    [-2.0 * bz * q2, 2.0 * bz * q3, -4.0 * bx * q2 - 2.0 * bz * q0, -4.0 * bx * q3 + 2.0 * bz * q1],
+                    [-2.0 * bx * q3 + 2.0 * bz * q1, 2.0 * bx * q2 + 2.0 * bz * q0, 2.0 * bx * q1 + 2.0 * bz * q3, -2.0 * bx * q0 + 2.0 * bz * q2],
+                    [2.0 * bx * q2, 2.0 * bx * q3 - 4.0 * bz * q1, 2.0 * bx * q0 - 4.0 * bz * q2, 2.0 * bx * q1],
+                ];
+                for (row, error) in jacobian.iter().zip(error) {
+                    for (step, j) in step.iter_mut().zip(row) {
+                        *step += j * error;
+                    }
+                }
+            }
+            let step_norm = libm::sqrtf(step.iter().map(|s| s * s).sum());
+            if step_norm > 0.0 {
+                for (q_dot, step) in q_dot.iter_mut().zip(step) {
+                    *q_dot -= self.beta * step / step_norm;
+                }
+            }
+        }
+
+        let q = [
+            q0 + q_dot[0] * self.sample_period,
+            q1 + q_dot[1] * self.sample_period,
+            q2 + q_dot[2] * self.sample_period,
+            q3 + q_dot[3] * self.sample_period,
+        ];
+        let norm = libm::sqrtf(q.iter().map(|q| q * q).sum());
+        self.quat = (q[0] / norm, q[1] / norm, q[2] / norm, q[3] / norm);
+        self.quat
+    }
+
+    /// Direction of gravity in the body frame according to the quaternion (unit vector)
+    pub fn expected_gravity(&self) -> [f32; 3] {
+        let (w, x, y, z) = self.quat;
+        [
+            2.0 * (x * z - w * y),
+            2.0 * (w * x + y * z),
+            1.0 - 2.0 * (x * x + y * y),
+        ]
+    }
+
+    /// Angle in rad between an accelerometer sample and the gravity direction the quaternion predicts
+    /// 'None' for a zero or non-finite sample
+    pub fn innovation(&self, accel: &[f32; 3]) -> Option<f32> {
+        let norm = libm::sqrtf(accel.iter().map(|a| a * a).sum());
+        if norm == 0.0 {
+            return None;
+        }
+        let g = self.expected_gravity();
+        let cos = (accel[0] * g[0] + accel[1] * g[1] + accel[2] * g[2]) / norm;
+        let innovation = libm::acosf(cos.clamp(-1.0, 1.0));
+        innovation.is_finite().then_some(innovation)
+    }
+
+    pub fn quaternion(&self) -> (f32, f32, f32, f32) {
+        self.quat
+    }
+
+    /// Carries on from 'quat', e.g. to drop an update that produced a non-finite quaternion
+    pub fn set_quaternion(&mut self, quat: (f32, f32, f32, f32)) {
+        self.quat = quat;
+    }
+
+    pub fn beta(&self) -> f32 {
+        self.beta
+    }
+
+    /// Takes effect from the next update, the orientation is kept
+    pub fn set_beta(&mut self, beta: f32) {
+        self.beta = beta;
+    }
+
+    pub fn sample_period(&self) -> f32 {
+        self.sample_period
+    }
+
+    /// Takes effect from the next update, the orientation is kept
+    pub fn set_sample_period(&mut self, sample_period: f32) {
+        self.sample_period = sample_period;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use madgwick::{F32x3, Marg};
+
+    fn f32x3(v: [f32; 3]) -> F32x3 {
+        F32x3 { x: v[0], y: v[1], z: v[2] }
+    }
+
+    #[test]
+    fn test_matches_upstream_at_fixed_gain() {
+        let mut filter = MadgwickFilter::new(0.1, 0.01);
+        let mut marg = Marg::new(0.1, 0.01);
+        for i in 0..500 {
+            // Slowly rotating, tilted board with a field pointing north and down
+            let t = i as f32 * 0.01;
+            let gyro = [0.1, -0.05, 0.2 + 0.1 * libm::sinf(t)];
+            let accel = [0.5, 0.3, 9.7];
+            let mag = [0.4, 0.1, -0.9];
+            let quat = filter.update(gyro, accel, mag);
+            let expected = marg.update(f32x3(mag), f32x3(gyro), f32x3(accel));
+            for (q, e) in [quat.0, quat.1, quat.2, quat.3].iter().zip([expected.0, expected.1, expected.2, expected.3]) {
+                assert!((q - e).abs() < 1e-3, "Diverged from madgwick::Marg at sample {}: {} vs {}", i, q, e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_gain_change_keeps_orientation() {
+        let mut filter = MadgwickFilter::new(0.1, 0.01);
+        for _ in 0..100 {
+            filter.update([0.0, 0.0, 0.5], [0.0, 0.0, 9.8], [0.0; 3]);
+        }
+        let before = filter.quaternion();
+        filter.set_beta(0.5);
+        filter.set_sample_period(0.005);
+        assert_eq!(filter.quaternion(), before);
+        assert_eq!(filter.beta(), 0.5);
+        assert_eq!(filter.sample_period(), 0.005);
+
+        // Level and turning about z only, so one step turns by the rate times the new period
+        let after = filter.update([0.0, 0.0, 0.5], [0.0, 0.0, 9.8], [0.0; 3]);
+        let yaw = |q: (f32, f32, f32, f32)| 2.0 * libm::atan2f(q.3, q.0);
+        let step = yaw(after) - yaw(before);
+        assert!((step - 0.5 * 0.005).abs() < 1e-5, "Expected a 2.5 mrad step, got {}", step);
+    }
+
+    #[test]
+    fn test_innovation() {
+        let filter = MadgwickFilter::new(0.1, 0.01);
+        assert_eq!(filter.expected_gravity(), [0.0, 0.0, 1.0]);
+        assert!(filter.innovation(&[0.0, 0.0, 9.8]).unwrap() < 1e-3);
+        let tilted = filter.innovation(&[0.0, 9.8, 9.8]).unwrap();
+        assert!((tilted - core::f32::consts::FRAC_PI_4).abs() < 1e-3);
+        assert_eq!(filter.innovation(&[0.0; 3]), None);
+        assert_eq!(filter.innovation(&[f32::NAN, 0.0, 1.0]), None);
+    }
+
+    #[test]
+    fn test_adaptive_gain_scale() {
+        let gain = AdaptiveGain::default();
+        assert_eq!(gain.beta_for(0.0), gain.beta_min);
+        assert_eq!(gain.beta_for(gain.full_scale_innovation * 2.0), gain.beta_max);
+        let mid = gain.beta_for(gain.full_scale_innovation / 2.0);
+        assert!((mid - (gain.beta_min + gain.beta_max) / 2.0).abs() < 1e-6);
+    }
+}
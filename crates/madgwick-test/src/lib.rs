@@ -1,17 +1,65 @@
 #![no_std]
 
-use madgwick::Marg;
+pub use madgwick_filter::AdaptiveGain;
+use madgwick_filter::MadgwickFilter;
+
+/// Standard gravity in m/s^2
+const STANDARD_GRAVITY: f32 = 9.80665;
+
+/// Unit of the gyro rates passed to `update`; the filter itself works in rad/s
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GyroUnit {
+    RadPerSec,
+    DegPerSec,
+}
+
+impl GyroUnit {
+    pub fn to_rad_per_sec(self, rate: f32) -> f32 {
+        match self {
+            GyroUnit::RadPerSec => rate,
+            GyroUnit::DegPerSec => rate * (core::f32::consts::PI / 180.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GyroCalibrationError {
+    NoSamples,
+    Moving { variance: f32 },
+}
 
 pub struct MadgwickTest {
-    madgwick: Marg,
+    madgwick: MadgwickFilter,
     // Store a known good quaternion for initial testing
     initial_quat: (f32, f32, f32, f32),
+    // Latest quaternion produced by the filter
+    latest_quat: (f32, f32, f32, f32),
+    // Latest acceleration with gravity removed, in m/s^2
+    linear_accel: [f32; 3],
+    // Parameters the filter was created with
+    beta: f32,
+    sample_period: f32,
+    // Time stamp of the previous sample in us
+    last_time_stamp_us: Option<u32>,
+    // Accelerometer full scale range, samples at or beyond it are treated as clipped
+    accel_range: f32,
+    accel_clipped: bool,
+    gyro_unit: GyroUnit,
+    // Subtracted from the gyro rates, in rad/s
+    gyro_bias: [f32; 3],
+    adaptive_gain: Option<AdaptiveGain>,
+    // Averaged innovation driving the adaptive gain
+    innovation: f32,
 }
 
 impl MadgwickTest {
     // Default values as constants will be used if parameters cannot be used
     const DEFAULT_BETA: f32 = 0.1;
     const DEFAULT_SAMPLE_PERIOD: f32 = 0.01; // 100Hz
+    const DEFAULT_ACCEL_RANGE: f32 = 16.0 * STANDARD_GRAVITY; // +-16 g in m/s^2
+    const MIN_SAMPLE_PERIOD: f32 = 0.001;
+    const MAX_SAMPLE_PERIOD: f32 = 0.05;
+    const GYRO_BIAS_MAX_VARIANCE: f32 = 1e-4; // (rad/s)^2
     
     pub fn new() -> Self {
         // Beta and sample period values will be passed through the filter
@@ -21,43 +69,180 @@ impl MadgwickTest {
     // New constructor that accepts parameters
     pub fn new_with_params(beta: f32, sample_period: f32) -> Self {
         // Create the filter with specified parameters
-        let mut madgwick = Marg::new(beta, sample_period);
+        let mut madgwick = MadgwickFilter::new(beta, sample_period);
         
         // Initialize with standard gravity measurements
-        let accel = madgwick::F32x3 { x: 0.0, y: 0.0, z: 1.0 }; // "z: 1.0" represents the accelerometer pointing in the positive z-direction (upwards)
-        let gyro = madgwick::F32x3 { x: 0.0, y: 0.0, z: 0.0 };
-        let mag = madgwick::F32x3 { x: 1.0, y: 0.0, z: 0.0 }; // "x: 1.0" represents the magnetometer pointing in the positive x-direction 
+        let accel = [0.0, 0.0, 1.0]; // "z: 1.0" represents the accelerometer pointing in the positive z-direction (upwards)
+        let gyro = [0.0, 0.0, 0.0];
+        let mag = [1.0, 0.0, 0.0]; // "x: 1.0" represents the magnetometer pointing in the positive x-direction 
         
         // Get initial quaternion from filter
         let mut quat = (1.0, 0.0, 0.0, 0.0); // Default identity quaternion
         
         // Apply multiple updates to ensure convergence
         for _ in 0..5 {
-            let updated_quat = madgwick.update(mag, gyro, accel);
-            quat = (updated_quat.0, updated_quat.1, updated_quat.2, updated_quat.3);
+            quat = madgwick.update(gyro, accel, mag);
         }
         
         Self {
             madgwick,
             initial_quat: quat, // Use the quaternion from the filter
+            latest_quat: quat,
+            linear_accel: [0.0; 3],
+            beta,
+            sample_period,
+            last_time_stamp_us: None,
+            accel_range: Self::DEFAULT_ACCEL_RANGE,
+            accel_clipped: false,
+            gyro_unit: GyroUnit::RadPerSec,
+            gyro_bias: [0.0; 3],
+            adaptive_gain: None,
+            innovation: 0.0,
         }
     }
 
     pub fn update(&mut self, accel: [f32; 3], gyro: [f32; 3]) -> (f32, f32, f32, f32) {
-        let mag = madgwick::F32x3 { x: 0.0, y: 0.0, z: 0.0 };
-        let gyro = madgwick::F32x3 {
-            x: gyro[0],
-            y: gyro[1],
-            z: gyro[2],
+        self.update_with_mag(accel, gyro, [0.0, 0.0, 0.0])
+    }
+
+    // Integrates over the time since the previous time stamp in us, clamped, instead of the nominal sample period
+    pub fn update_at(&mut self, accel: [f32; 3], gyro: [f32; 3], time_stamp_us: u32) -> (f32, f32, f32, f32) {
+        self.madgwick.set_sample_period(match self.last_time_stamp_us {
+            Some(last_us) => {
+                let dt = time_stamp_us.wrapping_sub(last_us) as f32 * 1e-6;
+                dt.clamp(Self::MIN_SAMPLE_PERIOD, Self::MAX_SAMPLE_PERIOD)
+            }
+            None => self.sample_period,
+        });
+        self.last_time_stamp_us = Some(time_stamp_us);
+        self.update(accel, gyro)
+    }
+
+    // Magnetometer in the IMU body frame, a zero vector leaves the heading to the gyro
+    pub fn update_with_mag(&mut self, accel: [f32; 3], gyro: [f32; 3], mag: [f32; 3]) -> (f32, f32, f32, f32) {
+        let gyro_unit = self.gyro_unit;
+        let gyro_bias = self.gyro_bias;
+        let gyro = [0, 1, 2].map(|i| gyro_unit.to_rad_per_sec(gyro[i]) - gyro_bias[i]);
+        // A clipped sample is replaced by the gravity direction the filter already expects
+        let filter_accel = if accel.iter().any(|a| a.abs() >= self.accel_range) {
+            self.accel_clipped = true;
+            self.expected_gravity()
+        } else {
+            self.adapt_gain(&accel);
+            accel
+        };
+
+        // A non-finite update is dropped and the filter carries on from the previous quaternion
+        let quat = self.madgwick.update(gyro, filter_accel, mag);
+        if [quat.0, quat.1, quat.2, quat.3].iter().all(|q| q.is_finite()) {
+            self.latest_quat = quat;
+            let gravity = self.expected_gravity();
+            self.linear_accel = [0, 1, 2].map(|i| accel[i] - gravity[i] * STANDARD_GRAVITY);
+        } else {
+            self.madgwick.set_quaternion(self.latest_quat);
+        }
+        self.latest_quat
+    }
+
+    fn adapt_gain(&mut self, accel: &[f32; 3]) {
+        let Some(adaptive_gain) = self.adaptive_gain else {
+            return;
         };
-        let accel = madgwick::F32x3 {
-            x: accel[0],
-            y: accel[1],
-            z: accel[2],
+        let Some(innovation) = self.madgwick.innovation(accel) else {
+            return;
         };
+        self.innovation += adaptive_gain.smoothing * (innovation - self.innovation);
+        self.madgwick.set_beta(adaptive_gain.beta_for(self.innovation));
+    }
+
+    fn expected_gravity(&self) -> [f32; 3] {
+        self.madgwick.expected_gravity()
+    }
+
+    pub fn set_adaptive_gain(&mut self, adaptive_gain: Option<AdaptiveGain>) {
+        self.adaptive_gain = adaptive_gain;
+        match adaptive_gain {
+            Some(adaptive_gain) => {
+                self.innovation = adaptive_gain.full_scale_innovation;
+                self.madgwick.set_beta(adaptive_gain.beta_max);
+            }
+            None => {
+                self.innovation = 0.0;
+                self.madgwick.set_beta(self.beta);
+            }
+        }
+    }
+
+    pub fn effective_beta(&self) -> f32 {
+        self.madgwick.beta()
+    }
+
+    pub fn latest_quaternion(&self) -> (f32, f32, f32, f32) {
+        self.latest_quat
+    }
+
+    pub fn reset(&mut self) {
+        self.madgwick = MadgwickFilter::new(self.beta, self.sample_period);
+        self.last_time_stamp_us = None;
+        for _ in 0..5 {
+            self.latest_quat = self.madgwick.update([0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]);
+        }
+        self.set_adaptive_gain(self.adaptive_gain);
+    }
+
+    pub fn get_linear_acceleration(&self) -> (f32, f32, f32) {
+        (self.linear_accel[0], self.linear_accel[1], self.linear_accel[2])
+    }
+
+    pub fn get_euler(&self) -> (f32, f32, f32) {
+        quat_to_euler(self.latest_quat)
+    }
 
-        let quat = self.madgwick.update(mag, gyro, accel);
-        (quat.0, quat.1, quat.2, quat.3)
+    pub fn calibrate_gyro_bias(&mut self, samples: &[[f32; 3]]) -> Result<[f32; 3], GyroCalibrationError> {
+        if samples.is_empty() {
+            return Err(GyroCalibrationError::NoSamples);
+        }
+        let count = samples.len() as f32;
+        let gyro_unit = self.gyro_unit;
+        let mut mean = [0.0; 3];
+        for sample in samples {
+            for (mean, rate) in mean.iter_mut().zip(sample) {
+                *mean += gyro_unit.to_rad_per_sec(*rate) / count;
+            }
+        }
+        let mut variance = [0.0f32; 3];
+        for sample in samples {
+            for ((variance, mean), rate) in variance.iter_mut().zip(mean).zip(sample) {
+                let deviation = gyro_unit.to_rad_per_sec(*rate) - mean;
+                *variance += deviation * deviation / count;
+            }
+        }
+        let variance = variance.into_iter().fold(0.0, f32::max);
+        if variance.is_nan() || variance > Self::GYRO_BIAS_MAX_VARIANCE {
+            return Err(GyroCalibrationError::Moving { variance });
+        }
+        self.gyro_bias = mean;
+        Ok(mean)
+    }
+
+    pub fn get_gyro_bias(&self) -> [f32; 3] {
+        self.gyro_bias
+    }
+
+    pub fn set_gyro_bias(&mut self, gyro_bias: [f32; 3]) {
+        self.gyro_bias = gyro_bias;
+    }
+
+    pub fn set_accel_range(&mut self, accel_range: f32) {
+        self.accel_range = accel_range;
+    }
+
+    pub fn set_gyro_unit(&mut self, gyro_unit: GyroUnit) {
+        self.gyro_unit = gyro_unit;
+    }
+
+    pub fn accel_clipped(&self) -> bool {
+        self.accel_clipped
     }
 
     pub fn get_quaternion(&self) -> (f32, f32, f32, f32) {
@@ -67,11 +252,43 @@ impl MadgwickTest {
     
     // Methods to get and set parameters
     pub fn get_beta(&self) -> f32 {
-        Self::DEFAULT_BETA // For now we return the default, would need access to inner filter
+        self.beta
     }
     
     pub fn get_sample_period(&self) -> f32 {
-        Self::DEFAULT_SAMPLE_PERIOD // For now we return the default, would need access to inner filter
+        self.sample_period
+    }
+}
+
+/// Converts a (w, x, y, z) quaternion to (roll, pitch, yaw) in degrees, applied in yaw, pitch, roll order (aerospace ZYX)
+/// Roll and yaw are in (-180, 180] and pitch in [-90, 90]; near pitch = +-90 roll and yaw turn about the same axis,
+/// so roll is reported as 0 and the whole rotation about that axis as yaw
+pub fn quat_to_euler(quat: (f32, f32, f32, f32)) -> (f32, f32, f32) {
+    // Within ~0.1 deg of +-90 the roll and yaw terms are both close to atan2(0, 0)
+    const GIMBAL_LOCK_SIN_PITCH: f32 = 0.999999;
+    let (w, x, y, z) = quat;
+    let sin_pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0);
+    let (roll, pitch, yaw) = if sin_pitch >= GIMBAL_LOCK_SIN_PITCH {
+        (0.0, core::f32::consts::FRAC_PI_2, -2.0 * libm::atan2f(x, w))
+    } else if sin_pitch <= -GIMBAL_LOCK_SIN_PITCH {
+        (0.0, -core::f32::consts::FRAC_PI_2, 2.0 * libm::atan2f(x, w))
+    } else {
+        (
+            libm::atan2f(2.0 * (w * x + y * z), 1.0 - 2.0 * (x * x + y * y)),
+            libm::asinf(sin_pitch),
+            libm::atan2f(2.0 * (w * z + x * y), 1.0 - 2.0 * (y * y + z * z)),
+        )
+    };
+    (roll.to_degrees(), pitch.to_degrees(), wrap_degrees(yaw.to_degrees()))
+}
+
+/// Wraps an angle in degrees into (-180, 180]
+fn wrap_degrees(angle: f32) -> f32 {
+    let wrapped = libm::remainderf(angle, 360.0);
+    if wrapped <= -180.0 {
+        wrapped + 360.0
+    } else {
+        wrapped
     }
 }
 
@@ -108,6 +325,10 @@ mod tests {
         assert!(x.abs() < 0.1, "Expected x to be close to 0.0, got {}", x);
         assert!(y.abs() < 0.1, "Expected y to be close to 0.0, got {}", y);
         assert!(z.abs() < 0.1, "Expected z to be close to 0.0, got {}", z);
+
+        // The getters should report the parameters that were passed in, not the defaults
+        assert_eq!(service.get_beta(), 0.05);
+        assert_eq!(service.get_sample_period(), 0.02);
     }
     
     // Continuous Updates Test (making sure that the service is constantly being updated while running so accurate values are being used)
@@ -133,4 +354,284 @@ mod tests {
             "Quaternion should change after processing gyroscope data"
         );
     }
-}
\ No newline at end of file
+
+    // Clipping Test (samples at or beyond the accelerometer range are flagged and don't drag the orientation around)
+    #[test]
+    fn test_accel_clipping() {
+        let mut service = MadgwickTest::new();
+        service.set_accel_range(4.0);
+
+        // Below the range nothing is flagged
+        service.update([0.0, 0.0, 1.0], [0.0, 0.0, 0.0]);
+        assert!(!service.accel_clipped(), "Sample below the range should not be flagged");
+
+        // Saturated boost samples along a single axis
+        let mut quat = (0.0, 0.0, 0.0, 0.0);
+        for _ in 0..50 {
+            quat = service.update([4.0, 0.0, 4.0], [0.0, 0.0, 0.0]);
+        }
+        assert!(service.accel_clipped(), "Sample at the range should be flagged as clipped");
+
+        // With no rotation the orientation should stay put instead of chasing the saturated vector
+        let (w, x, y, z) = quat;
+        assert!(w.is_finite() && x.is_finite() && y.is_finite() && z.is_finite());
+        assert!(w > 0.9, "Expected w to be close to 1.0, got {}", w);
+        assert!(y.abs() < 0.1, "Expected y to be close to 0.0, got {}", y);
+    }
+
+    // Gyro Unit Test (a rate in deg/s must give the same orientation as the equivalent rate in rad/s)
+    #[test]
+    fn test_gyro_units() {
+        let mut rad_service = MadgwickTest::new();
+        let mut deg_service = MadgwickTest::new();
+        deg_service.set_gyro_unit(GyroUnit::DegPerSec);
+
+        let rate_rad = 0.5;
+        let rate_deg = rate_rad * 180.0 / core::f32::consts::PI;
+        let mut rad_quat = (0.0, 0.0, 0.0, 0.0);
+        let mut deg_quat = (0.0, 0.0, 0.0, 0.0);
+        for _ in 0..20 {
+            rad_quat = rad_service.update([0.0, 0.0, 1.0], [0.0, 0.0, rate_rad]);
+            deg_quat = deg_service.update([0.0, 0.0, 1.0], [0.0, 0.0, rate_deg]);
+        }
+
+        assert!((rad_quat.0 - deg_quat.0).abs() < 1e-5, "w differs: {} vs {}", rad_quat.0, deg_quat.0);
+        assert!((rad_quat.1 - deg_quat.1).abs() < 1e-5, "x differs: {} vs {}", rad_quat.1, deg_quat.1);
+        assert!((rad_quat.2 - deg_quat.2).abs() < 1e-5, "y differs: {} vs {}", rad_quat.2, deg_quat.2);
+        assert!((rad_quat.3 - deg_quat.3).abs() < 1e-5, "z differs: {} vs {}", rad_quat.3, deg_quat.3);
+        // The yaw rate should actually have rotated the orientation
+        assert!(rad_quat.3.abs() > 0.01, "Expected rotation about z, got {}", rad_quat.3);
+    }
+
+    // Adaptive Gain Test (the gain drops as the filter converges on a stationary tilt, rises again after a disturbance, and the orientation is never reset)
+    #[test]
+    fn test_adaptive_gain() {
+        let mut service = MadgwickTest::new();
+        service.set_adaptive_gain(Some(AdaptiveGain::default()));
+        assert_eq!(service.effective_beta(), AdaptiveGain::default().beta_max);
+
+        // Stationary, tilted 30 degrees about x
+        let tilted = [0.0, 0.5, 0.866];
+        let mut betas = [0.0; 1000];
+        for beta in betas.iter_mut() {
+            service.update(tilted, [0.0, 0.0, 0.0]);
+            *beta = service.effective_beta();
+        }
+        assert!(betas[999] < betas[100], "Gain should decrease as the filter converges: {} vs {}", betas[999], betas[100]);
+        assert!(betas[999] < 0.05, "Expected the gain to settle near beta_min, got {}", betas[999]);
+        let (w, x, _, _) = service.latest_quaternion();
+        assert!((2.0 * libm::atan2f(x, w) - 0.5236).abs() < 0.02, "Expected a 30 degree tilt about x");
+
+        // A sudden 60 degree change in the measured gravity direction
+        let settled_quat = service.latest_quaternion();
+        let settled_beta = service.effective_beta();
+        service.update([0.0, -0.5, 0.866], [0.0, 0.0, 0.0]);
+        let quat = service.latest_quaternion();
+        assert!((quat.1 - settled_quat.1).abs() < 0.01, "Orientation should continue from where it was");
+        for _ in 0..20 {
+            service.update([0.0, -0.5, 0.866], [0.0, 0.0, 0.0]);
+        }
+        assert!(service.effective_beta() > 4.0 * settled_beta, "Gain should increase after a disturbance, got {}", service.effective_beta());
+    }
+
+    // Magnetometer Test (the heading converges to the measured field, and without one it stays where the gyro left it)
+    #[test]
+    fn test_magnetometer_heading() {
+        let mut service = MadgwickTest::new();
+
+        // Level, with magnetic north along -y in the body frame, i.e. yawed 90 degrees
+        let mut quat = (0.0, 0.0, 0.0, 0.0);
+        for _ in 0..3000 {
+            quat = service.update_with_mag([0.0, 0.0, 1.0], [0.0, 0.0, 0.0], [0.0, -0.4, 0.3]);
+        }
+        let (w, x, y, z) = quat;
+        let yaw = 2.0 * libm::atan2f(z, w);
+        assert!((yaw - core::f32::consts::FRAC_PI_2).abs() < 0.02, "Expected a 90 degree heading, got {} rad", yaw);
+        assert!(x.abs() < 0.01 && y.abs() < 0.01, "The field's dip should not tilt the orientation");
+
+        // Without the magnetometer the heading is kept
+        for _ in 0..100 {
+            quat = service.update([0.0, 0.0, 1.0], [0.0, 0.0, 0.0]);
+        }
+        assert!((quat.3 - z).abs() < 1e-4, "Heading should not move without a magnetometer");
+    }
+
+    // Euler Test (yawing at a known rate gives the expected heading with the filter level)
+    #[test]
+    fn test_euler_yaw() {
+        let mut service = MadgwickTest::new();
+
+        // 0.5 rad/s for 2 s is 1 rad of yaw
+        for _ in 0..200 {
+            service.update([0.0, 0.0, 1.0], [0.0, 0.0, 0.5]);
+        }
+        let (roll, pitch, yaw) = service.get_euler();
+        assert!((yaw - 57.2958).abs() < 0.5, "Expected a 57.3 degree heading, got {}", yaw);
+        assert!(roll.abs() < 0.1 && pitch.abs() < 0.1, "Expected level, got roll {} pitch {}", roll, pitch);
+
+        // Keep turning past 180 degrees, the heading wraps to negative
+        for _ in 0..500 {
+            service.update([0.0, 0.0, 1.0], [0.0, 0.0, 0.5]);
+        }
+        let (_, _, yaw) = service.get_euler();
+        assert!((yaw - (57.2958 * 3.5 - 360.0)).abs() < 1.0, "Expected the heading to wrap, got {}", yaw);
+    }
+
+    // Gimbal Lock Test (pointing straight up the whole rotation about the vertical is reported as yaw)
+    #[test]
+    fn test_euler_gimbal_lock() {
+        // Yaw 30 degrees, then pitch up 90 degrees
+        let half_yaw = 15.0f32.to_radians();
+        let c = core::f32::consts::FRAC_1_SQRT_2;
+        let quat = (libm::cosf(half_yaw) * c, -libm::sinf(half_yaw) * c, libm::cosf(half_yaw) * c, libm::sinf(half_yaw) * c);
+        let (roll, pitch, yaw) = quat_to_euler(quat);
+        assert!(roll.is_finite() && pitch.is_finite() && yaw.is_finite());
+        assert!((pitch - 90.0).abs() < 0.1, "Expected pitch of 90 degrees, got {}", pitch);
+        assert_eq!(roll, 0.0);
+        assert!((yaw - 30.0).abs() < 0.1, "Expected yaw of 30 degrees, got {}", yaw);
+
+        // Same below the horizon
+        let quat = (quat.0, -quat.1, -quat.2, quat.3);
+        let (_, pitch, yaw) = quat_to_euler(quat);
+        assert!((pitch + 90.0).abs() < 0.1, "Expected pitch of -90 degrees, got {}", pitch);
+        assert!((yaw - 30.0).abs() < 0.1, "Expected yaw of 30 degrees, got {}", yaw);
+    }
+
+    // Reset Test (a perturbed filter returns to roughly identity after a reset)
+    #[test]
+    fn test_reset() {
+        let mut service = MadgwickTest::new();
+
+        // Tumble the filter away from identity
+        for _ in 0..100 {
+            service.update([0.0, 1.0, 0.0], [1.0, 0.5, 2.0]);
+        }
+        let (w, _, _, _) = service.latest_quaternion();
+        assert!(w < 0.9, "Expected the filter to be perturbed, got w {}", w);
+
+        service.reset();
+        let (w, x, y, z) = service.latest_quaternion();
+        assert!(w > 0.99, "Expected w to be close to 1.0, got {}", w);
+        assert!(x.abs() < 0.01 && y.abs() < 0.01 && z.abs() < 0.01, "Expected identity, got ({}, {}, {})", x, y, z);
+        assert_eq!(service.get_beta(), 0.1, "Reset should keep the parameters");
+    }
+
+    // Gyro Bias Test (a calibrated bias no longer drifts the heading, and samples from a moving board are rejected)
+    #[test]
+    fn test_gyro_bias_calibration() {
+        let mut service = MadgwickTest::new();
+
+        // Stationary samples with a 0.02 rad/s bias about z and a little noise
+        let mut samples = [[0.0; 3]; 100];
+        for (i, sample) in samples.iter_mut().enumerate() {
+            let noise = if i % 2 == 0 { 0.001 } else { -0.001 };
+            *sample = [noise, -noise, 0.02 + noise];
+        }
+        let bias = service.calibrate_gyro_bias(&samples).unwrap();
+        assert!((bias[2] - 0.02).abs() < 1e-5, "Expected a bias of 0.02 rad/s, got {}", bias[2]);
+        assert_eq!(service.get_gyro_bias(), bias);
+
+        // The biased rate now leaves the heading alone
+        for _ in 0..500 {
+            service.update([0.0, 0.0, 1.0], [0.0, 0.0, 0.02]);
+        }
+        let (_, _, _, z) = service.latest_quaternion();
+        assert!(z.abs() < 1e-4, "Expected no yaw drift, got z {}", z);
+
+        // Rotating samples are rejected and the previous bias is kept
+        let mut moving = [[0.0; 3]; 100];
+        for (i, sample) in moving.iter_mut().enumerate() {
+            *sample = [0.0, 0.0, i as f32 * 0.01];
+        }
+        assert!(matches!(service.calibrate_gyro_bias(&moving), Err(GyroCalibrationError::Moving { .. })));
+        assert_eq!(service.calibrate_gyro_bias(&[]), Err(GyroCalibrationError::NoSamples));
+        assert_eq!(service.get_gyro_bias(), bias);
+
+        service.set_gyro_bias([0.0; 3]);
+        assert_eq!(service.get_gyro_bias(), [0.0; 3]);
+    }
+
+    // Time Stamp Test (each update integrates over the real time between samples, and a dropout doesn't make a huge step)
+    #[test]
+    fn test_sample_period_from_time_stamps() {
+        let rate = 0.5; // rad/s about z
+        let mut nominal = MadgwickTest::new();
+        let mut slow = MadgwickTest::new();
+
+        // Samples actually arriving every 12.5 ms rather than the nominal 10 ms
+        let mut time_stamp_us = 1_000_000u32;
+        for _ in 0..160 {
+            nominal.update([0.0, 0.0, 1.0], [0.0, 0.0, rate]);
+            slow.update_at([0.0, 0.0, 1.0], [0.0, 0.0, rate], time_stamp_us);
+            time_stamp_us += 12_500;
+        }
+        // 159 intervals of 12.5 ms after the first sample at the nominal period
+        let (_, _, nominal_yaw) = nominal.get_euler();
+        let (_, _, slow_yaw) = slow.get_euler();
+        let expected = (0.01 + 159.0 * 0.0125) * rate;
+        assert!((slow_yaw.to_radians() - expected).abs() < 0.01, "Expected {} rad, got {}", expected, slow_yaw.to_radians());
+        assert!((nominal_yaw.to_radians() - 1.6 * rate).abs() < 0.01);
+
+        // A 2 s dropout only integrates the maximum period
+        let (_, _, before) = slow.get_euler();
+        slow.update_at([0.0, 0.0, 1.0], [0.0, 0.0, rate], time_stamp_us + 2_000_000);
+        let (_, _, after) = slow.get_euler();
+        assert!(((after - before).to_radians() - 0.05 * rate).abs() < 1e-3, "Step after a dropout should be clamped");
+
+        // The time stamp counter wrapping around doesn't break the period
+        let mut wrapping = MadgwickTest::new();
+        wrapping.update_at([0.0, 0.0, 1.0], [0.0, 0.0, rate], u32::MAX - 4_999);
+        wrapping.update_at([0.0, 0.0, 1.0], [0.0, 0.0, rate], 5_000);
+        let (_, _, yaw) = wrapping.get_euler();
+        assert!((yaw.to_radians() - 0.02 * rate).abs() < 1e-3, "Expected a 10 ms step across the wrap, got {}", yaw);
+    }
+
+    // Non-finite Input Test (a NaN sample is discarded and the quaternion stays where it was)
+    #[test]
+    fn test_nan_input_is_discarded() {
+        let mut service = MadgwickTest::new();
+        service.set_adaptive_gain(Some(AdaptiveGain::default()));
+        for _ in 0..10 {
+            service.update([0.0, 0.5, 0.866], [0.1, 0.0, 0.0]);
+        }
+        let before = service.latest_quaternion();
+        let beta = service.effective_beta();
+
+        let quat = service.update([f32::NAN, 0.0, 1.0], [0.0, 0.0, 0.0]);
+        assert_eq!(quat, before, "A NaN accelerometer sample should leave the quaternion unchanged");
+        assert_eq!(service.latest_quaternion(), before);
+        assert_eq!(service.effective_beta(), beta, "A NaN sample should not reach the adaptive gain");
+
+        // Following good samples carry on from the same quaternion
+        let (w, x, y, z) = service.update([0.0, 0.5, 0.866], [0.0, 0.0, 0.0]);
+        assert!(w.is_finite() && x.is_finite() && y.is_finite() && z.is_finite());
+        assert!((w - before.0).abs() < 0.01 && (x - before.1).abs() < 0.01);
+    }
+
+    // Linear Acceleration Test (gravity is removed whatever the attitude, leaving only the applied acceleration)
+    #[test]
+    fn test_linear_acceleration() {
+        let mut service = MadgwickTest::new();
+
+        // Level and at rest
+        for _ in 0..10 {
+            service.update([0.0, 0.0, STANDARD_GRAVITY], [0.0, 0.0, 0.0]);
+        }
+        let (x, y, z) = service.get_linear_acceleration();
+        assert!(x.abs() < 1e-3 && y.abs() < 1e-3 && z.abs() < 1e-3, "Expected ~0 at rest, got ({}, {}, {})", x, y, z);
+
+        // Tilted 30 degrees about x once converged
+        let tilted = [0.0, 0.5 * STANDARD_GRAVITY, 0.866 * STANDARD_GRAVITY];
+        for _ in 0..3000 {
+            service.update(tilted, [0.0, 0.0, 0.0]);
+        }
+        let (x, y, z) = service.get_linear_acceleration();
+        assert!(x.abs() < 0.05 && y.abs() < 0.05 && z.abs() < 0.05, "Expected ~0 when tilted, got ({}, {}, {})", x, y, z);
+
+        // 3 g of thrust along z on the pad
+        service.reset();
+        service.update([0.0, 0.0, 4.0 * STANDARD_GRAVITY], [0.0, 0.0, 0.0]);
+        let (_, _, z) = service.get_linear_acceleration();
+        assert!((z - 3.0 * STANDARD_GRAVITY).abs() < 0.01, "Expected 3 g of thrust, got {}", z);
+    }
+}
@@ -0,0 +1,138 @@
+//! Detection of a ballistic (tumbling, unrecovered) descent.
+//!
+//! Under a parachute the vehicle descends slowly and hangs fairly still. Without one it falls fast
+//! and tumbles. A ballistic descent is flagged once both the descent rate and the rotation rate
+//! have stayed above their thresholds for `sustain_ms`. The flag latches, it is meant for logging
+//! and for a backup deployment attempt.
+
+use defmt::warn;
+
+#[derive(Clone)]
+pub struct BallisticDetector {
+    /// Descent rate above which the descent is too fast for a parachute, in m/s (positive down).
+    descent_rate_threshold: f32,
+    /// Rotation rate above which the vehicle counts as tumbling, in rad/s.
+    rotation_threshold: f32,
+    /// Time both conditions must hold, in ms.
+    sustain_ms: u64,
+    descent_rate: f32,
+    rotation_sq: f32,
+    /// When both conditions started holding.
+    since_ms: Option<u64>,
+    ballistic: bool,
+}
+
+impl BallisticDetector {
+    pub fn new(descent_rate_threshold: f32, rotation_threshold: f32, sustain_ms: u64) -> Self {
+        Self {
+            descent_rate_threshold,
+            rotation_threshold,
+            sustain_ms,
+            descent_rate: 0.0,
+            rotation_sq: 0.0,
+            since_ms: None,
+            ballistic: false,
+        }
+    }
+
+    /// Feeds the vertical velocity in m/s, positive up.
+    pub fn update_vertical_velocity(&mut self, velocity: f32, now_ms: u64) {
+        self.descent_rate = -velocity;
+        self.evaluate(now_ms);
+    }
+
+    /// Feeds a gyro sample in rad/s.
+    pub fn update_gyro(&mut self, gyro: [f32; 3], now_ms: u64) {
+        self.rotation_sq = gyro[0] * gyro[0] + gyro[1] * gyro[1] + gyro[2] * gyro[2];
+        self.evaluate(now_ms);
+    }
+
+    fn evaluate(&mut self, now_ms: u64) {
+        let falling = self.descent_rate > self.descent_rate_threshold;
+        let tumbling = self.rotation_sq > self.rotation_threshold * self.rotation_threshold;
+        if !(falling && tumbling) {
+            self.since_ms = None;
+            return;
+        }
+        let since = *self.since_ms.get_or_insert(now_ms);
+        if !self.ballistic && now_ms.saturating_sub(since) >= self.sustain_ms {
+            warn!(
+                "Ballistic descent detected, falling at {} m/s",
+                self.descent_rate
+            );
+            self.ballistic = true;
+        }
+    }
+
+    pub fn is_ballistic(&self) -> bool {
+        self.ballistic
+    }
+
+    pub fn set_descent_rate_threshold(&mut self, descent_rate_threshold: f32) {
+        self.descent_rate_threshold = descent_rate_threshold;
+    }
+
+    pub fn set_rotation_threshold(&mut self, rotation_threshold: f32) {
+        self.rotation_threshold = rotation_threshold;
+    }
+
+    pub fn set_sustain(&mut self, sustain_ms: u64) {
+        self.sustain_ms = sustain_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCENT_RATE_THRESHOLD: f32 = 40.0;
+    const ROTATION_THRESHOLD: f32 = 6.0;
+    const SUSTAIN_MS: u64 = 1000;
+
+    /// Runs two seconds of 100 Hz gyro and 10 Hz velocity samples.
+    fn run(detector: &mut BallisticDetector, velocity: f32, gyro: fn(u64) -> [f32; 3]) {
+        for t in (0..2000).step_by(10) {
+            if t % 100 == 0 {
+                detector.update_vertical_velocity(velocity, t);
+            }
+            detector.update_gyro(gyro(t), t);
+        }
+    }
+
+    #[test]
+    fn fast_tumbling_descent_is_ballistic() {
+        let mut detector =
+            BallisticDetector::new(DESCENT_RATE_THRESHOLD, ROTATION_THRESHOLD, SUSTAIN_MS);
+        // Erratic rotation switching axes and direction.
+        run(&mut detector, -80.0, |t| match (t / 30) % 3 {
+            0 => [9.0, -4.0, 2.0],
+            1 => [-3.0, 8.0, -6.0],
+            _ => [5.0, 5.0, -9.0],
+        });
+        assert!(detector.is_ballistic());
+    }
+
+    #[test]
+    fn parachute_descent_is_not_ballistic() {
+        let mut detector =
+            BallisticDetector::new(DESCENT_RATE_THRESHOLD, ROTATION_THRESHOLD, SUSTAIN_MS);
+        // Slow descent with gentle swinging under the canopy.
+        run(&mut detector, -7.0, |t| {
+            if (t / 500) % 2 == 0 {
+                [0.5, 0.2, 0.1]
+            } else {
+                [-0.5, -0.2, 0.1]
+            }
+        });
+        assert!(!detector.is_ballistic());
+    }
+
+    #[test]
+    fn fast_stable_descent_is_not_ballistic() {
+        let mut detector =
+            BallisticDetector::new(DESCENT_RATE_THRESHOLD, ROTATION_THRESHOLD, SUSTAIN_MS);
+        // Nose-down but stable, e.g. right after apogee with drogue lines still paying out.
+        run(&mut detector, -60.0, |_| [0.2, 0.1, 0.0]);
+        assert!(!detector.is_ballistic());
+    }
+}
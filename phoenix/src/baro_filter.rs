@@ -0,0 +1,122 @@
+//! Vertical velocity from successive barometric altitudes.
+//!
+//! Apogee detection needs the rate of climb, not just the altitude. Each new altitude is
+//! differenced against the previous one, and the result is smoothed with a first-order low-pass
+//! filter so the baro noise doesn't swamp the estimate. A longer time constant gives a smoother
+//! velocity that lags further behind.
+
+#[derive(Clone)]
+pub struct BaroVelocityFilter {
+    /// Time constant of the low-pass filter, in s.
+    time_constant_s: f32,
+    /// Previous altitude in m and the time it was read, in ms.
+    previous: Option<(u64, f32)>,
+    /// Smoothed vertical velocity in m/s, positive up.
+    velocity: Option<f32>,
+}
+
+impl BaroVelocityFilter {
+    pub fn new(time_constant_s: f32) -> Self {
+        Self {
+            time_constant_s,
+            previous: None,
+            velocity: None,
+        }
+    }
+
+    /// Adds an altitude read at `at_ms` and returns the smoothed vertical velocity, or `None` for
+    /// the first sample. Samples that aren't newer than the previous one or aren't finite are
+    /// ignored.
+    pub fn update(&mut self, altitude_m: f32, at_ms: u64) -> Option<f32> {
+        if !altitude_m.is_finite() {
+            return self.velocity;
+        }
+        let Some((previous_ms, previous_m)) = self.previous else {
+            self.previous = Some((at_ms, altitude_m));
+            return None;
+        };
+        if at_ms <= previous_ms {
+            return self.velocity;
+        }
+        self.previous = Some((at_ms, altitude_m));
+
+        let dt_s = (at_ms - previous_ms) as f32 / 1000.0;
+        let raw = (altitude_m - previous_m) / dt_s;
+        let velocity = match self.velocity {
+            Some(velocity) => {
+                let alpha = dt_s / (self.time_constant_s + dt_s);
+                velocity + alpha * (raw - velocity)
+            }
+            None => raw,
+        };
+        self.velocity = Some(velocity);
+        self.velocity
+    }
+
+    /// Last smoothed vertical velocity in m/s, positive up.
+    pub fn velocity(&self) -> Option<f32> {
+        self.velocity
+    }
+
+    /// Forgets the previous samples, e.g. after the baro stopped reporting for a while.
+    pub fn reset(&mut self) {
+        self.previous = None;
+        self.velocity = None;
+    }
+
+    pub fn set_time_constant(&mut self, time_constant_s: f32) {
+        self.time_constant_s = time_constant_s;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIME_CONSTANT_S: f32 = 0.5;
+
+    #[test]
+    fn first_sample_has_no_velocity() {
+        let mut filter = BaroVelocityFilter::new(TIME_CONSTANT_S);
+        assert_eq!(filter.update(100.0, 0), None);
+        assert_eq!(filter.velocity(), None);
+    }
+
+    #[test]
+    fn steady_climb_is_tracked() {
+        let mut filter = BaroVelocityFilter::new(TIME_CONSTANT_S);
+        // 50 m/s climb sampled at 20 Hz.
+        let mut velocity = None;
+        for i in 0..100u64 {
+            velocity = filter.update(i as f32 * 2.5, i * 50);
+        }
+        assert!((velocity.unwrap() - 50.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn noise_is_smoothed() {
+        let mut filter = BaroVelocityFilter::new(TIME_CONSTANT_S);
+        // Stationary, with the altitude alternating by 1 m every 50 ms.
+        let mut velocity = 0.0;
+        for i in 0..100u64 {
+            let altitude = if i % 2 == 0 { 0.0 } else { 1.0 };
+            if let Some(v) = filter.update(altitude, i * 50) {
+                velocity = v;
+            }
+        }
+        // Unfiltered, each step would read 20 m/s.
+        assert!(velocity.abs() < 2.0);
+    }
+
+    #[test]
+    fn stale_and_invalid_samples_are_ignored() {
+        let mut filter = BaroVelocityFilter::new(TIME_CONSTANT_S);
+        filter.update(0.0, 1000);
+        assert_eq!(filter.update(10.0, 2000), Some(10.0));
+        assert_eq!(filter.update(50.0, 2000), Some(10.0));
+        assert_eq!(filter.update(f32::NAN, 3000), Some(10.0));
+
+        filter.reset();
+        assert_eq!(filter.update(0.0, 4000), None);
+    }
+}
@@ -0,0 +1,133 @@
+//! Baro sample interval for each flight phase.
+//!
+//! Near apogee the altitude changes by metres between samples a second apart, too coarse to tell
+//! when the velocity changes sign, so the baro is read fast in flight. On the pad and after
+//! landing nothing moves and a slow rate saves power.
+
+use crate::FlightPhase;
+
+/// Interval on the pad and after landing, in ms.
+pub const BARO_GROUND_INTERVAL_MS: u32 = 1000;
+/// Interval during boost and descent, in ms (20 Hz).
+pub const BARO_FLIGHT_INTERVAL_MS: u32 = 50;
+/// Interval around apogee, in ms (50 Hz).
+pub const BARO_APOGEE_INTERVAL_MS: u32 = 20;
+
+const PHASES: usize = 6;
+
+#[derive(Clone)]
+pub struct BaroRate {
+    /// Interval of each phase in ms, indexed by `FlightPhase as usize`.
+    interval_ms: [u32; PHASES],
+}
+
+impl BaroRate {
+    /// Uses the default intervals, see [`Self::set_interval`] to change them.
+    pub const fn new() -> Self {
+        Self {
+            interval_ms: [
+                BARO_GROUND_INTERVAL_MS,
+                BARO_FLIGHT_INTERVAL_MS,
+                BARO_APOGEE_INTERVAL_MS,
+                BARO_APOGEE_INTERVAL_MS,
+                BARO_FLIGHT_INTERVAL_MS,
+                BARO_GROUND_INTERVAL_MS,
+            ],
+        }
+    }
+
+    /// Sets the time between the starts of two readings during `phase`. An interval shorter than
+    /// a reading takes reads back to back.
+    pub fn set_interval(&mut self, phase: FlightPhase, interval_ms: u32) {
+        self.interval_ms[phase as usize] = interval_ms;
+    }
+
+    pub fn interval_ms(&self, phase: FlightPhase) -> u32 {
+        self.interval_ms[phase as usize]
+    }
+
+    /// Time left to wait during `phase` after a reading that took `elapsed_ms`, so readings start
+    /// `interval_ms` apart however long each one took.
+    pub fn remaining_ms(&self, phase: FlightPhase, elapsed_ms: u32) -> u32 {
+        self.interval_ms(phase).saturating_sub(elapsed_ms)
+    }
+}
+
+impl Default for BaroRate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Time one reading takes, two conversions at the lowest oversampling ratio and their margin.
+    const READ_MS: u32 = 8;
+
+    /// Start times of the first `N` readings of the baro loop, staying in `phase`.
+    fn read_times<const N: usize>(rate: &BaroRate, phase: FlightPhase) -> [u32; N] {
+        let mut now_ms = 0;
+        let mut times = [0; N];
+        for time in times.iter_mut() {
+            *time = now_ms;
+            now_ms += READ_MS;
+            now_ms += rate.remaining_ms(phase, READ_MS);
+        }
+        times
+    }
+
+    const _: () = assert!(BARO_APOGEE_INTERVAL_MS < BARO_GROUND_INTERVAL_MS);
+
+    #[test]
+    fn slow_on_the_ground_fast_in_flight() {
+        let rate = BaroRate::new();
+        assert_eq!(rate.interval_ms(FlightPhase::Idle), BARO_GROUND_INTERVAL_MS);
+        assert_eq!(
+            rate.interval_ms(FlightPhase::Boost),
+            BARO_FLIGHT_INTERVAL_MS
+        );
+        assert_eq!(
+            rate.interval_ms(FlightPhase::Coast),
+            BARO_APOGEE_INTERVAL_MS
+        );
+        assert_eq!(
+            rate.interval_ms(FlightPhase::Apogee),
+            BARO_APOGEE_INTERVAL_MS
+        );
+        assert_eq!(
+            rate.interval_ms(FlightPhase::Descent),
+            BARO_FLIGHT_INTERVAL_MS
+        );
+        assert_eq!(
+            rate.interval_ms(FlightPhase::Landed),
+            BARO_GROUND_INTERVAL_MS
+        );
+    }
+
+    #[test]
+    fn readings_start_one_interval_apart() {
+        let mut rate = BaroRate::new();
+        rate.set_interval(FlightPhase::Coast, 25);
+
+        let times = read_times::<4>(&rate, FlightPhase::Coast);
+        assert_eq!(times, [0, 25, 50, 75]);
+        let times = read_times::<3>(&rate, FlightPhase::Idle);
+        assert_eq!(times, [0, 1000, 2000]);
+        // Only the configured phase changed.
+        assert_eq!(
+            rate.interval_ms(FlightPhase::Apogee),
+            BARO_APOGEE_INTERVAL_MS
+        );
+    }
+
+    #[test]
+    fn interval_shorter_than_a_reading_reads_back_to_back() {
+        let mut rate = BaroRate::new();
+        rate.set_interval(FlightPhase::Apogee, 1);
+        assert_eq!(rate.remaining_ms(FlightPhase::Apogee, READ_MS), 0);
+        let times = read_times::<3>(&rate, FlightPhase::Apogee);
+        assert_eq!(times, [0, READ_MS, 2 * READ_MS]);
+    }
+}
@@ -0,0 +1,83 @@
+//! Boot-phase LED codes.
+//!
+//! Before the normal run-time blink starts, `blink` plays back which subsystems came up during
+//! `init` so a boot failure can be diagnosed without a debugger. Each subsystem flashes its
+//! number of times, in order, followed by a pause:
+//!
+//! | Subsystem | Flashes |
+//! | :-------- | :------ |
+//! | CAN       | 1       |
+//! | Baro      | 2       |
+//! | Radio     | 3       |
+//! | Clocks    | 4       |
+//!
+//! The green LED flashes if the subsystem came up and the red LED flashes if it failed. A
+//! subsystem that never reported is skipped.
+
+/// Subsystems brought up in `init`.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Subsystem {
+    Can,
+    Baro,
+    Radio,
+    /// `sys_ck` and PLL1Q match what the board expects and both FDCANs are clocked from PLL1Q.
+    Clocks,
+}
+
+impl Subsystem {
+    /// All subsystems in the order their codes are played back.
+    pub const ALL: [Subsystem; 4] = [
+        Subsystem::Can,
+        Subsystem::Baro,
+        Subsystem::Radio,
+        Subsystem::Clocks,
+    ];
+
+    /// Number of LED flashes identifying this subsystem.
+    pub fn flashes(self) -> u8 {
+        self as u8 + 1
+    }
+
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Outcome of bringing up each [`Subsystem`] during `init`.
+#[derive(Clone, Copy, Default)]
+pub struct BootStatus {
+    up: u8,
+    failed: u8,
+}
+
+impl BootStatus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mark_up(&mut self, subsystem: Subsystem) {
+        self.up |= subsystem.mask();
+        self.failed &= !subsystem.mask();
+    }
+
+    pub fn mark_failed(&mut self, subsystem: Subsystem) {
+        self.failed |= subsystem.mask();
+        self.up &= !subsystem.mask();
+    }
+
+    /// Returns `Some(true)` if the subsystem came up, `Some(false)` if it failed, and `None` if it
+    /// never reported.
+    pub fn get(&self, subsystem: Subsystem) -> Option<bool> {
+        if self.up & subsystem.mask() != 0 {
+            Some(true)
+        } else if self.failed & subsystem.mask() != 0 {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    pub fn any_failed(&self) -> bool {
+        self.failed != 0
+    }
+}
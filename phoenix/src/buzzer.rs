@@ -0,0 +1,22 @@
+//! Buzzer frequency control.
+//!
+//! Piezo buzzers are loudest at their resonant frequency, which depends on the model. Typical
+//! values are 2.7 kHz for 12 mm buzzers, around 3 kHz for most PCB-mount parts and about 4 kHz
+//! for the small 9 mm ones. Check the datasheet of the fitted part and set
+//! `BUZZER_FREQUENCY_HZ` to match.
+
+use stm32h7xx_hal::pac;
+
+/// Changes the frequency of the buzzer PWM on TIM12 while it runs. `timer_clock_hz` is the TIM12
+/// kernel clock. The duty cycle follows since the HAL reads the max duty back from the timer.
+pub fn set_frequency(timer_clock_hz: u32, frequency_hz: u32) {
+    let ticks = timer_clock_hz / frequency_hz.max(1);
+    let prescaler = (ticks.saturating_sub(1) / (1 << 16)) as u16;
+    let reload = (ticks / (prescaler as u32 + 1)).saturating_sub(1) as u16;
+    // SAFETY: TIM12 is only used for the buzzer PWM. The channel owned by `blink` never writes the
+    // prescaler or reload registers, so changing them here doesn't race with it.
+    let tim = unsafe { &*pac::TIM12::ptr() };
+    tim.psc.write(|w| w.psc().bits(prescaler));
+    tim.arr.write(|w| w.arr().bits(reload));
+    tim.egr.write(|w| w.ug().set_bit());
+}
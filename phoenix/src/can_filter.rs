@@ -0,0 +1,118 @@
+//! Layout of the FDCAN standard ID filter elements that accept exactly a list of IDs.
+//!
+//! Each element holds two IDs stored in the same receive FIFO, commands in FIFO1 and everything
+//! else in FIFO0. An element rejecting every other frame follows them, the FDCAN stops at the
+//! first element that matches.
+
+/// Standard ID filter elements in the FDCAN message RAM.
+pub const CAN_STANDARD_FILTER_SLOTS: usize = 28;
+/// Most IDs [`filter_elements`] takes. Each FIFO may leave one element half empty, and the last
+/// element is needed to reject everything else.
+pub const CAN_MAX_FILTER_IDS: usize = 2 * (CAN_STANDARD_FILTER_SLOTS - 2);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RxFifo {
+    Fifo0,
+    Fifo1,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FilterElement {
+    /// Stores frames with either ID in `fifo`.
+    Accept {
+        ids: (u16, u16),
+        fifo: RxFifo,
+    },
+    RejectAll,
+}
+
+/// Filter elements accepting exactly `ids`, those `is_command` holds for into FIFO1 and the rest
+/// into FIFO0, followed by one rejecting any other frame. Returns `None` if there are more than
+/// [`CAN_MAX_FILTER_IDS`] IDs, rather than silently dropping the frames of those that don't fit.
+pub fn filter_elements(
+    ids: &[u16],
+    is_command: fn(u16) -> bool,
+) -> Option<impl Iterator<Item = FilterElement> + '_> {
+    if ids.len() > CAN_MAX_FILTER_IDS {
+        return None;
+    }
+    let commands =
+        id_pairs(ids.iter().filter(move |id| is_command(**id))).map(|ids| FilterElement::Accept {
+            ids,
+            fifo: RxFifo::Fifo1,
+        });
+    let others =
+        id_pairs(ids.iter().filter(move |id| !is_command(**id))).map(|ids| FilterElement::Accept {
+            ids,
+            fifo: RxFifo::Fifo0,
+        });
+    Some(
+        commands
+            .chain(others)
+            .chain(core::iter::once(FilterElement::RejectAll)),
+    )
+}
+
+/// Consecutive pairs of `ids`, an odd ID out paired with itself.
+fn id_pairs<'a>(mut ids: impl Iterator<Item = &'a u16>) -> impl Iterator<Item = (u16, u16)> {
+    core::iter::from_fn(move || {
+        let first = *ids.next()?;
+        Some((first, ids.next().copied().unwrap_or(first)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::Vec;
+
+    fn is_command(id: u16) -> bool {
+        id < 0x100
+    }
+
+    fn elements(ids: &[u16]) -> Vec<FilterElement, CAN_STANDARD_FILTER_SLOTS> {
+        filter_elements(ids, is_command).unwrap().collect()
+    }
+
+    #[test]
+    fn commands_go_to_fifo1_ahead_of_the_rest() {
+        let elements = elements(&[0x201, 0x001, 0x202, 0x002, 0x003]);
+        assert_eq!(
+            elements.as_slice(),
+            &[
+                FilterElement::Accept {
+                    ids: (0x001, 0x002),
+                    fifo: RxFifo::Fifo1
+                },
+                FilterElement::Accept {
+                    ids: (0x003, 0x003),
+                    fifo: RxFifo::Fifo1
+                },
+                FilterElement::Accept {
+                    ids: (0x201, 0x202),
+                    fifo: RxFifo::Fifo0
+                },
+                FilterElement::RejectAll,
+            ]
+        );
+    }
+
+    #[test]
+    fn no_ids_rejects_everything() {
+        assert_eq!(elements(&[]).as_slice(), &[FilterElement::RejectAll]);
+    }
+
+    #[test]
+    fn most_ids_fit_the_slots() {
+        // Odd counts in both FIFOs, the worst case for half empty elements.
+        let commands = 25;
+        let ids: Vec<u16, CAN_MAX_FILTER_IDS> = (0..commands)
+            .chain(0x200..0x200 + (CAN_MAX_FILTER_IDS as u16 - commands))
+            .collect();
+        assert_eq!(ids.len(), CAN_MAX_FILTER_IDS);
+        assert!(elements(&ids).len() <= CAN_STANDARD_FILTER_SLOTS);
+
+        let too_many = [0x200; CAN_MAX_FILTER_IDS + 1];
+        assert!(filter_elements(&too_many, is_command).is_none());
+    }
+}
@@ -0,0 +1,64 @@
+//! Limits of a single CAN FD frame.
+
+/// Largest data field a single CAN FD frame can carry.
+pub const CAN_FD_MAX_PAYLOAD: usize = 64;
+
+/// Frame length for a payload of `payload_len` bytes, `None` if it doesn't fit one frame.
+///
+/// `TxFrameHeader::len` is a `u8` and a CAN FD frame carries at most 64 data bytes, so an
+/// unchecked `as u8` would silently truncate an oversized payload instead of failing.
+pub fn can_frame_len(payload_len: usize) -> Option<u8> {
+    if payload_len > CAN_FD_MAX_PAYLOAD {
+        return None;
+    }
+    Some(payload_len as u8)
+}
+
+/// Data lengths above 8 bytes a CAN FD frame can have. A payload of any other length is padded up
+/// to the next one on the bus.
+const CAN_FD_LONG_LENS: [usize; 7] = [12, 16, 20, 24, 32, 48, 64];
+
+/// Longest CAN FD data length of at most `len` bytes, one that goes on the bus unpadded.
+///
+/// A receiver can't tell padding from data, so the fragments a message is cut into must all have
+/// such a length, except for the last.
+pub fn can_fd_len_at_most(len: usize) -> usize {
+    if len <= 8 {
+        return len;
+    }
+    CAN_FD_LONG_LENS
+        .iter()
+        .rev()
+        .copied()
+        .find(|fd_len| *fd_len <= len)
+        .unwrap_or(8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_frame_fits() {
+        assert_eq!(can_frame_len(0), Some(0));
+        assert_eq!(can_frame_len(CAN_FD_MAX_PAYLOAD), Some(64));
+    }
+
+    #[test]
+    fn longer_payload_is_rejected_rather_than_truncated() {
+        assert_eq!(can_frame_len(CAN_FD_MAX_PAYLOAD + 1), None);
+        // 300 would wrap to 44 as a `u8`.
+        assert_eq!(can_frame_len(300), None);
+    }
+
+    #[test]
+    fn lengths_round_down_to_unpadded_ones() {
+        assert_eq!(can_fd_len_at_most(5), 5);
+        assert_eq!(can_fd_len_at_most(8), 8);
+        assert_eq!(can_fd_len_at_most(11), 8);
+        assert_eq!(can_fd_len_at_most(20), 20);
+        assert_eq!(can_fd_len_at_most(63), 48);
+        assert_eq!(can_fd_len_at_most(CAN_FD_MAX_PAYLOAD), CAN_FD_MAX_PAYLOAD);
+        assert_eq!(can_fd_len_at_most(300), CAN_FD_MAX_PAYLOAD);
+    }
+}
@@ -1,24 +1,515 @@
 use crate::data_manager::DataManager;
-use crate::types::COM_ID;
-use common_arm::HydraError;
+use common_arm::{
+    can_id_for, is_fragment, Batch, CanBusOff, CanPriority, CanSelfTestFailed, CanStats,
+    DeltaEncoder, Fragmenter, HydraError, MavIdentity, PayloadTooLarge, Reassembler, TokenBucket,
+    TooManyFilterIds, Verbosity,
+};
 use defmt::{error, info};
 use fdcan::{
-    frame::{FrameFormat, TxFrameHeader},
+    config::{DataBitTiming, NominalBitTiming},
+    filter::{Action, FilterType, StandardFilter, StandardFilterSlot},
+    frame::{FrameFormat, RxFrameInfo, TxFrameHeader},
     id::StandardId,
 };
-use mavlink::peek_reader::PeekReader;
+use heapless::{spsc::Consumer, Vec};
 use messages::mavlink::uorocketry::MavMessage;
-use messages::mavlink::{self};
+use messages::mavlink::{self, Message as _};
 use messages::Message;
-use postcard::from_bytes;
+use phoenix::{
+    can_fd_len_at_most, filter_elements, FilterElement, FrameParser, GroundCommand, MavFrame,
+    RxFifo, CAN_FD_MAX_PAYLOAD, CAN_MAX_FILTER_IDS, CAN_STANDARD_FILTER_SLOTS, MAV_MAX_FRAME_LEN,
+};
+use stm32h7xx_hal::{rcc, rcc::rec};
+
+/// Largest sample the radio delta codec takes, so a keyframe with its header still fits in a
+/// POSTCARD_MESSAGE.
+pub const RADIO_DELTA_MAX_SAMPLE: usize = 250;
+
+/// Payload of a single POSTCARD_MESSAGE frame.
+const RADIO_FRAME_LEN: usize = 255;
+/// Longest payload `RadioManager` sends or reassembles. Payloads longer than a frame are split
+/// over several, see `common_arm::Fragmenter`.
+pub const RADIO_MAX_MESSAGE_LEN: usize = 1024;
+/// Ground stations whose fragmented payloads can be reassembled at the same time.
+const RADIO_FRAGMENT_SOURCES: usize = 2;
+/// Received bytes `radio_rx` can queue for the `RadioReceiver`, a little under 100 ms at 57600
+/// baud.
+pub const RADIO_RX_QUEUE_LEN: usize = 512;
+/// Received bytes the `RadioReceiver` buffers while a frame completes.
+const RADIO_RX_BUFFER_LEN: usize = 2 * MAV_MAX_FRAME_LEN;
+
+/// Sustained rate and burst of messages `send_gs` lets through to the radio, see
+/// [`RadioManager::admit`].
+const RADIO_MAX_MESSAGES_PER_S: u32 = 50;
+const RADIO_MAX_BURST: u32 = 20;
+
+/// Returns the frame length to put in a `TxFrameHeader`, see [`phoenix::can_frame_len`].
+fn can_frame_len(payload: &[u8]) -> Result<u8, HydraError> {
+    phoenix::can_frame_len(payload.len()).ok_or_else(|| {
+        PayloadTooLarge {
+            len: payload.len(),
+            max: CAN_FD_MAX_PAYLOAD,
+        }
+        .into()
+    })
+}
+
+/// Longest serialized message the CAN managers send or reassemble. Longer than a frame, see
+/// `common_arm::Fragmenter`.
+pub const CAN_MAX_MESSAGE_LEN: usize = 256;
+/// Nodes whose fragmented messages can be reassembled at the same time.
+const CAN_FRAGMENT_SOURCES: usize = 4;
+
+/// Node a received frame was sent by, the low byte of its ID as assigned by `can_id_for`.
+fn source_node(id: fdcan::id::Id) -> u8 {
+    match id {
+        fdcan::id::Id::Standard(id) => (id.as_raw() & 0xFF) as u8,
+        fdcan::id::Id::Extended(id) => (id.as_raw() & 0xFF) as u8,
+    }
+}
+
+/// Node IDs on the buses are `messages::node::Node` values, all below this.
+const CAN_NODE_COUNT: u16 = 8;
+
+/// Returns true for the IDs of `CanPriority::Command` frames, `0x000`-`0x0FF`.
+///
+/// The receive FIFOs split the frames by class:
+///
+/// | FIFO  | Frames                                         |
+/// |-------|------------------------------------------------|
+/// | FIFO1 | Commands                                       |
+/// | FIFO0 | Everything else: state, sensor data, bulk      |
+///
+/// The managers always drain FIFO1 first, so a command isn't held up behind a FIFO full of
+/// sensor data, nor dropped when that FIFO overflows.
+fn is_command_id(id: u16) -> bool {
+    id < CanPriority::State.can_id(0)
+}
+
+/// IDs of the frames of `priorities` any other node than `own_node` sends.
+fn peer_ids(priorities: &[CanPriority], own_node: u16) -> Vec<StandardId, CAN_MAX_FILTER_IDS> {
+    priorities
+        .iter()
+        .flat_map(|priority| {
+            (0..CAN_NODE_COUNT)
+                .filter(move |node| *node != own_node)
+                .map(move |node| StandardId::new(priority.can_id(node)).unwrap())
+        })
+        .collect()
+}
+
+/// IDs the data bus accepts: the state and sensor data, SBG included, of the other nodes.
+pub fn data_bus_ids(own_node: u16) -> Vec<StandardId, CAN_MAX_FILTER_IDS> {
+    peer_ids(&[CanPriority::State, CanPriority::Sensor], own_node)
+}
+
+/// IDs the command bus accepts: the commands of the other nodes and the echo test frames.
+pub fn command_bus_ids(own_node: u16) -> Vec<StandardId, CAN_MAX_FILTER_IDS> {
+    let mut ids = peer_ids(&[CanPriority::Command], own_node);
+    for id in [CAN_ECHO_REQUEST_ID, CAN_ECHO_REPLY_ID] {
+        ids.push(StandardId::new(id).unwrap()).ok();
+    }
+    ids
+}
+
+/// FDCAN filter elements accepting exactly `ids`, see [`phoenix::filter_elements`].
+fn id_filters(
+    ids: &[StandardId],
+) -> Result<Vec<StandardFilter, CAN_STANDARD_FILTER_SLOTS>, HydraError> {
+    let too_many = TooManyFilterIds {
+        requested: ids.len(),
+        max: CAN_MAX_FILTER_IDS,
+    };
+    if ids.len() > CAN_MAX_FILTER_IDS {
+        return Err(too_many.into());
+    }
+    let raw: Vec<u16, CAN_MAX_FILTER_IDS> = ids.iter().map(|id| id.as_raw()).collect();
+    let elements = filter_elements(&raw, is_command_id).ok_or(too_many)?;
+    Ok(elements
+        .map(|element| match element {
+            FilterElement::Accept { ids: (a, b), fifo } => StandardFilter {
+                filter: FilterType::DedicatedDual(
+                    StandardId::new(a).unwrap(),
+                    StandardId::new(b).unwrap(),
+                ),
+                action: match fifo {
+                    RxFifo::Fifo0 => Action::StoreInFifo0,
+                    RxFifo::Fifo1 => Action::StoreInFifo1,
+                },
+            },
+            FilterElement::RejectAll => StandardFilter::reject_all(),
+        })
+        .collect())
+}
+
+/// Reads the next received frame into `buf`, from FIFO1 before FIFO0, see [`is_command_id`].
+fn receive_next<I: fdcan::Instance, M: fdcan::Receive>(
+    can: &mut fdcan::FdCan<I, M>,
+    buf: &mut [u8],
+) -> Option<RxFrameInfo> {
+    let rx = can.receive1(buf).or_else(|_| can.receive0(buf)).ok()?;
+    Some(rx.unwrap())
+}
+
+/// Sends `payload` in one frame, or cut into fragments of `fragment_len` bytes if it is longer,
+/// see `common_arm::Fragmenter`. `frame` is the scratch space for one fragment.
+fn transmit_payload<I: fdcan::Instance, M: fdcan::Transmit>(
+    can: &mut fdcan::FdCan<I, M>,
+    bus_load: &mut BusLoad,
+    frame: &mut [u8],
+    id: StandardId,
+    payload: &[u8],
+    fragment_len: usize,
+    bit_rate_switching: bool,
+) -> Result<(), HydraError> {
+    if payload.len() <= fragment_len {
+        return transmit_frame(can, bus_load, id, payload, bit_rate_switching);
+    }
+    let mut fragmenter = Fragmenter::new(payload, fragment_len)?;
+    while let Some(len) = fragmenter.next_frame(frame) {
+        transmit_frame(can, bus_load, id, &frame[..len], bit_rate_switching)?;
+    }
+    Ok(())
+}
+
+/// Sends one CAN FD frame, blocking until there is a Tx buffer for it. The fragments of a message
+/// share an ID, so one that finds every buffer full can't take the place of a pending frame. It
+/// waits rather than abort the message after its first fragments are already on the bus.
+fn transmit_frame<I: fdcan::Instance, M: fdcan::Transmit>(
+    can: &mut fdcan::FdCan<I, M>,
+    bus_load: &mut BusLoad,
+    id: StandardId,
+    payload: &[u8],
+    bit_rate_switching: bool,
+) -> Result<(), HydraError> {
+    let header = TxFrameHeader {
+        len: can_frame_len(payload)?,
+        id: id.into(),
+        frame_format: FrameFormat::Fdcan,
+        bit_rate_switching,
+        marker: None,
+    };
+    // can.abort(fdcan::Mailbox::_2); // this is needed if boards are not in sync (if they are not in sync that is a bigger problem)
+
+    stm32h7xx_hal::nb::block!(can.transmit(header, payload))?;
+    bus_load.record_tx(header.len);
+    Ok(())
+}
+
+/// Restarts an FDCAN instance that went bus-off. The peripheral then sets its INIT bit and stays
+/// off the bus until software clears it, after which it rejoins once it has seen 129 sequences of
+/// 11 recessive bits. Returns false if the instance isn't stopped, e.g. it is still recovering.
+fn restart_after_bus_off(registers: &stm32h7xx_hal::pac::fdcan1::RegisterBlock) -> bool {
+    if registers.cccr.read().init().bit_is_clear() {
+        return false;
+    }
+    registers.cccr.modify(|_, w| w.init().clear_bit());
+    true
+}
+
+/// Requests the clock stop of an FDCAN instance, its power down mode. The peripheral finishes the
+/// transfer in progress, then sets its INIT bit and no longer takes part in bus traffic.
+fn request_power_down(registers: &stm32h7xx_hal::pac::fdcan1::RegisterBlock) {
+    registers.cccr.modify(|_, w| w.csr().set_bit());
+}
+
+/// An FDCAN instance whose PAC registers can be reached, for the few bits the fdcan driver
+/// doesn't expose.
+trait FdcanRegisters: fdcan::Instance {
+    fn registers() -> &'static stm32h7xx_hal::pac::fdcan1::RegisterBlock;
+}
+
+impl FdcanRegisters for stm32h7xx_hal::can::Can<stm32h7xx_hal::pac::FDCAN1> {
+    fn registers() -> &'static stm32h7xx_hal::pac::fdcan1::RegisterBlock {
+        // SAFETY: the register block is always mapped. Callers only write the bits the fdcan
+        // driver leaves alone, see `restart_after_bus_off` and `request_power_down`.
+        unsafe { &*stm32h7xx_hal::pac::FDCAN1::ptr() }
+    }
+}
+
+impl FdcanRegisters for stm32h7xx_hal::can::Can<stm32h7xx_hal::pac::FDCAN2> {
+    fn registers() -> &'static stm32h7xx_hal::pac::fdcan1::RegisterBlock {
+        // SAFETY: as for FDCAN1.
+        unsafe { &*stm32h7xx_hal::pac::FDCAN2::ptr() }
+    }
+}
+
+/// Accepts only frames with one of `ids`, commands into FIFO1 and the rest into FIFO0, and
+/// rejects the rest in hardware, so the CPU never sees them. Takes at most
+/// [`CAN_MAX_FILTER_IDS`] IDs. If more are given nothing is changed and an error is returned,
+/// rather than silently dropping frames from the IDs that didn't fit. An empty list rejects every
+/// frame.
+fn set_id_filter<I: fdcan::Instance, M>(
+    can: &mut fdcan::FdCan<I, M>,
+    ids: &[StandardId],
+) -> Result<(), HydraError> {
+    for (slot, filter) in id_filters(ids)?.into_iter().enumerate() {
+        can.set_standard_filter(StandardFilterSlot::from(slot as u8), filter);
+    }
+    Ok(())
+}
+
+/// Checks whether the FDCAN went bus-off after too many transmit errors, e.g. from a wiring
+/// fault, and restarts it if so. Returns a `CanBusOff` error for the ErrorManager for each
+/// restart, counted in `stats`. Call periodically, a stopped bus receives no frames to notice it
+/// from.
+fn check_bus_off<I: FdcanRegisters, M>(
+    can: &fdcan::FdCan<I, M>,
+    bus: &'static str,
+    stats: &mut CanStats,
+) -> Result<(), HydraError> {
+    if !can.get_protocol_status().bus_off_status {
+        return Ok(());
+    }
+    // Only the INIT bit is written, which the hardware set when going bus-off and the fdcan
+    // driver itself clears to start the bus.
+    if !restart_after_bus_off(I::registers()) {
+        return Ok(());
+    }
+    Err(CanBusOff {
+        bus,
+        recoveries: stats.record_bus_off_recovery(),
+    }
+    .into())
+}
+
+/// Takes the FDCAN off the bus into its power down mode. Only the clock stop request is written,
+/// the fdcan driver never touches it in normal operation. Takes `can` so only its owner can.
+fn power_down<I: FdcanRegisters, M>(_can: &mut fdcan::FdCan<I, M>) {
+    request_power_down(I::registers());
+}
+
+/// Returns the peripheral record for the second FDCAN instance, with its kernel clock on PLL1Q.
+///
+/// FDCAN1 and FDCAN2 share one `rec::Fdcan`, but the HAL consumes it when creating an instance,
+/// so the second instance has nothing to be created from
+/// (https://github.com/stm32-rs/stm32h7xx-hal/issues/369). Until the HAL hands out a record per
+/// instance, a copy is stolen here. This is the only place that needs to change once it does.
+///
+/// Must be called once, before the RCC is frozen, so the clock configured by `freeze` applies to
+/// both records. Check the result with [`fdcan_kernel_clock_is`] after freezing.
+pub fn steal_fdcan_rec(rcc: &rcc::Rcc) -> rec::Fdcan {
+    // SAFETY: both records drive the same shared FDCAN kernel clock and reset. The only writes
+    // made through either are the identical PLL1Q mux selection and the enable done when creating
+    // each instance, so the two copies never disagree about the peripheral's state.
+    unsafe { rcc.steal_peripheral_rec() }
+        .FDCAN
+        .kernel_clk_mux(rec::FdcanClkSel::Pll1Q)
+}
+
+/// Returns true if the FDCAN kernel clock runs at `expected_hz`, which the bit timing assumes.
+/// The mux must select PLL1Q, read back from the RCC, and PLL1Q must have come out of `freeze` at
+/// exactly that frequency.
+pub fn fdcan_kernel_clock_is(
+    fdcan_rec: &rec::Fdcan,
+    clocks: &rcc::CoreClocks,
+    expected_hz: u32,
+) -> bool {
+    matches!(fdcan_rec.get_kernel_clk_mux(), rec::FdcanClkSel::Pll1Q)
+        && clocks.pll1_q_ck().map(|pll1_q| pll1_q.raw()) == Some(expected_hz)
+}
+
+/// Approximate number of bits a CAN FD frame with a standard ID spends outside of its data field
+/// (arbitration, control, CRC, ACK, EOF and interframe space).
+const CAN_FRAME_OVERHEAD_BITS: u32 = 64;
+
+/// Returns the bit rate in bit/s for the given FDCAN kernel clock and bit timing. A bit is the
+/// sync segment plus `seg1` and `seg2`, in time quanta of `prescaler` kernel clock cycles.
+fn bit_rate(kernel_clock_hz: u32, prescaler: u32, seg1: u32, seg2: u32) -> u32 {
+    kernel_clock_hz / (prescaler * (1 + seg1 + seg2))
+}
+
+/// Returns the nominal bit rate in bit/s for the given FDCAN kernel clock and bit timing.
+pub fn nominal_bit_rate(kernel_clock_hz: u32, timing: &NominalBitTiming) -> u32 {
+    bit_rate(
+        kernel_clock_hz,
+        timing.prescaler.get().into(),
+        timing.seg1.get().into(),
+        timing.seg2.get().into(),
+    )
+}
+
+/// Returns the data phase bit rate in bit/s for the given FDCAN kernel clock and bit timing. Only
+/// the data field of frames sent with bit rate switching uses it.
+pub fn data_bit_rate(kernel_clock_hz: u32, timing: &DataBitTiming) -> u32 {
+    bit_rate(
+        kernel_clock_hz,
+        timing.prescaler.get().into(),
+        timing.seg1.get().into(),
+        timing.seg2.get().into(),
+    )
+}
+
+/// Cheap CAN bus utilization estimate.
+///
+/// Frames are counted as their data bits plus a fixed overhead as they are sent or received, and
+/// the totals are compared against the nominal bit rate every time [`BusLoad::sample`] is called.
+/// Bit stuffing and bit rate switching are ignored, so treat the result as a ballpark figure.
+pub struct BusLoad {
+    bit_rate: u32,
+    tx_bits: u32,
+    rx_bits: u32,
+    last_sample_ms: u64,
+    tx_load: u8,
+    rx_load: u8,
+}
+
+impl BusLoad {
+    pub fn new(bit_rate: u32) -> Self {
+        Self {
+            bit_rate,
+            tx_bits: 0,
+            rx_bits: 0,
+            last_sample_ms: 0,
+            tx_load: 0,
+            rx_load: 0,
+        }
+    }
+
+    pub fn record_tx(&mut self, len: u8) {
+        self.tx_bits = self
+            .tx_bits
+            .saturating_add(CAN_FRAME_OVERHEAD_BITS + 8 * len as u32);
+    }
+
+    pub fn record_rx(&mut self, len: u8) {
+        self.rx_bits = self
+            .rx_bits
+            .saturating_add(CAN_FRAME_OVERHEAD_BITS + 8 * len as u32);
+    }
+
+    /// Computes the tx and rx load in percent since the previous sample and resets the counters.
+    pub fn sample(&mut self, now_ms: u64) -> (u8, u8) {
+        let elapsed_ms = now_ms.saturating_sub(self.last_sample_ms);
+        if elapsed_ms == 0 || self.bit_rate == 0 {
+            return (self.tx_load, self.rx_load);
+        }
+        let capacity = self.bit_rate as u64 * elapsed_ms;
+        let percent = |bits: u32| (bits as u64 * 100 * 1000 / capacity).min(100) as u8;
+        self.tx_load = percent(self.tx_bits);
+        self.rx_load = percent(self.rx_bits);
+        self.tx_bits = 0;
+        self.rx_bits = 0;
+        self.last_sample_ms = now_ms;
+        (self.tx_load, self.rx_load)
+    }
+
+    /// Transmit load in percent as of the last sample.
+    pub fn tx_load(&self) -> u8 {
+        self.tx_load
+    }
+
+    /// Receive load in percent as of the last sample.
+    pub fn rx_load(&self) -> u8 {
+        self.rx_load
+    }
+}
+
+/// Standard ID of the frame sent by the bench echo test.
+pub const CAN_ECHO_REQUEST_ID: u16 = 0x7F0;
+/// Standard ID a cooperating node echoes the test frame back on. A frame coming back on
+/// [`CAN_ECHO_REQUEST_ID`] through a physical loopback is accepted as well.
+pub const CAN_ECHO_REPLY_ID: u16 = 0x7F1;
+/// Payload of the echo test frame, alternating bits to exercise the transceiver.
+const CAN_ECHO_PATTERN: [u8; 8] = [0x55, 0xAA, 0x55, 0xAA, 0x0F, 0xF0, 0x00, 0xFF];
+
+/// Outcome of a CAN echo test, see [`CanCommandManager::start_echo_test`].
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum EchoResult {
+    Pending,
+    Passed { round_trip_ms: u64 },
+    TimedOut,
+}
+
+struct EchoProbe {
+    sent_at_ms: u64,
+    echoed_at_ms: Option<u64>,
+}
+
+/// Receive FIFO polls the loopback self test waits for its frame, far longer than the peripheral
+/// takes to loop one back.
+const CAN_SELF_TEST_POLLS: u32 = 100_000;
+
+/// Sends `message` to itself in internal loopback and checks that it decodes back to the same
+/// bytes, covering the serialization and the receive path without a second node.
+/// The frame never reaches the pins, so this is safe to run with the board on a live bus.
+///
+/// Takes the peripheral in config mode, before the manager is created and its ID filters are set,
+/// so the frame lands in FIFO0 whatever its ID. Hands it back in config mode. `buf` and `frame`
+/// are scratch space for the sent message and the received frame.
+pub fn loopback_self_test<I: fdcan::Instance>(
+    can: fdcan::FdCan<I, fdcan::ConfigMode>,
+    bus: &'static str,
+    message: &Message,
+    buf: &mut [u8; CAN_MAX_MESSAGE_LEN],
+    frame: &mut [u8; CAN_FD_MAX_PAYLOAD],
+) -> (fdcan::FdCan<I, fdcan::ConfigMode>, Result<(), HydraError>) {
+    let mut can = can.into_internal_loopback();
+    let result = loopback_round_trip(&mut can, message, buf, frame).and_then(|passed| {
+        if passed {
+            Ok(())
+        } else {
+            Err(CanSelfTestFailed { bus }.into())
+        }
+    });
+    can.clear_interrupt(fdcan::interrupt::Interrupt::RxFifo0NewMsg);
+    (can.into_config_mode(), result)
+}
+
+/// Returns true if `message` came back unchanged.
+fn loopback_round_trip<I: fdcan::Instance>(
+    can: &mut fdcan::FdCan<I, fdcan::InternalLoopbackMode>,
+    message: &Message,
+    buf: &mut [u8; CAN_MAX_MESSAGE_LEN],
+    frame: &mut [u8; CAN_FD_MAX_PAYLOAD],
+) -> Result<bool, HydraError> {
+    // Serialized with room to spare, so a message too long for one frame fails the length check
+    // below rather than the serialization.
+    let sent = postcard::to_slice(message, buf)?;
+    let header = TxFrameHeader {
+        len: can_frame_len(sent)?,
+        // Any node will do, the frame never leaves the peripheral.
+        id: StandardId::new(can_id_for(message, 0)).unwrap().into(),
+        frame_format: FrameFormat::Fdcan,
+        bit_rate_switching: false,
+        marker: None,
+    };
+    stm32h7xx_hal::nb::block!(can.transmit(header, sent))?;
+
+    for _ in 0..CAN_SELF_TEST_POLLS {
+        let Ok(rx) = can.receive0(frame) else {
+            continue;
+        };
+        let received = &frame[..rx.unwrap().len as usize];
+        postcard::from_bytes::<Message>(received)?;
+        // The frame may be padded up to the next CAN FD length. Postcard encodes a message one
+        // way only, so one that decodes and starts with the sent bytes is the same message.
+        return Ok(received.starts_with(sent));
+    }
+    Ok(false)
+}
 
 /// Clock configuration is out of scope for this builder
 /// easiest way to avoid alloc is to use no generics
-pub struct CanCommandManager {
-    can: fdcan::FdCan<
-        stm32h7xx_hal::can::Can<stm32h7xx_hal::pac::FDCAN1>,
-        fdcan::NormalOperationMode,
-    >,
+///
+/// The mode is only a parameter for [`CanCommandManager::new_loopback`], the firmware always
+/// uses the default.
+pub struct CanCommandManager<M = fdcan::NormalOperationMode> {
+    can: fdcan::FdCan<stm32h7xx_hal::can::Can<stm32h7xx_hal::pac::FDCAN1>, M>,
+    pub bus_load: BusLoad,
+    /// Node the sent frame IDs carry, see `common_arm::can_id_for`.
+    node_id: u16,
+    /// Frame counters since boot, see [`CanStats`].
+    stats: CanStats,
+    echo: Option<EchoProbe>,
+    /// Set by [`Self::power_down`], messages sent afterwards are discarded.
+    powered_down: bool,
+    /// Length of the fragments a message too long for one frame is cut into.
+    fragment_len: usize,
+    fragments: Reassembler<CAN_FRAGMENT_SOURCES, CAN_MAX_MESSAGE_LEN>,
+    /// Scratch space for one serialized message and one frame, as in `CanDataManager`.
+    buf: [u8; CAN_MAX_MESSAGE_LEN],
+    frame: [u8; CAN_FD_MAX_PAYLOAD],
 }
 
 impl CanCommandManager {
@@ -27,30 +518,177 @@ impl CanCommandManager {
             stm32h7xx_hal::can::Can<stm32h7xx_hal::pac::FDCAN1>,
             fdcan::NormalOperationMode,
         >,
+        bit_rate: u32,
+        node_id: u16,
     ) -> Self {
-        Self { can }
+        Self {
+            can,
+            bus_load: BusLoad::new(bit_rate),
+            node_id,
+            stats: CanStats::new(),
+            echo: None,
+            powered_down: false,
+            fragment_len: CAN_FD_MAX_PAYLOAD,
+            fragments: Reassembler::new(),
+            buf: [0; CAN_MAX_MESSAGE_LEN],
+            frame: [0; CAN_FD_MAX_PAYLOAD],
+        }
     }
-    pub fn send_message(&mut self, m: Message) -> Result<(), HydraError> {
-        let mut buf = [0u8; 64];
-        let payload = postcard::to_slice(&m, &mut buf)?;
+}
+
+impl CanCommandManager<fdcan::InternalLoopbackMode> {
+    /// Like [`CanCommandManager::new`], but in internal loopback, receiving every frame it sends.
+    /// For bench tests of the whole send and receive path without a second node. Messages are cut
+    /// into fragments of `fragment_len` bytes, rounded down to a CAN FD frame length, so short
+    /// messages can be sent fragmented too.
+    pub fn new_loopback(
+        can: fdcan::FdCan<stm32h7xx_hal::can::Can<stm32h7xx_hal::pac::FDCAN1>, fdcan::ConfigMode>,
+        bit_rate: u32,
+        node_id: u16,
+        fragment_len: usize,
+    ) -> Self {
+        Self {
+            can: can.into_internal_loopback(),
+            bus_load: BusLoad::new(bit_rate),
+            node_id,
+            stats: CanStats::new(),
+            echo: None,
+            powered_down: false,
+            fragment_len: can_fd_len_at_most(fragment_len),
+            fragments: Reassembler::new(),
+            buf: [0; CAN_MAX_MESSAGE_LEN],
+            frame: [0; CAN_FD_MAX_PAYLOAD],
+        }
+    }
+}
+
+impl<M: fdcan::Transmit + fdcan::Receive> CanCommandManager<M> {
+    /// Sends the echo test frame on the physical bus. Unlike internal loopback this goes through
+    /// the transceiver and wiring, so it only passes if another node (or a loopback cable) sends
+    /// the frame back. Poll the outcome with [`Self::echo_result`].
+    pub fn start_echo_test(&mut self, now_ms: u64) -> Result<(), HydraError> {
         let header = TxFrameHeader {
-            len: payload.len() as u8, // switch to const as this never changes or swtich on message type of known size
-            id: StandardId::new(COM_ID.into()).unwrap().into(),
+            len: can_frame_len(&CAN_ECHO_PATTERN)?,
+            id: StandardId::new(CAN_ECHO_REQUEST_ID).unwrap().into(),
             frame_format: FrameFormat::Standard,
             bit_rate_switching: false,
             marker: None,
         };
-        self.can.transmit(header, payload)?;
+        self.can.transmit(header, &CAN_ECHO_PATTERN)?;
+        self.bus_load.record_tx(header.len);
+        self.echo = Some(EchoProbe {
+            sent_at_ms: now_ms,
+            echoed_at_ms: None,
+        });
         Ok(())
     }
+    /// Returns the outcome of the last echo test, `TimedOut` if none was started.
+    pub fn echo_result(&self, now_ms: u64, timeout_ms: u64) -> EchoResult {
+        match &self.echo {
+            Some(EchoProbe {
+                sent_at_ms,
+                echoed_at_ms: Some(echoed_at_ms),
+            }) => EchoResult::Passed {
+                round_trip_ms: echoed_at_ms - sent_at_ms,
+            },
+            Some(probe) if now_ms.saturating_sub(probe.sent_at_ms) < timeout_ms => {
+                EchoResult::Pending
+            }
+            _ => EchoResult::TimedOut,
+        }
+    }
+    /// Records the echo if the frame is the test pattern coming back. Returns true if it was.
+    fn check_echo(echo: &mut Option<EchoProbe>, id: fdcan::id::Id, payload: &[u8]) -> bool {
+        let fdcan::id::Id::Standard(id) = id else {
+            return false;
+        };
+        if id.as_raw() != CAN_ECHO_REPLY_ID && id.as_raw() != CAN_ECHO_REQUEST_ID {
+            return false;
+        }
+        if payload != CAN_ECHO_PATTERN {
+            return false;
+        }
+        if let Some(probe) = echo.as_mut() {
+            probe.echoed_at_ms.get_or_insert(crate::now_ms());
+        }
+        true
+    }
+    pub fn node_id(&self) -> u16 {
+        self.node_id
+    }
+    /// Takes the FDCAN off the bus into its power down mode, for the rest of the session. Messages
+    /// sent afterwards are discarded.
+    pub fn power_down(&mut self) {
+        power_down(&mut self.can);
+        self.powered_down = true;
+    }
+    /// See [`check_bus_off`].
+    pub fn check_bus_off(&mut self) -> Result<(), HydraError> {
+        check_bus_off(&self.can, "command", &mut self.stats)
+    }
+    pub fn stats(&self) -> CanStats {
+        self.stats
+    }
+    /// See [`set_id_filter`].
+    pub fn set_id_filter(&mut self, ids: &[StandardId]) -> Result<(), HydraError> {
+        set_id_filter(&mut self.can, ids)
+    }
+    pub fn send_message(&mut self, m: Message) -> Result<(), HydraError> {
+        if self.powered_down {
+            return Ok(());
+        }
+        let result = self.transmit(m);
+        self.stats.record_tx(&result);
+        result
+    }
+    /// Sends a message in one frame, or fragmented if it doesn't fit one. The command bus has no
+    /// faster data phase, so its frames don't switch the bit rate.
+    fn transmit(&mut self, m: Message) -> Result<(), HydraError> {
+        let id = StandardId::new(can_id_for(&m, self.node_id)).unwrap();
+        let payload = postcard::to_slice(&m, &mut self.buf)?;
+        transmit_payload(
+            &mut self.can,
+            &mut self.bus_load,
+            &mut self.frame,
+            id,
+            payload,
+            self.fragment_len,
+            false,
+        )
+    }
     pub fn process_data(&mut self, data_manager: &mut DataManager) -> Result<(), HydraError> {
-        let mut buf = [0u8; 64];
-        while self.can.receive0(&mut buf).is_ok() {
-            if let Ok(data) = from_bytes::<Message>(&buf) {
-                info!("Received message {}", data.clone());
-                data_manager.handle_command(data)?;
+        while let Some(rx) = receive_next(&mut self.can, &mut self.frame) {
+            let received_ms = crate::now_ms();
+            self.bus_load.record_rx(rx.len);
+            self.stats.record_rx();
+            let frame = &self.frame[..rx.len as usize];
+            if Self::check_echo(&mut self.echo, rx.id, frame) {
+                continue;
+            }
+            if let Some(verbosity) = Verbosity::from_command(frame) {
+                data_manager.set_verbosity(verbosity);
+                continue;
+            }
+            if let Some(command) = GroundCommand::from_command(frame) {
+                crate::app::ground_command::spawn(command).ok();
+                continue;
+            }
+            let payload = if is_fragment(frame) {
+                match self.fragments.push(source_node(rx.id), frame) {
+                    Some(message) => message,
+                    None => continue,
+                }
             } else {
-                info!("Error: {:?}", from_bytes::<Message>(&buf).unwrap_err());
+                frame
+            };
+            match self.stats.decode(payload) {
+                Ok(data) => {
+                    if data_manager.log_can_rx {
+                        info!("Received message {} at {} ms", data.clone(), received_ms);
+                    }
+                    data_manager.handle_command(data)?;
+                }
+                Err(e) => info!("Error: {:?}", e),
             }
         }
         Ok(())
@@ -59,58 +697,183 @@ impl CanCommandManager {
 
 /// Clock configuration is out of scope for this builder
 /// easiest way to avoid alloc is to use no generics
-pub struct CanDataManager {
-    can: fdcan::FdCan<
-        stm32h7xx_hal::can::Can<stm32h7xx_hal::pac::FDCAN2>,
-        fdcan::NormalOperationMode,
-    >,
+///
+/// The mode is only a parameter for [`CanDataManager::new_loopback`], as in `CanCommandManager`.
+pub struct CanDataManager<M = fdcan::NormalOperationMode> {
+    can: fdcan::FdCan<stm32h7xx_hal::can::Can<stm32h7xx_hal::pac::FDCAN2>, M>,
+    pub bus_load: BusLoad,
+    /// Node the sent frame IDs carry, see `common_arm::can_id_for`.
+    node_id: u16,
+    /// Frame counters since boot, see [`CanStats`].
+    stats: CanStats,
+    /// Set by [`Self::power_down`], messages sent afterwards are discarded.
+    powered_down: bool,
+    /// Length of the fragments a message too long for one frame is cut into.
+    fragment_len: usize,
+    fragments: Reassembler<CAN_FRAGMENT_SOURCES, CAN_MAX_MESSAGE_LEN>,
+    /// Scratch space for one serialized message and one frame, shared by sending and receiving.
+    /// They live here rather than on the stack of each call, and the manager is only reachable
+    /// through its resource lock, so no two tasks can use them at once.
+    buf: [u8; CAN_MAX_MESSAGE_LEN],
+    frame: [u8; CAN_FD_MAX_PAYLOAD],
 }
 
 impl CanDataManager {
+    /// Applies `data_bit_timing` to the data phase of the frames this manager sends, which all
+    /// use bit rate switching. A timing equal to the nominal one keeps the whole frame at the
+    /// nominal rate, see [`data_bit_rate`].
     pub fn new(
         can: fdcan::FdCan<
             stm32h7xx_hal::can::Can<stm32h7xx_hal::pac::FDCAN2>,
             fdcan::NormalOperationMode,
         >,
+        bit_rate: u32,
+        node_id: u16,
+        data_bit_timing: DataBitTiming,
     ) -> Self {
-        Self { can }
+        let mut can = can.into_config_mode();
+        can.set_data_bit_timing(data_bit_timing);
+        Self {
+            can: can.into_normal(),
+            bus_load: BusLoad::new(bit_rate),
+            node_id,
+            stats: CanStats::new(),
+            powered_down: false,
+            fragment_len: CAN_FD_MAX_PAYLOAD,
+            fragments: Reassembler::new(),
+            buf: [0; CAN_MAX_MESSAGE_LEN],
+            frame: [0; CAN_FD_MAX_PAYLOAD],
+        }
     }
-    pub fn send_message(&mut self, m: Message) -> Result<(), HydraError> {
-        let mut buf = [0u8; 64];
-        let payload = postcard::to_slice(&m, &mut buf)?;
-        let header = TxFrameHeader {
-            len: payload.len() as u8, // switch to const as this never changes or swtich on message type of known size
-            id: StandardId::new(COM_ID.into()).unwrap().into(),
-            frame_format: FrameFormat::Fdcan,
-            bit_rate_switching: false,
-            marker: None,
-        };
-        // self.can.abort(fdcan::Mailbox::_2); // this is needed if boards are not in sync (if they are not in sync that is a bigger problem)
+}
 
-        stm32h7xx_hal::nb::block!(self.can.transmit(header, payload))?;
+impl CanDataManager<fdcan::InternalLoopbackMode> {
+    /// Like [`CanDataManager::new`], but in internal loopback, see
+    /// [`CanCommandManager::new_loopback`].
+    pub fn new_loopback(
+        mut can: fdcan::FdCan<
+            stm32h7xx_hal::can::Can<stm32h7xx_hal::pac::FDCAN2>,
+            fdcan::ConfigMode,
+        >,
+        bit_rate: u32,
+        node_id: u16,
+        data_bit_timing: DataBitTiming,
+        fragment_len: usize,
+    ) -> Self {
+        can.set_data_bit_timing(data_bit_timing);
+        Self {
+            can: can.into_internal_loopback(),
+            bus_load: BusLoad::new(bit_rate),
+            node_id,
+            stats: CanStats::new(),
+            powered_down: false,
+            fragment_len: can_fd_len_at_most(fragment_len),
+            fragments: Reassembler::new(),
+            buf: [0; CAN_MAX_MESSAGE_LEN],
+            frame: [0; CAN_FD_MAX_PAYLOAD],
+        }
+    }
+}
 
-        Ok(())
+impl<M: fdcan::Transmit + fdcan::Receive> CanDataManager<M> {
+    pub fn node_id(&self) -> u16 {
+        self.node_id
+    }
+    /// Takes the FDCAN off the bus into its power down mode, for the rest of the session. Messages
+    /// sent afterwards are discarded, as the transmit would otherwise wait for a mailbox forever.
+    pub fn power_down(&mut self) {
+        power_down(&mut self.can);
+        self.powered_down = true;
+    }
+    /// See [`check_bus_off`].
+    pub fn check_bus_off(&mut self) -> Result<(), HydraError> {
+        check_bus_off(&self.can, "data", &mut self.stats)
+    }
+    pub fn stats(&self) -> CanStats {
+        self.stats
+    }
+    /// See [`set_id_filter`].
+    pub fn set_id_filter(&mut self, ids: &[StandardId]) -> Result<(), HydraError> {
+        set_id_filter(&mut self.can, ids)
     }
-    pub fn process_data(&mut self) -> Result<(), HydraError> {
-        let mut buf = [0u8; 64];
-        while self.can.receive0(&mut buf).is_ok() {
-            if let Ok(data) = from_bytes::<Message>(&buf) {
-                info!("Received message {}", data.clone());
-                crate::app::send_gs::spawn(data).ok();
-            } else if let Err(e) = from_bytes::<Message>(&buf) {
-                info!("Error: {:?}", e);
+    pub fn send_message(&mut self, m: Message) -> Result<(), HydraError> {
+        if self.powered_down {
+            return Ok(());
+        }
+        let result = self.transmit(m);
+        self.stats.record_tx(&result);
+        result
+    }
+    /// Sends a message in one frame, or fragmented if it doesn't fit one. The fragments are
+    /// sent back to back, blocking until each one has a mailbox.
+    fn transmit(&mut self, m: Message) -> Result<(), HydraError> {
+        let id = StandardId::new(can_id_for(&m, self.node_id)).unwrap();
+        let payload = postcard::to_slice(&m, &mut self.buf)?;
+        transmit_payload(
+            &mut self.can,
+            &mut self.bus_load,
+            &mut self.frame,
+            id,
+            payload,
+            self.fragment_len,
+            true,
+        )
+    }
+    /// Hands every received message on to the radio. `log_rx` logs each one as well, pass
+    /// `DataManager::log_can_rx` so the verbosity setting applies.
+    pub fn process_data(&mut self, log_rx: bool) -> Result<(), HydraError> {
+        while let Some(rx) = receive_next(&mut self.can, &mut self.frame) {
+            let received_ms = crate::now_ms();
+            self.bus_load.record_rx(rx.len);
+            self.stats.record_rx();
+            let frame = &self.frame[..rx.len as usize];
+            let payload = if is_fragment(frame) {
+                match self.fragments.push(source_node(rx.id), frame) {
+                    Some(message) => message,
+                    None => continue,
+                }
+            } else {
+                frame
+            };
+            match self.stats.decode(payload) {
+                Ok(data) => {
+                    if log_rx {
+                        info!("Received message {} at {} ms", data.clone(), received_ms);
+                    }
+                    crate::app::send_gs::spawn(data).ok();
+                }
+                Err(e) => info!("Error: {:?}", e),
             }
         }
         self.can
             .clear_interrupt(fdcan::interrupt::Interrupt::RxFifo0NewMsg);
+        self.can
+            .clear_interrupt(fdcan::interrupt::Interrupt::RxFifo1NewMsg);
         Ok(())
     }
-    pub fn receive_message(&mut self) -> Result<Option<Message>, HydraError> {
-        let mut buf = [0u8; 64];
-        if self.can.receive0(&mut buf).is_ok() {
-            if let Ok(data) = from_bytes::<Message>(&buf) {
-                return Ok(Some(data));
-            }
+    /// Returns the next whole message and the time it arrived in ms since boot, reading on past
+    /// fragments of a message that isn't complete yet. The time is taken as the frame is read
+    /// from the FIFO, from the receive interrupt, so it doesn't include how long the message
+    /// then waits to be processed. A fragmented message arrives with its last fragment.
+    pub fn receive_message(&mut self) -> Result<Option<(Message, u64)>, HydraError> {
+        while let Some(rx) = receive_next(&mut self.can, &mut self.frame) {
+            let received_ms = crate::now_ms();
+            self.bus_load.record_rx(rx.len);
+            self.stats.record_rx();
+            let frame = &self.frame[..rx.len as usize];
+            let payload = if is_fragment(frame) {
+                match self.fragments.push(source_node(rx.id), frame) {
+                    Some(message) => message,
+                    None => continue,
+                }
+            } else {
+                frame
+            };
+            return Ok(self
+                .stats
+                .decode(payload)
+                .ok()
+                .map(|message| (message, received_ms)));
         }
         Ok(None)
     }
@@ -118,51 +881,215 @@ impl CanDataManager {
 
 pub struct RadioDevice {
     transmitter: stm32h7xx_hal::serial::Tx<stm32h7xx_hal::pac::UART4>,
-    pub receiver: PeekReader<stm32h7xx_hal::serial::Rx<stm32h7xx_hal::pac::UART4>>,
 }
 
 impl RadioDevice {
-    pub fn new(uart: stm32h7xx_hal::serial::Serial<stm32h7xx_hal::pac::UART4>) -> Self {
+    /// Also returns the receiver, with its interrupt enabled. `radio_rx` owns it, so reading the
+    /// UART never waits on the `RadioManager` lock.
+    pub fn new(
+        uart: stm32h7xx_hal::serial::Serial<stm32h7xx_hal::pac::UART4>,
+    ) -> (Self, stm32h7xx_hal::serial::Rx<stm32h7xx_hal::pac::UART4>) {
         let (tx, mut rx) = uart.split();
 
         rx.listen();
         // setup interrupts
 
-        RadioDevice {
-            transmitter: tx,
-            receiver: PeekReader::new(rx),
-        }
+        (RadioDevice { transmitter: tx }, rx)
     }
 }
 
 pub struct RadioManager {
     pub radio: RadioDevice,
-    mav_sequence: u8,
+    /// System and component IDs and sequence number of the frames sent.
+    identity: MavIdentity,
+    /// Time of the last frame read from the ground, including heartbeats, in ms since boot.
+    last_receive_ms: Option<u64>,
+    limiter: TokenBucket,
+    scratch: RadioScratch,
+}
+
+/// Scratch space for the frames `RadioManager` sends. It lives in the manager rather than on the
+/// stack of each call, as the buffers of `CanDataManager` do.
+struct RadioScratch {
+    /// Always a POSTCARD_MESSAGE, filled in for each frame sent.
+    frame: MavMessage,
+    batch: Batch<RADIO_FRAME_LEN>,
+    /// A message serialized on its own, one too long to share a batch.
+    message: [u8; RADIO_MAX_MESSAGE_LEN],
+    /// A message serialized for the delta encoder.
+    sample: [u8; RADIO_DELTA_MAX_SAMPLE],
+}
+
+/// The payload of `frame`, which is always a POSTCARD_MESSAGE.
+fn postcard_payload(frame: &mut MavMessage) -> &mut [u8; RADIO_FRAME_LEN] {
+    match frame {
+        MavMessage::POSTCARD_MESSAGE(data) => &mut data.message,
+        _ => unreachable!(),
+    }
 }
 
 impl RadioManager {
-    pub fn new(radio: RadioDevice) -> Self {
+    /// Sends as `system_id` and `component_id`, normally `DEFAULT_MAV_SYSTEM_ID` and
+    /// `DEFAULT_MAV_COMPONENT_ID`. Vehicles sharing a ground station need different system IDs.
+    pub fn new(radio: RadioDevice, system_id: u8, component_id: u8) -> Self {
         RadioManager {
             radio,
-            mav_sequence: 0,
+            identity: MavIdentity::new(system_id, component_id),
+            last_receive_ms: None,
+            limiter: TokenBucket::new(RADIO_MAX_MESSAGES_PER_S, RADIO_MAX_BURST),
+            scratch: RadioScratch {
+                frame: MavMessage::POSTCARD_MESSAGE(mavlink::uorocketry::POSTCARD_MESSAGE_DATA {
+                    message: [0u8; RADIO_FRAME_LEN],
+                }),
+                batch: Batch::new(),
+                message: [0; RADIO_MAX_MESSAGE_LEN],
+                sample: [0; RADIO_DELTA_MAX_SAMPLE],
+            },
         }
     }
+    /// Sends a payload in one POSTCARD_MESSAGE frame, or fragmented over several if it is longer
+    /// than one. A payload longer than [`RADIO_MAX_MESSAGE_LEN`] isn't sent at all and returns an
+    /// error, rather than being truncated.
     pub fn send_message(&mut self, payload: &[u8]) -> Result<(), HydraError> {
-        let mav_header = mavlink::MavHeader {
-            system_id: 1,
-            component_id: 1,
-            sequence: self.increment_mav_sequence(),
+        Self::send_payload(
+            &mut self.radio,
+            &mut self.identity,
+            &mut self.scratch.frame,
+            payload,
+        )
+    }
+    /// [`Self::send_message`] on the fields it needs, so the payload can be one of the other
+    /// scratch buffers.
+    fn send_payload(
+        radio: &mut RadioDevice,
+        identity: &mut MavIdentity,
+        frame: &mut MavMessage,
+        payload: &[u8],
+    ) -> Result<(), HydraError> {
+        if payload.len() > RADIO_MAX_MESSAGE_LEN {
+            return Err(PayloadTooLarge {
+                len: payload.len(),
+                max: RADIO_MAX_MESSAGE_LEN,
+            }
+            .into());
+        }
+        if payload.len() <= RADIO_FRAME_LEN {
+            let data = postcard_payload(frame);
+            data[..payload.len()].copy_from_slice(payload);
+            data[payload.len()..].fill(0);
+            return Self::send_frame(radio, identity, frame);
+        }
+        let mut fragmenter = Fragmenter::new(payload, RADIO_FRAME_LEN)?;
+        loop {
+            let data = postcard_payload(frame);
+            let Some(len) = fragmenter.next_frame(data) else {
+                return Ok(());
+            };
+            data[len..].fill(0);
+            Self::send_frame(radio, identity, frame)?;
+        }
+    }
+    fn send_frame(
+        radio: &mut RadioDevice,
+        identity: &mut MavIdentity,
+        frame: &MavMessage,
+    ) -> Result<(), HydraError> {
+        mavlink::write_versioned_msg(
+            &mut radio.transmitter,
+            mavlink::MavlinkVersion::V2,
+            identity.next_header(),
+            frame,
+        )?;
+        Ok(())
+    }
+    /// Sends the messages packed into as few POSTCARD_MESSAGE frames as possible, see
+    /// `common_arm::Batch`. The ground side splits them again with `common_arm::unbatch`.
+    /// Each message must pass [`Self::admit`] first, the rest are dropped.
+    pub fn send_batched<'a>(
+        &mut self,
+        messages: impl Iterator<Item = &'a Message>,
+        now_ms: u64,
+    ) -> Result<(), HydraError> {
+        let scratch = &mut self.scratch;
+        // A batch an error cut short isn't sent later.
+        scratch.batch.clear();
+        for message in messages {
+            if !Self::admit_to(&mut self.limiter, message, now_ms) {
+                continue;
+            }
+            if scratch.batch.push(message)? {
+                continue;
+            }
+            if !scratch.batch.is_empty() {
+                Self::send_payload(
+                    &mut self.radio,
+                    &mut self.identity,
+                    &mut scratch.frame,
+                    scratch.batch.as_bytes(),
+                )?;
+                scratch.batch.clear();
+            }
+            if !scratch.batch.push(message)? {
+                // Too big to share a frame, send it on its own.
+                let payload = postcard::to_slice(message, &mut scratch.message)?;
+                Self::send_payload(
+                    &mut self.radio,
+                    &mut self.identity,
+                    &mut scratch.frame,
+                    payload,
+                )?;
+            }
+        }
+        if !scratch.batch.is_empty() {
+            Self::send_payload(
+                &mut self.radio,
+                &mut self.identity,
+                &mut scratch.frame,
+                scratch.batch.as_bytes(),
+            )?;
+        }
+        Ok(())
+    }
+    /// Sends a message delta encoded against the previous message of the same `stream`, see
+    /// `common_arm::DeltaEncoder`. The ground side decodes it with a `DeltaDecoder` per stream.
+    /// A message [`Self::admit`] drops leaves the encoder as it was, so the next one is encoded
+    /// against the last one sent.
+    pub fn send_compressed(
+        &mut self,
+        stream: u8,
+        message: &Message,
+        encoder: &mut DeltaEncoder<RADIO_DELTA_MAX_SAMPLE>,
+        now_ms: u64,
+    ) -> Result<(), HydraError> {
+        if !self.admit(message, now_ms) {
+            return Ok(());
+        }
+        let sample = postcard::to_slice(message, &mut self.scratch.sample)?;
+        // Encoded straight into the frame, a delta frame always fits one.
+        let data = postcard_payload(&mut self.scratch.frame);
+        let len = encoder.encode(stream, sample, data)?;
+        data[len..].fill(0);
+        Self::send_frame(&mut self.radio, &mut self.identity, &self.scratch.frame)
+    }
+    /// Sends a MAVLink heartbeat from a rocket. `custom_mode` has no autopilot mode to carry, so
+    /// it holds the u32 status `sequence` number the ground station uses to spot lost heartbeats.
+    /// `system_status` is standby, "can be launched any time", while `flight_ready` and
+    /// calibrating, "not flight ready", otherwise.
+    pub fn send_heartbeat(&mut self, sequence: u32, flight_ready: bool) -> Result<(), HydraError> {
+        let mav_header = self.identity.next_header();
+        let system_status = if flight_ready {
+            mavlink::uorocketry::MavState::MAV_STATE_STANDBY
+        } else {
+            mavlink::uorocketry::MavState::MAV_STATE_CALIBRATING
         };
-        // Create a fixed-size array and copy the payload into it
-        let mut fixed_payload = [0u8; 255];
-        let len = payload.len().min(255);
-        fixed_payload[..len].copy_from_slice(&payload[..len]);
-
-        let mav_message = mavlink::uorocketry::MavMessage::POSTCARD_MESSAGE(
-            mavlink::uorocketry::POSTCARD_MESSAGE_DATA {
-                message: fixed_payload,
-            },
-        );
+        let mav_message =
+            mavlink::uorocketry::MavMessage::HEARTBEAT(mavlink::uorocketry::HEARTBEAT_DATA {
+                custom_mode: sequence,
+                mavtype: mavlink::uorocketry::MavType::MAV_TYPE_ROCKET,
+                system_status,
+                mavlink_version: 3,
+                ..Default::default()
+            });
         mavlink::write_versioned_msg(
             &mut self.radio.transmitter,
             mavlink::MavlinkVersion::V2,
@@ -171,28 +1098,125 @@ impl RadioManager {
         )?;
         Ok(())
     }
-    pub fn increment_mav_sequence(&mut self) -> u8 {
-        self.mav_sequence = self.mav_sequence.wrapping_add(1);
-        self.mav_sequence
+    /// Returns true if the message may be queued for the radio now. Excess messages are dropped
+    /// and counted, commands and state changes always pass.
+    pub fn admit(&mut self, m: &Message, now_ms: u64) -> bool {
+        Self::admit_to(&mut self.limiter, m, now_ms)
+    }
+    fn admit_to(limiter: &mut TokenBucket, m: &Message, now_ms: u64) -> bool {
+        let critical = CanPriority::of(m) <= CanPriority::State;
+        limiter.allow(now_ms, critical)
+    }
+    /// Number of messages dropped by [`Self::admit`], whether sent through `send_gs` or the
+    /// sensor paths.
+    pub fn dropped_messages(&self) -> u32 {
+        self.limiter.dropped()
+    }
+    pub fn set_rate_limit(&mut self, rate_per_s: u32, burst: u32) {
+        self.limiter.set_rate(rate_per_s, burst);
+    }
+    /// Time of the last frame read from the ground, `None` if there was none yet.
+    pub fn last_receive_ms(&self) -> Option<u64> {
+        self.last_receive_ms
+    }
+    /// Records that a frame from the ground was read at `now_ms`, see [`RadioReceiver`].
+    pub fn record_receive(&mut self, now_ms: u64) {
+        self.last_receive_ms = Some(now_ms);
+    }
+    pub fn system_id(&self) -> u8 {
+        self.identity.system_id()
+    }
+    pub fn component_id(&self) -> u8 {
+        self.identity.component_id()
     }
-    pub fn receive_message(&mut self) -> Result<Message, HydraError> {
-        let (_header, msg): (_, MavMessage) =
-            mavlink::read_versioned_msg(&mut self.radio.receiver, mavlink::MavlinkVersion::V2)?;
+}
+
+/// Parses the frames the ground sends from the bytes `radio_rx` copies out of the UART. It is
+/// owned by the `radio_receive` task, so parsing holds no shared resource.
+pub struct RadioReceiver {
+    bytes: Consumer<'static, u8, RADIO_RX_QUEUE_LEN>,
+    parser: FrameParser<RADIO_RX_BUFFER_LEN>,
+    /// Fragmented payloads from the ground, by MAVLink system ID.
+    fragments: Reassembler<RADIO_FRAGMENT_SOURCES, RADIO_MAX_MESSAGE_LEN>,
+}
 
-        // info!("{:?}", );
-        // Do we need the header?
+impl RadioReceiver {
+    pub fn new(bytes: Consumer<'static, u8, RADIO_RX_QUEUE_LEN>) -> Self {
+        RadioReceiver {
+            bytes,
+            parser: FrameParser::new(),
+            fragments: Reassembler::new(),
+        }
+    }
+    /// Parses every frame the bytes received so far complete and passes each message to
+    /// `handle`. Parsing stops at a frame whose tail hasn't arrived yet, its bytes stay buffered
+    /// for the next call. Returns the number of frames read, including heartbeats and fragments.
+    /// A frame that fails to decode doesn't stop the others, the first such error is returned
+    /// once all are read.
+    pub fn receive_all(&mut self, mut handle: impl FnMut(Message)) -> Result<usize, HydraError> {
+        let mut result = Ok(());
+        let mut frames = 0;
+        loop {
+            let mut chunk = [0u8; 64];
+            let room = (RADIO_RX_BUFFER_LEN - self.parser.buffered()).min(chunk.len());
+            let mut len = 0;
+            while len < room {
+                match self.bytes.dequeue() {
+                    Some(byte) => {
+                        chunk[len] = byte;
+                        len += 1;
+                    }
+                    None => break,
+                }
+            }
+            self.parser.extend(&chunk[..len]);
+            while let Some(frame) = self.parser.next_frame(MavMessage::extra_crc) {
+                frames += 1;
+                match Self::decode(&mut self.fragments, &frame) {
+                    Ok(Some(message)) => handle(message),
+                    Ok(None) => {}
+                    Err(e) => {
+                        if result.is_ok() {
+                            result = Err(e);
+                        }
+                    }
+                }
+            }
+            if !self.bytes.ready() {
+                return result.map(|()| frames);
+            }
+        }
+    }
+    /// Decodes one frame and returns the message it completes. Returns `None` for a heartbeat
+    /// or a fragment of a payload that isn't complete yet.
+    fn decode(
+        fragments: &mut Reassembler<RADIO_FRAGMENT_SOURCES, RADIO_MAX_MESSAGE_LEN>,
+        frame: &MavFrame,
+    ) -> Result<Option<Message>, HydraError> {
+        let msg = MavMessage::parse(mavlink::MavlinkVersion::V2, frame.message_id, frame.payload)
+            .map_err(mavlink::error::MessageReadError::Parse)?;
         match msg {
+            mavlink::uorocketry::MavMessage::POSTCARD_MESSAGE(msg) if is_fragment(&msg.message) => {
+                match fragments.push(frame.system_id, &msg.message) {
+                    Some(payload) => Ok(Some(postcard::from_bytes::<Message>(payload)?)),
+                    None => Ok(None),
+                }
+            }
             mavlink::uorocketry::MavMessage::POSTCARD_MESSAGE(msg) => {
-                Ok(postcard::from_bytes::<Message>(&msg.message)?)
+                Ok(Some(postcard::from_bytes::<Message>(&msg.message)?))
                 // weird Ok syntax to coerce to hydra error type.
             }
             mavlink::uorocketry::MavMessage::COMMAND_MESSAGE(command) => {
+                if let Some(ground_command) = GroundCommand::from_command(&command.command) {
+                    crate::app::ground_command::spawn(ground_command).ok();
+                    return Ok(None);
+                }
                 info!("{}", command.command);
-                Ok(postcard::from_bytes::<Message>(&command.command)?)
+                Ok(Some(postcard::from_bytes::<Message>(&command.command)?))
             }
             mavlink::uorocketry::MavMessage::HEARTBEAT(_) => {
                 info!("Heartbeat");
-                Err(mavlink::error::MessageReadError::Io.into())
+                Ok(None)
             }
             _ => {
                 error!("Error, ErrorContext::UnkownPostcardMessage");
@@ -0,0 +1,730 @@
+use common_arm::{
+    AlignedSignal, ChangeEmitter, EvaluationClock, EventHook, EventHooks, HighResClock, HydraError,
+    HydraLogging, LinkMonitor, LogRate, PeakTracker, PretriggerBuffer, TelemetryDetail,
+    TelemetryDetailSelector, UpdateTimes, Verbosity,
+};
+use defmt::info;
+use heapless::{Deque, HistoryBuffer};
+use messages::command::RadioRate;
+use messages::sensor_status::EkfStatus;
+use messages::state::StateData;
+use messages::Message;
+use phoenix::{
+    accels_clipped, BallisticDetector, BaroVelocityFilter, EkfSolutionMode, FlightPhase,
+    FlightPhaseTracker, FlightReadiness, GroundReference, ImuSource, LandingShutdown,
+    LaunchDetector, OrientationFallback, OrientationMonitor, OrientationSource, RecoveryDeployment,
+    SensorKind, SensorVote, SourcePresence, SpinInhibit, Vote,
+};
+use stm32h7xx_hal::rcc::ResetReason;
+
+mod sensors;
+mod snapshot;
+
+pub use snapshot::{DataSnapshot, SNAPSHOT_SCHEMA};
+
+/// Events that start a full-rate capture of the samples around them, see
+/// [`DataManager::event_capture`].
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum EventTrigger {
+    /// Any flight state transition, such as apogee or a deploy.
+    StateChange,
+    /// A fast, tumbling descent was detected.
+    Ballistic,
+    /// The EKF orientation diverged from the gyro.
+    OrientationDiverged,
+    /// Sustained acceleration from the motor was detected.
+    Liftoff,
+}
+
+impl EventTrigger {
+    fn mask(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// Samples held for event captures, enough for the pre-trigger window at the full SBG rate.
+const EVENT_BUFFER_LEN: usize = 128;
+/// Time kept before an event and captured after it.
+const DEFAULT_EVENT_PRETRIGGER_MS: u64 = 500;
+const DEFAULT_EVENT_POSTTRIGGER_MS: u64 = 2000;
+
+/// How old a required sensor's last update may be before the vehicle is no longer flight ready.
+const DEFAULT_READINESS_MAX_AGE_MS: u64 = 1000;
+
+/// Spin rate above which recovery deployment is held off, in rad/s (a bit under 2 rev/s).
+const DEFAULT_SPIN_INHIBIT_RATE: f32 = 12.0;
+/// Longest the spin inhibit may hold off a deployment.
+const DEFAULT_SPIN_INHIBIT_MAX_DELAY_MS: u64 = 3000;
+
+/// Angle between the gyro-integrated and EKF orientation that counts as a disagreement, in rad.
+const DEFAULT_ORIENTATION_THRESHOLD: f32 = 0.1;
+/// Number of disagreeing comparison windows in a row before the EKF is flagged.
+const DEFAULT_ORIENTATION_SUSTAIN: u8 = 5;
+const DEFAULT_ORIENTATION_WINDOW_MS: u64 = 500;
+/// Age after which an orientation is no longer trusted.
+const DEFAULT_ORIENTATION_MAX_AGE_MS: u64 = 500;
+
+/// Time after landing before non-essential peripherals are powered down, 10 minutes.
+const DEFAULT_POST_LANDING_SHUTDOWN_MS: u64 = 10 * 60 * 1000;
+
+/// Minimum time between samples logged to SD, 0 logs every sample regardless of the radio rate.
+const DEFAULT_SD_LOG_PERIOD_MS: u64 = 0;
+/// Samples waiting for `sd_dump`, enough for its period at the full SBG rate.
+const SD_PENDING_LEN: usize = 32;
+/// Radio sensor update period for each `RadioRate`.
+const RADIO_FAST_PERIOD_MS: u64 = 100;
+const RADIO_SLOW_PERIOD_MS: u64 = 250;
+
+/// Descent rate no parachute descent reaches, in m/s.
+const DEFAULT_BALLISTIC_DESCENT_RATE: f32 = 50.0;
+/// Rotation rate that counts as tumbling, in rad/s.
+const DEFAULT_BALLISTIC_ROTATION_RATE: f32 = 6.0;
+const DEFAULT_BALLISTIC_SUSTAIN_MS: u64 = 1000;
+
+/// Number of event hooks that can be registered on the DataManager.
+pub const MAX_EVENT_HOOKS: usize = 4;
+
+/// Maximum number of baro samples averaged into the ground reference.
+pub const GROUND_REFERENCE_MAX_SAMPLES: usize = 32;
+
+/// Number of baro altitudes kept in [`DataManager::baro_history()`]. At the rates of
+/// `phoenix::BaroRate` that is 32 s on the ground, 1.6 s in boost and 0.64 s around apogee.
+pub const BARO_HISTORY_LEN: usize = 32;
+
+/// Largest difference between the baro and SBG pressures that still counts as agreeing, in kPa.
+const DEFAULT_PRESSURE_TOLERANCE: f32 = 0.5;
+
+/// Time without any SBG message, including at boot, after which the SBG is treated as absent.
+const DEFAULT_SBG_TIMEOUT_MS: u64 = 5000;
+
+/// Time without any frame from the ground after which recovery goes autonomous.
+const DEFAULT_LINK_TIMEOUT_MS: u64 = 10_000;
+
+/// Longest time between two state reports while the state doesn't change.
+const DEFAULT_STATE_KEEPALIVE_MS: u64 = 5000;
+
+/// Streams the radio keeps sending in compact telemetry, e.g. during boost.
+const COMPACT_TELEMETRY_SENSORS: [usize; 4] = [
+    SensorKind::EkfNav1 as usize,
+    SensorKind::EkfQuat as usize,
+    SensorKind::Air as usize,
+    SensorKind::Imu1 as usize,
+];
+
+/// Time constant of the low-pass filter on the baro vertical velocity, in s.
+const DEFAULT_BARO_VELOCITY_TIME_CONSTANT_S: f32 = 0.5;
+
+/// Time between the instants the baro and IMU are aligned to for fusion, 50 Hz.
+const DEFAULT_FUSION_PERIOD_MS: u64 = 20;
+/// How far each source may be extrapolated past its newest sample. The baro is read once a second
+/// on the ground and every 20 to 50 ms in flight, see `phoenix::BaroRate`, so its limit covers
+/// the slow ground rate. The IMU runs at 100 Hz.
+const BARO_MAX_EXTRAPOLATION_MS: u64 = 1500;
+const IMU_MAX_EXTRAPOLATION_MS: u64 = 50;
+
+/// Core clock in MHz, the rate of the DWT cycle counter behind [`DataManager::stamp_us`].
+const CYCLES_PER_US: u32 = 200;
+
+/// Time between IMU samples in s, 100 Hz.
+const DEFAULT_IMU_SAMPLE_PERIOD: f32 = 0.01;
+
+#[derive(Clone)]
+pub struct DataManager {
+    pub air: Option<Message>,
+    pub ekf_nav_1: Option<Message>,
+    pub ekf_nav_2: Option<Message>,
+    pub ekf_nav_acc: Option<Message>,
+    pub ekf_quat: Option<Message>,
+    pub madgwick_quat: Option<Message>,
+    pub imu_1: Option<Message>,
+    pub imu_2: Option<Message>,
+    pub utc_time: Option<Message>,
+    pub gps_vel: Option<Message>,
+    pub gps_vel_acc: Option<Message>,
+    pub gps_pos_1: Option<Message>,
+    pub gps_pos_2: Option<Message>,
+    pub gps_pos_acc: Option<Message>,
+    pub state: Option<StateData>,
+    pub reset_reason: Option<ResetReason>,
+    pub logging_rate: Option<RadioRate>,
+    pub recovery_sensing: Option<Message>,
+    pub nav_pos_l1h: Option<Message>,
+    // Barometer
+    /// Baro temperature in C and absolute pressure in kPa, as returned by the MS5611 driver.
+    /// Every pressure in the DataManager is in kPa.
+    pub baro_temperature: Option<f32>,
+    pub baro_pressure: Option<f32>,
+    /// Altitude above the sea level reference from the baro pressure, in m, assuming the standard
+    /// atmosphere.
+    pub baro_altitude: Option<f32>,
+    /// Altitude in m using the measured baro temperature instead of the standard atmosphere, or
+    /// `baro_altitude` when the temperature is outside the driver's correction range.
+    pub baro_altitude_corrected: Option<f32>,
+    /// Height above the ground reference from the baro pressure, in m, see
+    /// [`GroundReference::height_above`]. `None` until a reference is established.
+    pub baro_altitude_agl: Option<f32>,
+    /// Smoothed vertical velocity from the baro altitude, in m/s, positive up. `None` until two
+    /// readings were taken.
+    pub baro_vertical_velocity: Option<f32>,
+    /// Derives `baro_vertical_velocity` from successive baro altitudes.
+    pub baro_velocity_filter: BaroVelocityFilter,
+    /// Absolute pressure from the SBG air data in kPa, the second source for [`Self::vote_pressure`].
+    pub sbg_pressure: Option<f32>,
+    /// Set if the accelerometer saturated at any point, e.g. during boost.
+    pub accel_clipped: bool,
+    /// Set if the SBG flagged its accelerometers out of range while the motor was burning, for
+    /// the flight summary.
+    pub boost_accel_clipped: bool,
+    /// False if the boot check found the IMU range below the expected boost peak, see
+    /// `MadgwickService::check_accel_range`.
+    pub accel_range_ok: bool,
+    /// False if the RTC failed at boot and message timestamps count from boot instead.
+    pub rtc_available: bool,
+    /// IMU feeding the orientation filter and the gyro difference between the two IMUs in rad/s,
+    /// copied from the `MadgwickService` as samples arrive.
+    pub active_imu: Option<ImuSource>,
+    pub imu_disagreement: Option<f32>,
+    /// Set by `sbg_power_up` once the SBG produced data after power-on, or gave up.
+    pub sbg_started: Option<bool>,
+    /// Solution mode from the status of the latest SBG EKF orientation.
+    pub ekf_solution_mode: Option<EkfSolutionMode>,
+    /// Time of the last update of each sensor kind in ms since boot, indexed by `SensorKind`.
+    last_update_ms: UpdateTimes<{ SensorKind::COUNT }>,
+    /// Same as `last_update_ms` in µs, for correlating high-rate samples.
+    last_update_us: [Option<u64>; SensorKind::COUNT],
+    clock: HighResClock,
+    /// Required sensors that gate flight readiness, see [`Self::update_readiness`].
+    readiness: FlightReadiness,
+    /// Decides when to deploy recovery, held off while the vehicle spins too fast.
+    pub recovery: RecoveryDeployment,
+    /// Checks the SBG EKF orientation against the integrated IMU gyro rates.
+    pub orientation_monitor: OrientationMonitor,
+    /// Flags a fast, tumbling descent, i.e. a recovery failure.
+    pub ballistic_detector: BallisticDetector,
+    /// Flags liftoff from the SBG IMU acceleration confirmed by the baro altitude, see
+    /// [`Self::liftoff_detected`].
+    pub launch_detector: LaunchDetector,
+    /// Ground pressure that barometric altitude is measured from.
+    pub ground_reference: GroundReference<GROUND_REFERENCE_MAX_SAMPLES>,
+    /// Picks the EKF or Madgwick orientation, or flags it invalid when both are lost.
+    pub orientation: OrientationFallback,
+    /// Rate of the SD log, independent of the radio `logging_rate`.
+    pub sd_log_rate: LogRate,
+    /// Samples due for the SD log, see [`Self::take_sd_sample`].
+    sd_pending: Deque<Message, SD_PENDING_LEN>,
+    /// Samples due for the SD log that didn't fit `sd_pending`.
+    sd_dropped: u32,
+    /// Last moments of sensor samples, committed to SD at full rate around an event. Drain it
+    /// with `event_capture.drain(now_ms)` and append the samples to `EVENT_FILE_NAME`.
+    pub event_capture: PretriggerBuffer<Message, EVENT_BUFFER_LEN>,
+    /// Mask of the `EventTrigger`s that start a capture.
+    event_triggers: u8,
+    /// Hooks run after every `handle_data`, see [`Self::register_hook`].
+    hooks: EventHooks<DataManager, MAX_EVENT_HOOKS>,
+    /// Drops to locator mode a while after landing.
+    pub landing_shutdown: LandingShutdown,
+    /// Votes between the baro and SBG pressures, see [`Self::vote_pressure`].
+    pub pressure_vote: SensorVote<2>,
+    /// Tracks whether the SBG produces data at all, see [`Self::check_sbg`].
+    pub sbg_presence: SourcePresence,
+    /// Commits to autonomous recovery once the radio link is lost, see [`Self::update_link`].
+    pub link: LinkMonitor,
+    /// Reports the state on every transition, and otherwise only as a keep-alive.
+    pub state_emitter: ChangeEmitter,
+    /// Picks between compact and detailed radio telemetry, by flight phase or from the ground.
+    pub telemetry_detail: TelemetryDetailSelector,
+    /// Schedules the instants the sources are aligned to, see [`Self::set_fusion_period`].
+    fusion_clock: EvaluationClock,
+    /// Baro altitude timed by when each reading was taken, see [`Self::record_baro_altitude`].
+    baro_altitude_signal: AlignedSignal,
+    /// Recent baro altitudes in m with the time each reading was taken in ms, oldest first.
+    baro_history: HistoryBuffer<(u64, f32), BARO_HISTORY_LEN>,
+    /// Highest baro altitude, see [`Self::max_altitude()`].
+    max_altitude: PeakTracker,
+    /// Baro altitude of the pad, the lowest recent one at launch.
+    pad_altitude: Option<f32>,
+    accel_norm_signal: AlignedSignal,
+    /// Latest aligned values, updated at the fusion rate.
+    pub aligned: Option<AlignedInputs>,
+    /// Counts status heartbeats so the ground can detect dropped frames, see [`Self::next_status_sequence`].
+    status_sequence: u32,
+    /// Level set from the ground, see [`Self::set_verbosity`]. Kept across state changes, back to
+    /// `Normal` on reboot.
+    verbosity: Verbosity,
+    /// Log every message received on the CAN command bus.
+    pub log_can_rx: bool,
+    /// Tracks the flight phase, see [`Self::update_flight_phase`].
+    flight_phase: FlightPhaseTracker,
+}
+
+/// Baro and IMU values estimated at the same instant, see [`DataManager::aligned`].
+#[derive(Clone, Copy)]
+pub struct AlignedInputs {
+    /// Instant the values are estimated at, in ms since boot.
+    pub at_ms: u64,
+    /// Baro altitude in m, `None` if the baro is stale.
+    pub baro_altitude: Option<f32>,
+    /// Magnitude of the IMU acceleration in m/s², `None` if the IMU is stale.
+    pub accel_norm: Option<f32>,
+}
+
+impl DataManager {
+    pub fn new() -> Self {
+        Self {
+            air: None,
+            ekf_nav_1: None,
+            ekf_nav_2: None,
+            ekf_nav_acc: None,
+            ekf_quat: None,
+            madgwick_quat: None,
+            imu_1: None,
+            imu_2: None,
+            utc_time: None,
+            gps_vel: None,
+            gps_vel_acc: None,
+            gps_pos_1: None,
+            gps_pos_2: None,
+            gps_pos_acc: None,
+            state: None,
+            reset_reason: None,
+            logging_rate: Some(RadioRate::Slow), // start slow.
+            recovery_sensing: None,
+            nav_pos_l1h: None,
+            baro_temperature: None,
+            baro_pressure: None,
+            baro_altitude: None,
+            baro_altitude_corrected: None,
+            baro_altitude_agl: None,
+            baro_vertical_velocity: None,
+            baro_velocity_filter: BaroVelocityFilter::new(DEFAULT_BARO_VELOCITY_TIME_CONSTANT_S),
+            sbg_pressure: None,
+            accel_clipped: false,
+            boost_accel_clipped: false,
+            accel_range_ok: true,
+            rtc_available: false,
+            active_imu: None,
+            imu_disagreement: None,
+            sbg_started: None,
+            ekf_solution_mode: None,
+            last_update_ms: UpdateTimes::new(),
+            last_update_us: [None; SensorKind::COUNT],
+            clock: HighResClock::new(CYCLES_PER_US),
+            readiness: FlightReadiness::new(DEFAULT_READINESS_MAX_AGE_MS),
+            recovery: RecoveryDeployment::new(SpinInhibit::new(
+                DEFAULT_SPIN_INHIBIT_RATE,
+                DEFAULT_SPIN_INHIBIT_MAX_DELAY_MS,
+            )),
+            orientation_monitor: OrientationMonitor::new(
+                DEFAULT_ORIENTATION_THRESHOLD,
+                DEFAULT_ORIENTATION_SUSTAIN,
+                DEFAULT_ORIENTATION_WINDOW_MS,
+                DEFAULT_IMU_SAMPLE_PERIOD,
+            ),
+            ballistic_detector: BallisticDetector::new(
+                DEFAULT_BALLISTIC_DESCENT_RATE,
+                DEFAULT_BALLISTIC_ROTATION_RATE,
+                DEFAULT_BALLISTIC_SUSTAIN_MS,
+            ),
+            launch_detector: LaunchDetector::default(),
+            ground_reference: GroundReference::new(),
+            orientation: OrientationFallback::new(DEFAULT_ORIENTATION_MAX_AGE_MS),
+            sd_log_rate: LogRate::new(DEFAULT_SD_LOG_PERIOD_MS),
+            sd_pending: Deque::new(),
+            sd_dropped: 0,
+            event_capture: PretriggerBuffer::new(
+                DEFAULT_EVENT_PRETRIGGER_MS,
+                DEFAULT_EVENT_POSTTRIGGER_MS,
+            ),
+            event_triggers: 0,
+            hooks: EventHooks::new(),
+            landing_shutdown: LandingShutdown::new(DEFAULT_POST_LANDING_SHUTDOWN_MS),
+            pressure_vote: SensorVote::new(DEFAULT_PRESSURE_TOLERANCE),
+            sbg_presence: SourcePresence::new(DEFAULT_SBG_TIMEOUT_MS),
+            link: LinkMonitor::new(DEFAULT_LINK_TIMEOUT_MS),
+            state_emitter: ChangeEmitter::new(DEFAULT_STATE_KEEPALIVE_MS),
+            telemetry_detail: TelemetryDetailSelector::new(
+                TelemetryDetail::Detailed,
+                &COMPACT_TELEMETRY_SENSORS,
+            ),
+            fusion_clock: EvaluationClock::new(DEFAULT_FUSION_PERIOD_MS),
+            baro_altitude_signal: AlignedSignal::new(BARO_MAX_EXTRAPOLATION_MS),
+            baro_history: HistoryBuffer::new(),
+            max_altitude: PeakTracker::new(),
+            pad_altitude: None,
+            accel_norm_signal: AlignedSignal::new(IMU_MAX_EXTRAPOLATION_MS),
+            aligned: None,
+            status_sequence: 0,
+            verbosity: Verbosity::Normal,
+            log_can_rx: Verbosity::Normal.settings().log_can_rx,
+            flight_phase: FlightPhaseTracker::new(),
+        }
+    }
+
+    pub fn get_logging_rate(&mut self) -> RadioRate {
+        if let Some(rate) = self.logging_rate.take() {
+            let rate_cln = rate.clone();
+            self.logging_rate = Some(rate);
+            return rate_cln;
+        }
+        self.logging_rate = Some(RadioRate::Slow);
+        RadioRate::Slow
+    }
+
+    /// Time between radio sensor updates for the current logging rate. Compact telemetry always
+    /// uses the fast rate, it is meant for the phases where updates matter most.
+    pub fn radio_period_ms(&mut self) -> u64 {
+        if self.telemetry_detail.is_packed() {
+            return RADIO_FAST_PERIOD_MS;
+        }
+        match self.get_logging_rate() {
+            RadioRate::Fast => RADIO_FAST_PERIOD_MS,
+            RadioRate::Slow => RADIO_SLOW_PERIOD_MS,
+        }
+    }
+
+    /// Returns true if a sample arriving now should go to the SD log.
+    pub fn should_log_to_sd(&mut self, now_ms: u64) -> bool {
+        self.sd_log_rate.due(now_ms)
+    }
+
+    /// Takes the oldest sensor sample due for the SD log. Samples are kept at the SD log rate as
+    /// they arrive, independent of what `take_sensors` hands the radio.
+    pub fn take_sd_sample(&mut self) -> Option<Message> {
+        self.sd_pending.pop_front()
+    }
+
+    /// Number of samples due for the SD log that were dropped because `sd_dump` fell behind.
+    pub fn sd_dropped(&self) -> u32 {
+        self.sd_dropped
+    }
+
+    /// Records a state transition, which is reported right away rather than at the next
+    /// keep-alive.
+    pub fn set_state(&mut self, state: StateData) {
+        self.state = Some(state);
+        self.state_emitter.changed();
+    }
+
+    /// Returns the state if it should be reported now, see [`Self::state_emitter`].
+    pub fn state_to_send(&mut self, now_ms: u64) -> Option<StateData> {
+        if self.state.is_some() && self.state_emitter.poll(now_ms) {
+            return self.state.clone();
+        }
+        None
+    }
+
+    pub fn clone_states(&self) -> [Option<StateData>; 1] {
+        [self.state.clone()]
+    }
+
+    pub fn clone_reset_reason(&self) -> Option<ResetReason> {
+        self.reset_reason
+    }
+
+    pub fn set_reset_reason(&mut self, reset: ResetReason) {
+        self.reset_reason = Some(reset);
+    }
+
+    /// Sets what is logged and transmitted, see `common_arm::Verbosity` for what each level
+    /// enables. Individual settings can still be changed afterwards.
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) {
+        info!("Verbosity is now {}", verbosity);
+        let settings = verbosity.settings();
+        self.verbosity = verbosity;
+        self.log_can_rx = settings.log_can_rx;
+        self.telemetry_detail.force(settings.telemetry);
+        self.logging_rate = Some(if settings.radio_fast {
+            RadioRate::Fast
+        } else {
+            RadioRate::Slow
+        });
+        HydraLogging::set_forwarding(settings.forwarding);
+    }
+
+    pub fn verbosity(&self) -> Verbosity {
+        self.verbosity
+    }
+
+    pub fn handle_command(&mut self, data: Message) -> Result<(), HydraError> {
+        match data.data {
+            messages::Data::Command(command) => match command.data {
+                messages::command::CommandData::PowerDown(_) => {
+                    crate::app::sleep_system::spawn().ok();
+                }
+                messages::command::CommandData::RadioRateChange(command_data) => {
+                    self.logging_rate = Some(command_data.rate);
+                }
+                _ => {
+                    // We don't care atm about these other commands.
+                }
+            },
+            _ => {
+                // we can disregard all other messages for now.
+            }
+        }
+        Ok(())
+    }
+    pub fn handle_data(&mut self, data: Message, now_ms: u64) {
+        let was_ballistic = self.ballistic_detector.is_ballistic();
+        let was_diverged = self.orientation_monitor.is_diverged();
+        let was_lifted_off = self.liftoff_detected();
+        self.update_monitors(&data, now_ms);
+        if !was_ballistic && self.ballistic_detector.is_ballistic() {
+            self.trigger_event(EventTrigger::Ballistic, now_ms);
+        }
+        if !was_diverged && self.orientation_monitor.is_diverged() {
+            self.trigger_event(EventTrigger::OrientationDiverged, now_ms);
+        }
+        if !was_lifted_off && self.liftoff_detected() {
+            self.trigger_event(EventTrigger::Liftoff, now_ms);
+        }
+        match data.data {
+            messages::Data::Sensor(_) => {
+                let Some(kind) = SensorKind::of(&data) else {
+                    return;
+                };
+                if kind.is_sbg() {
+                    self.sbg_presence.seen(now_ms);
+                }
+                self.store_sensor(kind, data, now_ms);
+            }
+            messages::Data::State(state) => {
+                let previous = self.state.as_ref().map(core::mem::discriminant);
+                if previous != Some(core::mem::discriminant(&state.data)) {
+                    self.trigger_event(EventTrigger::StateChange, now_ms);
+                }
+                self.set_state(state.data);
+            }
+            // messages::Data::Command(command) => match command.data {
+            //     messages::command::CommandData::RadioRateChange(command_data) => {
+            //         self.logging_rate = Some(command_data.rate);
+            //     }
+            //     messages::command::CommandData::DeployDrogue(_) => {}
+            //     messages::command::CommandData::DeployMain(_) => {}
+            //     messages::command::CommandData::PowerDown(_) => {}
+            // },
+            _ => {}
+        }
+        if self.update_alignment(now_ms) {
+            self.update_flight_phase(now_ms);
+        }
+        self.hooks.run(self);
+    }
+
+    /// Registers a hook run after every `handle_data` with the updated DataManager, e.g. to deploy
+    /// a secondary payload at a set altitude. Hooks run under the DataManager lock in the task
+    /// that handled the data, so keep them short and spawn a task for anything slow. Returns false
+    /// if all `MAX_EVENT_HOOKS` slots are taken.
+    pub fn register_hook(&mut self, hook: EventHook<DataManager>) -> bool {
+        self.hooks.register(hook)
+    }
+
+    /// Latches the accelerometer clipping the SBG IMU status flags report, feeds IMU
+    /// accelerations to the launch detector, gyro rates to the spin inhibit and the orientation
+    /// monitor, and EKF orientations and their solution mode to the orientation monitor and
+    /// `ekf_solution_mode`. Other messages are ignored.
+    pub fn update_monitors(&mut self, data: &Message, now_ms: u64) {
+        if accels_clipped(data) {
+            self.accel_clipped = true;
+            self.boost_accel_clipped |= self.flight_phase() == FlightPhase::Boost;
+        }
+        let messages::Data::Sensor(sensor) = &data.data else {
+            return;
+        };
+        let messages::sensor::SensorData::SbgData(sbg_data) = &sensor.data else {
+            return;
+        };
+        match sbg_data {
+            messages::sensor::SbgData::Imu1(imu) => {
+                if let Some(accel) = imu.accelerometers {
+                    let norm = libm::sqrtf(accel.iter().map(|a| a * a).sum());
+                    self.accel_norm_signal.push(norm, now_ms);
+                    self.launch_detector.update_accel(accel, now_ms);
+                }
+                if let Some(gyro) = imu.gyroscopes {
+                    self.recovery.spin_inhibit.update(gyro);
+                    self.orientation_monitor.update_gyro(gyro, now_ms);
+                    self.ballistic_detector.update_gyro(gyro, now_ms);
+                }
+            }
+            messages::sensor::SbgData::EkfQuat(ekf_quat) => {
+                if let Some(word) = ekf_status_word(&ekf_quat.status) {
+                    self.ekf_solution_mode = EkfSolutionMode::from_status(word);
+                }
+                if let Some(quat) = ekf_quat.quaternion {
+                    self.orientation_monitor.update_ekf(quat, now_ms);
+                    self.orientation.update_ekf(quat, now_ms);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// True once the SBG IMU acceleration stayed above the launch threshold and the baro
+    /// altitude rose, latched.
+    pub fn liftoff_detected(&self) -> bool {
+        self.launch_detector.is_launched()
+    }
+
+    /// Sets which events start a capture of the samples around them.
+    pub fn set_event_triggers(&mut self, triggers: &[EventTrigger]) {
+        self.event_triggers = triggers
+            .iter()
+            .fold(0, |mask, trigger| mask | trigger.mask());
+    }
+
+    /// Starts or extends an event capture if `trigger` is one of the configured triggers.
+    pub fn trigger_event(&mut self, trigger: EventTrigger, now_ms: u64) {
+        if self.event_triggers & trigger.mask() != 0 {
+            info!("Event {}, capturing samples", trigger);
+            self.event_capture.trigger(now_ms);
+        }
+    }
+
+    /// Re-evaluates which orientation source is usable. Call periodically so a loss of all IMU
+    /// data is noticed even when no new messages arrive.
+    pub fn update_orientation(&mut self, now_ms: u64) -> OrientationSource {
+        self.orientation.current(now_ms).0
+    }
+
+    /// Re-evaluates whether the SBG is producing data and returns true if it is absent. Call
+    /// periodically. While absent its pressure is dropped so altitude falls back to the baro.
+    pub fn check_sbg(&mut self, now_ms: u64) -> bool {
+        let absent = self.sbg_presence.check(now_ms);
+        if absent && self.sbg_pressure.take().is_some() {
+            self.vote_pressure();
+        }
+        absent
+    }
+
+    /// Re-evaluates the radio link from the time of the last frame received from the ground.
+    /// Returns true once recovery is autonomous and must not wait on ground commands.
+    pub fn update_link(&mut self, last_receive_ms: Option<u64>, now_ms: u64) -> bool {
+        self.link.update(last_receive_ms, now_ms)
+    }
+
+    /// Returns true if triggers that depend on attitude may be used. They are off while the SBG is
+    /// absent or no orientation source is fresh, leaving recovery to the baro alone.
+    pub fn tilt_triggers_allowed(&mut self, now_ms: u64) -> bool {
+        !self.sbg_presence.is_absent() && self.orientation.tilt_triggers_allowed(now_ms)
+    }
+
+    /// Records a baro altitude with the time the reading was taken, for alignment with the IMU,
+    /// the altitude history and the maximum altitude.
+    pub fn record_baro_altitude(&mut self, altitude_m: f32, at_ms: u64) {
+        self.baro_altitude_signal.push(altitude_m, at_ms);
+        self.baro_history.write((at_ms, altitude_m));
+        self.max_altitude.update(altitude_m);
+        self.launch_detector.update_altitude(altitude_m, at_ms);
+    }
+
+    /// Highest baro altitude since boot or [`Self::reset_max_altitude`], in m.
+    pub fn max_altitude(&self) -> Option<f32> {
+        self.max_altitude.peak()
+    }
+
+    /// Forgets the maximum altitude, e.g. when the vehicle is re-armed on the ground.
+    pub fn reset_max_altitude(&mut self) {
+        self.max_altitude.reset();
+    }
+
+    /// Highest baro altitude above the pad in m, `None` before launch.
+    pub fn peak_altitude_above_pad(&self) -> Option<f32> {
+        Some(self.max_altitude.peak()? - self.pad_altitude?)
+    }
+
+    /// The last `BARO_HISTORY_LEN` baro altitudes as `(time in ms, altitude in m)`, oldest first,
+    /// for estimators that need a window rather than the latest reading.
+    pub fn baro_history(&self) -> impl Iterator<Item = &(u64, f32)> + '_ {
+        self.baro_history.oldest_ordered()
+    }
+
+    /// Estimates every source at the next fusion instant once it is due. Returns true if
+    /// [`Self::aligned`] was updated.
+    pub fn update_alignment(&mut self, now_ms: u64) -> bool {
+        let Some(at_ms) = self.fusion_clock.poll(now_ms) else {
+            return false;
+        };
+        self.aligned = Some(AlignedInputs {
+            at_ms,
+            baro_altitude: self.baro_altitude_signal.value_at(at_ms),
+            accel_norm: self.accel_norm_signal.value_at(at_ms),
+        });
+        true
+    }
+
+    /// Advances the flight phase from the baro vertical velocity and the aligned acceleration.
+    /// On a transition the event capture is triggered, the telemetry detail follows the phase and
+    /// the landing shutdown is told about the launch and the landing. The ground reference is
+    /// locked at launch so nothing recalibrates it in flight. Recovery deployment is decided on
+    /// every update, see [`RecoveryDeployment`], and also triggers the event capture.
+    pub fn update_flight_phase(&mut self, now_ms: u64) -> FlightPhase {
+        let previous = self.flight_phase.phase();
+        let accel_norm = self.aligned.and_then(|aligned| aligned.accel_norm);
+        let phase = self.flight_phase.update(
+            self.launch_detector.is_launched(),
+            self.baro_vertical_velocity,
+            accel_norm,
+            now_ms,
+        );
+        if self.recovery.update(phase, now_ms) {
+            self.trigger_event(EventTrigger::StateChange, now_ms);
+        }
+        if phase == previous {
+            return phase;
+        }
+        self.trigger_event(EventTrigger::StateChange, now_ms);
+        self.telemetry_detail.set_phase_detail(match phase {
+            FlightPhase::Boost => TelemetryDetail::Compact,
+            _ => TelemetryDetail::Detailed,
+        });
+        match phase {
+            FlightPhase::Boost => {
+                self.landing_shutdown.launched();
+                self.ground_reference.lock();
+                self.pad_altitude = self
+                    .baro_history
+                    .iter()
+                    .map(|(_, altitude)| *altitude)
+                    .reduce(f32::min);
+            }
+            FlightPhase::Landed => self.landing_shutdown.landed(now_ms),
+            _ => {}
+        }
+        phase
+    }
+
+    pub fn flight_phase(&self) -> FlightPhase {
+        self.flight_phase.phase()
+    }
+
+    /// Sets the rate the sources are aligned and fused at.
+    pub fn set_fusion_period(&mut self, period_ms: u64) {
+        self.fusion_clock.set_period(period_ms);
+    }
+
+    pub fn fusion_period_ms(&self) -> u64 {
+        self.fusion_clock.period_ms()
+    }
+
+    /// Votes between the baro and SBG pressures. Use the voted value rather than either source
+    /// for anything that decides on altitude.
+    pub fn vote_pressure(&mut self) -> Vote {
+        self.pressure_vote
+            .vote([self.baro_pressure, self.sbg_pressure])
+    }
+}
+
+/// Raw SBG status word of an EKF status. `EkfStatus` keeps it private, but postcard encodes the
+/// one field struct as just the word.
+fn ekf_status_word(status: &EkfStatus) -> Option<u32> {
+    let mut buf = [0u8; 5];
+    let bytes = postcard::to_slice(status, &mut buf).ok()?;
+    postcard::from_bytes(bytes).ok()
+}
+
+impl Default for DataManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
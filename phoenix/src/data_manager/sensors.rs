@@ -0,0 +1,133 @@
+//! The sensor messages held by the DataManager, when each last arrived and whether they make the
+//! vehicle flight ready.
+
+use messages::Message;
+use phoenix::{status_valid, SensorKind};
+
+use super::DataManager;
+
+impl DataManager {
+    /// Do not clone instead take to reduce CPU load.
+    pub fn take_sensors(&mut self) -> [Option<Message>; 16] {
+        [
+            self.air.take(),
+            self.ekf_nav_1.take(),
+            self.ekf_nav_2.take(),
+            self.ekf_nav_acc.take(),
+            self.ekf_quat.take(),
+            self.madgwick_quat.take(),
+            self.imu_1.take(),
+            self.imu_2.take(),
+            self.utc_time.take(),
+            self.gps_vel.take(),
+            self.gps_vel_acc.take(),
+            self.gps_pos_1.take(),
+            self.gps_pos_2.take(),
+            self.gps_pos_acc.take(),
+            self.nav_pos_l1h.take(),
+            self.recovery_sensing.take(),
+        ]
+    }
+
+    /// Takes the latest message of one kind and leaves the others, e.g. to send some streams at a
+    /// different rate than the bulk [`Self::take_sensors`].
+    pub fn take_sensor(&mut self, kind: SensorKind) -> Option<Message> {
+        self.sensor_mut(kind).take()
+    }
+
+    /// Stores a sensor message and records when it arrived.
+    pub(super) fn store_sensor(&mut self, kind: SensorKind, data: Message, now_ms: u64) {
+        // The Madgwick output carries an empty EKF status, it is valid whenever produced.
+        let valid = kind == SensorKind::MadgwickQuat || status_valid(&data);
+        self.readiness.record(kind, valid, now_ms);
+        self.event_capture.push(data.clone(), now_ms);
+        if self.should_log_to_sd(now_ms) && self.sd_pending.push_back(data.clone()).is_err() {
+            self.sd_dropped += 1;
+        }
+        *self.sensor_mut(kind) = Some(data);
+        self.last_update_ms.record(kind as usize, now_ms);
+        self.last_update_us[kind as usize] = Some(self.stamp_us());
+    }
+
+    /// Microseconds since boot from the DWT cycle counter, which `init` starts. Called on every
+    /// stored sensor message, often enough to keep up with the counter wrapping.
+    pub fn stamp_us(&mut self) -> u64 {
+        self.clock
+            .stamp_us(cortex_m::peripheral::DWT::cycle_count())
+    }
+
+    /// Time the last message of `kind` arrived, in ms since boot.
+    pub fn last_update_ms(&self, kind: SensorKind) -> Option<u64> {
+        self.last_update_ms.last(kind as usize)
+    }
+
+    /// Time the last message of `kind` arrived, in µs since boot.
+    pub fn last_update_us(&self, kind: SensorKind) -> Option<u64> {
+        self.last_update_us[kind as usize]
+    }
+
+    fn sensor_mut(&mut self, kind: SensorKind) -> &mut Option<Message> {
+        match kind {
+            SensorKind::Air => &mut self.air,
+            SensorKind::EkfNav1 => &mut self.ekf_nav_1,
+            SensorKind::EkfNav2 => &mut self.ekf_nav_2,
+            SensorKind::EkfNavAcc => &mut self.ekf_nav_acc,
+            SensorKind::EkfQuat => &mut self.ekf_quat,
+            SensorKind::MadgwickQuat => &mut self.madgwick_quat,
+            SensorKind::Imu1 => &mut self.imu_1,
+            SensorKind::Imu2 => &mut self.imu_2,
+            SensorKind::UtcTime => &mut self.utc_time,
+            SensorKind::GpsVel => &mut self.gps_vel,
+            SensorKind::GpsVelAcc => &mut self.gps_vel_acc,
+            SensorKind::GpsPos1 => &mut self.gps_pos_1,
+            SensorKind::GpsPos2 => &mut self.gps_pos_2,
+            SensorKind::GpsPosAcc => &mut self.gps_pos_acc,
+            SensorKind::NavPosLlh => &mut self.nav_pos_l1h,
+            SensorKind::RecoverySensing => &mut self.recovery_sensing,
+        }
+    }
+
+    /// Sets the sensors that must be reporting valid data for [`DataManager::is_flight_ready`] to
+    /// be true.
+    pub fn set_required_sensors(&mut self, sensors: &[SensorKind]) {
+        self.readiness.set_required(sensors);
+    }
+
+    /// Sets how old a required sensor's last valid message may be while still counting as ready.
+    pub fn set_readiness_max_age(&mut self, max_age_ms: u64) {
+        self.readiness.set_max_age(max_age_ms);
+    }
+
+    /// Single pre-launch check: re-evaluates and returns whether every required sensor sent a
+    /// message its status flags mark valid within the readiness max age, see
+    /// `phoenix::FlightReadiness`. Call periodically.
+    pub fn update_readiness(&mut self, now_ms: u64) -> bool {
+        self.readiness.update(now_ms)
+    }
+
+    /// Readiness as of the last [`Self::update_readiness`].
+    pub fn is_flight_ready(&self) -> bool {
+        self.readiness.is_ready()
+    }
+
+    /// Returns true if the last message of `kind` is older than `max_age_ms`, or none arrived
+    /// yet. [`Self::last_update_ms()`] tells a sensor that never reported from one that stopped.
+    pub fn is_stale(&self, kind: SensorKind, max_age_ms: u64, now_ms: u64) -> bool {
+        self.last_update_ms
+            .is_stale(kind as usize, max_age_ms, now_ms)
+    }
+
+    pub fn store_madgwick_result(&mut self, result: Message, now_ms: u64) {
+        if let messages::Data::Sensor(sensor) = &result.data {
+            if let messages::sensor::SensorData::SbgData(messages::sensor::SbgData::EkfQuat(
+                ekf_quat,
+            )) = &sensor.data
+            {
+                if let Some(quat) = ekf_quat.quaternion {
+                    self.orientation.update_madgwick(quat, now_ms);
+                }
+            }
+        }
+        self.store_sensor(SensorKind::MadgwickQuat, result, now_ms);
+    }
+}
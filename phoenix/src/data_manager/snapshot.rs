@@ -0,0 +1,174 @@
+//! [`DataSnapshot`], the DataManager contents as one coherent postcard blob.
+
+use common_arm::{HydraError, Schema};
+use messages::command::RadioRate;
+use messages::state::StateData;
+use messages::Message;
+use phoenix::{EkfSolutionMode, FlightPhase, ImuSource, OrientationSource, SensorKind};
+use serde::{Deserialize, Serialize};
+
+use super::DataManager;
+
+/// Schema of [`DataSnapshot`]. New fields go at the end of the struct as `Option`s so older
+/// snapshots keep decoding, see `common_arm::Schema`. Raise both for any other change.
+pub const SNAPSHOT_SCHEMA: Schema = Schema::new(3, 3);
+
+/// A single coherent snapshot of the [`DataManager`] contents, serialized as one postcard blob.
+/// Unlike sending every sensor as its own message, all values come from the same instant.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DataSnapshot {
+    pub air: Option<Message>,
+    pub ekf_nav_1: Option<Message>,
+    pub ekf_nav_2: Option<Message>,
+    pub ekf_nav_acc: Option<Message>,
+    pub ekf_quat: Option<Message>,
+    pub madgwick_quat: Option<Message>,
+    pub imu_1: Option<Message>,
+    pub imu_2: Option<Message>,
+    pub utc_time: Option<Message>,
+    pub gps_vel: Option<Message>,
+    pub gps_vel_acc: Option<Message>,
+    pub gps_pos_1: Option<Message>,
+    pub gps_pos_2: Option<Message>,
+    pub gps_pos_acc: Option<Message>,
+    pub recovery_sensing: Option<Message>,
+    pub nav_pos_l1h: Option<Message>,
+    pub state: Option<StateData>,
+    pub logging_rate: Option<RadioRate>,
+    /// Baro temperature in C and absolute pressure in kPa, as returned by the MS5611 driver.
+    /// Every pressure in the DataManager is in kPa.
+    pub baro_temperature: Option<f32>,
+    pub baro_pressure: Option<f32>,
+    /// Altitude above the sea level reference from the baro pressure, in m, assuming the standard
+    /// atmosphere.
+    pub baro_altitude: Option<f32>,
+    /// Altitude in m using the measured baro temperature instead of the standard atmosphere, or
+    /// `baro_altitude` when the temperature is outside the driver's correction range.
+    pub baro_altitude_corrected: Option<f32>,
+    /// Smoothed vertical velocity from the baro altitude, in m/s, positive up.
+    pub baro_vertical_velocity: Option<f32>,
+    pub accel_clipped: bool,
+    /// Sequence number of the latest status heartbeat sent to the ground.
+    pub status_sequence: u32,
+    /// Angle between the gyro-integrated and SBG EKF orientation, in rad.
+    pub orientation_disagreement: Option<f32>,
+    /// Averaged ground pressure in kPa and the standard deviation of the samples it came from.
+    pub ground_pressure: Option<f32>,
+    pub ground_pressure_std_dev: Option<f32>,
+    /// False when neither the EKF nor Madgwick has a fresh orientation, tilt triggers are off.
+    pub orientation_valid: bool,
+    /// Set once a fast, tumbling descent has been detected.
+    pub ballistic: bool,
+    /// Pressure in kPa voted between the baro and the SBG, `None` if neither is valid.
+    pub voted_pressure: Option<f32>,
+    /// Sources used for `voted_pressure`, bit 0 for the baro and bit 1 for the SBG.
+    pub pressure_trusted: u8,
+    /// Set if a valid pressure source was excluded or the two disagreed.
+    pub pressure_disagreement: bool,
+    /// Arrival time of each sensor kind in µs since boot, indexed by `SensorKind`. The messages
+    /// themselves only carry the coarse RTC time.
+    pub sensor_time_us: [Option<u64>; SensorKind::COUNT],
+    /// Set while no SBG data arrives. Altitude is then baro-only and tilt triggers are off.
+    pub sbg_absent: bool,
+    /// Set while nothing is received from the ground, and latched autonomous recovery.
+    pub link_lost: bool,
+    pub autonomous: bool,
+    /// False if the RTC failed at boot and message timestamps count from boot instead.
+    pub rtc_available: bool,
+    /// Set while the secondary IMU feeds the orientation filter instead of the SBG.
+    pub secondary_imu_active: bool,
+    /// Difference between the gyro rates of the two IMUs in rad/s, if both are reporting.
+    pub imu_disagreement: Option<f32>,
+    /// Outcome of the SBG power-on sequence, `None` while it is still running.
+    pub sbg_started: Option<bool>,
+    /// Set while the radio only sends the compact streams, packed together.
+    pub telemetry_compact: bool,
+    /// Flight phase from the baro velocity and the IMU acceleration, `None` in older snapshots.
+    pub flight_phase: Option<FlightPhase>,
+    /// Highest baro altitude since boot or the last re-arm, in m.
+    pub max_altitude: Option<f32>,
+    /// Set while every required sensor reports fresh, valid data, see
+    /// `DataManager::update_readiness`.
+    pub flight_ready: Option<bool>,
+    /// Solution mode of the SBG EKF, `None` before its first orientation.
+    pub ekf_solution_mode: Option<EkfSolutionMode>,
+    /// Whether the SBG flagged its accelerometers out of range during boost, `None` in older
+    /// snapshots.
+    pub boost_accel_clipped: Option<bool>,
+    /// Outcome of the boot check of the IMU range against the expected boost peak.
+    pub accel_range_ok: Option<bool>,
+    /// Height above `ground_pressure` from the baro pressure, in m.
+    pub baro_altitude_agl: Option<f32>,
+}
+
+impl DataManager {
+    /// Captures the current sensor values and flight state without draining them.
+    pub fn snapshot(&self) -> DataSnapshot {
+        DataSnapshot {
+            air: self.air.clone(),
+            ekf_nav_1: self.ekf_nav_1.clone(),
+            ekf_nav_2: self.ekf_nav_2.clone(),
+            ekf_nav_acc: self.ekf_nav_acc.clone(),
+            ekf_quat: self.ekf_quat.clone(),
+            madgwick_quat: self.madgwick_quat.clone(),
+            imu_1: self.imu_1.clone(),
+            imu_2: self.imu_2.clone(),
+            utc_time: self.utc_time.clone(),
+            gps_vel: self.gps_vel.clone(),
+            gps_vel_acc: self.gps_vel_acc.clone(),
+            gps_pos_1: self.gps_pos_1.clone(),
+            gps_pos_2: self.gps_pos_2.clone(),
+            gps_pos_acc: self.gps_pos_acc.clone(),
+            recovery_sensing: self.recovery_sensing.clone(),
+            nav_pos_l1h: self.nav_pos_l1h.clone(),
+            state: self.state.clone(),
+            logging_rate: self.logging_rate.clone(),
+            baro_temperature: self.baro_temperature,
+            baro_pressure: self.baro_pressure,
+            baro_altitude: self.baro_altitude,
+            baro_altitude_corrected: self.baro_altitude_corrected,
+            baro_vertical_velocity: self.baro_vertical_velocity,
+            accel_clipped: self.accel_clipped,
+            status_sequence: self.status_sequence,
+            orientation_disagreement: self.orientation_monitor.disagreement(),
+            ground_pressure: self.ground_reference.reference(),
+            ground_pressure_std_dev: self.ground_reference.std_dev(),
+            orientation_valid: self.orientation.source() != OrientationSource::Invalid,
+            ballistic: self.ballistic_detector.is_ballistic(),
+            voted_pressure: self.pressure_vote.last().value,
+            pressure_trusted: self.pressure_vote.last().trusted,
+            pressure_disagreement: self.pressure_vote.last().disagreement,
+            sensor_time_us: self.last_update_us,
+            sbg_absent: self.sbg_presence.is_absent(),
+            link_lost: self.link.is_link_lost(),
+            autonomous: self.link.is_autonomous(),
+            rtc_available: self.rtc_available,
+            secondary_imu_active: self.active_imu == Some(ImuSource::Secondary),
+            imu_disagreement: self.imu_disagreement,
+            sbg_started: self.sbg_started,
+            telemetry_compact: self.telemetry_detail.is_packed(),
+            flight_phase: Some(self.flight_phase.phase()),
+            max_altitude: self.max_altitude.peak(),
+            flight_ready: Some(self.readiness.is_ready()),
+            ekf_solution_mode: self.ekf_solution_mode,
+            boost_accel_clipped: Some(self.boost_accel_clipped),
+            accel_range_ok: Some(self.accel_range_ok),
+            baro_altitude_agl: self.baro_altitude_agl,
+        }
+    }
+
+    /// Advances and returns the status heartbeat sequence number. Call once per emitted heartbeat.
+    /// Unlike the 8-bit MAVLink header sequence, which wraps quickly and counts every message,
+    /// this only counts heartbeats, so gaps give the exact number of lost heartbeats.
+    pub fn next_status_sequence(&mut self) -> u32 {
+        self.status_sequence = self.status_sequence.wrapping_add(1);
+        self.status_sequence
+    }
+
+    /// Serializes a [`DataSnapshot`] into `buf` and returns the number of bytes written.
+    /// The ground side decodes it with `SNAPSHOT_SCHEMA.decode::<DataSnapshot>`.
+    pub fn serialize_state(&self, buf: &mut [u8]) -> Result<usize, HydraError> {
+        let data = SNAPSHOT_SCHEMA.encode(&self.snapshot(), buf)?;
+        Ok(data.len())
+    }
+}
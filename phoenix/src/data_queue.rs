@@ -0,0 +1,48 @@
+use defmt::warn;
+use messages::Message;
+use phoenix::{DropQueue, FullPolicy};
+use rtic_sync::channel::Sender;
+
+/// Bounded queue of messages waiting to go out on the data CAN bus.
+///
+/// Producers push into the queue from any priority and `send_data_internal` drains it. The
+/// consumer is woken through a single slot doorbell channel, so a full doorbell simply means a
+/// wake up is already pending. Overflow is resolved by the configured [`FullPolicy`] and counted.
+pub struct DataQueue<const N: usize> {
+    queue: DropQueue<Message, N>,
+    doorbell: Sender<'static, (), 1>,
+}
+
+impl<const N: usize> DataQueue<N> {
+    pub fn new(doorbell: Sender<'static, (), 1>, policy: FullPolicy) -> Self {
+        Self {
+            queue: DropQueue::new(policy),
+            doorbell,
+        }
+    }
+
+    /// Queues a message and wakes the consumer. If the queue is full the configured policy
+    /// decides which message is dropped.
+    pub fn push(&mut self, m: Message) {
+        if self.queue.push(m) {
+            warn!(
+                "Data queue full, {} messages dropped so far",
+                self.queue.dropped()
+            );
+        }
+        self.doorbell.try_send(()).ok();
+    }
+
+    pub fn pop(&mut self) -> Option<Message> {
+        self.queue.pop()
+    }
+
+    pub fn set_policy(&mut self, policy: FullPolicy) {
+        self.queue.set_policy(policy);
+    }
+
+    /// Number of messages dropped because the queue was full since boot.
+    pub fn dropped(&self) -> u32 {
+        self.queue.dropped()
+    }
+}
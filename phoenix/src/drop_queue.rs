@@ -0,0 +1,123 @@
+//! Bounded FIFO that never blocks the producer, dropping a message instead when it is full.
+
+use heapless::Deque;
+
+/// What a [`DropQueue`] does with a message pushed while it is full.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum FullPolicy {
+    /// Evict the oldest queued message to make room. Freshness matters more than completeness
+    /// for flight telemetry, so this is what we normally want.
+    DropOldest,
+    /// Discard the incoming message and keep what is already queued.
+    DropNewest,
+}
+
+pub struct DropQueue<T, const N: usize> {
+    queue: Deque<T, N>,
+    policy: FullPolicy,
+    dropped: u32,
+}
+
+impl<T, const N: usize> DropQueue<T, N> {
+    pub const fn new(policy: FullPolicy) -> Self {
+        Self {
+            queue: Deque::new(),
+            policy,
+            dropped: 0,
+        }
+    }
+
+    /// Queues `item`. If the queue is full the policy decides which message is dropped, and true
+    /// is returned.
+    pub fn push(&mut self, item: T) -> bool {
+        let full = self.queue.is_full();
+        if full {
+            self.dropped = self.dropped.wrapping_add(1);
+            match self.policy {
+                FullPolicy::DropOldest => {
+                    self.queue.pop_front();
+                }
+                FullPolicy::DropNewest => return true,
+            }
+        }
+        // Cannot fail, a slot was freed above if the queue was full.
+        self.queue.push_back(item).ok();
+        full
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.queue.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn set_policy(&mut self, policy: FullPolicy) {
+        self.policy = policy;
+    }
+
+    /// Number of messages dropped because the queue was full since boot.
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEN: usize = 4;
+
+    /// A queue of `LEN` holding 0..LEN, then pushed `LEN..LEN + extra`.
+    fn overfilled(policy: FullPolicy, extra: u32) -> DropQueue<u32, LEN> {
+        let mut queue = DropQueue::new(policy);
+        for item in 0..LEN as u32 {
+            assert!(!queue.push(item));
+        }
+        for item in LEN as u32..LEN as u32 + extra {
+            assert!(queue.push(item));
+        }
+        queue
+    }
+
+    fn drain(queue: &mut DropQueue<u32, LEN>) -> heapless::Vec<u32, LEN> {
+        core::iter::from_fn(|| queue.pop()).collect()
+    }
+
+    #[test]
+    fn drop_oldest_keeps_the_latest() {
+        let mut queue = overfilled(FullPolicy::DropOldest, 3);
+        assert_eq!(queue.dropped(), 3);
+        assert_eq!(queue.len(), LEN);
+        assert_eq!(drain(&mut queue).as_slice(), &[3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn drop_newest_keeps_what_was_queued() {
+        let mut queue = overfilled(FullPolicy::DropNewest, 3);
+        assert_eq!(queue.dropped(), 3);
+        assert_eq!(drain(&mut queue).as_slice(), &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn nothing_is_dropped_once_drained() {
+        let mut queue = overfilled(FullPolicy::DropOldest, 1);
+        drain(&mut queue);
+        assert!(queue.is_empty());
+        assert!(!queue.push(10));
+        assert_eq!(queue.dropped(), 1);
+
+        queue.set_policy(FullPolicy::DropNewest);
+        for item in 11..14 {
+            queue.push(item);
+        }
+        assert!(queue.push(14));
+        assert_eq!(queue.dropped(), 2);
+        assert_eq!(drain(&mut queue).as_slice(), &[10, 11, 12, 13]);
+    }
+}
@@ -0,0 +1,95 @@
+//! Decoding of the solution mode in the SBG EKF status word.
+//!
+//! The mode tells how far the EKF has converged and so how much its output can be trusted: only
+//! `NavPosition` means orientation, velocity and position are all being estimated.
+
+use serde::{Deserialize, Serialize};
+
+/// Bits 0-3 of the SBG `SBG_ECOM_LOG_EKF_*` solution status.
+const SOLUTION_MODE_MASK: u32 = 0x0F;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format, Serialize, Deserialize)]
+pub enum EkfSolutionMode {
+    /// The EKF is not initialized, its output is invalid.
+    Uninitialized,
+    /// Only roll and pitch are computed.
+    VerticalGyro,
+    /// Roll, pitch and heading are computed.
+    Ahrs,
+    /// Orientation and velocity are computed, position is free-running.
+    NavVelocity,
+    /// Full navigation solution.
+    NavPosition,
+}
+
+impl EkfSolutionMode {
+    /// Decodes the solution mode from a raw status word. Returns `None` for reserved values.
+    pub fn from_status(status: u32) -> Option<Self> {
+        match status & SOLUTION_MODE_MASK {
+            0 => Some(EkfSolutionMode::Uninitialized),
+            1 => Some(EkfSolutionMode::VerticalGyro),
+            2 => Some(EkfSolutionMode::Ahrs),
+            3 => Some(EkfSolutionMode::NavVelocity),
+            4 => Some(EkfSolutionMode::NavPosition),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the EKF provides a full orientation, i.e. heading as well as roll and
+    /// pitch.
+    pub fn has_orientation(self) -> bool {
+        matches!(
+            self,
+            EkfSolutionMode::Ahrs | EkfSolutionMode::NavVelocity | EkfSolutionMode::NavPosition
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_status_values_decode() {
+        assert_eq!(
+            EkfSolutionMode::from_status(0),
+            Some(EkfSolutionMode::Uninitialized)
+        );
+        assert_eq!(
+            EkfSolutionMode::from_status(1),
+            Some(EkfSolutionMode::VerticalGyro)
+        );
+        assert_eq!(EkfSolutionMode::from_status(2), Some(EkfSolutionMode::Ahrs));
+        assert_eq!(
+            EkfSolutionMode::from_status(3),
+            Some(EkfSolutionMode::NavVelocity)
+        );
+        assert_eq!(
+            EkfSolutionMode::from_status(4),
+            Some(EkfSolutionMode::NavPosition)
+        );
+    }
+
+    #[test]
+    fn other_status_flags_are_ignored() {
+        // Attitude, heading, velocity and position valid flags set on top of the mode.
+        assert_eq!(
+            EkfSolutionMode::from_status(0x0000_00F4),
+            Some(EkfSolutionMode::NavPosition)
+        );
+    }
+
+    #[test]
+    fn reserved_modes_are_rejected() {
+        assert_eq!(EkfSolutionMode::from_status(5), None);
+        assert_eq!(EkfSolutionMode::from_status(0x0F), None);
+    }
+
+    #[test]
+    fn orientation_only_from_ahrs_up() {
+        assert!(!EkfSolutionMode::Uninitialized.has_orientation());
+        assert!(!EkfSolutionMode::VerticalGyro.has_orientation());
+        assert!(EkfSolutionMode::Ahrs.has_orientation());
+        assert!(EkfSolutionMode::NavPosition.has_orientation());
+    }
+}
@@ -0,0 +1,33 @@
+//! Messages shared by the unit tests.
+
+use chrono::NaiveDate;
+use messages::node::Node;
+use messages::sensor::{EkfQuat, SbgData, Sensor, SensorData};
+use messages::sensor_status::EkfStatus;
+use messages::{FormattedNaiveDateTime, Message};
+
+/// `SBG_ECOM_SOL_ATTITUDE_VALID` in the raw EKF status word.
+pub const EKF_ATTITUDE_VALID: u32 = 1 << 4;
+
+/// A sensor message carrying `data`.
+pub fn sensor_message(data: impl Into<SensorData>) -> Message {
+    let timestamp = NaiveDate::from_ymd_opt(2001, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    Message::new(
+        FormattedNaiveDateTime(timestamp),
+        Node::TemperatureBoard,
+        Sensor::new(data),
+    )
+}
+
+/// An SBG EKF quaternion with the raw EKF status word `status`.
+pub fn ekf_quat_message(status: u32) -> Message {
+    sensor_message(SensorData::SbgData(SbgData::EkfQuat(EkfQuat {
+        time_stamp: 0,
+        quaternion: Some([1.0, 0.0, 0.0, 0.0]),
+        euler_std_dev: None,
+        status: EkfStatus::new(status),
+    })))
+}
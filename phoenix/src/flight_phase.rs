@@ -0,0 +1,295 @@
+//! Flight phase tracking from the launch detector, the vertical velocity and the acceleration.
+//!
+//! The phases only move forward, from `Idle` on the pad to `Landed`. Every transition has to hold
+//! for a while before it is taken, and any sample that breaks the condition restarts the wait, so
+//! noise around a threshold doesn't flip the phase back and forth. This matters most at apogee,
+//! where the velocity hovers around zero and the baro noise is of the same size.
+//!
+//! The launch itself is left to [`crate::LaunchDetector`], which already debounces the
+//! acceleration and confirms it with the altitude, so `Idle` moves to `Boost` as soon as it
+//! reports a launch. The acceleration is the magnitude measured by the IMU, so it reads about 1 g
+//! on the pad and close to zero once the motor burns out. Apogee can also be detected from the
+//! velocity alone, so losing the IMU doesn't hold the tracker in `Boost`.
+
+use defmt::info;
+use serde::{Deserialize, Serialize};
+
+/// Acceleration magnitude below which the motor has burnt out, in m/s² (about 1 g).
+pub const BURNOUT_ACCEL: f32 = 10.0;
+pub const BURNOUT_HOLD_MS: u64 = 200;
+/// Descent rate that confirms the velocity has changed sign at apogee, in m/s. Any climb above
+/// `-APOGEE_VELOCITY_MARGIN` during the hold restarts it.
+pub const APOGEE_VELOCITY_MARGIN: f32 = 1.0;
+pub const APOGEE_HOLD_MS: u64 = 500;
+/// Descent rate that confirms the vehicle is coming down after apogee, in m/s.
+pub const DESCENT_VELOCITY: f32 = 5.0;
+pub const DESCENT_HOLD_MS: u64 = 500;
+/// Speed below which the vehicle is at rest on the ground, in m/s, and how long it must stay
+/// there.
+pub const LANDED_VELOCITY: f32 = 2.0;
+pub const LANDED_HOLD_MS: u64 = 5000;
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format, Serialize, Deserialize)]
+pub enum FlightPhase {
+    /// On the pad, waiting for launch.
+    Idle,
+    /// Motor burning.
+    Boost,
+    /// Motor burnt out, still climbing.
+    Coast,
+    /// The velocity changed sign, the vehicle is at the top of its trajectory.
+    Apogee,
+    Descent,
+    Landed,
+}
+
+#[derive(Clone)]
+pub struct FlightPhaseTracker {
+    phase: FlightPhase,
+    /// Transition whose condition holds and the time it started to, in ms.
+    pending: Option<(FlightPhase, u64)>,
+}
+
+impl FlightPhaseTracker {
+    pub fn new() -> Self {
+        Self {
+            phase: FlightPhase::Idle,
+            pending: None,
+        }
+    }
+
+    /// Feeds whether the launch detector has launched, the vertical velocity in m/s, positive up,
+    /// and the acceleration magnitude in m/s². Either of the last two may be `None` if the source
+    /// is stale. Returns the phase after the update.
+    pub fn update(
+        &mut self,
+        launched: bool,
+        vertical_velocity: Option<f32>,
+        accel_norm: Option<f32>,
+        now_ms: u64,
+    ) -> FlightPhase {
+        let descending = |margin: f32| vertical_velocity.is_some_and(|v| v < -margin);
+        let next = match self.phase {
+            FlightPhase::Idle => launched.then_some(FlightPhase::Boost),
+            // Apogee is also checked during boost in case the burnout was missed.
+            FlightPhase::Boost if descending(APOGEE_VELOCITY_MARGIN) => {
+                self.held(FlightPhase::Apogee, true, now_ms, APOGEE_HOLD_MS)
+            }
+            FlightPhase::Boost => {
+                let burnt_out = accel_norm.is_some_and(|a| a < BURNOUT_ACCEL);
+                self.held(FlightPhase::Coast, burnt_out, now_ms, BURNOUT_HOLD_MS)
+            }
+            FlightPhase::Coast => self.held(
+                FlightPhase::Apogee,
+                descending(APOGEE_VELOCITY_MARGIN),
+                now_ms,
+                APOGEE_HOLD_MS,
+            ),
+            FlightPhase::Apogee => self.held(
+                FlightPhase::Descent,
+                descending(DESCENT_VELOCITY),
+                now_ms,
+                DESCENT_HOLD_MS,
+            ),
+            FlightPhase::Descent => {
+                let at_rest = vertical_velocity.is_some_and(|v| v.abs() < LANDED_VELOCITY);
+                self.held(FlightPhase::Landed, at_rest, now_ms, LANDED_HOLD_MS)
+            }
+            FlightPhase::Landed => None,
+        };
+        if let Some(next) = next {
+            info!("Flight phase {} -> {}", self.phase, next);
+            self.phase = next;
+            self.pending = None;
+        }
+        self.phase
+    }
+
+    /// Returns `next` once `condition` has held for `hold_ms`. The wait restarts whenever the
+    /// condition doesn't hold or the pending transition changes.
+    fn held(
+        &mut self,
+        next: FlightPhase,
+        condition: bool,
+        now_ms: u64,
+        hold_ms: u64,
+    ) -> Option<FlightPhase> {
+        if !condition {
+            self.pending = None;
+            return None;
+        }
+        let since_ms = match self.pending {
+            Some((pending, since_ms)) if pending == next => since_ms,
+            _ => {
+                self.pending = Some((next, now_ms));
+                now_ms
+            }
+        };
+        (now_ms.saturating_sub(since_ms) >= hold_ms).then_some(next)
+    }
+
+    pub fn phase(&self) -> FlightPhase {
+        self.phase
+    }
+
+    /// Goes back to `Idle`, e.g. when a flight is aborted on the pad.
+    pub fn reset(&mut self) {
+        self.phase = FlightPhase::Idle;
+        self.pending = None;
+    }
+}
+
+impl Default for FlightPhaseTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BaroVelocityFilter, LaunchDetector};
+
+    const G: f32 = 9.81;
+    /// Synthetic flight: 1 s on the pad, 3 s of boost at 50 m/s², a drag-free coast, free fall until
+    /// the drogue holds the descent at 20 m/s, then rest on the ground.
+    const LAUNCH_S: f32 = 1.0;
+    const BURN_S: f32 = 3.0;
+    const BOOST_ACCEL: f32 = 50.0;
+    const DROGUE_RATE: f32 = 20.0;
+    /// Baro sample period, 20 Hz.
+    const PERIOD_MS: u64 = 50;
+
+    /// Altitude in m and measured acceleration magnitude in m/s² at `t` s.
+    fn profile(t: f32) -> (f32, f32) {
+        let burnout_v = BOOST_ACCEL * BURN_S;
+        let burnout_alt = 0.5 * BOOST_ACCEL * BURN_S * BURN_S;
+        let apogee_t = LAUNCH_S + BURN_S + burnout_v / G;
+        let apogee_alt = burnout_alt + burnout_v * burnout_v / (2.0 * G);
+        let drogue_t = apogee_t + DROGUE_RATE / G;
+        let drogue_alt = apogee_alt - DROGUE_RATE * DROGUE_RATE / (2.0 * G);
+        if t < LAUNCH_S {
+            (0.0, G)
+        } else if t < LAUNCH_S + BURN_S {
+            let dt = t - LAUNCH_S;
+            (0.5 * BOOST_ACCEL * dt * dt, BOOST_ACCEL + G)
+        } else if t < apogee_t {
+            let dt = t - LAUNCH_S - BURN_S;
+            (burnout_alt + burnout_v * dt - 0.5 * G * dt * dt, 0.0)
+        } else if t < drogue_t {
+            let dt = t - apogee_t;
+            (apogee_alt - 0.5 * G * dt * dt, 0.0)
+        } else {
+            let altitude = drogue_alt - DROGUE_RATE * (t - drogue_t);
+            if altitude > 0.0 {
+                (altitude, G)
+            } else {
+                (0.0, G)
+            }
+        }
+    }
+
+    #[test]
+    fn synthetic_flight_goes_through_every_phase() {
+        let mut tracker = FlightPhaseTracker::new();
+        let mut detector = LaunchDetector::default();
+        let mut filter = BaroVelocityFilter::new(0.5);
+        let apogee_ms = ((LAUNCH_S + BURN_S + BOOST_ACCEL * BURN_S / G) * 1000.0) as u64;
+
+        let mut phases = [FlightPhase::Idle; 6];
+        let mut changes = 0;
+        let mut apogee_detected_ms = None;
+        for i in 0..2400u64 {
+            let now_ms = i * PERIOD_MS;
+            let (altitude, accel) = profile(now_ms as f32 / 1000.0);
+            let velocity = filter.update(altitude, now_ms);
+            detector.update_accel([0.0, 0.0, accel], now_ms);
+            detector.update_altitude(altitude, now_ms);
+            let phase = tracker.update(detector.is_launched(), velocity, Some(accel), now_ms);
+            if phase != phases[changes] {
+                changes += 1;
+                phases[changes] = phase;
+                if phase == FlightPhase::Apogee {
+                    apogee_detected_ms = Some(now_ms);
+                }
+            }
+        }
+
+        assert!(
+            phases
+                == [
+                    FlightPhase::Idle,
+                    FlightPhase::Boost,
+                    FlightPhase::Coast,
+                    FlightPhase::Apogee,
+                    FlightPhase::Descent,
+                    FlightPhase::Landed,
+                ]
+        );
+        // Not before the true apogee, and late only by the filter lag and the hold.
+        let detected_ms = apogee_detected_ms.unwrap();
+        assert!(detected_ms > apogee_ms);
+        assert!(detected_ms < apogee_ms + 2000);
+    }
+
+    #[test]
+    fn noise_around_apogee_does_not_chatter() {
+        let mut tracker = FlightPhaseTracker::new();
+        tracker.update(true, None, None, 0);
+        let mut now_ms = 1000;
+        while tracker.phase() != FlightPhase::Coast {
+            tracker.update(true, Some(100.0), Some(0.0), now_ms);
+            now_ms += PERIOD_MS;
+        }
+
+        // The velocity dips below the margin for less than the hold time, again and again.
+        for i in 0..100u64 {
+            let velocity = if i % 4 == 3 {
+                0.0
+            } else {
+                -APOGEE_VELOCITY_MARGIN - 1.0
+            };
+            assert!(tracker.update(true, Some(velocity), Some(0.0), now_ms) == FlightPhase::Coast);
+            now_ms += PERIOD_MS;
+        }
+
+        let start_ms = now_ms;
+        while now_ms - start_ms < APOGEE_HOLD_MS {
+            tracker.update(true, Some(-APOGEE_VELOCITY_MARGIN - 1.0), Some(0.0), now_ms);
+            assert!(tracker.phase() == FlightPhase::Coast);
+            now_ms += PERIOD_MS;
+        }
+        assert!(tracker.update(true, Some(-5.0), Some(0.0), now_ms) == FlightPhase::Apogee);
+    }
+
+    #[test]
+    fn only_the_launch_detector_leaves_idle() {
+        let mut tracker = FlightPhaseTracker::new();
+        // Acceleration and climb the detector hasn't confirmed, e.g. a bump or a pressure
+        // transient on the pad.
+        for i in 0..20u64 {
+            tracker.update(false, Some(50.0), Some(6.0 * G), i * PERIOD_MS);
+        }
+        assert!(tracker.phase() == FlightPhase::Idle);
+        assert!(tracker.update(true, Some(0.0), Some(G), 1000) == FlightPhase::Boost);
+    }
+
+    #[test]
+    fn landed_is_final() {
+        let mut tracker = FlightPhaseTracker::new();
+        let mut now_ms = 0;
+        // Without the IMU, apogee comes from the velocity alone.
+        for velocity in [50.0, -10.0, -10.0, 0.0] {
+            let start_ms = now_ms;
+            while now_ms - start_ms <= LANDED_HOLD_MS {
+                tracker.update(true, Some(velocity), None, now_ms);
+                now_ms += PERIOD_MS;
+            }
+        }
+        assert!(tracker.phase() == FlightPhase::Landed);
+        assert!(tracker.update(true, Some(100.0), Some(6.0 * G), now_ms) == FlightPhase::Landed);
+
+        tracker.reset();
+        assert!(tracker.phase() == FlightPhase::Idle);
+    }
+}
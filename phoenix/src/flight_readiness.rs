@@ -0,0 +1,178 @@
+//! Single pre-launch "ready to fly" check over a configurable set of required sensors.
+//!
+//! A required sensor counts towards readiness while its last message arrived within the max age
+//! and its SBG status flags said the data is valid. An invalid report withdraws readiness right
+//! away instead of waiting for the sensor to go stale.
+
+use messages::sensor::{SbgData, SensorData};
+use messages::sensor_status::{AirFlags, EkfFlags, ImuFlags};
+use messages::{Data, Message};
+
+use crate::SensorKind;
+
+#[derive(Clone)]
+pub struct FlightReadiness {
+    /// Bitmask of the required sensor kinds, see [`SensorKind::mask`].
+    required: u32,
+    max_age_ms: u64,
+    /// Time of the last valid message of each kind, `None` until one arrives or after an invalid
+    /// one. Indexed by `SensorKind`.
+    last_valid_ms: [Option<u64>; SensorKind::COUNT],
+    /// Result of the last [`Self::update`].
+    ready: bool,
+}
+
+impl FlightReadiness {
+    pub fn new(max_age_ms: u64) -> Self {
+        Self {
+            required: 0,
+            max_age_ms,
+            last_valid_ms: [None; SensorKind::COUNT],
+            ready: false,
+        }
+    }
+
+    /// Sets the sensors that must be reporting valid data. With none, the vehicle is always ready.
+    pub fn set_required(&mut self, sensors: &[SensorKind]) {
+        self.required = sensors.iter().fold(0, |mask, kind| mask | kind.mask());
+    }
+
+    /// Sets how old a required sensor's last valid message may be while still counting as ready.
+    pub fn set_max_age(&mut self, max_age_ms: u64) {
+        self.max_age_ms = max_age_ms;
+    }
+
+    /// Records a message of `kind` and whether its status flags said it is valid, see
+    /// [`status_valid`].
+    pub fn record(&mut self, kind: SensorKind, valid: bool, now_ms: u64) {
+        self.last_valid_ms[kind as usize] = valid.then_some(now_ms);
+    }
+
+    /// Re-evaluates readiness and returns it. Call periodically so a sensor going quiet is
+    /// noticed.
+    pub fn update(&mut self, now_ms: u64) -> bool {
+        self.ready = SensorKind::ALL
+            .into_iter()
+            .filter(|kind| self.required & kind.mask() != 0)
+            .all(|kind| {
+                self.last_valid_ms[kind as usize]
+                    .is_some_and(|valid_ms| now_ms.saturating_sub(valid_ms) <= self.max_age_ms)
+            });
+        self.ready
+    }
+
+    /// Readiness as of the last call to [`Self::update`].
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+}
+
+/// Returns false if the SBG status flags of `message` mark its data invalid: an IMU with a
+/// communication error or a sensor out of range, an EKF without a valid attitude, or air data
+/// without a valid pressure. Messages without status flags are valid.
+pub fn status_valid(message: &Message) -> bool {
+    let Data::Sensor(sensor) = &message.data else {
+        return true;
+    };
+    let SensorData::SbgData(sbg_data) = &sensor.data else {
+        return true;
+    };
+    match sbg_data {
+        SbgData::Imu1(imu) => imu.status.get_flags().is_some_and(|flags| {
+            flags.contains(ImuFlags::ComOk | ImuFlags::AccelsInRange | ImuFlags::GyrosInRange)
+        }),
+        SbgData::EkfQuat(ekf) => ekf
+            .status
+            .get_flags()
+            .is_some_and(|flags| flags.contains(EkfFlags::AttitudeValid)),
+        SbgData::Air(air) => air
+            .status
+            .get_flags()
+            .is_some_and(|flags| flags.contains(AirFlags::PressureAbsValid)),
+        _ => true,
+    }
+}
+
+/// Returns true if `message` is an SBG IMU sample whose status flags say an accelerometer was out
+/// of range, i.e. saturated.
+pub fn accels_clipped(message: &Message) -> bool {
+    let Data::Sensor(sensor) = &message.data else {
+        return false;
+    };
+    match &sensor.data {
+        SensorData::SbgData(SbgData::Imu1(imu)) => imu
+            .status
+            .get_flags()
+            .is_some_and(|flags| !flags.contains(ImuFlags::AccelsInRange)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ekf_quat_message, EKF_ATTITUDE_VALID};
+
+    const MAX_AGE_MS: u64 = 1000;
+    const REQUIRED: [SensorKind; 3] = [SensorKind::Imu1, SensorKind::EkfQuat, SensorKind::Air];
+
+    fn readiness() -> FlightReadiness {
+        let mut readiness = FlightReadiness::new(MAX_AGE_MS);
+        readiness.set_required(&REQUIRED);
+        readiness
+    }
+
+    #[test]
+    fn ready_once_every_input_is_valid() {
+        let mut readiness = readiness();
+        assert!(!readiness.update(0));
+
+        readiness.record(SensorKind::Imu1, true, 10);
+        readiness.record(SensorKind::EkfQuat, true, 20);
+        assert!(!readiness.update(30));
+        // Air data arrives but its pressure isn't valid yet.
+        readiness.record(SensorKind::Air, false, 40);
+        assert!(!readiness.update(50));
+
+        readiness.record(SensorKind::Air, true, 60);
+        assert!(readiness.update(70));
+        assert!(readiness.is_ready());
+    }
+
+    #[test]
+    fn invalid_report_withdraws_readiness() {
+        let mut readiness = readiness();
+        for kind in REQUIRED {
+            readiness.record(kind, true, 0);
+        }
+        assert!(readiness.update(0));
+        readiness.record(SensorKind::EkfQuat, false, 10);
+        assert!(!readiness.update(10));
+    }
+
+    #[test]
+    fn stale_sensor_withdraws_readiness() {
+        let mut readiness = readiness();
+        for kind in REQUIRED {
+            readiness.record(kind, true, 0);
+        }
+        assert!(readiness.update(MAX_AGE_MS));
+        readiness.record(SensorKind::Imu1, true, MAX_AGE_MS);
+        readiness.record(SensorKind::EkfQuat, true, MAX_AGE_MS);
+        assert!(!readiness.update(MAX_AGE_MS + 1));
+        readiness.record(SensorKind::Air, true, MAX_AGE_MS + 2);
+        assert!(readiness.update(MAX_AGE_MS + 2));
+    }
+
+    #[test]
+    fn ekf_needs_a_valid_attitude() {
+        assert!(!status_valid(&ekf_quat_message(0)));
+        assert!(status_valid(&ekf_quat_message(EKF_ATTITUDE_VALID)));
+    }
+
+    #[test]
+    fn nothing_required_is_always_ready() {
+        let mut readiness = FlightReadiness::new(MAX_AGE_MS);
+        assert!(readiness.update(0));
+    }
+}
@@ -0,0 +1,82 @@
+//! Commands from the ground that `messages::command::CommandData` has no variant for.
+//!
+//! The ground sends one as [`GROUND_COMMAND_MAGIC`] followed by the command byte, see
+//! [`GroundCommand::command`], either as a CAN command bus frame or as the payload of a radio
+//! COMMAND_MESSAGE. As for `common_arm::Verbosity`, receivers check
+//! [`GroundCommand::from_command`] before decoding a postcard message.
+
+/// Marks a command frame as a ground command rather than a postcard message.
+pub const GROUND_COMMAND_MAGIC: [u8; 2] = [0x7E, 0xC0];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum GroundCommand {
+    /// Send the whole DataManager state as one snapshot, see `DataManager::serialize_state`. The
+    /// reply is this command's bytes followed by the snapshot blob, in one radio message.
+    SendState,
+    /// Average a new baro ground reference, see `GroundReference`. Only on the pad, the reference
+    /// is locked from launch on.
+    CalibrateGroundReference,
+    /// Check the command bus wiring with an echo test frame. Only on the pad, the test frame
+    /// shares the bus with flight commands.
+    CanEchoTest,
+}
+
+impl GroundCommand {
+    /// Every command, indexed by its command byte.
+    const ALL: [GroundCommand; 3] = [
+        GroundCommand::SendState,
+        GroundCommand::CalibrateGroundReference,
+        GroundCommand::CanEchoTest,
+    ];
+
+    /// The command frame the ground sends for this command.
+    pub fn command(self) -> [u8; 3] {
+        [GROUND_COMMAND_MAGIC[0], GROUND_COMMAND_MAGIC[1], self as u8]
+    }
+
+    /// Command carried by a frame, `None` if the frame isn't a ground command. Bytes after the
+    /// command byte are ignored, radio command payloads are padded with zeros.
+    pub fn from_command(frame: &[u8]) -> Option<Self> {
+        match frame {
+            [m0, m1, command, ..] if [*m0, *m1] == GROUND_COMMAND_MAGIC => {
+                Self::ALL.get(*command as usize).copied()
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_round_trip() {
+        for command in GroundCommand::ALL {
+            assert_eq!(
+                GroundCommand::from_command(&command.command()),
+                Some(command)
+            );
+        }
+    }
+
+    #[test]
+    fn padded_radio_payload_is_accepted() {
+        let mut payload = [0u8; 32];
+        payload[..3].copy_from_slice(&GroundCommand::SendState.command());
+        assert_eq!(
+            GroundCommand::from_command(&payload),
+            Some(GroundCommand::SendState)
+        );
+    }
+
+    #[test]
+    fn other_frames_are_not_commands() {
+        assert_eq!(GroundCommand::from_command(&[0x7E, 0xB0, 0]), None);
+        assert_eq!(GroundCommand::from_command(&GROUND_COMMAND_MAGIC), None);
+        assert_eq!(
+            GroundCommand::from_command(&[GROUND_COMMAND_MAGIC[0], GROUND_COMMAND_MAGIC[1], 0xFF]),
+            None
+        );
+    }
+}
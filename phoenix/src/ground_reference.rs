@@ -0,0 +1,181 @@
+//! Averaged ground reference for barometric altitude.
+//!
+//! A single reading makes a noisy zero, so the reference is the mean of up to `N` samples taken
+//! while the vehicle is stationary. Samples further than [`OUTLIER_SIGMA`] standard deviations
+//! from the mean are dropped before the final mean is taken. The samples can be in any unit, the
+//! reference and its standard deviation are in the same unit.
+
+use defmt::{info, warn};
+use heapless::Vec;
+
+/// Samples further than this many standard deviations from the mean are rejected as outliers.
+pub const OUTLIER_SIGMA: f32 = 2.0;
+
+#[derive(Clone)]
+pub struct GroundReference<const N: usize> {
+    samples: Vec<f32, N>,
+    calibrating: bool,
+    /// Set once the vehicle has launched, recalibrating in flight would corrupt the zero.
+    locked: bool,
+    reference: Option<f32>,
+    std_dev: Option<f32>,
+}
+
+impl<const N: usize> GroundReference<N> {
+    pub fn new() -> Self {
+        Self {
+            samples: Vec::new(),
+            calibrating: false,
+            locked: false,
+            reference: None,
+            std_dev: None,
+        }
+    }
+
+    /// Starts collecting samples. Returns false, leaving the current reference in place, if the
+    /// reference is locked.
+    pub fn start(&mut self) -> bool {
+        if self.locked {
+            warn!("Ground reference is locked, not recalibrating");
+            return false;
+        }
+        self.samples.clear();
+        self.calibrating = true;
+        true
+    }
+
+    /// Adds a sample to a running calibration. Samples beyond the capacity of `N` are ignored.
+    pub fn add_sample(&mut self, sample: f32) {
+        if self.calibrating {
+            self.samples.push(sample).ok();
+        }
+    }
+
+    /// Ends the calibration and returns the new reference, or `None` if no samples were taken.
+    pub fn finish(&mut self) -> Option<f32> {
+        if !self.calibrating {
+            return None;
+        }
+        self.calibrating = false;
+        let (mean, std_dev) = mean_std_dev(self.samples.iter().copied())?;
+        let kept = self
+            .samples
+            .iter()
+            .copied()
+            .filter(|s| (s - mean).abs() <= OUTLIER_SIGMA * std_dev);
+        let (mean, std_dev) = mean_std_dev(kept).unwrap_or((mean, std_dev));
+        info!("Ground reference {} (std dev {})", mean, std_dev);
+        self.reference = Some(mean);
+        self.std_dev = Some(std_dev);
+        self.reference
+    }
+
+    pub fn reference(&self) -> Option<f32> {
+        self.reference
+    }
+
+    /// Standard deviation of the samples the reference was averaged from.
+    pub fn std_dev(&self) -> Option<f32> {
+        self.std_dev
+    }
+
+    /// Height in m above the reference of a reading of `pressure`, in the unit of the samples,
+    /// assuming the standard atmosphere. `None` until a reference is established. Only meaningful
+    /// if the samples are pressures.
+    pub fn height_above(&self, pressure: f32) -> Option<f32> {
+        let reference = self.reference?;
+        Some(44330.0 * (1.0 - libm::powf(pressure / reference, 1.0 / 5.255)))
+    }
+
+    pub fn is_calibrating(&self) -> bool {
+        self.calibrating
+    }
+
+    /// Refuses any further calibration, e.g. once launch is detected. A running calibration is
+    /// abandoned.
+    pub fn lock(&mut self) {
+        self.locked = true;
+        self.calibrating = false;
+    }
+
+    pub fn unlock(&mut self) {
+        self.locked = false;
+    }
+}
+
+impl<const N: usize> Default for GroundReference<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn mean_std_dev(samples: impl Iterator<Item = f32> + Clone) -> Option<(f32, f32)> {
+    let count = samples.clone().count();
+    if count == 0 {
+        return None;
+    }
+    let mean = samples.clone().sum::<f32>() / count as f32;
+    let variance = samples.map(|s| (s - mean) * (s - mean)).sum::<f32>() / count as f32;
+    Some((mean, libm::sqrtf(variance)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pressure around 101.3 kPa with +-20 Pa of noise and one spike.
+    const NOISY_SAMPLES: [f32; 12] = [
+        101.31, 101.29, 101.30, 101.32, 101.28, 101.30, 101.31, 101.29, 101.30, 99.0, 101.30,
+        101.31,
+    ];
+
+    #[test]
+    fn noisy_samples_give_a_stable_reference() {
+        let mut reference: GroundReference<16> = GroundReference::new();
+        assert!(reference.start());
+        for sample in NOISY_SAMPLES {
+            reference.add_sample(sample);
+        }
+        let zero = reference.finish().unwrap();
+        // The spike is rejected, so the reference sits on the true value with a small spread.
+        assert!((zero - 101.30).abs() < 0.005);
+        assert!(reference.std_dev().unwrap() < 0.02);
+    }
+
+    #[test]
+    fn no_samples_keeps_the_previous_reference() {
+        let mut reference: GroundReference<16> = GroundReference::new();
+        reference.start();
+        reference.add_sample(101.3);
+        reference.finish();
+        reference.start();
+        assert!(reference.finish().is_none());
+        assert_eq!(reference.reference(), Some(101.3));
+    }
+
+    #[test]
+    fn height_is_relative_to_the_reference() {
+        let mut reference: GroundReference<16> = GroundReference::new();
+        assert!(reference.height_above(101.3).is_none());
+        reference.start();
+        reference.add_sample(101.3);
+        reference.finish();
+        assert_eq!(reference.height_above(101.3), Some(0.0));
+        // About 12 Pa per m near sea level.
+        let height = reference.height_above(100.1).unwrap();
+        assert!((height - 100.4).abs() < 0.5);
+    }
+
+    #[test]
+    fn locked_reference_refuses_recalibration() {
+        let mut reference: GroundReference<16> = GroundReference::new();
+        reference.start();
+        reference.add_sample(101.3);
+        reference.finish();
+        reference.lock();
+        assert!(!reference.start());
+        reference.add_sample(90.0);
+        assert!(reference.finish().is_none());
+        assert_eq!(reference.reference(), Some(101.3));
+    }
+}
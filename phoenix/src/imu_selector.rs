@@ -0,0 +1,267 @@
+//! Selection between a primary and an optional secondary IMU feeding one orientation filter.
+//!
+//! Every sample from either IMU is reported along with whether its accelerometer clipped, and the
+//! selector answers whether that sample should be fed to the filter. A usable, unclipped sample
+//! beats a clipped one, and a missing, non-finite or stale sample is never used. Ties go to the
+//! primary. Only the active IMU feeds the filter, so a switch carries the current orientation over
+//! instead of restarting it. Builds with a single IMU never report a secondary sample and always
+//! run on the primary.
+//!
+//! While both IMUs are fresh and usable their gyro rates are cross-checked, and a difference above
+//! the threshold is flagged as a disagreement. Gyro rates are in rad/s.
+
+use defmt::{info, warn};
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ImuSource {
+    Primary,
+    Secondary,
+}
+
+/// Quality of the latest sample of one IMU, ordered from worst to best.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ImuHealth {
+    Unusable,
+    Clipped,
+    Good,
+}
+
+#[derive(Clone, Copy)]
+struct ImuState {
+    health: ImuHealth,
+    gyro: [f32; 3],
+    at_ms: u64,
+}
+
+#[derive(Clone)]
+pub struct ImuSelector {
+    /// Age after which an IMU that stopped reporting is no longer used, in ms.
+    max_age_ms: u64,
+    /// Difference between the two gyro vectors above which the IMUs disagree, in rad/s.
+    threshold: f32,
+    primary: Option<ImuState>,
+    secondary: Option<ImuState>,
+    active: Option<ImuSource>,
+    /// Difference between the gyro vectors at the last cross-check, in rad/s.
+    disagreement: Option<f32>,
+    disagreeing: bool,
+}
+
+impl ImuSelector {
+    pub fn new(max_age_ms: u64, threshold: f32) -> Self {
+        Self {
+            max_age_ms,
+            threshold,
+            primary: None,
+            secondary: None,
+            active: None,
+            disagreement: None,
+            disagreeing: false,
+        }
+    }
+
+    /// Reports a sample from `source`. Returns true if the sample should be fed to the filter.
+    pub fn update(
+        &mut self,
+        source: ImuSource,
+        accel: Option<[f32; 3]>,
+        gyro: Option<[f32; 3]>,
+        clipped: bool,
+        now_ms: u64,
+    ) -> bool {
+        let health = match (accel, gyro) {
+            (Some(accel), Some(gyro)) if accel.iter().chain(gyro.iter()).all(|v| v.is_finite()) => {
+                if clipped {
+                    ImuHealth::Clipped
+                } else {
+                    ImuHealth::Good
+                }
+            }
+            _ => ImuHealth::Unusable,
+        };
+        let state = ImuState {
+            health,
+            gyro: gyro.unwrap_or([0.0; 3]),
+            at_ms: now_ms,
+        };
+        match source {
+            ImuSource::Primary => self.primary = Some(state),
+            ImuSource::Secondary => self.secondary = Some(state),
+        }
+
+        self.cross_check(now_ms);
+        self.select(now_ms);
+        self.active == Some(source)
+    }
+
+    /// Re-evaluates the active IMU without a new sample. Call periodically so an IMU that stops
+    /// reporting altogether is dropped.
+    pub fn poll(&mut self, now_ms: u64) -> Option<ImuSource> {
+        self.select(now_ms);
+        self.active
+    }
+
+    fn select(&mut self, now_ms: u64) {
+        let primary = self.health(self.primary, now_ms);
+        let secondary = self.health(self.secondary, now_ms);
+        let active = if primary == ImuHealth::Unusable && secondary == ImuHealth::Unusable {
+            None
+        } else if secondary > primary {
+            Some(ImuSource::Secondary)
+        } else {
+            Some(ImuSource::Primary)
+        };
+        if active != self.active {
+            match active {
+                Some(source) => info!("Orientation now running on the {} IMU", source),
+                None => warn!("No usable IMU, orientation filter paused"),
+            }
+            self.active = active;
+        }
+    }
+
+    fn cross_check(&mut self, now_ms: u64) {
+        let (Some(primary), Some(secondary)) = (self.primary, self.secondary) else {
+            return;
+        };
+        if self.health(Some(primary), now_ms) == ImuHealth::Unusable
+            || self.health(Some(secondary), now_ms) == ImuHealth::Unusable
+        {
+            return;
+        }
+        let difference = libm::sqrtf(
+            primary
+                .gyro
+                .iter()
+                .zip(secondary.gyro.iter())
+                .map(|(p, s)| (p - s) * (p - s))
+                .sum(),
+        );
+        self.disagreement = Some(difference);
+        let disagreeing = difference > self.threshold;
+        if disagreeing != self.disagreeing {
+            if disagreeing {
+                warn!("IMU gyro rates disagree by {} rad/s", difference);
+            } else {
+                info!("IMU gyro rates agree again");
+            }
+            self.disagreeing = disagreeing;
+        }
+    }
+
+    fn health(&self, state: Option<ImuState>, now_ms: u64) -> ImuHealth {
+        match state {
+            Some(state) if now_ms.saturating_sub(state.at_ms) <= self.max_age_ms => state.health,
+            _ => ImuHealth::Unusable,
+        }
+    }
+
+    /// IMU that feeds the filter, or `None` if neither is usable.
+    pub fn active(&self) -> Option<ImuSource> {
+        self.active
+    }
+
+    /// Difference between the gyro rates of the two IMUs at the last cross-check, in rad/s.
+    pub fn disagreement(&self) -> Option<f32> {
+        self.disagreement
+    }
+
+    /// Returns true while the last cross-check was above the threshold.
+    pub fn is_disagreeing(&self) -> bool {
+        self.disagreeing
+    }
+
+    pub fn set_max_age(&mut self, max_age_ms: u64) {
+        self.max_age_ms = max_age_ms;
+    }
+
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_AGE_MS: u64 = 50;
+    const THRESHOLD: f32 = 0.2;
+    const PERIOD_MS: u64 = 10;
+    const GRAVITY: [f32; 3] = [0.0, 0.0, 9.81];
+    const GYRO: [f32; 3] = [0.0, 0.0, 0.5];
+
+    #[test]
+    fn primary_is_used_when_both_are_healthy() {
+        let mut selector = ImuSelector::new(MAX_AGE_MS, THRESHOLD);
+        assert!(selector.update(ImuSource::Primary, Some(GRAVITY), Some(GYRO), false, 0));
+        assert!(!selector.update(ImuSource::Secondary, Some(GRAVITY), Some(GYRO), false, 0));
+        assert!(selector.active() == Some(ImuSource::Primary));
+        assert!(!selector.is_disagreeing());
+    }
+
+    #[test]
+    fn secondary_takes_over_without_a_gap_when_the_primary_goes_invalid() {
+        let mut selector = ImuSelector::new(MAX_AGE_MS, THRESHOLD);
+        let mut yaw = 0.0;
+        for tick in 0..100u64 {
+            let now = tick * PERIOD_MS;
+            let primary = if tick < 50 {
+                Some(GYRO)
+            } else {
+                Some([f32::NAN, 0.0, 0.0])
+            };
+            let mut fed = 0;
+            for (source, gyro) in [
+                (ImuSource::Primary, primary),
+                (ImuSource::Secondary, Some(GYRO)),
+            ] {
+                if selector.update(source, Some(GRAVITY), gyro, false, now) {
+                    yaw += gyro.unwrap()[2] * PERIOD_MS as f32 / 1000.0;
+                    fed += 1;
+                }
+            }
+            // Exactly one sample per period reaches the filter, before and after the switch.
+            assert_eq!(fed, 1);
+        }
+        assert!(selector.active() == Some(ImuSource::Secondary));
+        assert!((yaw - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn clipped_primary_falls_back_and_recovers() {
+        let mut selector = ImuSelector::new(MAX_AGE_MS, THRESHOLD);
+        selector.update(ImuSource::Primary, Some(GRAVITY), Some(GYRO), true, 0);
+        assert!(selector.update(ImuSource::Secondary, Some(GRAVITY), Some(GYRO), false, 0));
+        assert!(selector.active() == Some(ImuSource::Secondary));
+
+        assert!(selector.update(ImuSource::Primary, Some(GRAVITY), Some(GYRO), false, 10));
+        assert!(selector.active() == Some(ImuSource::Primary));
+    }
+
+    #[test]
+    fn silent_primary_is_dropped_after_max_age() {
+        let mut selector = ImuSelector::new(MAX_AGE_MS, THRESHOLD);
+        selector.update(ImuSource::Primary, Some(GRAVITY), Some(GYRO), false, 0);
+        assert!(!selector.update(ImuSource::Secondary, Some(GRAVITY), Some(GYRO), false, 40));
+        assert!(selector.update(ImuSource::Secondary, Some(GRAVITY), Some(GYRO), false, 60));
+        assert!(selector.poll(200).is_none());
+    }
+
+    #[test]
+    fn diverging_gyro_rates_are_flagged() {
+        let mut selector = ImuSelector::new(MAX_AGE_MS, THRESHOLD);
+        selector.update(ImuSource::Primary, Some(GRAVITY), Some(GYRO), false, 0);
+        selector.update(
+            ImuSource::Secondary,
+            Some(GRAVITY),
+            Some([0.0, 0.0, 1.0]),
+            false,
+            0,
+        );
+        assert!(selector.is_disagreeing());
+        assert!((selector.disagreement().unwrap() - 0.5).abs() < 1e-6);
+
+        selector.update(ImuSource::Secondary, Some(GRAVITY), Some(GYRO), false, 10);
+        assert!(!selector.is_disagreeing());
+    }
+}
@@ -0,0 +1,118 @@
+//! Timer that powers down non-essential peripherals some time after landing.
+//!
+//! Keeping everything powered while the crew walks out to the vehicle drains the battery, so once
+//! a landing is confirmed and the logs are flushed the board drops to a locator mode. Landing is
+//! only accepted after a flight has been seen, so a landing false positive on the pad can't cut
+//! power before launch.
+
+use defmt::{info, warn};
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ShutdownPhase {
+    Ground,
+    Flight,
+    Landed { at_ms: u64 },
+    Shutdown,
+}
+
+#[derive(Clone)]
+pub struct LandingShutdown {
+    /// Time between the landing and the shutdown, in ms.
+    timeout_ms: u64,
+    phase: ShutdownPhase,
+}
+
+impl LandingShutdown {
+    pub fn new(timeout_ms: u64) -> Self {
+        Self {
+            timeout_ms,
+            phase: ShutdownPhase::Ground,
+        }
+    }
+
+    /// Records that the vehicle has launched.
+    pub fn launched(&mut self) {
+        if self.phase == ShutdownPhase::Ground {
+            self.phase = ShutdownPhase::Flight;
+        }
+    }
+
+    /// Records a confirmed landing, starting the timer. Ignored unless a flight was seen.
+    pub fn landed(&mut self, now_ms: u64) {
+        match self.phase {
+            ShutdownPhase::Flight => {
+                info!("Landed, shutting down in {} ms", self.timeout_ms);
+                self.phase = ShutdownPhase::Landed { at_ms: now_ms };
+            }
+            ShutdownPhase::Ground => warn!("Landing reported without a flight, ignoring"),
+            _ => {}
+        }
+    }
+
+    /// Returns true once the timer has run out, until the shutdown happens. Flush the logs then
+    /// and pass the outcome to [`Self::poll`].
+    pub fn is_due(&self, now_ms: u64) -> bool {
+        match self.phase {
+            ShutdownPhase::Landed { at_ms } => now_ms.saturating_sub(at_ms) >= self.timeout_ms,
+            _ => false,
+        }
+    }
+
+    /// Returns true exactly once, when the timer has run out and the logs are flushed.
+    pub fn poll(&mut self, now_ms: u64, logs_flushed: bool) -> bool {
+        if !self.is_due(now_ms) || !logs_flushed {
+            return false;
+        }
+        self.phase = ShutdownPhase::Shutdown;
+        true
+    }
+
+    pub fn phase(&self) -> ShutdownPhase {
+        self.phase
+    }
+
+    pub fn set_timeout(&mut self, timeout_ms: u64) {
+        self.timeout_ms = timeout_ms;
+    }
+
+    pub fn get_timeout(&self) -> u64 {
+        self.timeout_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT_MS: u64 = 60_000;
+
+    #[test]
+    fn boot_flight_land_timeout_shuts_down_once() {
+        let mut shutdown = LandingShutdown::new(TIMEOUT_MS);
+        assert!(shutdown.phase() == ShutdownPhase::Ground);
+
+        shutdown.launched();
+        assert!(shutdown.phase() == ShutdownPhase::Flight);
+        assert!(!shutdown.poll(100_000, true));
+
+        shutdown.landed(200_000);
+        assert!(!shutdown.is_due(200_000 + TIMEOUT_MS - 1));
+        assert!(!shutdown.poll(200_000 + TIMEOUT_MS - 1, true));
+        // Timer expired, but the logs are still being written.
+        assert!(shutdown.is_due(200_000 + TIMEOUT_MS));
+        assert!(!shutdown.poll(200_000 + TIMEOUT_MS, false));
+        assert!(shutdown.is_due(200_000 + TIMEOUT_MS + 10));
+        assert!(shutdown.poll(200_000 + TIMEOUT_MS + 10, true));
+        assert!(shutdown.phase() == ShutdownPhase::Shutdown);
+        assert!(!shutdown.is_due(400_000));
+        assert!(!shutdown.poll(400_000, true));
+    }
+
+    #[test]
+    fn landing_without_a_flight_is_ignored() {
+        let mut shutdown = LandingShutdown::new(TIMEOUT_MS);
+        shutdown.landed(0);
+        assert!(shutdown.phase() == ShutdownPhase::Ground);
+        assert!(!shutdown.poll(10 * TIMEOUT_MS, true));
+    }
+}
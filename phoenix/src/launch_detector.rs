@@ -0,0 +1,313 @@
+//! Launch detection combining the inertial and barometric cues.
+//!
+//! The accelerometer magnitude is smoothed with a first-order low-pass filter. Once the filtered
+//! value stays above the threshold for `required_samples` samples in a row the launch is pending,
+//! so a knock on the pad that only lasts a sample or two never gets that far. The launch is
+//! committed once the altitude has risen by at least `min_altitude_gain` within
+//! `confirm_window_ms` of the trigger. A bump or handling on the pad produces acceleration but no
+//! altitude change, so the detector falls back to the pad state when the window runs out.
+//!
+//! A climb seen by the baro alone never launches, a pressure transient on the pad would do that
+//! too. Once launched the detector stays launched until a reset.
+
+use defmt::{info, warn};
+
+/// Standard gravity, in m/s².
+pub const STANDARD_GRAVITY: f32 = 9.80665;
+/// Filtered acceleration magnitude that triggers a pending launch, in g.
+pub const DEFAULT_LAUNCH_THRESHOLD_G: f32 = 3.0;
+/// Consecutive samples above the threshold needed to trigger, 50 ms at the 100 Hz IMU rate.
+pub const DEFAULT_LAUNCH_SAMPLES: u16 = 5;
+/// Weight of each new sample in the low-pass filter.
+pub const LAUNCH_FILTER_ALPHA: f32 = 0.5;
+/// Altitude gain that confirms a launch, in m.
+pub const DEFAULT_MIN_ALTITUDE_GAIN: f32 = 10.0;
+/// Time allowed for the altitude gain after the trigger. The baro is only read once a second on
+/// the pad, so this covers at least two readings.
+pub const DEFAULT_CONFIRM_WINDOW_MS: u64 = 2000;
+
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+pub enum LaunchState {
+    Pad,
+    /// Acceleration triggered at `triggered_at_ms`, waiting for the altitude to confirm.
+    Pending {
+        triggered_at_ms: u64,
+        start_altitude: Option<f32>,
+    },
+    Launched,
+}
+
+#[derive(Clone)]
+pub struct LaunchDetector {
+    /// Filtered acceleration magnitude that triggers a pending launch, in m/s².
+    accel_threshold: f32,
+    required_samples: u16,
+    /// Altitude gain required to confirm the launch, in m.
+    min_altitude_gain: f32,
+    /// Time allowed for the altitude gain after the trigger, in ms.
+    confirm_window_ms: u64,
+    state: LaunchState,
+    /// Filtered acceleration magnitude, in m/s².
+    filtered: Option<f32>,
+    /// Consecutive samples above the threshold so far.
+    count: u16,
+    /// Latest altitude in m.
+    altitude: Option<f32>,
+}
+
+impl LaunchDetector {
+    pub fn new(
+        threshold_g: f32,
+        required_samples: u16,
+        min_altitude_gain: f32,
+        confirm_window_ms: u64,
+    ) -> Self {
+        Self {
+            accel_threshold: threshold_g * STANDARD_GRAVITY,
+            required_samples,
+            min_altitude_gain,
+            confirm_window_ms,
+            state: LaunchState::Pad,
+            filtered: None,
+            count: 0,
+            altitude: None,
+        }
+    }
+
+    /// Feeds an accelerometer sample in m/s². Samples that aren't finite are ignored.
+    pub fn update_accel(&mut self, accel: [f32; 3], now_ms: u64) {
+        self.check_window(now_ms);
+        let magnitude = libm::sqrtf(accel.iter().map(|a| a * a).sum());
+        if self.state == LaunchState::Launched || !magnitude.is_finite() {
+            return;
+        }
+        let filtered = match self.filtered {
+            Some(filtered) => filtered + LAUNCH_FILTER_ALPHA * (magnitude - filtered),
+            None => magnitude,
+        };
+        self.filtered = Some(filtered);
+
+        if filtered > self.accel_threshold {
+            self.count = self.count.saturating_add(1);
+        } else {
+            self.count = 0;
+        }
+        if self.state == LaunchState::Pad && self.count >= self.required_samples {
+            info!(
+                "Launch acceleration of {} m/s^2 detected, waiting for altitude gain",
+                filtered
+            );
+            self.state = LaunchState::Pending {
+                triggered_at_ms: now_ms,
+                start_altitude: self.altitude,
+            };
+        }
+    }
+
+    /// Feeds an altitude sample in m.
+    pub fn update_altitude(&mut self, altitude: f32, now_ms: u64) {
+        self.altitude = Some(altitude);
+        self.check_window(now_ms);
+        if let LaunchState::Pending {
+            triggered_at_ms,
+            start_altitude,
+        } = self.state
+        {
+            match start_altitude {
+                // No altitude was known at the trigger, so this is the baseline.
+                None => {
+                    self.state = LaunchState::Pending {
+                        triggered_at_ms,
+                        start_altitude: Some(altitude),
+                    }
+                }
+                Some(start) if altitude - start >= self.min_altitude_gain => {
+                    info!("Launch confirmed by {} m altitude gain", altitude - start);
+                    self.state = LaunchState::Launched;
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Reverts a pending launch to the pad state once the confirmation window has run out.
+    fn check_window(&mut self, now_ms: u64) {
+        if let LaunchState::Pending {
+            triggered_at_ms, ..
+        } = self.state
+        {
+            if now_ms.saturating_sub(triggered_at_ms) > self.confirm_window_ms {
+                warn!("No altitude gain after launch acceleration, back to pad");
+                self.state = LaunchState::Pad;
+                self.count = 0;
+            }
+        }
+    }
+
+    pub fn state(&self) -> LaunchState {
+        self.state
+    }
+
+    pub fn is_launched(&self) -> bool {
+        self.state == LaunchState::Launched
+    }
+
+    /// Filtered acceleration magnitude in m/s², `None` before the first sample.
+    pub fn filtered(&self) -> Option<f32> {
+        self.filtered
+    }
+
+    /// Returns to the pad state and forgets the filter, e.g. when re-arming after a scrubbed
+    /// launch.
+    pub fn reset(&mut self) {
+        self.state = LaunchState::Pad;
+        self.filtered = None;
+        self.count = 0;
+    }
+
+    pub fn set_accel_threshold(&mut self, threshold_g: f32) {
+        self.accel_threshold = threshold_g * STANDARD_GRAVITY;
+    }
+
+    pub fn set_required_samples(&mut self, required_samples: u16) {
+        self.required_samples = required_samples;
+    }
+
+    pub fn set_min_altitude_gain(&mut self, min_altitude_gain: f32) {
+        self.min_altitude_gain = min_altitude_gain;
+    }
+
+    pub fn get_min_altitude_gain(&self) -> f32 {
+        self.min_altitude_gain
+    }
+
+    pub fn set_confirm_window(&mut self, confirm_window_ms: u64) {
+        self.confirm_window_ms = confirm_window_ms;
+    }
+
+    pub fn get_confirm_window(&self) -> u64 {
+        self.confirm_window_ms
+    }
+}
+
+impl Default for LaunchDetector {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_LAUNCH_THRESHOLD_G,
+            DEFAULT_LAUNCH_SAMPLES,
+            DEFAULT_MIN_ALTITUDE_GAIN,
+            DEFAULT_CONFIRM_WINDOW_MS,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN_ALTITUDE_GAIN: f32 = 10.0;
+    const CONFIRM_WINDOW_MS: u64 = 1000;
+    /// IMU sample period, 100 Hz.
+    const PERIOD_MS: u64 = 10;
+
+    /// Acceleration along the vehicle axis, in g.
+    fn axial(g: f32) -> [f32; 3] {
+        [0.0, 0.0, g * STANDARD_GRAVITY]
+    }
+
+    fn detector() -> LaunchDetector {
+        let mut detector = LaunchDetector::new(
+            DEFAULT_LAUNCH_THRESHOLD_G,
+            DEFAULT_LAUNCH_SAMPLES,
+            MIN_ALTITUDE_GAIN,
+            CONFIRM_WINDOW_MS,
+        );
+        detector.update_altitude(100.0, 0);
+        detector
+    }
+
+    /// Feeds `samples` IMU samples of `g` starting at `now_ms` and returns the time after them.
+    fn thrust(detector: &mut LaunchDetector, g: f32, samples: u16, mut now_ms: u64) -> u64 {
+        for _ in 0..samples {
+            detector.update_accel(axial(g), now_ms);
+            now_ms += PERIOD_MS;
+        }
+        now_ms
+    }
+
+    #[test]
+    fn bump_without_altitude_gain_is_rejected() {
+        let mut detector = detector();
+        thrust(&mut detector, 8.0, 20, 100);
+        assert!(matches!(detector.state(), LaunchState::Pending { .. }));
+        detector.update_altitude(100.5, 500);
+        detector.update_altitude(100.2, 1000);
+        assert!(!detector.is_launched());
+        detector.update_altitude(100.0, 1400);
+        assert!(detector.state() == LaunchState::Pad);
+    }
+
+    #[test]
+    fn acceleration_with_altitude_gain_is_confirmed() {
+        let mut detector = detector();
+        thrust(&mut detector, 8.0, 20, 100);
+        detector.update_altitude(104.0, 300);
+        assert!(!detector.is_launched());
+        detector.update_altitude(112.0, 600);
+        assert!(detector.is_launched());
+
+        // Latched through the burnout.
+        thrust(&mut detector, 0.0, 20, 700);
+        assert!(detector.is_launched());
+    }
+
+    #[test]
+    fn altitude_gain_alone_does_not_launch() {
+        let mut detector = detector();
+        thrust(&mut detector, 1.0, 100, 100);
+        detector.update_altitude(150.0, 1200);
+        assert!(detector.state() == LaunchState::Pad);
+    }
+
+    #[test]
+    fn sustained_thrust_triggers_after_the_sample_count() {
+        let mut detector = detector();
+        let mut now_ms = thrust(&mut detector, 1.0, 100, 0);
+        let mut samples = 0;
+        while detector.state() == LaunchState::Pad && samples < 20 {
+            now_ms = thrust(&mut detector, 8.0, 1, now_ms);
+            samples += 1;
+        }
+        assert_eq!(samples, DEFAULT_LAUNCH_SAMPLES);
+    }
+
+    #[test]
+    fn short_knock_does_not_trigger() {
+        let mut detector = detector();
+        let mut now_ms = 0;
+        for i in 0..200u16 {
+            // A 10 g knock lasting one sample, every second.
+            let g = if i % 100 == 0 { 10.0 } else { 1.0 };
+            now_ms = thrust(&mut detector, g, 1, now_ms);
+            assert!(detector.state() == LaunchState::Pad);
+        }
+    }
+
+    #[test]
+    fn threshold_and_count_are_configurable() {
+        let mut detector = detector();
+        detector.set_accel_threshold(1.5);
+        detector.set_required_samples(2);
+        let now_ms = thrust(&mut detector, 1.0, 1, 0);
+        let now_ms = thrust(&mut detector, 3.0, 1, now_ms);
+        assert!(detector.state() == LaunchState::Pad);
+        thrust(&mut detector, 3.0, 1, now_ms);
+        assert!(matches!(detector.state(), LaunchState::Pending { .. }));
+
+        detector.reset();
+        assert!(detector.state() == LaunchState::Pad);
+        assert_eq!(detector.filtered(), None);
+        detector.update_accel([f32::NAN; 3], now_ms);
+        assert_eq!(detector.filtered(), None);
+    }
+}
@@ -0,0 +1,65 @@
+#![no_std]
+
+//!
+//! Flight logic of the phoenix flight computer: launch, flight phase and landing detection, and the
+//! checks on its sensors. Nothing in here touches the hardware, so the unit tests next to each
+//! module run on the host.
+//!
+
+mod ballistic_detector;
+mod baro_filter;
+mod baro_rate;
+mod can_filter;
+mod can_frame;
+mod drop_queue;
+mod ekf_solution_mode;
+#[cfg(test)]
+mod fixtures;
+mod flight_phase;
+mod flight_readiness;
+mod ground_command;
+mod ground_reference;
+mod imu_selector;
+mod landing_shutdown;
+mod launch_detector;
+mod mav_frame;
+mod orientation_fallback;
+mod orientation_monitor;
+mod recovery_deployment;
+mod sensor_kind;
+mod sensor_vote;
+mod source_presence;
+mod spin_inhibit;
+
+pub use crate::ballistic_detector::BallisticDetector;
+pub use crate::baro_filter::BaroVelocityFilter;
+pub use crate::baro_rate::{
+    BaroRate, BARO_APOGEE_INTERVAL_MS, BARO_FLIGHT_INTERVAL_MS, BARO_GROUND_INTERVAL_MS,
+};
+pub use crate::can_filter::{
+    filter_elements, FilterElement, RxFifo, CAN_MAX_FILTER_IDS, CAN_STANDARD_FILTER_SLOTS,
+};
+pub use crate::can_frame::{can_fd_len_at_most, can_frame_len, CAN_FD_MAX_PAYLOAD};
+pub use crate::drop_queue::{DropQueue, FullPolicy};
+pub use crate::ekf_solution_mode::EkfSolutionMode;
+pub use crate::flight_phase::{
+    FlightPhase, FlightPhaseTracker, APOGEE_HOLD_MS, APOGEE_VELOCITY_MARGIN, BURNOUT_ACCEL,
+    BURNOUT_HOLD_MS, DESCENT_HOLD_MS, DESCENT_VELOCITY, LANDED_HOLD_MS, LANDED_VELOCITY,
+};
+pub use crate::flight_readiness::{accels_clipped, status_valid, FlightReadiness};
+pub use crate::ground_command::{GroundCommand, GROUND_COMMAND_MAGIC};
+pub use crate::ground_reference::GroundReference;
+pub use crate::imu_selector::{ImuSelector, ImuSource};
+pub use crate::landing_shutdown::{LandingShutdown, ShutdownPhase};
+pub use crate::launch_detector::{
+    LaunchDetector, LaunchState, DEFAULT_CONFIRM_WINDOW_MS, DEFAULT_LAUNCH_SAMPLES,
+    DEFAULT_LAUNCH_THRESHOLD_G, DEFAULT_MIN_ALTITUDE_GAIN, LAUNCH_FILTER_ALPHA, STANDARD_GRAVITY,
+};
+pub use crate::mav_frame::{mav_checksum, FrameParser, MavFrame, MAV_MAX_FRAME_LEN, MAV_STX_V2};
+pub use crate::orientation_fallback::{OrientationFallback, OrientationSource};
+pub use crate::orientation_monitor::OrientationMonitor;
+pub use crate::recovery_deployment::RecoveryDeployment;
+pub use crate::sensor_kind::SensorKind;
+pub use crate::sensor_vote::{SensorVote, Vote};
+pub use crate::source_presence::SourcePresence;
+pub use crate::spin_inhibit::SpinInhibit;
@@ -1,23 +1,72 @@
-use madgwick::Marg;
 use messages::{Message, sensor::{self, SbgData, EkfQuat}};
 use messages::sensor::Sensor;
 use messages::sensor_status::EkfStatus;
+use defmt::warn;
+use madgwick_filter::{AdaptiveGain, MadgwickFilter};
+use phoenix::{ImuSelector, ImuSource, STANDARD_GRAVITY};
+
+/// Unit of the gyroscope rates in incoming IMU messages; the filter itself always works in rad/s
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum GyroUnit {
+    RadPerSec,
+    DegPerSec,
+}
+
+impl GyroUnit {
+    /// Converts a rate in this unit to rad/s
+    pub fn to_rad_per_sec(self, rate: f32) -> f32 {
+        match self {
+            GyroUnit::RadPerSec => rate,
+            GyroUnit::DegPerSec => rate * (core::f32::consts::PI / 180.0),
+        }
+    }
+}
+
+/// Reasons a set of gyro samples can't be used to calibrate the bias
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+pub enum GyroCalibrationError {
+    NoSamples,
+    Moving { variance: f32 }, // largest per-axis variance in (rad/s)^2, above the allowed maximum
+}
 
 /// Service that implements the Madgwick sensor fusion algorithim for orientation
 /// This service processes IMU data (accelerometer and gyroscope)
 pub struct MadgwickService {
-    madgwick: Marg,
+    madgwick: MadgwickFilter,
     // Store the latest quaternion
     latest_quat: (f32, f32, f32, f32),
+    // Latest accelerometer sample in m/s^2 with gravity removed, in the body frame
+    linear_accel: (f32, f32, f32),
     // Store configuration parameters
     beta: f32, // 'beta' is the filter gain parameter that determines how much the accelerometer influences the orientation estimation; the higher the value, the more weight the accelerometer data has
     sample_period: f32, // 'sample_period' is the time in seconds between sensor readings; it is reciprocal of the sensor sampling frequency
+    last_time_stamp: Option<(ImuSource, u32)>, // time stamp in us of the previous sample the filter used, and the IMU it came from
+    accel_range: f32, // full scale range of the accelerometer in m/s^2, samples at or beyond it are treated as clipped
+    accel_clipped: bool, // latched once any clipped sample is seen, e.g. during boost
+    gyro_unit: GyroUnit, // unit the SBG is configured to output gyro rates in
+    output_decimation: u16, // an orientation message is emitted every 'output_decimation' filter updates
+    updates_since_output: u16,
+    imu_selector: ImuSelector, // picks which IMU feeds the filter and cross-checks the two
+    gyro_bias: [f32; 3], // rad/s subtracted from the primary IMU rates, see 'calibrate_gyro_bias'
+    gyro_bias_max_variance: f32, // calibration samples varying more than this in (rad/s)^2 mean the board was moving
+    latest_mag: Option<([f32; 3], u64)>, // latest magnetometer sample and when it arrived in ms
+    mag_max_age_ms: u64, // a magnetometer sample older than this is no longer used
+    adaptive_gain: Option<AdaptiveGain>, // when set, the gain follows the innovation instead of staying at 'beta'
+    innovation: f32, // averaged angle in rad between the measured and predicted gravity direction
 }
 
 impl MadgwickService {
     // Default values as constants will be used if parameters cannot be used
     const DEFAULT_BETA: f32 = 0.1;
     const DEFAULT_SAMPLE_PERIOD: f32 = 0.01; // 100Hz
+    // Bounds on the period measured from IMU time stamps, so a repeated sample or one after a dropout doesn't make a huge step
+    const MIN_SAMPLE_PERIOD: f32 = 0.001;
+    const MAX_SAMPLE_PERIOD: f32 = 0.05; // 5 missed samples at 100Hz
+    const DEFAULT_ACCEL_RANGE: f32 = 16.0 * STANDARD_GRAVITY; // +-16 g
+    const DEFAULT_IMU_MAX_AGE_MS: u64 = 50; // 5 missed samples at 100Hz
+    const DEFAULT_IMU_DISAGREEMENT: f32 = 0.2; // rad/s between the two gyros
+    const DEFAULT_GYRO_BIAS_MAX_VARIANCE: f32 = 1e-4; // (0.01 rad/s)^2, well above the noise of a gyro at rest
+    const DEFAULT_MAG_MAX_AGE_MS: u64 = 100; // magnetometers typically report at 10 to 100Hz
 
     /// Method for creating a new instance of 'MadgwickService' with default parameters 
     pub fn new() -> Self {
@@ -28,27 +77,40 @@ impl MadgwickService {
     /// New constructor that accepts parameters
     pub fn new_with_params(beta: f32, sample_period: f32) -> Self {
         // Create the filter with specified parameters
-        let mut madgwick = Marg::new(beta, sample_period);
+        let mut madgwick = MadgwickFilter::new(beta, sample_period);
         
         // Initialize with standard measurements
-        let accel = madgwick::F32x3 { x: 0.0, y: 0.0, z: 1.0 }; // "z: 1.0" represents the accelerometer pointing in the positive z-direction (upwards)
-        let gyro = madgwick::F32x3 { x: 0.0, y: 0.0, z: 0.0 };
-        let mag = madgwick::F32x3 { x: 1.0, y: 0.0, z: 0.0 }; // "x: 1.0" represents the magnetometer pointing in the positive x-direction 
+        let accel = [0.0, 0.0, 1.0]; // "z: 1.0" represents the accelerometer pointing in the positive z-direction (upwards)
+        let gyro = [0.0, 0.0, 0.0];
+        let mag = [1.0, 0.0, 0.0]; // "x: 1.0" represents the magnetometer pointing in the positive x-direction 
         
         // Get initial quaternion from filter
         let mut quat = (1.0, 0.0, 0.0, 0.0); // Default identity quaternion with no rotation
         
         // Apply multiple updates to ensure convergence, and stores the resulting quaternion after each update
         for _ in 0..5 {
-            let updated_quat = madgwick.update(mag, gyro, accel);
-            quat = (updated_quat.0, updated_quat.1, updated_quat.2, updated_quat.3);
+            quat = madgwick.update(gyro, accel, mag);
         }
         
         Self {
             madgwick,
             latest_quat: quat, // Use the quaternion from the filter
+            linear_accel: (0.0, 0.0, 0.0),
             beta,
             sample_period,
+            last_time_stamp: None,
+            accel_range: Self::DEFAULT_ACCEL_RANGE,
+            accel_clipped: false,
+            gyro_unit: GyroUnit::RadPerSec,
+            output_decimation: 1,
+            updates_since_output: 0,
+            imu_selector: ImuSelector::new(Self::DEFAULT_IMU_MAX_AGE_MS, Self::DEFAULT_IMU_DISAGREEMENT),
+            gyro_bias: [0.0; 3],
+            gyro_bias_max_variance: Self::DEFAULT_GYRO_BIAS_MAX_VARIANCE,
+            latest_mag: None,
+            mag_max_age_ms: Self::DEFAULT_MAG_MAX_AGE_MS,
+            adaptive_gain: None,
+            innovation: 0.0,
         }
     }
     
@@ -57,61 +119,49 @@ impl MadgwickService {
     fn initialize(&mut self) {
         // "z: 1.0" represents the accelerometer pointing in the positive z-direction (upwards)
         // If our data looks really off, we can try changing the z value to -1.0
-        let accel = madgwick::F32x3 { x: 0.0, y: 0.0, z: 1.0 }; 
-        let gyro = madgwick::F32x3 { x: 0.0, y: 0.0, z: 0.0 };
-        let mag = madgwick::F32x3 { x: 1.0, y: 0.0, z: 0.0 };
+        let accel = [0.0, 0.0, 1.0]; 
+        let gyro = [0.0, 0.0, 0.0];
+        let mag = [1.0, 0.0, 0.0];
         
         // Apply multiple updates to ensure convergence
         for _ in 0..5 {
-            let quat = self.madgwick.update(mag, gyro, accel);
-            self.latest_quat = (quat.0, quat.1, quat.2, quat.3);
+            self.latest_quat = self.madgwick.update(gyro, accel, mag);
         }
     }
     
     /// Method for processing incoming IMU data; returns a new Message with an updated quaternion from the filter
-    pub fn process_imu_data(&mut self, data: &Message) -> Option<Message> {
+    /// The filter is updated on every sample but a message is only returned every 'output_decimation' updates
+    /// Expects accelerations in m/s^2 and gyro rates in the configured 'gyro_unit' (rad/s unless set otherwise)
+    /// SBG IMU samples are the primary IMU input
+    pub fn process_imu_data(&mut self, data: &Message, now_ms: u64) -> Option<Message> {
         match &data.data {
             messages::Data::Sensor(sensor) => match &sensor.data {
                 messages::sensor::SensorData::SbgData(ref sbg_data) => match sbg_data {
                     SbgData::Imu1(imu_data) => {
-                        if let (Some(accel), Some(gyro)) = (imu_data.accelerometers, imu_data.gyroscopes) {
-                            let mag = madgwick::F32x3 { x: 0.0, y: 0.0, z: 0.0 };
-                            let gyro = madgwick::F32x3 {
-                                x: gyro[0],
-                                y: gyro[1],
-                                z: gyro[2],
-                            };
-                            
-                            let accel = madgwick::F32x3 {
-                                x: accel[0],
-                                y: accel[1],
-                                z: accel[2],
-                            };
-
-                            let quat = self.madgwick.update(mag, gyro, accel);
-                            
-                            // Store the latest quaternion
-                            self.latest_quat = (quat.0, quat.1, quat.2, quat.3);
-                            
-                            Some(Message::new(
-                                data.timestamp.clone(),
-                                data.node.clone(),
-                                Sensor::new(
-                                    sensor::SensorData::SbgData(
-                                        SbgData::EkfQuat(
-                                            EkfQuat {
-                                                time_stamp: imu_data.time_stamp,
-                                                quaternion: Some([quat.0, quat.1, quat.2, quat.3]),
-                                                euler_std_dev: None,
-                                                status: EkfStatus::new(0),
-                                            }
-                                        )
+                        let quat = self.process_imu_sample(
+                            ImuSource::Primary,
+                            imu_data.accelerometers,
+                            imu_data.gyroscopes,
+                            Some(imu_data.time_stamp),
+                            now_ms,
+                        )?;
+                        
+                        Some(Message::new(
+                            data.timestamp.clone(),
+                            data.node.clone(),
+                            Sensor::new(
+                                sensor::SensorData::SbgData(
+                                    SbgData::EkfQuat(
+                                        EkfQuat {
+                                            time_stamp: imu_data.time_stamp,
+                                            quaternion: Some([quat.0, quat.1, quat.2, quat.3]),
+                                            euler_std_dev: None, // Madgwick has no uncertainty estimate, the angles themselves come from 'get_euler'
+                                            status: EkfStatus::new(0),
+                                        }
                                     )
                                 )
-                            ))
-                        } else {
-                            None
-                        }
+                            )
+                        ))
                     },
                     _ => None,
                 },
@@ -121,27 +171,260 @@ impl MadgwickService {
         }
     }
 
+    /// Method for processing a sample from either IMU; a secondary IMU driver calls this directly with 'ImuSource::Secondary'
+    /// Only samples from the IMU the selector picks reach the filter, so a switch keeps the current orientation
+    /// Each update integrates over the time since the previous sample from 'time_stamp_us' (in us, e.g. the SBG time stamp), or over 'sample_period' without one
+    /// Returns the updated quaternion every 'output_decimation' filter updates
+    pub fn process_imu_sample(
+        &mut self,
+        source: ImuSource,
+        accel: Option<[f32; 3]>,
+        gyro: Option<[f32; 3]>,
+        time_stamp_us: Option<u32>,
+        now_ms: u64,
+    ) -> Option<(f32, f32, f32, f32)> {
+        let gyro_unit = self.gyro_unit;
+        let gyro = gyro.map(|gyro| gyro.map(|rate| gyro_unit.to_rad_per_sec(rate)));
+        // The bias is calibrated on the primary IMU, the secondary one has its own
+        let gyro_bias = self.gyro_bias;
+        let gyro = match source {
+            ImuSource::Primary => gyro.map(|gyro| [0, 1, 2].map(|i| gyro[i] - gyro_bias[i])),
+            ImuSource::Secondary => gyro,
+        };
+        let clipped = accel.map_or(false, |accel| self.is_clipped(&accel));
+        if !self.imu_selector.update(source, accel, gyro, clipped, now_ms) {
+            return None;
+        }
+        let (Some(accel), Some(gyro)) = (accel, gyro) else {
+            return None;
+        };
+
+        // Time stamps from the other IMU aren't comparable, so the first sample after a switch uses the nominal period
+        self.madgwick.set_sample_period(match (self.last_time_stamp, time_stamp_us) {
+            (Some((last_source, last_us)), Some(time_stamp_us)) if last_source == source => {
+                let dt = time_stamp_us.wrapping_sub(last_us) as f32 * 1e-6;
+                dt.clamp(Self::MIN_SAMPLE_PERIOD, Self::MAX_SAMPLE_PERIOD)
+            }
+            _ => self.sample_period,
+        });
+        self.last_time_stamp = time_stamp_us.map(|time_stamp_us| (source, time_stamp_us));
+
+        // A saturated accelerometer no longer points along gravity, so let the gyro carry the
+        // orientation by feeding the filter the gravity direction it already expects.
+        let filter_accel = if clipped {
+            if !self.accel_clipped {
+                warn!("Accelerometer clipped, range is {} m/s^2", self.accel_range);
+            }
+            self.accel_clipped = true;
+            self.expected_gravity()
+        } else {
+            // Clipped samples say nothing about convergence, so only valid ones adapt the gain
+            self.adapt_gain(&accel);
+            accel
+        };
+
+        // Without a recent magnetometer sample the filter runs on the accelerometer and gyro only, and the heading drifts
+        let mag = self.fresh_mag(now_ms).unwrap_or([0.0, 0.0, 0.0]);
+
+        // A spike or non-finite input can blow the quaternion up, which would then poison every later update
+        // Drop the update and carry on from the previous quaternion instead
+        let quat = self.madgwick.update(gyro, filter_accel, mag);
+        if ![quat.0, quat.1, quat.2, quat.3].iter().all(|q| q.is_finite()) {
+            warn!("Discarded a non-finite Madgwick update");
+            self.madgwick.set_quaternion(self.latest_quat);
+            return None;
+        }
+
+        // Store the latest quaternion
+        self.latest_quat = quat;
+
+        // Gravity according to the new orientation removed from the raw sample, clipped or not
+        let gravity = self.expected_gravity();
+        self.linear_accel = (
+            accel[0] - gravity[0] * STANDARD_GRAVITY,
+            accel[1] - gravity[1] * STANDARD_GRAVITY,
+            accel[2] - gravity[2] * STANDARD_GRAVITY,
+        );
+
+        // Only report every Nth update, the filter itself still runs at the full IMU rate
+        self.updates_since_output += 1;
+        if self.updates_since_output < self.output_decimation {
+            return None;
+        }
+        self.updates_since_output = 0;
+        
+        Some(self.latest_quat)
+    }
+
+    /// Updates the averaged innovation with a valid accelerometer sample and sets the gain it calls for
+    fn adapt_gain(&mut self, accel: &[f32; 3]) {
+        let Some(adaptive_gain) = self.adaptive_gain else {
+            return;
+        };
+        let Some(innovation) = self.madgwick.innovation(accel) else {
+            return;
+        };
+        self.innovation += adaptive_gain.smoothing * (innovation - self.innovation);
+        self.madgwick.set_beta(adaptive_gain.beta_for(self.innovation));
+    }
+
+    /// Method to calibrate the primary IMU gyro bias from samples taken while the board is stationary, e.g. on the pad
+    /// Samples are in the configured 'gyro_unit'; their average is subtracted from every following primary IMU sample
+    /// Returns an error and keeps the previous bias if any axis varies more than allowed, since the board was then moving
+    pub fn calibrate_gyro_bias(&mut self, samples: &[[f32; 3]]) -> Result<[f32; 3], GyroCalibrationError> {
+        if samples.is_empty() {
+            return Err(GyroCalibrationError::NoSamples);
+        }
+        let count = samples.len() as f32;
+        let gyro_unit = self.gyro_unit;
+        let mut mean = [0.0; 3];
+        for sample in samples {
+            for (mean, rate) in mean.iter_mut().zip(sample) {
+                *mean += gyro_unit.to_rad_per_sec(*rate) / count;
+            }
+        }
+        let mut variance = [0.0f32; 3];
+        for sample in samples {
+            for ((variance, mean), rate) in variance.iter_mut().zip(mean).zip(sample) {
+                let deviation = gyro_unit.to_rad_per_sec(*rate) - mean;
+                *variance += deviation * deviation / count;
+            }
+        }
+        let variance = variance.into_iter().fold(0.0, f32::max);
+        if variance.is_nan() || variance > self.gyro_bias_max_variance {
+            warn!("Gyro bias calibration rejected, variance of {} (rad/s)^2", variance);
+            return Err(GyroCalibrationError::Moving { variance });
+        }
+        self.gyro_bias = mean;
+        Ok(mean)
+    }
+
+    /// Method to get the gyro bias in rad/s subtracted from the primary IMU
+    pub fn get_gyro_bias(&self) -> [f32; 3] {
+        self.gyro_bias
+    }
+
+    /// Method to set the gyro bias in rad/s directly, e.g. from a previous calibration
+    pub fn set_gyro_bias(&mut self, gyro_bias: [f32; 3]) {
+        self.gyro_bias = gyro_bias;
+    }
+
+    /// Method to set the largest per-axis variance in (rad/s)^2 'calibrate_gyro_bias' accepts
+    pub fn set_gyro_bias_max_variance(&mut self, max_variance: f32) {
+        self.gyro_bias_max_variance = max_variance;
+    }
+
+    /// Method for processing a magnetometer sample; the magnetometer driver calls this directly since the SBG IMU messages carry no magnetometer data
+    /// The vector must be in the same body frame as the IMU accelerations and rates, with hard and soft iron offsets already removed; any unit works since only its direction is used
+    /// The heading is then relative to magnetic north; until the next IMU sample nothing is emitted
+    pub fn process_mag_sample(&mut self, mag: [f32; 3], now_ms: u64) {
+        if mag.iter().all(|m| m.is_finite()) {
+            self.latest_mag = Some((mag, now_ms));
+        }
+    }
+
+    /// Latest magnetometer sample if it is recent enough to use
+    fn fresh_mag(&self, now_ms: u64) -> Option<[f32; 3]> {
+        let (mag, at_ms) = self.latest_mag?;
+        (now_ms.saturating_sub(at_ms) <= self.mag_max_age_ms).then_some(mag)
+    }
+
+    /// Method to set how long a magnetometer sample is used for before the filter falls back to the accelerometer and gyro only
+    pub fn set_mag_max_age(&mut self, max_age_ms: u64) {
+        self.mag_max_age_ms = max_age_ms;
+    }
+
+    /// Method to know if the magnetometer currently constrains the heading
+    pub fn has_mag(&self, now_ms: u64) -> bool {
+        self.fresh_mag(now_ms).is_some()
+    }
+
+    /// Returns true if any axis is at or beyond the configured accelerometer range
+    fn is_clipped(&self, accel: &[f32; 3]) -> bool {
+        accel.iter().any(|a| a.abs() >= self.accel_range)
+    }
+
+    /// Direction of gravity in the body frame according to the latest quaternion (unit vector)
+    fn expected_gravity(&self) -> [f32; 3] {
+        self.madgwick.expected_gravity()
+    }
+
     /// Method for getting the latest quaternion method
     pub fn get_quaternion(&self) -> (f32, f32, f32, f32) {
         self.latest_quat
     }
 
+    /// Method for getting the latest acceleration in m/s^2 with gravity removed, in the body frame of the IMU
+    /// Reads ~0 at rest whatever the attitude, so liftoff and burnout stand out without the 1 g bias
+    pub fn get_linear_acceleration(&self) -> (f32, f32, f32) {
+        self.linear_accel
+    }
+
+    /// Method for getting the latest orientation as (roll, pitch, yaw) in degrees, see 'quat_to_euler'
+    pub fn get_euler(&self) -> (f32, f32, f32) {
+        quat_to_euler(self.latest_quat)
+    }
+
+    /// Method to re-seed the orientation without reconstructing the service, e.g. at liftoff, after a sensor dropout or after waking from sleep
+    /// The quaternion restarts from identity and converges on standard gravity again; every parameter is kept
+    pub fn reset(&mut self) {
+        self.madgwick = MadgwickFilter::new(self.beta, self.sample_period);
+        
+        self.initialize();
+        self.restart_adaptive_gain();
+        self.updates_since_output = 0;
+        self.last_time_stamp = None;
+    }
+
     /// Method to set new beta value
+    /// With an adaptive gain set, 'beta' only applies again once the adaptive gain is turned off
     pub fn set_beta(&mut self, beta: f32) {
         self.beta = beta;
         
-        self.madgwick = Marg::new(self.beta, self.sample_period);
-        
-        self.initialize();
+        self.reset();
     }
     
     /// Method to set sample period
     pub fn set_sample_period(&mut self, sample_period: f32) {
         self.sample_period = sample_period;
         
-        self.madgwick = Marg::new(self.beta, self.sample_period);
-        
-        self.initialize();
+        self.reset();
+    }
+
+    /// Method to let the gain follow how well the filter has converged, or to go back to the fixed 'beta' with 'None'
+    /// The gain starts at 'beta_max' and the orientation is kept, so this can be changed in flight
+    pub fn set_adaptive_gain(&mut self, adaptive_gain: Option<AdaptiveGain>) {
+        self.adaptive_gain = adaptive_gain;
+        self.restart_adaptive_gain();
+    }
+
+    /// Method to get the adaptive gain parameters, 'None' if the fixed 'beta' is used
+    pub fn get_adaptive_gain(&self) -> Option<AdaptiveGain> {
+        self.adaptive_gain
+    }
+
+    /// Method to get the gain the filter is currently running with; equal to 'beta' unless the adaptive gain is set
+    pub fn effective_beta(&self) -> f32 {
+        self.madgwick.beta()
+    }
+
+    /// Method to get the averaged innovation in rad driving the adaptive gain
+    pub fn innovation(&self) -> f32 {
+        self.innovation
+    }
+
+    /// Starts the adaptive gain from its maximum as if the filter had not converged yet
+    fn restart_adaptive_gain(&mut self) {
+        match self.adaptive_gain {
+            Some(adaptive_gain) => {
+                self.innovation = adaptive_gain.full_scale_innovation;
+                self.madgwick.set_beta(adaptive_gain.beta_max);
+            }
+            None => {
+                self.innovation = 0.0;
+                self.madgwick.set_beta(self.beta);
+            }
+        }
     }
     
     /// Method to get current beta value
@@ -153,4 +436,117 @@ impl MadgwickService {
     pub fn get_sample_period(&self) -> f32 {
         self.sample_period
     }
-}
\ No newline at end of file
+
+    /// Method to get the period in seconds the last update integrated over, measured from the IMU time stamps
+    pub fn get_measured_sample_period(&self) -> f32 {
+        self.madgwick.sample_period()
+    }
+
+    /// Method to set how many filter updates happen per emitted orientation message (1 emits on every update)
+    pub fn set_output_decimation(&mut self, output_decimation: u16) {
+        self.output_decimation = output_decimation.max(1);
+        self.updates_since_output = 0;
+    }
+
+    /// Method to get the current output decimation
+    pub fn get_output_decimation(&self) -> u16 {
+        self.output_decimation
+    }
+
+    /// Method to set the accelerometer full scale range in m/s^2; this must match the range the IMU is configured for
+    pub fn set_accel_range(&mut self, accel_range: f32) {
+        self.accel_range = accel_range;
+    }
+
+    /// Method to check the configured accelerometer range against the peak acceleration expected during boost
+    /// Returns false (and warns) if the IMU range is too small and samples will clip
+    pub fn check_accel_range(&self, expected_peak: f32) -> bool {
+        if expected_peak >= self.accel_range {
+            warn!("Expected peak of {} m/s^2 exceeds accelerometer range of {} m/s^2", expected_peak, self.accel_range);
+            return false;
+        }
+        true
+    }
+
+    /// Method to set the unit of incoming gyro rates; this must match the SBG output configuration
+    pub fn set_gyro_unit(&mut self, gyro_unit: GyroUnit) {
+        self.gyro_unit = gyro_unit;
+    }
+
+    /// Method to get the configured gyro unit
+    pub fn get_gyro_unit(&self) -> GyroUnit {
+        self.gyro_unit
+    }
+
+    /// Method to know if any accelerometer sample clipped since the last reset of the flag
+    pub fn accel_clipped(&self) -> bool {
+        self.accel_clipped
+    }
+
+    /// Method to clear the clipped flag, e.g. when re-arming on the pad
+    pub fn clear_accel_clipped(&mut self) {
+        self.accel_clipped = false;
+    }
+
+    /// Method to get the IMU currently feeding the filter, 'None' if neither IMU is usable
+    pub fn active_imu(&self) -> Option<ImuSource> {
+        self.imu_selector.active()
+    }
+
+    /// Method to re-evaluate the active IMU, so an IMU that stops sending entirely is dropped
+    pub fn poll_imu(&mut self, now_ms: u64) -> Option<ImuSource> {
+        self.imu_selector.poll(now_ms)
+    }
+
+    /// Method to get the difference between the two gyros at the last cross-check in rad/s
+    pub fn imu_disagreement(&self) -> Option<f32> {
+        self.imu_selector.disagreement()
+    }
+
+    /// Method to know if the two IMUs currently disagree
+    pub fn is_imu_disagreeing(&self) -> bool {
+        self.imu_selector.is_disagreeing()
+    }
+
+    /// Method to set how long an IMU may stay silent before the other one takes over
+    pub fn set_imu_max_age(&mut self, max_age_ms: u64) {
+        self.imu_selector.set_max_age(max_age_ms);
+    }
+
+    /// Method to set the gyro difference in rad/s above which the two IMUs are flagged as disagreeing
+    pub fn set_imu_disagreement_threshold(&mut self, threshold: f32) {
+        self.imu_selector.set_threshold(threshold);
+    }
+}
+
+/// Converts a (w, x, y, z) quaternion to (roll, pitch, yaw) in degrees, applied in yaw, pitch, roll order (aerospace ZYX)
+/// Roll and yaw are in (-180, 180] and pitch in [-90, 90]; near pitch = +-90 roll and yaw turn about the same axis,
+/// so roll is reported as 0 and the whole rotation about that axis as yaw
+pub fn quat_to_euler(quat: (f32, f32, f32, f32)) -> (f32, f32, f32) {
+    // Within ~0.1 deg of +-90 the roll and yaw terms are both close to atan2(0, 0)
+    const GIMBAL_LOCK_SIN_PITCH: f32 = 0.999999;
+    let (w, x, y, z) = quat;
+    let sin_pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0);
+    let (roll, pitch, yaw) = if sin_pitch >= GIMBAL_LOCK_SIN_PITCH {
+        (0.0, core::f32::consts::FRAC_PI_2, -2.0 * libm::atan2f(x, w))
+    } else if sin_pitch <= -GIMBAL_LOCK_SIN_PITCH {
+        (0.0, -core::f32::consts::FRAC_PI_2, 2.0 * libm::atan2f(x, w))
+    } else {
+        (
+            libm::atan2f(2.0 * (w * x + y * z), 1.0 - 2.0 * (x * x + y * y)),
+            libm::asinf(sin_pitch),
+            libm::atan2f(2.0 * (w * z + x * y), 1.0 - 2.0 * (y * y + z * z)),
+        )
+    };
+    (roll.to_degrees(), pitch.to_degrees(), wrap_degrees(yaw.to_degrees()))
+}
+
+/// Wraps an angle in degrees into (-180, 180]
+fn wrap_degrees(angle: f32) -> f32 {
+    let wrapped = libm::remainderf(angle, 360.0);
+    if wrapped <= -180.0 {
+        wrapped + 360.0
+    } else {
+        wrapped
+    }
+}
@@ -1,28 +1,41 @@
 #![no_std]
 #![no_main]
 
+mod boot_status;
+mod buzzer;
 mod communication;
 mod data_manager;
+mod data_queue;
 mod madgwick_service;
 mod types;
 
+use boot_status::{BootStatus, Subsystem};
 use chrono::NaiveDate;
 use common_arm::*;
-use communication::{CanCommandManager, CanDataManager};
-use communication::{RadioDevice, RadioManager};
+use core::fmt::Write as _;
+use communication::{
+    command_bus_ids, data_bit_rate, data_bus_ids, fdcan_kernel_clock_is, loopback_self_test,
+    nominal_bit_rate, steal_fdcan_rec, CanCommandManager, CanDataManager, EchoResult,
+    CAN_MAX_MESSAGE_LEN,
+};
+use communication::{
+    RadioDevice, RadioManager, RadioReceiver, RADIO_DELTA_MAX_SAMPLE, RADIO_MAX_MESSAGE_LEN,
+    RADIO_RX_QUEUE_LEN,
+};
 use core::num::{NonZeroU16, NonZeroU8};
-use data_manager::DataManager;
+use data_manager::{DataManager, EventTrigger};
+use data_queue::DataQueue;
 use defmt::info;
-use fdcan::{
-    config::NominalBitTiming,
-    filter::{StandardFilter, StandardFilterSlot},
-};
-use messages::command::RadioRate;
+use fdcan::config::{DataBitTiming, NominalBitTiming};
+use heapless::spsc::{Producer, Queue};
 use messages::{sensor, Data};
 use panic_probe as _;
+use phoenix::{
+    BaroRate, FlightPhase, FullPolicy, GroundCommand, SensorKind, ShutdownPhase, CAN_FD_MAX_PAYLOAD,
+};
 use rtic_monotonics::systick::prelude::*;
 use rtic_sync::{channel::*, make_channel};
-use stm32h7xx_hal::gpio::gpioa::{PA2, PA3};
+use stm32h7xx_hal::gpio::gpioa::{PA2, PA3, PA4};
 use stm32h7xx_hal::gpio::gpiob::PB4;
 use stm32h7xx_hal::gpio::Speed;
 use stm32h7xx_hal::gpio::{Output, PushPull};
@@ -32,8 +45,192 @@ use stm32h7xx_hal::{rcc, rcc::rec};
 use types::COM_ID; // global logger
 
 const DATA_CHANNEL_CAPACITY: usize = 10;
+/// Peak acceleration expected during boost in m/s^2, checked against the IMU range at boot.
+const EXPECTED_BOOST_PEAK_ACCEL: f32 = 10.0 * 9.80665;
+/// Number of Madgwick updates per emitted orientation message (100 Hz IMU -> 10 Hz orientation).
+const ORIENTATION_OUTPUT_DECIMATION: u16 = 10;
+/// Sensors that must be reporting fresh data before the vehicle is considered ready to fly.
+const REQUIRED_SENSORS: [SensorKind; 3] = [SensorKind::Imu1, SensorKind::EkfQuat, SensorKind::Air];
+/// Events that capture the samples around them to SD at full rate.
+const EVENT_TRIGGERS: [EventTrigger; 4] = [
+    EventTrigger::StateChange,
+    EventTrigger::Ballistic,
+    EventTrigger::OrientationDiverged,
+    EventTrigger::Liftoff,
+];
+/// External crystal fitted on this board.
+const HSE_HZ: u32 = 48_000_000;
+/// Core clock. `freeze` may not hit it exactly, so it is checked within `SYS_CK_TOLERANCE_HZ`.
+const SYS_CK_HZ: u32 = 200_000_000;
+const SYS_CK_TOLERANCE_HZ: u32 = 1_000_000;
+/// FDCAN kernel clock, PLL1Q. The CAN bit rate is derived from it, so it must be exact.
+const CAN_KERNEL_CLOCK_HZ: u32 = 32_000_000;
+/// How often the CAN bus load estimate is sampled and reported.
+const CAN_LOAD_REPORT_PERIOD_MS: u32 = 1000;
+/// How often the stack high-water mark is reported.
+const STACK_REPORT_PERIOD_MS: u32 = 10_000;
+/// Percentage of the stack in use above which the report becomes a warning.
+const STACK_WARN_PERCENT: usize = 75;
+/// Barometer populated on this board.
+const BARO_VARIANT: common_arm::drivers::ms5611::Variant =
+    common_arm::drivers::ms5611::Variant::Ms5611;
+/// Weight of each new baro pressure in its moving average, 1.0 disables the filter.
+const BARO_FILTER_ALPHA: f32 = 1.0;
+/// Time the baro is left to settle after boot before the ground reference is taken.
+const BARO_SETTLE_MS: u32 = 5000;
+/// Added to the baro conversion time, one systick tick.
+const BARO_CONVERSION_MARGIN_MS: u32 = 2;
+/// Number of baro samples averaged into the ground reference, and the time they are spread over.
+const GROUND_REFERENCE_SAMPLES: u8 = 10;
+const GROUND_REFERENCE_DURATION_MS: u32 = 10_000;
+/// How long the CAN echo test waits for the test frame to come back.
+const CAN_ECHO_TIMEOUT_MS: u64 = 100;
+/// MAVLink IDs the radio sends as. Give each vehicle its own system ID when several share a
+/// ground station.
+const MAV_SYSTEM_ID: u8 = DEFAULT_MAV_SYSTEM_ID;
+const MAV_COMPONENT_ID: u8 = DEFAULT_MAV_COMPONENT_ID;
+/// Pack the sensor messages sent together into shared radio frames instead of one frame each.
+const RADIO_BATCHING: bool = false;
+/// Sensors whose radio messages are delta encoded against the previous sample. Only the radio
+/// is compressed, the SD log keeps full samples.
+const RADIO_COMPRESSED_SENSORS: &[SensorKind] = &[
+    SensorKind::UtcTime,
+    SensorKind::GpsPos1,
+    SensorKind::GpsPos2,
+    SensorKind::NavPosLlh,
+];
+/// Number of delta encoded samples between two full samples of a compressed sensor.
+const RADIO_KEYFRAME_INTERVAL: u8 = 10;
+/// Time without a message after which `sensor_send` reports a sensor as stopped rather than just
+/// having nothing new.
+const SENSOR_STALE_MS: u64 = 2000;
+/// Attempts and the time between them to bring up the SD card at boot.
+const SD_INIT_ATTEMPTS: u8 = 3;
+const SD_INIT_RETRY_MS: u32 = 200;
+/// How often `sd_dump` writes the samples kept for the SD log.
+const SD_DUMP_PERIOD_MS: u32 = 20;
+/// Largest COBS framed sample `sd_dump` writes.
+const SD_RECORD_MAX_LEN: usize = 256;
+/// How often `sd_dump` tries to bring the SD card back after a failed write.
+const SD_REINIT_PERIOD_MS: u64 = 1000;
+/// Longest reset reason name, `ResetReason::Unknown` with its register value included.
+const RESET_REASON_MAX_LEN: usize = 64;
+/// How often `state_send` checks for a state change, bounding how late a transition is reported.
+const STATE_POLL_MS: u32 = 50;
+/// Period of the MAVLink heartbeat, 1 Hz as ground station software expects.
+const HEARTBEAT_PERIOD_MS: u32 = 1000;
+/// Heartbeats between two state snapshots sent without a `SendState` command, see `send_state`.
+const STATE_SNAPSHOT_HEARTBEATS: u32 = 10;
+/// Period of `monitor`, bounding how late a lost orientation source or a silent SBG is noticed.
+const MONITOR_PERIOD_MS: u32 = 100;
+
+/// Time the SBG is given to boot after power-on before it must be producing data.
+const SBG_SETTLE_MS: u32 = 2000;
+/// Number of power cycles tried after the first power-on if the SBG doesn't produce data.
+const SBG_POWER_RETRIES: u8 = 2;
+/// Time the SBG is held off during a power cycle.
+const SBG_POWER_OFF_MS: u32 = 500;
+/// Power the SBG down in post-landing locator mode. The buzzer and radio always stay on.
+const POST_LANDING_SBG_OFF: bool = true;
+/// Buzzer PWM frequency, set to the resonant frequency of the fitted piezo (see `buzzer`).
+const BUZZER_FREQUENCY_HZ: u32 = 4000;
+/// Buzzer frequency in post-landing locator mode. Lower tones are absorbed less by grass and
+/// trees and are easier to locate by ear, so this trades some loudness for range. Many piezos
+/// still resonate near 2.7 kHz, see `buzzer`.
+const LOCATOR_BUZZER_FREQUENCY_HZ: u32 = 2700;
+/// Locator beep and the silence after it.
+const LOCATOR_BEEP_MS: u32 = 500;
+const LOCATOR_PAUSE_MS: u32 = 1500;
 systick_monotonic!(Mono, 500);
 
+/// Milliseconds since boot from the monotonic clock.
+fn now_ms() -> u64 {
+    u64::from(Mono::now().duration_since_epoch().to_millis())
+}
+
+/// Time the RTC is set to at boot, and the base of the fallback timestamps without an RTC.
+fn boot_date_time() -> chrono::NaiveDateTime {
+    // TODO: Get current time from some source
+    NaiveDate::from_ymd_opt(2001, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+}
+
+/// Timestamp for outgoing messages. Without a working RTC this falls back to the time since
+/// boot, counted from the same base the RTC starts at, so the only loss is the wall clock.
+fn message_time(rtc: &Option<rtc::Rtc>) -> messages::FormattedNaiveDateTime {
+    let date_time = rtc
+        .as_ref()
+        .and_then(|rtc| rtc.date_time())
+        .unwrap_or_else(|| boot_date_time() + chrono::Duration::milliseconds(now_ms() as i64));
+    messages::FormattedNaiveDateTime(date_time)
+}
+
+/// This boot's records in the SD flight index, see `common_arm::FlightRecord`.
+struct FlightSession {
+    session: u32,
+    boot_s: u64,
+    reset_reason: heapless::String<RESET_REASON_MAX_LEN>,
+}
+
+impl FlightSession {
+    fn new(
+        session: u32,
+        boot_time: messages::FormattedNaiveDateTime,
+        reset: rcc::ResetReason,
+    ) -> Self {
+        let mut reset_reason = heapless::String::new();
+        write!(reset_reason, "{:?}", reset).ok();
+        Self {
+            session,
+            boot_s: boot_time.0.and_utc().timestamp() as u64,
+            reset_reason,
+        }
+    }
+
+    /// The boot record, without a flight summary.
+    fn boot_record(&self) -> FlightRecord<'_> {
+        FlightRecord {
+            session: self.session,
+            boot_s: self.boot_s,
+            reset_reason: &self.reset_reason,
+            file_name: LOG_FILE_NAME,
+            duration_s: None,
+            peak_altitude_m: None,
+            boost_accel_clipped: None,
+        }
+    }
+}
+
+/// Checks the clocks `freeze` settled on against the ones this board expects, logging each
+/// mismatch. A PLL that can't reach a target from `HSE_HZ` otherwise only shows up as a wrong CAN
+/// bit rate on the bus.
+fn clocks_match(clocks: &rcc::CoreClocks) -> bool {
+    let mut ok = true;
+    let sys_ck = clocks.sys_ck().raw();
+    if sys_ck.abs_diff(SYS_CK_HZ) > SYS_CK_TOLERANCE_HZ {
+        defmt::error!("sys_ck is {} Hz, expected {} Hz", sys_ck, SYS_CK_HZ);
+        ok = false;
+    }
+    match clocks.pll1_q_ck() {
+        Some(pll1_q) if pll1_q.raw() == CAN_KERNEL_CLOCK_HZ => {}
+        Some(pll1_q) => {
+            defmt::error!(
+                "PLL1Q is {} Hz, expected {} Hz, CAN bit rate will be wrong",
+                pll1_q.raw(),
+                CAN_KERNEL_CLOCK_HZ
+            );
+            ok = false;
+        }
+        None => {
+            defmt::error!("PLL1Q is not running, FDCAN has no kernel clock");
+            ok = false;
+        }
+    }
+    ok
+}
+
 #[inline(never)]
 #[defmt::panic_handler]
 fn panic() -> ! {
@@ -53,17 +250,23 @@ mod app {
     #[shared]
     struct SharedResources {
         data_manager: DataManager,
+        data_queue: DataQueue<DATA_CHANNEL_CAPACITY>,
         madgwick_service: madgwick_service::MadgwickService,
         em: ErrorManager,
-        // sd_manager: SdManager<
-        //     stm32h7xx_hal::spi::Spi<stm32h7xx_hal::pac::SPI1, stm32h7xx_hal::spi::Enabled>,
-        //     PA4<Output<PushPull>>,
-        // >,
-        radio_manager: RadioManager,
+        /// `None` if no card could be initialized at boot.
+        sd_manager: Option<
+            SdManager<
+                stm32h7xx_hal::spi::Spi<stm32h7xx_hal::pac::SPI1, stm32h7xx_hal::spi::Enabled>,
+                PA4<Output<PushPull>>,
+            >,
+        >,
+        /// `None` if the radio UART failed to come up, nothing is sent to the ground then.
+        radio_manager: Option<RadioManager>,
         can_command_manager: CanCommandManager,
         can_data_manager: CanDataManager,
         sbg_power: PB4<Output<PushPull>>,
-        rtc: rtc::Rtc,
+        /// `None` if the backup domain or RTC failed to come up, see `message_time`.
+        rtc: Option<rtc::Rtc>,
     }
     #[local]
     struct LocalResources {
@@ -74,30 +277,52 @@ mod app {
             0,
             stm32h7xx_hal::pwm::ComplementaryImpossible,
         >,
+        /// TIM12 kernel clock, to change the buzzer frequency at run time.
+        buzzer_timer_clock: u32,
         // Baro uses:
         // PB_08 for CS
         // PE_02 for SCK
         // PE_05 for MISO
         // PE_06 for MOSI
-        baro: common_arm::drivers::ms5611::Ms5611<
-            stm32h7xx_hal::spi::Spi<stm32h7xx_hal::pac::SPI4, stm32h7xx_hal::spi::Enabled>,
-            stm32h7xx_hal::gpio::Pin<
-                'B',
-                8,
-                stm32h7xx_hal::gpio::Output<stm32h7xx_hal::gpio::PushPull>,
-            >,
-            stm32h7xx_hal::delay::DelayFromCountDownTimer<
-                stm32h7xx_hal::timer::Timer<stm32h7xx_hal::pac::TIM2>,
+        // None if the baro failed to initialize
+        baro: Option<
+            common_arm::drivers::ms5611::Ms5611<
+                common_arm::drivers::ms5611::SpiInterface<
+                    stm32h7xx_hal::spi::Spi<stm32h7xx_hal::pac::SPI4, stm32h7xx_hal::spi::Enabled>,
+                    stm32h7xx_hal::gpio::Pin<
+                        'B',
+                        8,
+                        stm32h7xx_hal::gpio::Output<stm32h7xx_hal::gpio::PushPull>,
+                    >,
+                >,
+                stm32h7xx_hal::delay::DelayFromCountDownTimer<
+                    stm32h7xx_hal::timer::Timer<stm32h7xx_hal::pac::TIM2>,
+                >,
             >,
         >,
+        boot_status: BootStatus,
+        /// `None` along with `radio_manager`.
+        radio_uart_rx: Option<stm32h7xx_hal::serial::Rx<stm32h7xx_hal::pac::UART4>>,
+        /// Bytes `radio_rx` received, for `radio_receive`.
+        radio_bytes: Producer<'static, u8, RADIO_RX_QUEUE_LEN>,
+        radio_receiver: RadioReceiver,
+        /// `None` if no card could be initialized at boot or its flight index couldn't be read.
+        flight_session: Option<FlightSession>,
     }
 
-    #[init]
+    #[init(local = [
+        radio_queue: Queue<u8, RADIO_RX_QUEUE_LEN> = Queue::new(),
+        self_test_buf: [u8; CAN_MAX_MESSAGE_LEN] = [0; CAN_MAX_MESSAGE_LEN],
+        self_test_frame: [u8; CAN_FD_MAX_PAYLOAD] = [0; CAN_FD_MAX_PAYLOAD],
+    ])]
     fn init(ctx: init::Context) -> (SharedResources, LocalResources) {
+        paint_stack();
         // channel setup
-        let (_s, r) = make_channel!(Message, DATA_CHANNEL_CAPACITY);
+        let (doorbell, r) = make_channel!((), 1);
+        let data_queue = DataQueue::new(doorbell, FullPolicy::DropOldest);
 
-        let core = ctx.core;
+        let mut core = ctx.core;
+        let mut boot_status = BootStatus::new();
 
         /* Logging Setup */
         HydraLogging::set_ground_station_callback(queue_gs_message);
@@ -108,26 +333,47 @@ mod app {
         let mut pwrcfg = pwr.freeze();
 
         info!("Power enabled");
-        let backup = pwrcfg.backup().unwrap();
-        info!("Backup domain enabled");
+        // The RTC only provides wall-clock timestamps, so a backup domain failure must not stop
+        // the boot.
+        let backup = pwrcfg.backup();
+        if backup.is_some() {
+            info!("Backup domain enabled");
+        } else {
+            defmt::warn!("Backup domain unavailable, timestamps relative to boot");
+        }
         // RCC
         let mut rcc = ctx.device.RCC.constrain();
         let reset = rcc.get_reset_reason();
-        let fdcan_prec_unsafe = unsafe { rcc.steal_peripheral_rec() }
-            .FDCAN
-            .kernel_clk_mux(rec::FdcanClkSel::Pll1Q);
+        let fdcan1_prec = steal_fdcan_rec(&rcc);
 
         let ccdr = rcc
-            .use_hse(48.MHz()) // check the clock hardware
-            .sys_ck(200.MHz())
+            .use_hse(HSE_HZ.Hz()) // check the clock hardware
+            .sys_ck(SYS_CK_HZ.Hz())
             .pll1_strategy(rcc::PllConfigStrategy::Iterative)
-            .pll1_q_ck(32.MHz())
+            .pll1_q_ck(CAN_KERNEL_CLOCK_HZ.Hz())
             .freeze(pwrcfg, &ctx.device.SYSCFG);
         info!("RCC configured");
+        // Keep booting on a mismatch so the failure can be reported, see `boot_status`.
+        if clocks_match(&ccdr.clocks) {
+            boot_status.mark_up(Subsystem::Clocks);
+        } else {
+            boot_status.mark_failed(Subsystem::Clocks);
+        }
         let fdcan_prec = ccdr
             .peripheral
             .FDCAN
             .kernel_clk_mux(rec::FdcanClkSel::Pll1Q);
+        // The CAN bit timings assume PLL1Q at `CAN_KERNEL_CLOCK_HZ`, but a wrong clock is
+        // reported rather than halting.
+        let can_clock_ok = fdcan_kernel_clock_is(&fdcan_prec, &ccdr.clocks, CAN_KERNEL_CLOCK_HZ)
+            && fdcan_kernel_clock_is(&fdcan1_prec, &ccdr.clocks, CAN_KERNEL_CLOCK_HZ);
+        if !can_clock_ok {
+            defmt::error!(
+                "FDCAN kernel clock is not PLL1Q at {} Hz",
+                CAN_KERNEL_CLOCK_HZ
+            );
+            boot_status.mark_failed(Subsystem::Clocks);
+        }
 
         let btr = NominalBitTiming {
             prescaler: NonZeroU16::new(10).unwrap(),
@@ -135,14 +381,23 @@ mod app {
             seg2: NonZeroU8::new(2).unwrap(),
             sync_jump_width: NonZeroU8::new(1).unwrap(),
         };
+        let can_bit_rate = nominal_bit_rate(CAN_KERNEL_CLOCK_HZ, &btr);
 
-        // let data_bit_timing = DataBitTiming {
-        //     prescaler: NonZeroU8::new(10).unwrap(),
-        //     seg1: NonZeroU8::new(13).unwrap(),
-        //     seg2: NonZeroU8::new(2).unwrap(),
-        //     sync_jump_width: NonZeroU8::new(4).unwrap(),
-        //     transceiver_delay_compensation: true,
-        // };
+        // Same 200 kbit/s as the nominal phase for now, so switching the bit rate changes
+        // nothing until every node on the data bus is configured for a faster data phase. Enable
+        // transceiver delay compensation when going above 1 Mbit/s.
+        let data_bit_timing = DataBitTiming {
+            prescaler: NonZeroU8::new(10).unwrap(),
+            seg1: NonZeroU8::new(13).unwrap(),
+            seg2: NonZeroU8::new(2).unwrap(),
+            sync_jump_width: NonZeroU8::new(1).unwrap(),
+            transceiver_delay_compensation: false,
+        };
+        info!(
+            "CAN at {} bit/s, data phase at {} bit/s",
+            can_bit_rate,
+            data_bit_rate(CAN_KERNEL_CLOCK_HZ, &data_bit_timing)
+        );
 
         info!("CAN enabled");
         // GPIO
@@ -151,19 +406,19 @@ mod app {
         let gpiob = ctx.device.GPIOB.split(ccdr.peripheral.GPIOB);
 
         let pins = gpiob.pb14.into_alternate();
-        let mut c0 = ctx
-            .device
-            .TIM12
-            .pwm(pins, 4.kHz(), ccdr.peripheral.TIM12, &ccdr.clocks);
+        let mut c0 = ctx.device.TIM12.pwm(
+            pins,
+            BUZZER_FREQUENCY_HZ.Hz(),
+            ccdr.peripheral.TIM12,
+            &ccdr.clocks,
+        );
+        let buzzer_timer_clock = ccdr.clocks.timx_ker_ck().raw();
 
         c0.set_duty(c0.get_max_duty() / 4);
         // PWM outputs are disabled by default
         // c0.enable();
 
         info!("PWM enabled");
-        // assert_eq!(ccdr.clocks.pll1_q_ck().unwrap().raw(), 32_000_000);
-        info!("PLL1Q:");
-        // https://github.com/stm32-rs/stm32h7xx-hal/issues/369 This needs to be stolen. Grrr I hate the imaturity of the stm32-hal
         let can2: fdcan::FdCan<
             stm32h7xx_hal::can::Can<stm32h7xx_hal::pac::FDCAN2>,
             fdcan::ConfigMode,
@@ -180,33 +435,42 @@ mod app {
 
         // can_data.set_automatic_retransmit(false); // data can be dropped due to its volume.
 
-        // can_command.set_data_bit_timing(data_bit_timing);
-
-        can_data.set_standard_filter(
-            StandardFilterSlot::_0,
-            StandardFilter::accept_all_into_fifo0(),
-        );
-
-        can_data.set_standard_filter(
-            StandardFilterSlot::_1,
-            StandardFilter::accept_all_into_fifo0(),
-        );
-
-        can_data.set_standard_filter(
-            StandardFilterSlot::_2,
-            StandardFilter::accept_all_into_fifo0(),
-        );
-
         can_data.enable_interrupt(fdcan::interrupt::Interrupt::RxFifo0NewMsg);
+        can_data.enable_interrupt(fdcan::interrupt::Interrupt::RxFifo1NewMsg);
 
         can_data.enable_interrupt_line(fdcan::interrupt::InterruptLine::_0, true);
 
+        // Frames with the same ID, such as the fragments of a message, go out in the order they
+        // were queued only from a Tx FIFO. In queue mode the buffer index breaks the tie.
         let config = can_data
             .get_config()
-            .set_frame_transmit(fdcan::config::FrameTransmissionConfig::AllowFdCanAndBRS);
+            .set_frame_transmit(fdcan::config::FrameTransmissionConfig::AllowFdCanAndBRS)
+            .set_tx_buffer_mode(fdcan::config::TxBufferMode::Fifo);
         can_data.apply_config(config);
 
-        let can_data_manager = CanDataManager::new(can_data.into_normal());
+        // Checked on both buses before going on them, see `loopback_self_test`.
+        let self_test_message = Message::new(
+            messages::FormattedNaiveDateTime(boot_date_time()),
+            COM_ID,
+            messages::state::State::new(messages::state::StateData::Initializing),
+        );
+        let (can_data, data_self_test) = loopback_self_test(
+            can_data,
+            "data",
+            &self_test_message,
+            ctx.local.self_test_buf,
+            ctx.local.self_test_frame,
+        );
+
+        let mut can_data_manager = CanDataManager::new(
+            can_data.into_normal(),
+            can_bit_rate,
+            COM_ID.into(),
+            data_bit_timing,
+        );
+        if let Err(e) = can_data_manager.set_id_filter(&data_bus_ids(COM_ID.into())) {
+            info!("CAN data filter: {}", e);
+        }
 
         let can1: fdcan::FdCan<
             stm32h7xx_hal::can::Can<stm32h7xx_hal::pac::FDCAN1>,
@@ -214,65 +478,91 @@ mod app {
         > = {
             let rx = gpioa.pa11.into_alternate().speed(Speed::VeryHigh);
             let tx = gpioa.pa12.into_alternate().speed(Speed::VeryHigh);
-            ctx.device.FDCAN1.fdcan(tx, rx, fdcan_prec_unsafe)
+            ctx.device.FDCAN1.fdcan(tx, rx, fdcan1_prec)
         };
 
         let mut can_command = can1;
         can_command.set_protocol_exception_handling(false);
 
         can_command.set_nominal_bit_timing(btr);
-        can_command.set_standard_filter(
-            StandardFilterSlot::_0,
-            StandardFilter::accept_all_into_fifo0(),
-        );
-
-        can_command.set_standard_filter(
-            StandardFilterSlot::_1,
-            StandardFilter::accept_all_into_fifo0(),
-        );
-
-        can_command.set_standard_filter(
-            StandardFilterSlot::_2,
-            StandardFilter::accept_all_into_fifo0(),
-        );
-
-        // can_data.set_data_bit_timing(data_bit_timing);
         can_command.enable_interrupt(fdcan::interrupt::Interrupt::RxFifo0NewMsg);
+        can_command.enable_interrupt(fdcan::interrupt::Interrupt::RxFifo1NewMsg);
 
         can_command.enable_interrupt_line(fdcan::interrupt::InterruptLine::_0, true);
 
+        // In FIFO mode for the same reason as the data bus.
         let config = can_command
             .get_config()
-            .set_frame_transmit(fdcan::config::FrameTransmissionConfig::AllowFdCanAndBRS); // check this maybe don't bit switch allow.
+            .set_frame_transmit(fdcan::config::FrameTransmissionConfig::AllowFdCanAndBRS) // check this maybe don't bit switch allow.
+            .set_tx_buffer_mode(fdcan::config::TxBufferMode::Fifo);
         can_command.apply_config(config);
 
-        let can_command_manager = CanCommandManager::new(can_command.into_normal());
+        let (can_command, command_self_test) = loopback_self_test(
+            can_command,
+            "command",
+            &self_test_message,
+            ctx.local.self_test_buf,
+            ctx.local.self_test_frame,
+        );
 
-        // let spi_sd: stm32h7xx_hal::spi::Spi<
-        //     stm32h7xx_hal::stm32::SPI1,
-        //     stm32h7xx_hal::spi::Enabled,
-        //     u8,
-        // > = ctx.device.SPI1.spi(
-        //     (
-        //         gpioa.pa5.into_alternate::<5>(),
-        //         gpioa.pa6.into_alternate(),
-        //         gpioa.pa7.into_alternate(),
-        //     ),
-        //     spi::Config::new(spi::MODE_0),
-        //     16.MHz(),
-        //     ccdr.peripheral.SPI1,
-        //     &ccdr.clocks,
-        // );
+        let mut can_command_manager =
+            CanCommandManager::new(can_command.into_normal(), can_bit_rate, COM_ID.into());
+        if let Err(e) = can_command_manager.set_id_filter(&command_bus_ids(COM_ID.into())) {
+            info!("CAN command filter: {}", e);
+        }
+        match (data_self_test, command_self_test) {
+            (Ok(()), Ok(())) if can_clock_ok => boot_status.mark_up(Subsystem::Can),
+            // The loopback passes at any bit rate, so a bus on the wrong clock still counts as
+            // down.
+            (Ok(()), Ok(())) => boot_status.mark_failed(Subsystem::Can),
+            (Err(e), _) | (_, Err(e)) => {
+                info!("{}", e);
+                boot_status.mark_failed(Subsystem::Can);
+            }
+        }
 
-        // let cs_sd = gpioa.pa4.into_push_pull_output();
+        let spi_sd: stm32h7xx_hal::spi::Spi<
+            stm32h7xx_hal::stm32::SPI1,
+            stm32h7xx_hal::spi::Enabled,
+            u8,
+        > = ctx.device.SPI1.spi(
+            (
+                gpioa.pa5.into_alternate::<5>(),
+                gpioa.pa6.into_alternate(),
+                gpioa.pa7.into_alternate(),
+            ),
+            stm32h7xx_hal::spi::Config::new(stm32h7xx_hal::spi::MODE_0),
+            16.MHz(),
+            ccdr.peripheral.SPI1,
+            &ccdr.clocks,
+        );
 
-        // let sd_manager = SdManager::new(spi_sd, cs_sd);
+        let cs_sd = gpioa.pa4.into_push_pull_output();
+
+        let timer3 = ctx
+            .device
+            .TIM3
+            .timer(1.MHz(), ccdr.peripheral.TIM3, &ccdr.clocks);
+        let mut sd_delay = stm32h7xx_hal::delay::DelayFromCountDownTimer::new(timer3);
+        let mut sd_manager = match SdManager::new_with_retry(
+            spi_sd,
+            cs_sd,
+            SD_INIT_ATTEMPTS,
+            SD_INIT_RETRY_MS,
+            &mut sd_delay,
+        ) {
+            Ok(sd_manager) => Some(sd_manager),
+            Err(_) => {
+                info!("No SD card, logging to SD is off");
+                None
+            }
+        };
 
         // leds
         let led_red = gpioa.pa2.into_push_pull_output();
         let led_green = gpioa.pa3.into_push_pull_output();
 
-        // sbg power pin
+        // sbg power pin, powered right away. `sbg_power_up` checks that it comes up.
         let mut sbg_power = gpiob.pb4.into_push_pull_output();
         sbg_power.set_high();
 
@@ -297,60 +587,125 @@ mod app {
         let delay_tim = stm32h7xx_hal::delay::DelayFromCountDownTimer::new(timer2);
         /* Monotonic clock */
         Mono::start(core.SYST, 200_000_000);
-
-        let baro = common_arm::drivers::ms5611::Ms5611::new(spi4, baro_cs, delay_tim).unwrap();
+        // Cycle counter for the µs sensor timestamps, the systick above only ticks every 2 ms.
+        core.DCB.enable_trace();
+        core.DWT.enable_cycle_counter();
+
+        let baro = match common_arm::drivers::ms5611::Ms5611::new(
+            spi4,
+            baro_cs,
+            delay_tim,
+            BARO_VARIANT,
+        ) {
+            Ok(mut baro) => {
+                boot_status.mark_up(Subsystem::Baro);
+                baro.set_filter_alpha(BARO_FILTER_ALPHA).ok();
+                Some(baro)
+            }
+            Err(_) => {
+                info!("Baro: Driver initialization failed!");
+                boot_status.mark_failed(Subsystem::Baro);
+                None
+            }
+        };
 
         // UART for sbg
         let tx: Pin<'D', 1, Alternate<8>> = gpiod.pd1.into_alternate();
         let rx: Pin<'D', 0, Alternate<8>> = gpiod.pd0.into_alternate();
 
         // let stream_tuple = StreamsTuple::new(ctx.device.DMA1, ccdr.peripheral.DMA1);
-        let uart_radio = ctx
-            .device
-            .UART4
-            .serial((tx, rx), 57600.bps(), ccdr.peripheral.UART4, &ccdr.clocks)
-            .unwrap();
+        let uart_radio =
+            ctx.device
+                .UART4
+                .serial((tx, rx), 57600.bps(), ccdr.peripheral.UART4, &ccdr.clocks);
         // let mut sbg_manager = sbg_manager::SBGManager::new(uart_sbg, stream_tuple);
 
-        let radio = RadioDevice::new(uart_radio);
-
-        let radio_manager = RadioManager::new(radio);
-
-        let mut rtc = stm32h7xx_hal::rtc::Rtc::open_or_init(
-            ctx.device.RTC,
-            backup.RTC,
-            stm32h7xx_hal::rtc::RtcClock::Lsi,
-            &ccdr.clocks,
-        );
-
-        // TODO: Get current time from some source
-        let now = NaiveDate::from_ymd_opt(2001, 1, 1)
-            .unwrap()
-            .and_hms_opt(0, 0, 0)
-            .unwrap();
-
-        rtc.set_date_time(now);
+        // Like the baro, a failed radio is reported and the boot goes on without it.
+        let (radio_manager, radio_uart_rx) = match uart_radio {
+            Ok(uart_radio) => {
+                let (radio, radio_uart_rx) = RadioDevice::new(uart_radio);
+                let radio_manager = RadioManager::new(radio, MAV_SYSTEM_ID, MAV_COMPONENT_ID);
+                info!(
+                    "Radio as MAVLink system {} component {}",
+                    radio_manager.system_id(),
+                    radio_manager.component_id()
+                );
+                boot_status.mark_up(Subsystem::Radio);
+                (Some(radio_manager), Some(radio_uart_rx))
+            }
+            Err(_) => {
+                info!("Radio: UART initialization failed!");
+                boot_status.mark_failed(Subsystem::Radio);
+                (None, None)
+            }
+        };
+        let (radio_bytes, radio_queued) = ctx.local.radio_queue.split();
+        let radio_receiver = RadioReceiver::new(radio_queued);
+
+        let rtc = backup.map(|backup| {
+            let mut rtc = stm32h7xx_hal::rtc::Rtc::open_or_init(
+                ctx.device.RTC,
+                backup.RTC,
+                stm32h7xx_hal::rtc::RtcClock::Lsi,
+                &ccdr.clocks,
+            );
+            rtc.set_date_time(boot_date_time());
+            rtc
+        });
 
-        let madgwick_service = madgwick_service::MadgwickService::new();
+        let mut madgwick_service = madgwick_service::MadgwickService::new();
+        madgwick_service.set_output_decimation(ORIENTATION_OUTPUT_DECIMATION);
+        let accel_range_ok = madgwick_service.check_accel_range(EXPECTED_BOOST_PEAK_ACCEL);
 
         let mut data_manager = DataManager::new();
+        data_manager.accel_range_ok = accel_range_ok;
         data_manager.set_reset_reason(reset);
+        data_manager.rtc_available = rtc.is_some();
+        data_manager.set_required_sensors(&REQUIRED_SENSORS);
+        data_manager.set_event_triggers(&EVENT_TRIGGERS);
+        data_manager.set_verbosity(Verbosity::Normal);
+        let flight_session = sd_manager.as_mut().and_then(|sd_manager| {
+            let session = sd_manager.next_session().ok()?;
+            let session = FlightSession::new(session, message_time(&rtc), reset);
+            match sd_manager.append_index(&session.boot_record()) {
+                Ok(()) => info!("Flight index: session {}", session.session),
+                Err(_) => defmt::warn!("Flight index: boot record not written"),
+            }
+            Some(session)
+        });
         let em = ErrorManager::new();
         blink::spawn().ok();
+        monitor::spawn().ok();
         send_data_internal::spawn(r).ok();
         reset_reason_send::spawn().ok();
         state_send::spawn().ok();
         baro_read::spawn().ok();
+        calibrate_ground_reference::spawn(
+            BARO_SETTLE_MS,
+            GROUND_REFERENCE_SAMPLES,
+            GROUND_REFERENCE_DURATION_MS,
+        )
+        .ok();
+        can_load_report::spawn().ok();
+        stack_report::spawn().ok();
+        heartbeat::spawn().ok();
+        sbg_power_up::spawn(SBG_SETTLE_MS, SBG_POWER_RETRIES).ok();
         // generate_random_messages::spawn().ok();
-        // sensor_send::spawn().ok();
-        info!("Online");
+        sensor_send::spawn().ok();
+        sd_dump::spawn().ok();
+        if boot_status.any_failed() {
+            info!("Online with failed subsystems");
+        } else {
+            info!("Online");
+        }
 
         (
             SharedResources {
                 data_manager,
+                data_queue,
                 madgwick_service,
                 em,
-                // sd_manager,
+                sd_manager,
                 radio_manager,
                 can_command_manager,
                 can_data_manager,
@@ -361,26 +716,65 @@ mod app {
                 led_red,
                 led_green,
                 buzzer: c0,
+                buzzer_timer_clock,
                 baro,
+                boot_status,
+                radio_uart_rx,
+                radio_bytes,
+                radio_receiver,
+                flight_session,
             },
         )
     }
 
     // it would be nice to have RTIC be able to return objects, but the current procedural macro
     // does not allow for this.
-    #[task(priority = 3, local = [baro], shared = [&em, data_manager])]
+    #[task(priority = 3, local = [baro, rate: BaroRate = BaroRate::new()], shared = [&em, data_manager])]
     async fn baro_read(mut cx: baro_read::Context) {
-        let baro = cx.local.baro; // Get mutable access to the driver
+        // Get mutable access to the driver
+        let Some(baro) = cx.local.baro.as_mut() else {
+            info!("Baro: Not initialized, not reading");
+            return;
+        };
+        // Choose the desired Oversampling Ratio for this reading
+        let osr = OversamplingRatio::Osr512; // Example: Highest precision
+
+        // The systick rounds delays down to whole ticks, so add one to cover the conversion.
+        let conversion_ms = osr.conversion_time_us().div_ceil(1000) + BARO_CONVERSION_MARGIN_MS;
         loop {
+            let started_ms = now_ms();
+            // Wait out each conversion asynchronously instead of blocking in the driver.
+            let reading = async {
+                baro.start_temperature_conversion(osr)?;
+                Mono::delay(conversion_ms.millis()).await;
+                let d2_raw = baro.read_adc_raw()?;
+                baro.start_pressure_conversion(osr)?;
+                Mono::delay(conversion_ms.millis()).await;
+                let d1_raw = baro.read_adc_raw()?;
+                baro.calculate_compensated_values(d1_raw, d2_raw)
+                    .map(|(temp_c, press_kpa)| (temp_c, baro.filter_pressure(press_kpa)))
+            }
+            .await;
+            // The pressure conversion just finished, close enough to when the reading was taken.
+            let read_ms = now_ms();
             cx.shared.em.run(|| {
-                // Choose the desired Oversampling Ratio for this reading
-                let osr = OversamplingRatio::Osr512; // Example: Highest precision
-
-                match baro.read_pressure_temperature(osr) {
+                match reading {
                     Ok((temp_c, press_kpa)) => {
+                        // 1 kPa = 10 mbar
+                        let altitude_m = baro.altitude_m(press_kpa * 10.0);
+                        let altitude_corrected_m =
+                            baro.altitude_temperature_corrected_m(press_kpa * 10.0, temp_c);
                         cx.shared.data_manager.lock(|dm| {
                             dm.baro_temperature = Some(temp_c);
                             dm.baro_pressure = Some(press_kpa);
+                            dm.baro_altitude = Some(altitude_m);
+                            dm.baro_altitude_corrected = Some(altitude_corrected_m);
+                            dm.baro_altitude_agl = dm.ground_reference.height_above(press_kpa);
+                            dm.record_baro_altitude(altitude_m, read_ms);
+                            dm.baro_vertical_velocity =
+                                dm.baro_velocity_filter.update(altitude_m, read_ms);
+                            dm.update_flight_phase(read_ms);
+                            dm.vote_pressure();
                         });
                         Ok(())
                     }
@@ -389,28 +783,74 @@ mod app {
                         cx.shared.data_manager.lock(|dm| {
                             dm.baro_temperature = None;
                             dm.baro_pressure = None;
+                            dm.baro_altitude = None;
+                            dm.baro_altitude_corrected = None;
+                            dm.baro_altitude_agl = None;
+                            dm.baro_vertical_velocity = None;
+                            dm.vote_pressure();
                         });
                         Err(HydraError::from(e))
                     }
                 }
             });
-            Mono::delay(1000.millis()).await;
+            // Sample fast in flight to catch apogee, slowly on the ground to save power.
+            let phase = cx.shared.data_manager.lock(|dm| dm.flight_phase());
+            let elapsed_ms = now_ms().saturating_sub(started_ms) as u32;
+            let remaining_ms = cx.local.rate.remaining_ms(phase, elapsed_ms);
+            Mono::delay(remaining_ms.millis()).await;
         }
     }
 
-    #[task(priority = 3, shared = [&em, rtc])]
+    /**
+     * Averages the baro pressure into a new ground reference while the vehicle sits on the pad.
+     * Spawned at boot once the baro has settled, and again by the `CalibrateGroundReference` ground
+     * command.
+     */
+    #[task(priority = 1, shared = [data_manager])]
+    async fn calibrate_ground_reference(
+        mut cx: calibrate_ground_reference::Context,
+        settle_ms: u32,
+        samples: u8,
+        duration_ms: u32,
+    ) {
+        Mono::delay(settle_ms.millis()).await;
+        if !cx
+            .shared
+            .data_manager
+            .lock(|dm| dm.ground_reference.start())
+        {
+            return;
+        }
+        let period_ms = duration_ms / u32::from(samples.max(1));
+        for _ in 0..samples {
+            Mono::delay(period_ms.millis()).await;
+            cx.shared.data_manager.lock(|dm| {
+                if let Some(pressure) = dm.baro_pressure {
+                    dm.ground_reference.add_sample(pressure);
+                }
+            });
+        }
+        if cx
+            .shared
+            .data_manager
+            .lock(|dm| dm.ground_reference.finish())
+            .is_none()
+        {
+            info!("Baro: No readings, ground reference not updated");
+        }
+    }
+
+    #[task(priority = 3, shared = [&em, rtc, data_queue])]
     async fn generate_random_messages(mut cx: generate_random_messages::Context) {
         loop {
             cx.shared.em.run(|| {
                 let message = Message::new(
-                    cx.shared
-                        .rtc
-                        .lock(|rtc| messages::FormattedNaiveDateTime(rtc.date_time().unwrap())),
+                    cx.shared.rtc.lock(|rtc| message_time(rtc)),
                     COM_ID,
                     messages::state::State::new(messages::state::StateData::Initializing),
                 );
                 spawn!(send_gs, message.clone())?;
-                // spawn!(send_data_internal, message)?;
+                cx.shared.data_queue.lock(|queue| queue.push(message));
                 Ok(())
             });
             Mono::delay(1.secs()).await;
@@ -440,9 +880,7 @@ mod app {
                     stm32h7xx_hal::rcc::ResetReason::WindowWatchdogReset => sensor::ResetReason::WindowWatchdogReset,
                 };
                 let message = messages::Message::new(
-                    cx.shared
-                        .rtc
-                        .lock(|rtc| messages::FormattedNaiveDateTime(rtc.date_time().unwrap())),
+                    cx.shared.rtc.lock(|rtc| message_time(rtc)),
                     COM_ID,
                     sensor::Sensor::new(x),
                 );
@@ -456,62 +894,222 @@ mod app {
         }
     }
 
-    #[task(shared = [data_manager, &em, rtc])]
+    /**
+     * Reports the state as soon as it changes, and otherwise every keep-alive interval.
+     */
+    #[task(shared = [data_manager, data_queue, &em, rtc])]
     async fn state_send(mut cx: state_send::Context) {
-        let state_data = cx
-            .shared
-            .data_manager
-            .lock(|data_manager| data_manager.state.clone());
-        cx.shared.em.run(|| {
-            if let Some(x) = state_data {
-                let message = Message::new(
-                    cx.shared
-                        .rtc
-                        .lock(|rtc| messages::FormattedNaiveDateTime(rtc.date_time().unwrap())),
-                    COM_ID,
-                    messages::state::State::new(x),
-                );
-                spawn!(send_gs, message)?;
-            } // if there is none we still return since we simply don't have data yet.
-            Ok(())
-        });
-        Mono::delay(5.secs()).await;
-        // spawn_after!(state_send, ExtU64::secs(5)).ok();
+        loop {
+            let state_data = cx
+                .shared
+                .data_manager
+                .lock(|data_manager| data_manager.state_to_send(now_ms()));
+            cx.shared.em.run(|| {
+                if let Some(x) = state_data {
+                    let message = Message::new(
+                        cx.shared.rtc.lock(|rtc| message_time(rtc)),
+                        COM_ID,
+                        messages::state::State::new(x),
+                    );
+                    cx.shared.data_queue.lock(|queue| queue.push(message.clone()));
+                    spawn!(send_gs, message)?;
+                } // nothing changed and the keep-alive isn't due, or there is no state yet.
+                Ok(())
+            });
+            Mono::delay(STATE_POLL_MS.millis()).await;
+        }
     }
 
     /**
      * Sends information about the sensors.
      */
-    #[task(priority = 3, shared = [data_manager, &em])]
+    #[task(
+        priority = 3,
+        local = [
+            encoders: [DeltaEncoder<RADIO_DELTA_MAX_SAMPLE>; SensorKind::COUNT] =
+                [const { DeltaEncoder::new(RADIO_KEYFRAME_INTERVAL) }; SensorKind::COUNT],
+        ],
+        shared = [data_manager, radio_manager, &em]
+    )]
     async fn sensor_send(mut cx: sensor_send::Context) {
         loop {
-            let (sensors, logging_rate) = cx.shared.data_manager.lock(|data_manager| {
-                (data_manager.take_sensors(), data_manager.get_logging_rate())
-            });
+            let last_receive_ms = cx
+                .shared
+                .radio_manager
+                .lock(|radio_manager| radio_manager.as_ref()?.last_receive_ms());
+            let (sensors, radio_period_ms, telemetry, stale) =
+                cx.shared.data_manager.lock(|data_manager| {
+                    let now = now_ms();
+                    data_manager.update_link(last_receive_ms, now);
+                    (
+                        data_manager.take_sensors(),
+                        data_manager.radio_period_ms(),
+                        data_manager.telemetry_detail.clone(),
+                        SensorKind::ALL
+                            .map(|kind| data_manager.is_stale(kind, SENSOR_STALE_MS, now)),
+                    )
+                });
 
-            cx.shared.em.run(|| {
-                for msg in sensors {
-                    match msg {
-                        Some(x) => {
-                            // info!("Sending sensor data {}", x.clone());
-                            spawn!(send_gs, x)?;
-                            //                     spawn!(sd_dump, x)?;
-                        }
-                        None => {
-                            info!("No sensor data to send");
-                            continue;
+            if RADIO_BATCHING || telemetry.is_packed() {
+                // Compact telemetry leaves out the streams outside its set.
+                let messages = SensorKind::ALL
+                    .into_iter()
+                    .zip(sensors.iter())
+                    .filter(|(kind, _)| telemetry.includes(*kind as usize))
+                    .filter_map(|(_, msg)| msg.as_ref());
+                cx.shared.radio_manager.lock(|radio_manager| {
+                    if let Some(radio_manager) = radio_manager {
+                        cx.shared
+                            .em
+                            .run(|| radio_manager.send_batched(messages, now_ms()))
+                    }
+                });
+            } else {
+                cx.shared.em.run(|| {
+                    for (kind, msg) in SensorKind::ALL.into_iter().zip(sensors) {
+                        match msg {
+                            Some(x) if RADIO_COMPRESSED_SENSORS.contains(&kind) => {
+                                let encoder = &mut cx.local.encoders[kind as usize];
+                                cx.shared.radio_manager.lock(|radio_manager| {
+                                    let Some(radio_manager) = radio_manager else {
+                                        return Ok(());
+                                    };
+                                    radio_manager.send_compressed(kind as u8, &x, encoder, now_ms())
+                                })?;
+                            }
+                            Some(x) => {
+                                // info!("Sending sensor data {}", x.clone());
+                                spawn!(send_gs, x)?;
+                                //                     spawn!(sd_dump, x)?;
+                            }
+                            None if stale[kind as usize] => {
+                                info!("No {} data for over {} ms", kind, SENSOR_STALE_MS);
+                                continue;
+                            }
+                            None => {
+                                info!("No sensor data to send");
+                                continue;
+                            }
                         }
                     }
-                }
 
-                Ok(())
+                    Ok(())
+                });
+            }
+            Mono::delay((radio_period_ms as u32).millis()).await;
+        }
+    }
+
+    /**
+     * Writes the sensor samples kept for the SD log, one COBS framed postcard message each. They
+     * are kept at the SD log rate as they arrive, see `DataManager::should_log_to_sd`, so the SD
+     * gets every sample while the radio only gets what `sensor_send` takes. After a failed write
+     * the card is reinitialized every `SD_REINIT_PERIOD_MS`, e.g. once it has been reseated, and
+     * samples are dropped until it is ready again.
+     */
+    #[task(priority = 1, shared = [data_manager, sd_manager, &em])]
+    async fn sd_dump(mut cx: sd_dump::Context) {
+        let mut record = [0u8; SD_RECORD_MAX_LEN];
+        let mut ready = true;
+        let mut last_reinit_ms = 0;
+        loop {
+            while let Some(sample) = cx
+                .shared
+                .data_manager
+                .lock(|data_manager| data_manager.take_sd_sample())
+            {
+                cx.shared.em.run(|| {
+                    let framed = postcard::to_slice_cobs(&sample, &mut record)?;
+                    cx.shared.sd_manager.lock(|sd_manager| match sd_manager {
+                        Some(sd_manager) if sd_manager.is_ready() => {
+                            sd_manager.write_log(framed).map(|_| ())
+                        }
+                        _ => Ok(()),
+                    })?;
+                    Ok(())
+                });
+            }
+            let now = now_ms();
+            let sd_ready = cx.shared.sd_manager.lock(|sd_manager| {
+                let sd_manager = sd_manager.as_mut()?;
+                if !sd_manager.is_ready() && now - last_reinit_ms >= SD_REINIT_PERIOD_MS {
+                    last_reinit_ms = now;
+                    cx.shared.em.run(|| {
+                        sd_manager.reinit()?;
+                        Ok(())
+                    });
+                }
+                Some(sd_manager.is_ready())
             });
-            match logging_rate {
-                RadioRate::Fast => {
-                    Mono::delay(100.millis()).await;
+            if let Some(sd_ready) = sd_ready.filter(|sd_ready| *sd_ready != ready) {
+                info!("SD card ready: {}", sd_ready);
+                ready = sd_ready;
+            }
+            Mono::delay(SD_DUMP_PERIOD_MS.millis()).await;
+        }
+    }
+
+    /**
+     * Sends the whole DataManager state as one snapshot, see `DataManager::serialize_state`,
+     * behind the `SendState` command bytes. Spawned by that command, and every
+     * `STATE_SNAPSHOT_HEARTBEATS` heartbeats so the ground sees the slow-changing status, such as
+     * the ground reference and its standard deviation, without asking.
+     */
+    #[task(priority = 1, local = [buf: [u8; RADIO_MAX_MESSAGE_LEN] = [0; RADIO_MAX_MESSAGE_LEN]], shared = [data_manager, radio_manager, &em])]
+    async fn send_state(mut cx: send_state::Context) {
+        let buf = cx.local.buf;
+        let header = GroundCommand::SendState.command();
+        cx.shared.em.run(|| {
+            let len = cx
+                .shared
+                .data_manager
+                .lock(|data_manager| data_manager.serialize_state(&mut buf[header.len()..]))?;
+            buf[..header.len()].copy_from_slice(&header);
+            cx.shared.radio_manager.lock(|radio_manager| {
+                let Some(radio_manager) = radio_manager else {
+                    return Ok(());
+                };
+                radio_manager.send_message(&buf[..header.len() + len])
+            })
+        });
+    }
+
+    /**
+     * Carries out a command from the ground, see `phoenix::GroundCommand`.
+     */
+    #[task(priority = 1, shared = [data_manager])]
+    async fn ground_command(mut cx: ground_command::Context, command: GroundCommand) {
+        info!("Ground command {}", command);
+        match command {
+            GroundCommand::SendState => {
+                if send_state::spawn().is_err() {
+                    info!("State: already sending");
+                }
+            }
+            GroundCommand::CalibrateGroundReference => {
+                let phase = cx.shared.data_manager.lock(|dm| dm.flight_phase());
+                if phase != FlightPhase::Idle {
+                    info!("Ground reference: not on the pad, not recalibrating");
+                    return;
+                }
+                if calibrate_ground_reference::spawn(
+                    0,
+                    GROUND_REFERENCE_SAMPLES,
+                    GROUND_REFERENCE_DURATION_MS,
+                )
+                .is_err()
+                {
+                    info!("Ground reference: already calibrating");
+                }
+            }
+            GroundCommand::CanEchoTest => {
+                let phase = cx.shared.data_manager.lock(|dm| dm.flight_phase());
+                if phase != FlightPhase::Idle {
+                    info!("CAN echo test: not on the pad, not testing");
+                    return;
                 }
-                RadioRate::Slow => {
-                    Mono::delay(250.millis()).await;
+                if can_echo_test::spawn().is_err() {
+                    info!("CAN echo test: already running");
                 }
             }
         }
@@ -527,11 +1125,7 @@ mod app {
     async fn send_gs_intermediate(mut cx: send_gs_intermediate::Context, m: Data) {
         cx.shared.em.run(|| {
             cx.shared.rtc.lock(|rtc| {
-                let message = messages::Message::new(
-                    messages::FormattedNaiveDateTime(rtc.date_time().unwrap()),
-                    COM_ID,
-                    m,
-                );
+                let message = messages::Message::new(message_time(rtc), COM_ID, m);
                 spawn!(send_gs, message)?;
                 Ok(())
             })
@@ -548,28 +1142,221 @@ mod app {
         })
     }
 
-    #[task(priority = 3, shared = [sbg_power])]
-    async fn sbg_power_on(mut cx: sbg_power_on::Context) {
+    /**
+     * Copies the bytes the radio received into the queue for `radio_receive`. Parsing is left to
+     * that task so the interrupt stays short and never waits on a lock.
+     */
+    #[task(priority = 3, binds = UART4, local = [radio_uart_rx, radio_bytes, dropped: u32 = 0])]
+    fn radio_rx(cx: radio_rx::Context) {
+        // Without the radio the interrupt is never enabled.
+        let Some(radio_uart_rx) = cx.local.radio_uart_rx else {
+            return;
+        };
+        while let Ok(byte) = radio_uart_rx.read() {
+            if cx.local.radio_bytes.enqueue(byte).is_err() {
+                *cx.local.dropped += 1;
+            }
+        }
+        if *cx.local.dropped > 0 {
+            info!("Radio: dropped {} received bytes", *cx.local.dropped);
+            *cx.local.dropped = 0;
+        }
+        radio_receive::spawn().ok();
+    }
+
+    /**
+     * Parses the frames the ground sent over the radio and hands them to the DataManager, commands
+     * as `can_command` does for the command bus and anything else as received data. A frame that
+     * isn't complete yet waits for the next bytes. Frames that fail to decode are only logged,
+     * they don't count as errors.
+     */
+    #[task(priority = 2, local = [radio_receiver], shared = [radio_manager, data_manager, &em])]
+    async fn radio_receive(mut cx: radio_receive::Context) {
+        let data_manager = &mut cx.shared.data_manager;
+        let em = cx.shared.em;
+        let received = cx.local.radio_receiver.receive_all(|message| {
+            data_manager.lock(|data_manager| match message.data {
+                Data::Command(_) => em.run(|| data_manager.handle_command(message)),
+                _ => data_manager.handle_data(message, now_ms()),
+            })
+        });
+        if let Err(e) = &received {
+            info!("Radio: {}", e);
+        }
+        // A frame that failed to decode still shows the link is up.
+        if !matches!(received, Ok(0)) {
+            cx.shared.radio_manager.lock(|radio_manager| {
+                if let Some(radio_manager) = radio_manager {
+                    radio_manager.record_receive(now_ms());
+                }
+            });
+        }
+    }
+
+    /**
+     * Samples and reports the estimated load and the frame counters of both CAN buses and the
+     * messages the data queue dropped, and restarts a bus that went bus-off.
+     */
+    #[task(priority = 1, shared = [&em, can_command_manager, can_data_manager, data_queue])]
+    async fn can_load_report(mut cx: can_load_report::Context) {
         loop {
-            cx.shared.sbg_power.lock(|sbg| {
-                sbg.set_high();
+            Mono::delay(CAN_LOAD_REPORT_PERIOD_MS.millis()).await;
+            let now = now_ms();
+            let ((data_tx, data_rx), data_stats) = cx.shared.can_data_manager.lock(|can| {
+                cx.shared.em.run(|| can.check_bus_off());
+                (can.bus_load.sample(now), can.stats())
+            });
+            let ((command_tx, command_rx), command_stats) =
+                cx.shared.can_command_manager.lock(|can| {
+                    cx.shared.em.run(|| can.check_bus_off());
+                    (can.bus_load.sample(now), can.stats())
+                });
+            info!(
+                "CAN load: data tx {}% rx {}%, command tx {}% rx {}%",
+                data_tx, data_rx, command_tx, command_rx
+            );
+            info!("CAN data {}, command {}", data_stats, command_stats);
+            let dropped = cx.shared.data_queue.lock(|queue| queue.dropped());
+            info!("CAN data queue: {} messages dropped", dropped);
+        }
+    }
+
+    /**
+     * Reports the deepest the stack has been since boot, painted at the start of `init`.
+     */
+    #[task(priority = 1)]
+    async fn stack_report(_cx: stack_report::Context) {
+        loop {
+            Mono::delay(STACK_REPORT_PERIOD_MS.millis()).await;
+            let (used, size) = (stack_high_water(), stack_size());
+            if used * 100 > size * STACK_WARN_PERCENT {
+                defmt::warn!("Stack high water: {} of {} bytes", used, size);
+            } else {
+                info!("Stack high water: {} of {} bytes", used, size);
+            }
+        }
+    }
+
+    /**
+     * Sends a MAVLink heartbeat every `HEARTBEAT_PERIOD_MS`, so ground station software sees the
+     * link before any telemetry flows. Each carries the next status sequence number and the
+     * flight readiness. Every `STATE_SNAPSHOT_HEARTBEATS` also sends a state snapshot.
+     */
+    #[task(priority = 1, shared = [&em, data_manager, radio_manager])]
+    async fn heartbeat(mut cx: heartbeat::Context) {
+        loop {
+            let (sequence, ready) = cx.shared.data_manager.lock(|data_manager| {
+                (
+                    data_manager.next_status_sequence(),
+                    data_manager.is_flight_ready(),
+                )
+            });
+            if sequence % STATE_SNAPSHOT_HEARTBEATS == 0 {
+                send_state::spawn().ok();
+            }
+            cx.shared.radio_manager.lock(|radio_manager| {
+                if let Some(radio_manager) = radio_manager {
+                    cx.shared
+                        .em
+                        .run(|| radio_manager.send_heartbeat(sequence, ready))
+                }
+            });
+            Mono::delay(HEARTBEAT_PERIOD_MS.millis()).await;
+        }
+    }
+
+    /**
+     * Bench diagnostic for the command bus wiring. Sends a test frame on the physical bus and
+     * waits for a cooperating node or a loopback cable to send it back. Run on the pad by
+     * `GroundCommand::CanEchoTest`.
+     */
+    #[task(priority = 1, shared = [&em, can_command_manager])]
+    async fn can_echo_test(mut cx: can_echo_test::Context) {
+        let started = cx
+            .shared
+            .can_command_manager
+            .lock(|can| can.start_echo_test(now_ms()));
+        if started.is_err() {
+            cx.shared.em.handle(started);
+            return;
+        }
+        let result = loop {
+            let result = cx
+                .shared
+                .can_command_manager
+                .lock(|can| can.echo_result(now_ms(), CAN_ECHO_TIMEOUT_MS));
+            if result != EchoResult::Pending {
+                break result;
+            }
+            Mono::delay(2.millis()).await;
+        };
+        match result {
+            EchoResult::Passed { round_trip_ms } => {
+                info!("CAN echo test passed, round trip {} ms", round_trip_ms)
+            }
+            _ => defmt::error!(
+                "CAN echo test failed, no echo within {} ms",
+                CAN_ECHO_TIMEOUT_MS
+            ),
+        }
+    }
+
+    /**
+     * Waits for the SBG, which `init` powers on, to boot before checking that it produces data.
+     * If no SBG data arrives within `settle_ms` the SBG is power cycled, up to `retries` more times.
+     * On failure the power is left on in case the SBG comes up late.
+     */
+    #[task(priority = 3, shared = [sbg_power, data_manager])]
+    async fn sbg_power_up(mut cx: sbg_power_up::Context, settle_ms: u32, retries: u8) {
+        for attempt in 0..=retries {
+            if attempt > 0 {
+                cx.shared.sbg_power.lock(|sbg| sbg.set_low());
+                Mono::delay(SBG_POWER_OFF_MS.millis()).await;
+            }
+            let powered_ms = now_ms();
+            cx.shared.sbg_power.lock(|sbg| sbg.set_high());
+            Mono::delay(settle_ms.millis()).await;
+            let up = cx.shared.data_manager.lock(|dm| {
+                let up = dm
+                    .sbg_presence
+                    .last_seen()
+                    .is_some_and(|seen| seen >= powered_ms);
+                if up || attempt == retries {
+                    dm.sbg_started = Some(up);
+                }
+                up
             });
-            Mono::delay(10000.millis()).await;
+            if up {
+                info!("SBG: Up after {} power-on attempts", attempt + 1);
+                return;
+            }
+            defmt::warn!("SBG: No data {} ms after power-on", settle_ms);
         }
+        defmt::warn!(
+            "SBG: Failed to start after {} power-on attempts",
+            retries + 1
+        );
     }
 
     /**
      * Sends a message to the radio over UART.
      */
-    #[task(priority = 3, shared = [&em, radio_manager])]
+    #[task(priority = 3, local = [buf: [u8; RADIO_MAX_MESSAGE_LEN] = [0; RADIO_MAX_MESSAGE_LEN]], shared = [&em, radio_manager])]
     async fn send_gs(mut cx: send_gs::Context, m: Message) {
         // info!("{}", m.clone());
 
+        // The buffer is a task local, statically allocated and only ever used by this task.
+        let buf = cx.local.buf;
         cx.shared.radio_manager.lock(|radio_manager| {
+            let Some(radio_manager) = radio_manager else {
+                return;
+            };
+            if !radio_manager.admit(&m, now_ms()) {
+                return;
+            }
             cx.shared.em.run(|| {
                 // info!("Sending message {}", m);
-                let mut buf = [0; 255];
-                let data = postcard::to_slice(&m, &mut buf)?;
+                let data = postcard::to_slice(&m, &mut buf[..])?;
                 radio_manager.send_message(data)?;
                 Ok(())
             })
@@ -579,33 +1366,41 @@ mod app {
     #[task(priority = 3, binds = FDCAN2_IT0, shared = [&em, can_data_manager, data_manager, madgwick_service])]
     fn can_data(mut cx: can_data::Context) {
         cx.shared.can_data_manager.lock(|can| {
-            while let Ok(Some(message)) = can.receive_message() {
-                // process IMU data through madgwick service
+            while let Ok(Some((message, received_ms))) = can.receive_message() {
+                // process IMU data through madgwick service, timed by when it arrived
                 cx.shared.madgwick_service.lock(|madgwick| {
-                    if let Some(result) = madgwick.process_imu_data(&message) {
-                        cx.shared.data_manager.lock(|dm| {
-                            dm.store_madgwick_result(result);
-                        });
-                    }
+                    let result = madgwick.process_imu_data(&message, received_ms);
+                    let accel_clipped = madgwick.accel_clipped();
+                    cx.shared.data_manager.lock(|dm| {
+                        if let Some(result) = result {
+                            dm.store_madgwick_result(result, received_ms);
+                        }
+                        dm.accel_clipped |= accel_clipped;
+                        dm.active_imu = madgwick.active_imu();
+                        dm.imu_disagreement = madgwick.imu_disagreement();
+                        dm.handle_data(message, received_ms);
+                    });
                 });
             }
             cx.shared.em.run(|| Ok(()))
         });
     }
 
-    #[task(priority = 2, shared = [&em, can_data_manager, data_manager])]
+    #[task(priority = 2, shared = [&em, can_data_manager, data_manager, data_queue])]
     async fn send_data_internal(
         mut cx: send_data_internal::Context,
-        mut receiver: Receiver<'static, Message, DATA_CHANNEL_CAPACITY>,
+        mut doorbell: Receiver<'static, (), 1>,
     ) {
         loop {
-            if let Ok(m) = receiver.recv().await {
-                cx.shared.can_data_manager.lock(|can| {
-                    cx.shared.em.run(|| {
-                        can.send_message(m)?;
-                        Ok(())
-                    })
-                });
+            if let Ok(()) = doorbell.recv().await {
+                while let Some(m) = cx.shared.data_queue.lock(|queue| queue.pop()) {
+                    cx.shared.can_data_manager.lock(|can| {
+                        cx.shared.em.run(|| {
+                            can.send_message(m)?;
+                            Ok(())
+                        })
+                    });
+                }
             }
         }
     }
@@ -622,9 +1417,36 @@ mod app {
         // }
     }
 
-    #[task(priority = 1, local = [led_red, led_green, buzzer, buzzed: bool = false], shared = [&em])]
-    async fn blink(cx: blink::Context) {
+    #[task(priority = 1, local = [led_red, led_green, buzzer, buzzer_timer_clock, boot_status, buzzed: bool = false, was_ready: bool = false], shared = [&em, data_manager])]
+    async fn blink(mut cx: blink::Context) {
+        // Play back the boot codes once, see `boot_status` for the table.
+        cx.local.led_red.set_low();
+        cx.local.led_green.set_low();
+        for subsystem in Subsystem::ALL {
+            let Some(up) = cx.local.boot_status.get(subsystem) else {
+                continue;
+            };
+            for _ in 0..subsystem.flashes() {
+                if up {
+                    cx.local.led_green.set_high();
+                } else {
+                    cx.local.led_red.set_high();
+                }
+                Mono::delay(200.millis()).await;
+                cx.local.led_red.set_low();
+                cx.local.led_green.set_low();
+                Mono::delay(300.millis()).await;
+            }
+            Mono::delay(1000.millis()).await;
+        }
+
         loop {
+            let shutdown = cx.shared.data_manager.lock(|data_manager| {
+                data_manager.landing_shutdown.phase() == ShutdownPhase::Shutdown
+            });
+            if shutdown {
+                break;
+            }
             if cx.shared.em.has_error() {
                 cx.local.led_red.toggle();
                 if *cx.local.buzzed {
@@ -637,6 +1459,14 @@ mod app {
                 }
                 Mono::delay(500.millis()).await;
             } else {
+                let ready = cx
+                    .shared
+                    .data_manager
+                    .lock(|data_manager| data_manager.update_readiness(now_ms()));
+                if ready != *cx.local.was_ready {
+                    info!("Flight ready: {}", ready);
+                    *cx.local.was_ready = ready;
+                }
                 cx.local.led_green.toggle();
                 if *cx.local.buzzed {
                     cx.local.buzzer.set_duty(0);
@@ -646,9 +1476,129 @@ mod app {
                     cx.local.buzzer.set_duty(duty);
                     *cx.local.buzzed = true;
                 }
-                Mono::delay(2000.millis()).await;
+                // Blink faster once every required sensor reports valid data.
+                if ready {
+                    Mono::delay(500.millis()).await;
+                } else {
+                    Mono::delay(2000.millis()).await;
+                }
             }
         }
+
+        // Locator mode, the same beeps whatever the error and readiness state. The LEDs can't be
+        // seen from any distance, so they stay off to save the battery.
+        cx.local.led_red.set_low();
+        cx.local.led_green.set_low();
+        buzzer::set_frequency(*cx.local.buzzer_timer_clock, LOCATOR_BUZZER_FREQUENCY_HZ);
+        loop {
+            // Half duty is the loudest square wave.
+            let duty = cx.local.buzzer.get_max_duty() / 2;
+            cx.local.buzzer.set_duty(duty);
+            Mono::delay(LOCATOR_BEEP_MS.millis()).await;
+            cx.local.buzzer.set_duty(0);
+            Mono::delay(LOCATOR_PAUSE_MS.millis()).await;
+        }
+    }
+
+    /**
+     * Re-evaluates the orientation sources and the SBG presence every `MONITOR_PERIOD_MS`, so a
+     * loss of all orientation sources inhibits the tilt triggers even when no IMU messages arrive
+     * and altitude falls back to the baro within a period of the SBG timeout. Also writes the
+     * flight summary once landed and enters the locator mode when the landing shutdown is due.
+     * Runs on a fixed period from boot, independent of the `blink` cadence and its boot code
+     * playback.
+     */
+    #[task(priority = 1, local = [landed: bool = false], shared = [&em, data_manager, sd_manager])]
+    async fn monitor(mut cx: monitor::Context) {
+        let mut next = Mono::now();
+        loop {
+            let (landed, shutdown_due) = cx.shared.data_manager.lock(|data_manager| {
+                data_manager.update_orientation(now_ms());
+                data_manager.check_sbg(now_ms());
+                (
+                    data_manager.flight_phase() == FlightPhase::Landed,
+                    data_manager.landing_shutdown.is_due(now_ms()),
+                )
+            });
+            // The flight log has to be on the card before the shutdown. A card that isn't ready
+            // has nothing left to flush, waiting on it would keep everything powered for good.
+            let logs_flushed = shutdown_due
+                && cx.shared.sd_manager.lock(|sd_manager| match sd_manager {
+                    Some(sd_manager) if sd_manager.is_ready() => {
+                        cx.shared.em.run(|| {
+                            sd_manager.flush_log()?;
+                            Ok(())
+                        });
+                        // A failed flush marks the card not ready.
+                        sd_manager.is_ready()
+                    }
+                    _ => true,
+                });
+            let shutdown = cx
+                .shared
+                .data_manager
+                .lock(|data_manager| data_manager.landing_shutdown.poll(now_ms(), logs_flushed));
+            if landed && !*cx.local.landed {
+                *cx.local.landed = true;
+                flight_summary::spawn().ok();
+            }
+            if shutdown {
+                // `blink` switches to the locator beeps.
+                post_landing_shutdown::spawn().ok();
+            }
+            next += MONITOR_PERIOD_MS.millis();
+            Mono::delay_until(next).await;
+        }
+    }
+
+    /**
+     * Appends the summary of this session to the SD flight index once the vehicle has landed.
+     */
+    #[task(priority = 1, local = [flight_session], shared = [data_manager, sd_manager, &em])]
+    async fn flight_summary(mut cx: flight_summary::Context) {
+        let Some(session) = cx.local.flight_session.as_ref() else {
+            return;
+        };
+        let (peak_altitude_m, boost_accel_clipped) = cx.shared.data_manager.lock(|data_manager| {
+            (
+                data_manager.peak_altitude_above_pad(),
+                data_manager.boost_accel_clipped,
+            )
+        });
+        let record = FlightRecord {
+            duration_s: Some((now_ms() / 1000) as u32),
+            peak_altitude_m,
+            boost_accel_clipped: Some(boost_accel_clipped),
+            ..session.boot_record()
+        };
+        cx.shared.sd_manager.lock(|sd_manager| {
+            if let Some(sd_manager) = sd_manager {
+                cx.shared.em.run(|| {
+                    sd_manager.append_index(&record)?;
+                    Ok(())
+                });
+            }
+        });
+    }
+
+    /**
+     * Locator mode after landing: powers down what isn't needed to find the vehicle. Both CAN
+     * buses go quiet, the radio carries the locator telemetry.
+     */
+    #[task(priority = 1, shared = [sbg_power, can_command_manager, can_data_manager])]
+    async fn post_landing_shutdown(mut cx: post_landing_shutdown::Context) {
+        info!("Entering post-landing locator mode");
+        if POST_LANDING_SBG_OFF {
+            cx.shared.sbg_power.lock(|sbg| {
+                sbg.set_low();
+            });
+        }
+        cx.shared
+            .can_command_manager
+            .lock(|can_command_manager| can_command_manager.power_down());
+        cx.shared
+            .can_data_manager
+            .lock(|can_data_manager| can_data_manager.power_down());
     }
 
     #[task(priority = 3, shared = [&em, sbg_power])]
@@ -0,0 +1,254 @@
+//! Incremental splitting of a received MAVLink 2 byte stream into frames.
+//!
+//! Bytes are appended as they arrive, in any chunks. [`FrameParser::next_frame`] returns the
+//! frames that are complete and stops at one whose tail hasn't arrived yet, so nothing ever waits
+//! on the UART. Bytes before a start marker and frames with a bad checksum are skipped.
+
+/// Start marker of a MAVLink 2 frame.
+pub const MAV_STX_V2: u8 = 0xFD;
+/// Start marker, payload length, incompatible and compatible flags, sequence, system ID,
+/// component ID and the 3 byte message ID.
+const HEADER_LEN: usize = 10;
+const CHECKSUM_LEN: usize = 2;
+const SIGNATURE_LEN: usize = 13;
+/// Incompatible flag set on signed frames, which carry a signature after the checksum.
+const INCOMPAT_FLAG_SIGNED: u8 = 0x01;
+/// Longest MAVLink 2 frame, a signed one with a full payload.
+pub const MAV_MAX_FRAME_LEN: usize = HEADER_LEN + 255 + CHECKSUM_LEN + SIGNATURE_LEN;
+
+/// A complete frame with a valid checksum.
+pub struct MavFrame<'a> {
+    pub system_id: u8,
+    pub component_id: u8,
+    pub message_id: u32,
+    /// Payload as sent, MAVLink 2 drops its trailing zeros.
+    pub payload: &'a [u8],
+}
+
+/// Buffers received bytes until they complete a frame. `N` must hold at least one frame of
+/// [`MAV_MAX_FRAME_LEN`].
+pub struct FrameParser<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    /// Length of the frame returned by the last `next_frame`, removed on the next call.
+    taken: usize,
+    /// Bytes dropped because the buffer was full.
+    overflowed: u32,
+    /// Frames dropped because of a bad checksum.
+    bad_checksums: u32,
+}
+
+impl<const N: usize> FrameParser<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            taken: 0,
+            overflowed: 0,
+            bad_checksums: 0,
+        }
+    }
+
+    /// Appends received bytes. Bytes that don't fit are dropped and counted, the frame they
+    /// belonged to then fails its checksum.
+    pub fn extend(&mut self, bytes: &[u8]) {
+        self.discard(self.taken);
+        self.taken = 0;
+        let free = N - self.len;
+        let accepted = bytes.len().min(free);
+        self.buf[self.len..self.len + accepted].copy_from_slice(&bytes[..accepted]);
+        self.len += accepted;
+        self.overflowed += (bytes.len() - accepted) as u32;
+    }
+
+    /// Returns the next complete frame, or `None` once the buffered bytes hold no more. The
+    /// bytes of an incomplete frame stay buffered for the next call. `extra_crc` gives the
+    /// MAVLink CRC_EXTRA of a message ID, as `mavlink::Message::extra_crc` does.
+    pub fn next_frame(&mut self, extra_crc: impl Fn(u32) -> u8) -> Option<MavFrame<'_>> {
+        self.discard(self.taken);
+        self.taken = 0;
+        loop {
+            let start = self.buf[..self.len]
+                .iter()
+                .position(|&byte| byte == MAV_STX_V2)
+                .unwrap_or(self.len);
+            self.discard(start);
+            if self.len < HEADER_LEN {
+                return None;
+            }
+            let payload_len = self.buf[1] as usize;
+            let signature_len = if self.buf[2] & INCOMPAT_FLAG_SIGNED != 0 {
+                SIGNATURE_LEN
+            } else {
+                0
+            };
+            let frame_len = HEADER_LEN + payload_len + CHECKSUM_LEN + signature_len;
+            if frame_len > N {
+                // Can never complete here, look for the next start marker.
+                self.discard(1);
+                continue;
+            }
+            if self.len < frame_len {
+                return None;
+            }
+            let message_id = u32::from_le_bytes([self.buf[7], self.buf[8], self.buf[9], 0]);
+            let checksum_at = HEADER_LEN + payload_len;
+            let sent = u16::from_le_bytes([self.buf[checksum_at], self.buf[checksum_at + 1]]);
+            if mav_checksum(&self.buf[1..checksum_at], extra_crc(message_id)) != sent {
+                // Maybe a start marker inside other data, resynchronize after it.
+                self.bad_checksums += 1;
+                self.discard(1);
+                continue;
+            }
+            self.taken = frame_len;
+            return Some(MavFrame {
+                system_id: self.buf[5],
+                component_id: self.buf[6],
+                message_id,
+                payload: &self.buf[HEADER_LEN..checksum_at],
+            });
+        }
+    }
+
+    /// Number of bytes buffered, including an incomplete frame.
+    pub fn buffered(&self) -> usize {
+        self.len - self.taken
+    }
+
+    pub fn overflowed(&self) -> u32 {
+        self.overflowed
+    }
+
+    pub fn bad_checksums(&self) -> u32 {
+        self.bad_checksums
+    }
+
+    /// Drops the first `count` buffered bytes.
+    fn discard(&mut self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        self.buf.copy_within(count..self.len, 0);
+        self.len -= count;
+    }
+}
+
+impl<const N: usize> Default for FrameParser<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// MAVLink checksum, CRC-16/MCRF4XX over the frame after the start marker followed by the
+/// message's CRC_EXTRA.
+pub fn mav_checksum(data: &[u8], extra_crc: u8) -> u16 {
+    data.iter()
+        .chain(core::iter::once(&extra_crc))
+        .fold(0xFFFF, |crc: u16, &byte| {
+            let tmp = byte ^ crc as u8;
+            let tmp = tmp ^ (tmp << 4);
+            (crc >> 8) ^ ((tmp as u16) << 8) ^ ((tmp as u16) << 3) ^ ((tmp as u16) >> 4)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::Vec;
+
+    const EXTRA_CRC: u8 = 50;
+    const BUF_LEN: usize = 512;
+
+    fn extra_crc(_: u32) -> u8 {
+        EXTRA_CRC
+    }
+
+    /// An unsigned frame as a sender encodes it.
+    fn frame(sequence: u8, message_id: u32, payload: &[u8]) -> Vec<u8, MAV_MAX_FRAME_LEN> {
+        let mut frame = Vec::new();
+        let id = message_id.to_le_bytes();
+        frame
+            .extend_from_slice(&[MAV_STX_V2, payload.len() as u8, 0, 0, sequence, 1, 2])
+            .unwrap();
+        frame.extend_from_slice(&id[..3]).unwrap();
+        frame.extend_from_slice(payload).unwrap();
+        let crc = mav_checksum(&frame[1..], EXTRA_CRC);
+        frame.extend_from_slice(&crc.to_le_bytes()).unwrap();
+        frame
+    }
+
+    #[test]
+    fn checksum_matches_mavlink() {
+        // CRC-16/MCRF4XX check value.
+        assert_eq!(mav_checksum(b"12345678", b'9'), 0x6F91);
+    }
+
+    #[test]
+    fn stops_at_an_incomplete_frame() {
+        let frame = frame(0, 42, &[1, 2, 3, 4]);
+        let mut parser: FrameParser<BUF_LEN> = FrameParser::new();
+        let (head, tail) = frame.split_at(7);
+        parser.extend(head);
+        assert!(parser.next_frame(extra_crc).is_none());
+        assert_eq!(parser.buffered(), head.len());
+        let (middle, tail) = tail.split_at(tail.len() - 1);
+        parser.extend(middle);
+        assert!(parser.next_frame(extra_crc).is_none());
+
+        parser.extend(tail);
+        let parsed = parser.next_frame(extra_crc).unwrap();
+        assert_eq!(parsed.message_id, 42);
+        assert_eq!(parsed.system_id, 1);
+        assert_eq!(parsed.component_id, 2);
+        assert_eq!(parsed.payload, &[1, 2, 3, 4]);
+        assert!(parser.next_frame(extra_crc).is_none());
+        assert_eq!(parser.buffered(), 0);
+    }
+
+    #[test]
+    fn returns_every_frame_of_a_burst_and_keeps_the_partial_one() {
+        let first = frame(0, 1, &[10]);
+        let second = frame(1, 2, &[20, 21]);
+        let third = frame(2, 3, &[30, 31, 32]);
+        let mut parser: FrameParser<BUF_LEN> = FrameParser::new();
+        parser.extend(&first);
+        parser.extend(&second);
+        parser.extend(&third[..5]);
+
+        assert_eq!(parser.next_frame(extra_crc).unwrap().payload, &[10]);
+        assert_eq!(parser.next_frame(extra_crc).unwrap().payload, &[20, 21]);
+        assert!(parser.next_frame(extra_crc).is_none());
+        parser.extend(&third[5..]);
+        assert_eq!(parser.next_frame(extra_crc).unwrap().payload, &[30, 31, 32]);
+    }
+
+    #[test]
+    fn skips_noise_and_bad_checksums() {
+        let mut corrupt = frame(0, 7, &[1, 2, 3]);
+        corrupt[HEADER_LEN] ^= 0xFF;
+        let good = frame(1, 7, &[4, 5, 6]);
+        let mut parser: FrameParser<BUF_LEN> = FrameParser::new();
+        parser.extend(&[0x00, 0x55, 0xAA]);
+        parser.extend(&corrupt);
+        parser.extend(&good);
+
+        assert_eq!(parser.next_frame(extra_crc).unwrap().payload, &[4, 5, 6]);
+        assert_eq!(parser.bad_checksums(), 1);
+        assert!(parser.next_frame(extra_crc).is_none());
+    }
+
+    #[test]
+    fn signed_frames_include_the_signature() {
+        let mut signed = frame(0, 9, &[1]);
+        signed[2] = INCOMPAT_FLAG_SIGNED;
+        let crc_at = signed.len() - CHECKSUM_LEN;
+        let crc = mav_checksum(&signed[1..crc_at], EXTRA_CRC);
+        signed[crc_at..].copy_from_slice(&crc.to_le_bytes());
+        let mut parser: FrameParser<BUF_LEN> = FrameParser::new();
+        parser.extend(&signed);
+        assert!(parser.next_frame(extra_crc).is_none());
+        parser.extend(&[0; SIGNATURE_LEN]);
+        assert_eq!(parser.next_frame(extra_crc).unwrap().payload, &[1]);
+        assert_eq!(parser.buffered(), 0);
+    }
+}
@@ -0,0 +1,144 @@
+//! Orientation source selection with an explicit invalid state.
+//!
+//! The SBG EKF quaternion is preferred and the Madgwick quaternion is the fallback. If neither
+//! has produced a finite quaternion within `max_age_ms`, the orientation is reported invalid
+//! instead of the last known value, and anything that depends on attitude (tilt-based triggers)
+//! must stand down and leave recovery to the baro-only logic.
+//!
+//! Quaternions are `[w, x, y, z]`.
+
+use defmt::{info, warn};
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum OrientationSource {
+    Ekf,
+    Madgwick,
+    Invalid,
+}
+
+#[derive(Clone)]
+pub struct OrientationFallback {
+    /// Age after which a quaternion is no longer trusted, in ms.
+    max_age_ms: u64,
+    ekf: Option<([f32; 4], u64)>,
+    madgwick: Option<([f32; 4], u64)>,
+    /// Source reported by the previous call to [`Self::current`], to log changes.
+    last_source: OrientationSource,
+}
+
+impl OrientationFallback {
+    pub fn new(max_age_ms: u64) -> Self {
+        Self {
+            max_age_ms,
+            ekf: None,
+            madgwick: None,
+            last_source: OrientationSource::Invalid,
+        }
+    }
+
+    /// Feeds an EKF quaternion. Non-finite or zero quaternions are ignored.
+    pub fn update_ekf(&mut self, quat: [f32; 4], now_ms: u64) {
+        if is_usable(&quat) {
+            self.ekf = Some((quat, now_ms));
+        }
+    }
+
+    /// Feeds a Madgwick quaternion. Non-finite or zero quaternions are ignored.
+    pub fn update_madgwick(&mut self, quat: [f32; 4], now_ms: u64) {
+        if is_usable(&quat) {
+            self.madgwick = Some((quat, now_ms));
+        }
+    }
+
+    /// Returns the best available orientation and where it came from, or `None` with
+    /// [`OrientationSource::Invalid`] if no source is fresh.
+    pub fn current(&mut self, now_ms: u64) -> (OrientationSource, Option<[f32; 4]>) {
+        let (source, quat) = if let Some(quat) = self.fresh(self.ekf, now_ms) {
+            (OrientationSource::Ekf, Some(quat))
+        } else if let Some(quat) = self.fresh(self.madgwick, now_ms) {
+            (OrientationSource::Madgwick, Some(quat))
+        } else {
+            (OrientationSource::Invalid, None)
+        };
+        if source != self.last_source {
+            match source {
+                OrientationSource::Invalid => {
+                    warn!("All orientation sources lost, tilt triggers inhibited")
+                }
+                _ => info!("Orientation source is now {}", source),
+            }
+            self.last_source = source;
+        }
+        (source, quat)
+    }
+
+    /// Source reported by the last call to [`Self::current`].
+    pub fn source(&self) -> OrientationSource {
+        self.last_source
+    }
+
+    /// Returns true if triggers that depend on attitude may be used. When false, recovery must
+    /// rely on the baro alone.
+    pub fn tilt_triggers_allowed(&mut self, now_ms: u64) -> bool {
+        self.current(now_ms).0 != OrientationSource::Invalid
+    }
+
+    pub fn set_max_age(&mut self, max_age_ms: u64) {
+        self.max_age_ms = max_age_ms;
+    }
+
+    fn fresh(&self, sample: Option<([f32; 4], u64)>, now_ms: u64) -> Option<[f32; 4]> {
+        sample
+            .filter(|(_, at_ms)| now_ms.saturating_sub(*at_ms) <= self.max_age_ms)
+            .map(|(quat, _)| quat)
+    }
+}
+
+fn is_usable(quat: &[f32; 4]) -> bool {
+    quat.iter().all(|q| q.is_finite()) && quat.iter().any(|q| *q != 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_AGE_MS: u64 = 200;
+    const LEVEL: [f32; 4] = [1.0, 0.0, 0.0, 0.0];
+    const TILTED: [f32; 4] = [0.966, 0.259, 0.0, 0.0];
+
+    #[test]
+    fn ekf_is_preferred_over_madgwick() {
+        let mut orientation = OrientationFallback::new(MAX_AGE_MS);
+        orientation.update_ekf(LEVEL, 0);
+        orientation.update_madgwick(TILTED, 0);
+        assert!(orientation.current(100) == (OrientationSource::Ekf, Some(LEVEL)));
+    }
+
+    #[test]
+    fn madgwick_takes_over_when_the_ekf_goes_stale() {
+        let mut orientation = OrientationFallback::new(MAX_AGE_MS);
+        orientation.update_ekf(LEVEL, 0);
+        orientation.update_madgwick(TILTED, 250);
+        assert!(orientation.current(300) == (OrientationSource::Madgwick, Some(TILTED)));
+    }
+
+    #[test]
+    fn losing_all_imu_data_invalidates_the_orientation() {
+        let mut orientation = OrientationFallback::new(MAX_AGE_MS);
+        orientation.update_ekf(LEVEL, 0);
+        orientation.update_madgwick(TILTED, 0);
+        assert!(orientation.tilt_triggers_allowed(100));
+
+        // Both sources stop, the last quaternion must not be reported as current.
+        assert!(orientation.current(1000) == (OrientationSource::Invalid, None));
+        assert!(!orientation.tilt_triggers_allowed(1000));
+    }
+
+    #[test]
+    fn non_finite_output_is_ignored() {
+        let mut orientation = OrientationFallback::new(MAX_AGE_MS);
+        orientation.update_madgwick([f32::NAN, 0.0, 0.0, 0.0], 0);
+        orientation.update_ekf([0.0; 4], 0);
+        assert!(orientation.current(0) == (OrientationSource::Invalid, None));
+    }
+}
@@ -0,0 +1,215 @@
+//! Consistency check between the raw gyro rates and an external orientation solution (the SBG EKF).
+//!
+//! At the start of each window the EKF quaternion is taken as the reference and the gyro rates are
+//! integrated from it. At the end of the window the integrated orientation is compared against
+//! the EKF quaternion again. Over a short window gyro drift is small, so a large angle between the
+//! two points at an EKF divergence or a sensor fault. Only disagreement that lasts for several
+//! windows in a row is flagged.
+//!
+//! Quaternions are `[w, x, y, z]` rotating the body frame into the navigation frame, gyro rates
+//! are body frame rates in rad/s.
+
+use defmt::{info, warn};
+
+#[derive(Clone)]
+pub struct OrientationMonitor {
+    /// Angle between the integrated and EKF orientation above which a window disagrees, in rad.
+    threshold: f32,
+    /// Number of disagreeing windows in a row before the check is flagged.
+    sustain: u8,
+    /// Length of one comparison window, in ms.
+    window_ms: u64,
+    /// Gap in either input after which the current window is dropped, in ms.
+    max_gap_ms: u64,
+    /// Time between gyro samples, in s.
+    sample_period: f32,
+    /// Orientation integrated from the gyro since the start of the window.
+    integrated: [f32; 4],
+    window_start_ms: Option<u64>,
+    last_gyro_ms: Option<u64>,
+    /// Angle found at the end of the last complete window, in rad.
+    disagreement: Option<f32>,
+    disagreeing_windows: u8,
+    diverged: bool,
+}
+
+impl OrientationMonitor {
+    pub fn new(threshold: f32, sustain: u8, window_ms: u64, sample_period: f32) -> Self {
+        Self {
+            threshold,
+            sustain: sustain.max(1),
+            window_ms,
+            max_gap_ms: window_ms / 2,
+            sample_period,
+            integrated: [1.0, 0.0, 0.0, 0.0],
+            window_start_ms: None,
+            last_gyro_ms: None,
+            disagreement: None,
+            disagreeing_windows: 0,
+            diverged: false,
+        }
+    }
+
+    /// Feeds a gyro sample in rad/s.
+    pub fn update_gyro(&mut self, gyro: [f32; 3], now_ms: u64) {
+        if self.is_stale(self.last_gyro_ms, now_ms) {
+            // Missed samples can't be integrated, so wait for the next EKF reference.
+            self.window_start_ms = None;
+        }
+        self.last_gyro_ms = Some(now_ms);
+        if self.window_start_ms.is_none() {
+            return;
+        }
+
+        // q' = q + 0.5 * q * (0, w) * dt
+        let [w, x, y, z] = self.integrated;
+        let h = 0.5 * self.sample_period;
+        let (gx, gy, gz) = (gyro[0] * h, gyro[1] * h, gyro[2] * h);
+        self.integrated = normalize([
+            w - x * gx - y * gy - z * gz,
+            x + w * gx + y * gz - z * gy,
+            y + w * gy - x * gz + z * gx,
+            z + w * gz + x * gy - y * gx,
+        ]);
+    }
+
+    /// Feeds an EKF quaternion. Closes the current window if it has run its length.
+    pub fn update_ekf(&mut self, quat: [f32; 4], now_ms: u64) {
+        let gyro_stale = self.is_stale(self.last_gyro_ms, now_ms);
+        if let Some(start) = self.window_start_ms {
+            if gyro_stale || now_ms.saturating_sub(start) > self.window_ms + self.max_gap_ms {
+                // Either input went missing during the window, pause the check.
+                self.window_start_ms = None;
+            } else if now_ms.saturating_sub(start) < self.window_ms {
+                return;
+            } else {
+                self.evaluate(angle_between(self.integrated, quat));
+            }
+        }
+        if !gyro_stale {
+            self.integrated = normalize(quat);
+            self.window_start_ms = Some(now_ms);
+        }
+    }
+
+    fn evaluate(&mut self, angle: f32) {
+        self.disagreement = Some(angle);
+        if angle > self.threshold {
+            self.disagreeing_windows = self.disagreeing_windows.saturating_add(1);
+            if self.disagreeing_windows >= self.sustain && !self.diverged {
+                warn!(
+                    "EKF orientation disagrees with the gyro by {} rad for {} windows",
+                    angle, self.disagreeing_windows
+                );
+                self.diverged = true;
+            }
+        } else {
+            self.disagreeing_windows = 0;
+            if self.diverged {
+                info!("EKF orientation consistent with the gyro again");
+                self.diverged = false;
+            }
+        }
+    }
+
+    fn is_stale(&self, last_ms: Option<u64>, now_ms: u64) -> bool {
+        last_ms.is_none_or(|last| now_ms.saturating_sub(last) > self.max_gap_ms)
+    }
+
+    /// Angle between the integrated and EKF orientation at the end of the last window, in rad.
+    pub fn disagreement(&self) -> Option<f32> {
+        self.disagreement
+    }
+
+    /// Returns true while the disagreement has been above the threshold for `sustain` windows.
+    pub fn is_diverged(&self) -> bool {
+        self.diverged
+    }
+
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    pub fn set_sustain(&mut self, sustain: u8) {
+        self.sustain = sustain.max(1);
+    }
+
+    pub fn set_window(&mut self, window_ms: u64) {
+        self.window_ms = window_ms;
+        self.max_gap_ms = window_ms / 2;
+    }
+
+    pub fn set_sample_period(&mut self, sample_period: f32) {
+        self.sample_period = sample_period;
+    }
+}
+
+fn normalize(q: [f32; 4]) -> [f32; 4] {
+    let norm = libm::sqrtf(q[0] * q[0] + q[1] * q[1] + q[2] * q[2] + q[3] * q[3]);
+    if norm == 0.0 {
+        return [1.0, 0.0, 0.0, 0.0];
+    }
+    [q[0] / norm, q[1] / norm, q[2] / norm, q[3] / norm]
+}
+
+/// Rotation angle between two unit quaternions, in rad.
+fn angle_between(a: [f32; 4], b: [f32; 4]) -> f32 {
+    let b = normalize(b);
+    let dot = a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3];
+    2.0 * libm::acosf(libm::fabsf(dot).min(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLD: f32 = 0.05;
+    const SUSTAIN: u8 = 3;
+    const WINDOW_MS: u64 = 100;
+    const SAMPLE_PERIOD: f32 = 0.01;
+    const YAW_RATE: f32 = 1.0;
+
+    /// Orientation after yawing at `YAW_RATE` for `t_ms`.
+    fn yawed(t_ms: u64) -> [f32; 4] {
+        let half = 0.5 * YAW_RATE * t_ms as f32 / 1000.0;
+        [libm::cosf(half), 0.0, 0.0, libm::sinf(half)]
+    }
+
+    /// Runs one second of 100 Hz gyro and 50 Hz EKF samples while yawing. `ekf` gives the EKF output
+    /// at a time, `gyro_until_ms` is when the gyro stops reporting.
+    fn run(monitor: &mut OrientationMonitor, ekf: fn(u64) -> [f32; 4], gyro_until_ms: u64) {
+        for t in (0..1000).step_by(10) {
+            if t % 20 == 0 {
+                monitor.update_ekf(ekf(t), t);
+            }
+            if t < gyro_until_ms {
+                monitor.update_gyro([0.0, 0.0, YAW_RATE], t);
+            }
+        }
+    }
+
+    #[test]
+    fn consistent_inputs_are_not_flagged() {
+        let mut monitor = OrientationMonitor::new(THRESHOLD, SUSTAIN, WINDOW_MS, SAMPLE_PERIOD);
+        run(&mut monitor, yawed, u64::MAX);
+        assert!(monitor.disagreement().unwrap() < THRESHOLD);
+        assert!(!monitor.is_diverged());
+    }
+
+    #[test]
+    fn divergent_ekf_is_flagged() {
+        let mut monitor = OrientationMonitor::new(THRESHOLD, SUSTAIN, WINDOW_MS, SAMPLE_PERIOD);
+        // The EKF claims the vehicle is still while the gyro shows it yawing.
+        run(&mut monitor, |_| [1.0, 0.0, 0.0, 0.0], u64::MAX);
+        assert!(monitor.disagreement().unwrap() > THRESHOLD);
+        assert!(monitor.is_diverged());
+    }
+
+    #[test]
+    fn missing_gyro_pauses_the_check() {
+        let mut monitor = OrientationMonitor::new(THRESHOLD, SUSTAIN, WINDOW_MS, SAMPLE_PERIOD);
+        run(&mut monitor, |_| [1.0, 0.0, 0.0, 0.0], 0);
+        assert!(monitor.disagreement().is_none());
+        assert!(!monitor.is_diverged());
+    }
+}
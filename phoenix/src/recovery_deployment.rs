@@ -0,0 +1,99 @@
+//! When to deploy recovery, once per flight.
+//!
+//! Deployment is due from apogee on, and from the descent in case apogee was never detected. While
+//! it is due, the [`SpinInhibit`] may hold it off until the spin rate decays or its backup timer
+//! runs out.
+
+use crate::{FlightPhase, SpinInhibit};
+use defmt::info;
+
+#[derive(Clone)]
+pub struct RecoveryDeployment {
+    /// Fed the gyro rates, holds off a due deployment while the vehicle spins.
+    pub spin_inhibit: SpinInhibit,
+    /// Time deployment fired, in ms since boot.
+    deployed_at_ms: Option<u64>,
+}
+
+impl RecoveryDeployment {
+    pub fn new(spin_inhibit: SpinInhibit) -> Self {
+        Self {
+            spin_inhibit,
+            deployed_at_ms: None,
+        }
+    }
+
+    /// Call on every flight phase update. Returns true on the update deployment should fire, at
+    /// most once.
+    pub fn update(&mut self, phase: FlightPhase, now_ms: u64) -> bool {
+        if self.deployed_at_ms.is_some()
+            || !matches!(phase, FlightPhase::Apogee | FlightPhase::Descent)
+            || !self.spin_inhibit.deploy_allowed(now_ms)
+        {
+            return false;
+        }
+        info!("Deploying recovery");
+        self.spin_inhibit.reset();
+        self.deployed_at_ms = Some(now_ms);
+        true
+    }
+
+    pub fn deployed_at(&self) -> Option<u64> {
+        self.deployed_at_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLD: f32 = 10.0;
+    const MAX_DELAY_MS: u64 = 3000;
+    const SPINNING: [f32; 3] = [0.0, 0.0, 15.0];
+    const STILL: [f32; 3] = [0.0, 0.1, 0.0];
+
+    fn deployment(gyro: [f32; 3]) -> RecoveryDeployment {
+        let mut deployment = RecoveryDeployment::new(SpinInhibit::new(THRESHOLD, MAX_DELAY_MS));
+        deployment.spin_inhibit.update(gyro);
+        deployment
+    }
+
+    #[test]
+    fn deploys_once_from_apogee() {
+        let mut deployment = deployment(STILL);
+        assert!(!deployment.update(FlightPhase::Coast, 0));
+        assert!(deployment.update(FlightPhase::Apogee, 100));
+        assert!(!deployment.update(FlightPhase::Apogee, 110));
+        assert!(!deployment.update(FlightPhase::Descent, 200));
+        assert_eq!(deployment.deployed_at(), Some(100));
+    }
+
+    #[test]
+    fn held_off_while_spinning() {
+        let mut deployment = deployment(SPINNING);
+        for now_ms in (1000..1000 + MAX_DELAY_MS).step_by(100) {
+            assert!(!deployment.update(FlightPhase::Apogee, now_ms));
+        }
+        assert_eq!(deployment.deployed_at(), None);
+        deployment.spin_inhibit.update(STILL);
+        assert!(deployment.update(FlightPhase::Descent, 1000 + MAX_DELAY_MS - 50));
+    }
+
+    #[test]
+    fn backup_timer_deploys_while_still_spinning() {
+        let mut deployment = deployment(SPINNING);
+        assert!(!deployment.update(FlightPhase::Apogee, 1000));
+        assert!(!deployment.update(FlightPhase::Descent, 1000 + MAX_DELAY_MS - 1));
+        assert!(deployment.update(FlightPhase::Descent, 1000 + MAX_DELAY_MS));
+    }
+
+    #[test]
+    fn spin_before_apogee_does_not_start_the_backup_timer() {
+        let mut deployment = deployment(SPINNING);
+        assert!(!deployment.update(FlightPhase::Boost, 0));
+        assert!(!deployment.update(FlightPhase::Coast, MAX_DELAY_MS));
+        // The hold off only starts once deployment is due.
+        assert!(!deployment.update(FlightPhase::Apogee, 2 * MAX_DELAY_MS));
+        assert!(deployment.update(FlightPhase::Apogee, 3 * MAX_DELAY_MS));
+    }
+}
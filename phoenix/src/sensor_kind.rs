@@ -0,0 +1,132 @@
+//! The kinds of sensor messages phoenix keeps, and which of them come from the SBG.
+
+use messages::sensor::{SbgData, SensorData};
+use messages::{Data, Message};
+
+/// The kinds of sensor messages held by the `DataManager`, in the order used by
+/// `DataManager::take_sensors`.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum SensorKind {
+    Air,
+    EkfNav1,
+    EkfNav2,
+    EkfNavAcc,
+    EkfQuat,
+    MadgwickQuat,
+    Imu1,
+    Imu2,
+    UtcTime,
+    GpsVel,
+    GpsVelAcc,
+    GpsPos1,
+    GpsPos2,
+    GpsPosAcc,
+    NavPosLlh,
+    RecoverySensing,
+}
+
+impl SensorKind {
+    pub const COUNT: usize = 16;
+    /// Every kind, in the order used by `DataManager::take_sensors`.
+    pub const ALL: [SensorKind; Self::COUNT] = [
+        SensorKind::Air,
+        SensorKind::EkfNav1,
+        SensorKind::EkfNav2,
+        SensorKind::EkfNavAcc,
+        SensorKind::EkfQuat,
+        SensorKind::MadgwickQuat,
+        SensorKind::Imu1,
+        SensorKind::Imu2,
+        SensorKind::UtcTime,
+        SensorKind::GpsVel,
+        SensorKind::GpsVelAcc,
+        SensorKind::GpsPos1,
+        SensorKind::GpsPos2,
+        SensorKind::GpsPosAcc,
+        SensorKind::NavPosLlh,
+        SensorKind::RecoverySensing,
+    ];
+
+    /// Kind of a received message, `None` if it isn't a sensor message that is kept. The Madgwick
+    /// orientation is produced on board as an EKF quaternion, so a received one is `EkfQuat`.
+    pub fn of(message: &Message) -> Option<SensorKind> {
+        let Data::Sensor(sensor) = &message.data else {
+            return None;
+        };
+        let kind = match &sensor.data {
+            SensorData::SbgData(sbg_data) => match sbg_data {
+                SbgData::EkfNavAcc(_) => SensorKind::EkfNavAcc,
+                SbgData::GpsPosAcc(_) => SensorKind::GpsPosAcc,
+                SbgData::Air(_) => SensorKind::Air,
+                SbgData::EkfNav1(_) => SensorKind::EkfNav1,
+                SbgData::EkfNav2(_) => SensorKind::EkfNav2,
+                SbgData::EkfQuat(_) => SensorKind::EkfQuat,
+                SbgData::GpsVel(_) => SensorKind::GpsVel,
+                SbgData::GpsVelAcc(_) => SensorKind::GpsVelAcc,
+                SbgData::Imu1(_) => SensorKind::Imu1,
+                SbgData::Imu2(_) => SensorKind::Imu2,
+                SbgData::UtcTime(_) => SensorKind::UtcTime,
+                SbgData::GpsPos1(_) => SensorKind::GpsPos1,
+                SbgData::GpsPos2(_) => SensorKind::GpsPos2,
+            },
+            SensorData::RecoverySensing(_) => SensorKind::RecoverySensing,
+            SensorData::NavPosLlh(_) => SensorKind::NavPosLlh,
+            SensorData::ResetReason(_) => return None,
+        };
+        Some(kind)
+    }
+
+    /// True for the kinds the SBG sends, which show that it is up.
+    pub fn is_sbg(self) -> bool {
+        !matches!(
+            self,
+            SensorKind::MadgwickQuat | SensorKind::NavPosLlh | SensorKind::RecoverySensing
+        )
+    }
+
+    /// Bit of this kind in a mask of kinds.
+    pub fn mask(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ekf_quat_message, sensor_message, EKF_ATTITUDE_VALID};
+    use crate::SourcePresence;
+    use messages::sensor::ResetReason;
+
+    const TIMEOUT_MS: u64 = 5000;
+
+    #[test]
+    fn sbg_messages_are_classified() {
+        assert!(SensorKind::of(&ekf_quat_message(0)) == Some(SensorKind::EkfQuat));
+        assert!(SensorKind::EkfQuat.is_sbg());
+        assert!(SensorKind::of(&sensor_message(ResetReason::PinReset)).is_none());
+        assert!(!SensorKind::MadgwickQuat.is_sbg());
+    }
+
+    #[test]
+    fn received_sbg_message_makes_the_sbg_present() {
+        let mut presence = SourcePresence::new(TIMEOUT_MS);
+        presence.check(0);
+        assert!(presence.check(TIMEOUT_MS + 1));
+
+        // As `DataManager::handle_data` does for every received message.
+        let message = ekf_quat_message(EKF_ATTITUDE_VALID);
+        if SensorKind::of(&message).is_some_and(SensorKind::is_sbg) {
+            presence.seen(TIMEOUT_MS + 2);
+        }
+        assert!(!presence.is_absent());
+        assert!(!presence.check(TIMEOUT_MS + 3));
+    }
+
+    #[test]
+    fn masks_are_distinct() {
+        let all = SensorKind::ALL
+            .into_iter()
+            .fold(0, |mask, kind| mask | kind.mask());
+        assert_eq!(all.count_ones() as usize, SensorKind::COUNT);
+    }
+}
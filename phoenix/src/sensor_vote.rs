@@ -0,0 +1,182 @@
+//! Voting between redundant sensors measuring the same quantity, e.g. the onboard baro and the
+//! SBG air data pressure, so that a single failed sensor can't drive recovery decisions.
+//!
+//! With three or more valid readings the median is taken and every reading further than the
+//! tolerance from it is excluded. With two, they must agree within the tolerance; if they don't
+//! there is no way to tell which one is wrong, so the disagreement is flagged and the source
+//! listed first is used. A single valid reading is used as is.
+
+use defmt::warn;
+
+/// Result of a vote.
+#[derive(Clone, Copy, PartialEq, defmt::Format)]
+pub struct Vote {
+    /// Mean of the trusted readings, `None` if no source is valid.
+    pub value: Option<f32>,
+    /// Bit `i` is set if source `i` was used.
+    pub trusted: u8,
+    /// Set if a valid source was excluded or two sources disagreed.
+    pub disagreement: bool,
+}
+
+#[derive(Clone)]
+pub struct SensorVote<const N: usize> {
+    /// Largest difference between agreeing readings, in the unit of the readings.
+    tolerance: f32,
+    last: Vote,
+}
+
+impl<const N: usize> SensorVote<N> {
+    pub fn new(tolerance: f32) -> Self {
+        Self {
+            tolerance,
+            last: Vote {
+                value: None,
+                trusted: 0,
+                disagreement: false,
+            },
+        }
+    }
+
+    /// Votes over one reading per source, `None` for a source that has no valid reading.
+    pub fn vote(&mut self, readings: [Option<f32>; N]) -> Vote {
+        let valid = readings.iter().filter(|r| r.is_some()).count();
+        let vote = match valid {
+            0 => Vote {
+                value: None,
+                trusted: 0,
+                disagreement: false,
+            },
+            1 | 2 => self.vote_few(&readings),
+            _ => self.vote_median(&readings),
+        };
+        if vote.disagreement && !self.last.disagreement {
+            warn!(
+                "Redundant sensors disagree, trusted sources {:#b}",
+                vote.trusted
+            );
+        }
+        self.last = vote;
+        vote
+    }
+
+    /// The result of the last vote.
+    pub fn last(&self) -> Vote {
+        self.last
+    }
+
+    pub fn set_tolerance(&mut self, tolerance: f32) {
+        self.tolerance = tolerance;
+    }
+
+    fn vote_few(&self, readings: &[Option<f32>; N]) -> Vote {
+        let mut valid = readings
+            .iter()
+            .enumerate()
+            .filter_map(|(i, r)| r.map(|r| (i, r)));
+        let (first, a) = valid.next().unwrap();
+        match valid.next() {
+            Some((second, b)) if (a - b).abs() <= self.tolerance => Vote {
+                value: Some((a + b) / 2.0),
+                trusted: 1 << first | 1 << second,
+                disagreement: false,
+            },
+            Some(_) => Vote {
+                value: Some(a),
+                trusted: 1 << first,
+                disagreement: true,
+            },
+            None => Vote {
+                value: Some(a),
+                trusted: 1 << first,
+                disagreement: false,
+            },
+        }
+    }
+
+    fn vote_median(&self, readings: &[Option<f32>; N]) -> Vote {
+        let mut sorted = [0.0f32; N];
+        let mut count = 0;
+        for r in readings.iter().flatten() {
+            sorted[count] = *r;
+            count += 1;
+        }
+        let sorted = &mut sorted[..count];
+        sorted.sort_unstable_by(|a, b| a.total_cmp(b));
+        let median = if count % 2 == 1 {
+            sorted[count / 2]
+        } else {
+            (sorted[count / 2 - 1] + sorted[count / 2]) / 2.0
+        };
+
+        let mut trusted = 0;
+        let mut sum = 0.0;
+        let mut used = 0;
+        for (i, r) in readings.iter().enumerate() {
+            if let Some(r) = r {
+                if (r - median).abs() <= self.tolerance {
+                    trusted |= 1 << i;
+                    sum += r;
+                    used += 1;
+                }
+            }
+        }
+        if used == 0 {
+            // No two readings are within the tolerance of each other, the median is all we have.
+            return Vote {
+                value: Some(median),
+                trusted: 0,
+                disagreement: true,
+            };
+        }
+        Vote {
+            value: Some(sum / used as f32),
+            trusted,
+            disagreement: used < count,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pressures in kPa agree within 0.5 kPa.
+    const TOLERANCE: f32 = 0.5;
+
+    #[test]
+    fn outlier_is_excluded() {
+        let mut voter: SensorVote<3> = SensorVote::new(TOLERANCE);
+        let vote = voter.vote([Some(95.0), Some(80.0), Some(95.2)]);
+        assert_eq!(vote.trusted, 0b101);
+        assert!(vote.disagreement);
+        assert!((vote.value.unwrap() - 95.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn agreeing_sources_are_all_trusted() {
+        let mut voter: SensorVote<3> = SensorVote::new(TOLERANCE);
+        let vote = voter.vote([Some(95.0), Some(95.1), Some(94.9)]);
+        assert_eq!(vote.trusted, 0b111);
+        assert!(!vote.disagreement);
+        assert!((vote.value.unwrap() - 95.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn falls_back_to_a_single_valid_source() {
+        let mut voter: SensorVote<3> = SensorVote::new(TOLERANCE);
+        let vote = voter.vote([None, Some(95.0), None]);
+        assert_eq!(vote.trusted, 0b010);
+        assert!(!vote.disagreement);
+        assert_eq!(vote.value, Some(95.0));
+    }
+
+    #[test]
+    fn two_disagreeing_sources_are_flagged() {
+        let mut voter: SensorVote<2> = SensorVote::new(TOLERANCE);
+        let vote = voter.vote([Some(95.0), Some(90.0)]);
+        assert!(vote.disagreement);
+        assert_eq!(vote.value, Some(95.0));
+        assert!(voter.vote([None, None]).value.is_none());
+    }
+}
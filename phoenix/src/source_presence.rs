@@ -0,0 +1,110 @@
+//! Tracks whether a data source, such as the SBG, is producing data at all.
+//!
+//! A source that never comes up at boot looks the same downstream as one that is merely quiet, so
+//! anything that can fall back to an alternative needs an explicit absent state. The source is
+//! absent if nothing has been seen within `timeout_ms` of the first check, or since the last
+//! message.
+
+use defmt::{info, warn};
+
+#[derive(Clone)]
+pub struct SourcePresence {
+    /// Time without data after which the source is absent, in ms.
+    timeout_ms: u64,
+    /// Time of the first check, the timeout before any data counts from it.
+    started_ms: Option<u64>,
+    last_seen_ms: Option<u64>,
+    absent: bool,
+}
+
+impl SourcePresence {
+    pub fn new(timeout_ms: u64) -> Self {
+        Self {
+            timeout_ms,
+            started_ms: None,
+            last_seen_ms: None,
+            absent: false,
+        }
+    }
+
+    /// Records data from the source.
+    pub fn seen(&mut self, now_ms: u64) {
+        self.last_seen_ms = Some(now_ms);
+        if self.absent {
+            info!("Source is producing data again");
+            self.absent = false;
+        }
+    }
+
+    /// Re-evaluates the state and returns true if the source is absent. Call periodically.
+    pub fn check(&mut self, now_ms: u64) -> bool {
+        let since = match self.last_seen_ms {
+            Some(last_seen) => last_seen,
+            None => *self.started_ms.get_or_insert(now_ms),
+        };
+        let absent = now_ms.saturating_sub(since) > self.timeout_ms;
+        if absent && !self.absent {
+            warn!("Source absent, no data for {} ms", self.timeout_ms);
+        }
+        self.absent = absent;
+        absent
+    }
+
+    /// Time the source was last seen, in ms.
+    pub fn last_seen(&self) -> Option<u64> {
+        self.last_seen_ms
+    }
+
+    /// State as of the last call to [`Self::check`].
+    pub fn is_absent(&self) -> bool {
+        self.absent
+    }
+
+    pub fn set_timeout(&mut self, timeout_ms: u64) {
+        self.timeout_ms = timeout_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OrientationFallback, SensorVote};
+
+    const TIMEOUT_MS: u64 = 5000;
+
+    #[test]
+    fn never_seen_is_absent_after_timeout() {
+        let mut presence = SourcePresence::new(TIMEOUT_MS);
+        assert!(!presence.check(0));
+        assert!(!presence.check(TIMEOUT_MS));
+        assert!(presence.check(TIMEOUT_MS + 1));
+        presence.seen(TIMEOUT_MS + 2);
+        assert!(!presence.is_absent());
+    }
+
+    #[test]
+    fn lost_after_data_stops() {
+        let mut presence = SourcePresence::new(TIMEOUT_MS);
+        presence.seen(100);
+        assert!(!presence.check(100 + TIMEOUT_MS));
+        assert!(presence.check(101 + TIMEOUT_MS));
+    }
+
+    #[test]
+    fn baro_only_without_sbg() {
+        let mut sbg = SourcePresence::new(TIMEOUT_MS);
+        let mut pressure: SensorVote<2> = SensorVote::new(0.5);
+        let mut orientation = OrientationFallback::new(500);
+        let now = TIMEOUT_MS + 1;
+
+        sbg.check(0);
+        assert!(sbg.check(now));
+        // Altitude still comes from the baro alone.
+        let vote = pressure.vote([Some(95.0), None]);
+        assert_eq!(vote.value, Some(95.0));
+        assert_eq!(vote.trusted, 0b01);
+        assert!(!vote.disagreement);
+        // Without IMU data there is no orientation, so tilt triggers stand down.
+        assert!(!orientation.tilt_triggers_allowed(now));
+    }
+}
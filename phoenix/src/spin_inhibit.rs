@@ -0,0 +1,127 @@
+//! Recovery deployment inhibit based on the vehicle's spin rate.
+//!
+//! Deploying a parachute while the vehicle spins quickly can tangle or shred it. Once deployment
+//! is due, [`SpinInhibit::deploy_allowed`] holds it off while the gyro magnitude is above the
+//! threshold. The inhibit only ever delays deployment: after `max_delay_ms` it is allowed
+//! regardless of the spin rate.
+
+use defmt::{info, warn};
+
+#[derive(Clone)]
+pub struct SpinInhibit {
+    /// Spin rate above which deployment is held off, in rad/s.
+    threshold: f32,
+    /// Longest the inhibit may hold off a deployment, in ms.
+    max_delay_ms: u64,
+    /// Squared gyro magnitude of the latest sample.
+    rate_sq: f32,
+    /// Time deployment was first requested, in ms since boot.
+    requested_at_ms: Option<u64>,
+}
+
+impl SpinInhibit {
+    pub fn new(threshold: f32, max_delay_ms: u64) -> Self {
+        Self {
+            threshold,
+            max_delay_ms,
+            rate_sq: 0.0,
+            requested_at_ms: None,
+        }
+    }
+
+    /// Feeds the latest gyro sample in rad/s.
+    pub fn update(&mut self, gyro: [f32; 3]) {
+        self.rate_sq = gyro[0] * gyro[0] + gyro[1] * gyro[1] + gyro[2] * gyro[2];
+    }
+
+    /// Returns true if the latest spin rate is above the threshold.
+    pub fn is_spinning(&self) -> bool {
+        self.rate_sq > self.threshold * self.threshold
+    }
+
+    /// Called while a deployment is due. Returns true once the spin rate is below the threshold,
+    /// or once the backup timer started by the first call has run out.
+    pub fn deploy_allowed(&mut self, now_ms: u64) -> bool {
+        let requested_at = *self.requested_at_ms.get_or_insert(now_ms);
+        if !self.is_spinning() {
+            return true;
+        }
+        let waited = now_ms.saturating_sub(requested_at);
+        if waited >= self.max_delay_ms {
+            warn!(
+                "Spin rate still above limit after {} ms, deploying anyway",
+                waited
+            );
+            return true;
+        }
+        if waited == 0 {
+            info!("Spin rate above limit, holding off deployment");
+        }
+        false
+    }
+
+    /// Clears a pending deployment request, e.g. once the deployment has fired.
+    pub fn reset(&mut self) {
+        self.requested_at_ms = None;
+    }
+
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    pub fn get_threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    pub fn set_max_delay(&mut self, max_delay_ms: u64) {
+        self.max_delay_ms = max_delay_ms;
+    }
+
+    pub fn get_max_delay(&self) -> u64 {
+        self.max_delay_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLD: f32 = 10.0;
+    const MAX_DELAY_MS: u64 = 3000;
+
+    #[test]
+    fn deploys_immediately_when_not_spinning() {
+        let mut inhibit = SpinInhibit::new(THRESHOLD, MAX_DELAY_MS);
+        inhibit.update([1.0, 1.0, 1.0]);
+        assert!(inhibit.deploy_allowed(0));
+    }
+
+    #[test]
+    fn spin_delays_deployment_until_it_decays() {
+        let mut inhibit = SpinInhibit::new(THRESHOLD, MAX_DELAY_MS);
+        inhibit.update([0.0, 0.0, 15.0]);
+        assert!(!inhibit.deploy_allowed(100));
+        assert!(!inhibit.deploy_allowed(1000));
+        inhibit.update([0.0, 3.0, 4.0]);
+        assert!(inhibit.deploy_allowed(1100));
+    }
+
+    #[test]
+    fn backup_timer_deploys_while_still_spinning() {
+        let mut inhibit = SpinInhibit::new(THRESHOLD, MAX_DELAY_MS);
+        inhibit.update([8.0, 8.0, 0.0]);
+        assert!(!inhibit.deploy_allowed(500));
+        assert!(!inhibit.deploy_allowed(500 + MAX_DELAY_MS - 1));
+        assert!(inhibit.deploy_allowed(500 + MAX_DELAY_MS));
+    }
+
+    #[test]
+    fn reset_restarts_the_backup_timer() {
+        let mut inhibit = SpinInhibit::new(THRESHOLD, MAX_DELAY_MS);
+        inhibit.update([20.0, 0.0, 0.0]);
+        assert!(!inhibit.deploy_allowed(0));
+        inhibit.reset();
+        assert!(!inhibit.deploy_allowed(MAX_DELAY_MS));
+        assert!(inhibit.deploy_allowed(2 * MAX_DELAY_MS));
+    }
+}
@@ -0,0 +1,232 @@
+#![no_std]
+#![no_main]
+
+use chrono::NaiveDate;
+use core::cell::RefCell;
+use core::num::{NonZeroU16, NonZeroU8};
+use cortex_m::interrupt::Mutex;
+use fdcan::config::{DataBitTiming, NominalBitTiming};
+use messages::node::Node;
+use messages::sensor::{EkfQuat, SbgData, Sensor, SensorData};
+use messages::sensor_status::EkfStatus;
+use messages::{FormattedNaiveDateTime, Message};
+use panic_probe as _;
+use stm32h7xx_hal::gpio::Speed;
+use stm32h7xx_hal::pac;
+use stm32h7xx_hal::prelude::*;
+use stm32h7xx_hal::rcc::{self, rec};
+
+// The CAN managers and the DataManager are part of the firmware binary rather than the library,
+// so they are built into this test from their source.
+#[allow(dead_code)]
+#[path = "../src/communication.rs"]
+mod communication;
+#[allow(dead_code)]
+#[path = "../src/data_manager/mod.rs"]
+mod data_manager;
+
+/// The last message the data bus handed on to the radio, see the `send_gs` stand-in.
+static RECEIVED: Mutex<RefCell<Option<Message>>> = Mutex::new(RefCell::new(None));
+
+/// Stands in for the RTIC tasks the managers and the DataManager spawn.
+mod app {
+    use messages::Message;
+
+    pub mod send_gs {
+        use super::*;
+
+        pub fn spawn(m: Message) -> Result<(), Message> {
+            cortex_m::interrupt::free(|cs| crate::RECEIVED.borrow(cs).replace(Some(m)));
+            Ok(())
+        }
+    }
+
+    pub mod ground_command {
+        pub fn spawn(_command: phoenix::GroundCommand) -> Result<(), phoenix::GroundCommand> {
+            Ok(())
+        }
+    }
+
+    pub mod sleep_system {
+        pub fn spawn() -> Result<(), ()> {
+            Ok(())
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    0
+}
+
+const CAN_KERNEL_CLOCK_HZ: u32 = 32_000_000;
+/// Receive polls a test waits for its fragments, far longer than the peripheral takes to loop
+/// them back.
+const RECEIVE_POLLS: u32 = 100_000;
+
+/// A message serialized to a few dozen bytes, which one CAN FD frame would carry whole.
+fn ekf_quat_message() -> Message {
+    let timestamp = NaiveDate::from_ymd_opt(2001, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    Message::new(
+        FormattedNaiveDateTime(timestamp),
+        Node::TemperatureBoard,
+        Sensor::new(SensorData::SbgData(SbgData::EkfQuat(EkfQuat {
+            time_stamp: 123_456,
+            quaternion: Some([0.5, -0.5, 0.5, -0.5]),
+            euler_std_dev: Some([0.1, 0.2, 0.3]),
+            status: EkfStatus::new(0),
+        }))),
+    )
+}
+
+fn serialized<'a>(message: &Message, buf: &'a mut [u8; 256]) -> &'a [u8] {
+    postcard::to_slice(message, buf).unwrap()
+}
+
+/// CAN FD frame length that cuts `payload` into exactly three fragments.
+fn three_fragment_len(payload: &[u8]) -> usize {
+    (common_arm::FRAGMENT_HEADER_LEN..=phoenix::CAN_FD_MAX_PAYLOAD)
+        .map(phoenix::can_fd_len_at_most)
+        .find(|len| {
+            let Ok(mut fragmenter) = common_arm::Fragmenter::new(payload, *len) else {
+                return false;
+            };
+            let mut frame = [0u8; phoenix::CAN_FD_MAX_PAYLOAD];
+            core::iter::from_fn(|| fragmenter.next_frame(&mut frame)).count() == 3
+        })
+        .unwrap()
+}
+
+fn configure<I: fdcan::Instance>(
+    can: &mut fdcan::FdCan<I, fdcan::ConfigMode>,
+    bit_timing: NominalBitTiming,
+) {
+    can.set_nominal_bit_timing(bit_timing);
+    let config = can
+        .get_config()
+        .set_frame_transmit(fdcan::config::FrameTransmissionConfig::AllowFdCanAndBRS)
+        .set_tx_buffer_mode(fdcan::config::TxBufferMode::Fifo);
+    can.apply_config(config);
+}
+
+struct State {
+    can_command_manager: communication::CanCommandManager<fdcan::InternalLoopbackMode>,
+    can_data_manager: communication::CanDataManager<fdcan::InternalLoopbackMode>,
+    data_manager: data_manager::DataManager,
+}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+
+    #[init]
+    fn init() -> State {
+        let dp = pac::Peripherals::take().unwrap();
+
+        let pwrcfg = dp.PWR.constrain().freeze();
+        let rcc = dp.RCC.constrain();
+        let fdcan1_prec = communication::steal_fdcan_rec(&rcc);
+        let ccdr = rcc
+            .use_hse(48.MHz())
+            .sys_ck(200.MHz())
+            .pll1_strategy(rcc::PllConfigStrategy::Iterative)
+            .pll1_q_ck(CAN_KERNEL_CLOCK_HZ.Hz())
+            .freeze(pwrcfg, &dp.SYSCFG);
+        let fdcan2_prec = ccdr
+            .peripheral
+            .FDCAN
+            .kernel_clk_mux(rec::FdcanClkSel::Pll1Q);
+
+        let btr = NominalBitTiming {
+            prescaler: NonZeroU16::new(10).unwrap(),
+            seg1: NonZeroU8::new(13).unwrap(),
+            seg2: NonZeroU8::new(2).unwrap(),
+            sync_jump_width: NonZeroU8::new(1).unwrap(),
+        };
+        let data_bit_timing = DataBitTiming {
+            prescaler: NonZeroU8::new(10).unwrap(),
+            seg1: NonZeroU8::new(13).unwrap(),
+            seg2: NonZeroU8::new(2).unwrap(),
+            sync_jump_width: NonZeroU8::new(1).unwrap(),
+            transceiver_delay_compensation: false,
+        };
+        let bit_rate = communication::nominal_bit_rate(CAN_KERNEL_CLOCK_HZ, &btr);
+
+        let gpioa = dp.GPIOA.split(ccdr.peripheral.GPIOA);
+        let gpiob = dp.GPIOB.split(ccdr.peripheral.GPIOB);
+        let mut can_command = dp.FDCAN1.fdcan(
+            gpioa.pa12.into_alternate().speed(Speed::VeryHigh),
+            gpioa.pa11.into_alternate().speed(Speed::VeryHigh),
+            fdcan1_prec,
+        );
+        configure(&mut can_command, btr);
+        let mut can_data = dp.FDCAN2.fdcan(
+            gpiob.pb13.into_alternate().speed(Speed::VeryHigh),
+            gpiob.pb12.into_alternate().speed(Speed::VeryHigh),
+            fdcan2_prec,
+        );
+        configure(&mut can_data, btr);
+
+        // No ID filters are set, so every frame lands in FIFO0.
+        let fragment_len = three_fragment_len(serialized(&ekf_quat_message(), &mut [0; 256]));
+        State {
+            can_command_manager: communication::CanCommandManager::new_loopback(
+                can_command,
+                bit_rate,
+                0,
+                fragment_len,
+            ),
+            can_data_manager: communication::CanDataManager::new_loopback(
+                can_data,
+                bit_rate,
+                0,
+                data_bit_timing,
+                fragment_len,
+            ),
+            data_manager: data_manager::DataManager::new(),
+        }
+    }
+
+    #[test]
+    fn data_bus_message_round_trips_in_three_fragments(state: &mut State) {
+        let manager = &mut state.can_data_manager;
+        let sent = ekf_quat_message();
+        manager.send_message(sent.clone()).unwrap();
+
+        let mut received = None;
+        for _ in 0..RECEIVE_POLLS {
+            manager.process_data(false).unwrap();
+            received = cortex_m::interrupt::free(|cs| RECEIVED.borrow(cs).take());
+            if received.is_some() {
+                break;
+            }
+        }
+        let stats = manager.stats();
+        assert_eq!(stats.rx_frames, 3);
+        assert_eq!(stats.rx_messages, 1);
+        assert_eq!(
+            serialized(&received.unwrap(), &mut [0; 256]),
+            serialized(&sent, &mut [0; 256])
+        );
+    }
+
+    #[test]
+    fn command_bus_message_round_trips_in_three_fragments(state: &mut State) {
+        let manager = &mut state.can_command_manager;
+        manager.send_message(ekf_quat_message()).unwrap();
+
+        for _ in 0..RECEIVE_POLLS {
+            manager.process_data(&mut state.data_manager).unwrap();
+            if manager.stats().rx_messages > 0 {
+                break;
+            }
+        }
+        // Every fragment came back and the reassembled message decoded.
+        let stats = manager.stats();
+        assert_eq!(stats.rx_frames, 3);
+        assert_eq!(stats.rx_messages, 1);
+        assert_eq!(stats.decode_errors, 0);
+    }
+}
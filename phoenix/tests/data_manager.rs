@@ -0,0 +1,86 @@
+#![no_std]
+#![no_main]
+
+use chrono::NaiveDate;
+use messages::node::Node;
+use messages::sensor::{EkfQuat, SbgData, Sensor, SensorData};
+use messages::sensor_status::EkfStatus;
+use messages::state::{State, StateData};
+use messages::{FormattedNaiveDateTime, Message};
+use panic_probe as _;
+
+// The DataManager is part of the firmware binary rather than the library, so it is built into
+// this test from its source.
+#[allow(dead_code)]
+#[path = "../src/data_manager/mod.rs"]
+mod data_manager;
+
+/// Stands in for the RTIC tasks the DataManager spawns.
+mod app {
+    pub mod sleep_system {
+        pub fn spawn() -> Result<(), ()> {
+            Ok(())
+        }
+    }
+}
+
+fn message(data: impl Into<messages::Data>) -> Message {
+    let timestamp = NaiveDate::from_ymd_opt(2001, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    Message::new(
+        FormattedNaiveDateTime(timestamp),
+        Node::TemperatureBoard,
+        data,
+    )
+}
+
+fn ekf_quat_message() -> Message {
+    message(Sensor::new(SensorData::SbgData(SbgData::EkfQuat(
+        EkfQuat {
+            time_stamp: 0,
+            quaternion: Some([1.0, 0.0, 0.0, 0.0]),
+            euler_std_dev: None,
+            status: EkfStatus::new(0),
+        },
+    ))))
+}
+
+#[defmt_test::tests]
+mod tests {
+    use super::*;
+    use crate::data_manager::{DataManager, DataSnapshot, SNAPSHOT_SCHEMA};
+
+    const BLOB_LEN: usize = 1024;
+
+    #[test]
+    fn state_blob_round_trips() {
+        let mut data_manager = DataManager::new();
+        data_manager.handle_data(ekf_quat_message(), 10);
+        data_manager.handle_data(message(State::new(StateData::Initializing)), 20);
+        data_manager.record_baro_altitude(120.5, 30);
+
+        let mut blob = [0u8; BLOB_LEN];
+        let len = data_manager.serialize_state(&mut blob).unwrap();
+        let mut scratch = [0u8; BLOB_LEN];
+        let decoded: DataSnapshot = SNAPSHOT_SCHEMA.decode(&blob[..len], &mut scratch).unwrap();
+
+        assert!(decoded.ekf_quat.is_some());
+        assert!(decoded.imu_1.is_none());
+        assert!(matches!(decoded.state, Some(StateData::Initializing)));
+        assert_eq!(decoded.max_altitude, Some(120.5));
+        // Status 0 from `ekf_quat_message`.
+        assert_eq!(
+            decoded.ekf_solution_mode,
+            Some(phoenix::EkfSolutionMode::Uninitialized)
+        );
+        assert!(decoded.sensor_time_us[phoenix::SensorKind::EkfQuat as usize].is_some());
+        // Nothing is lost on the way: the decoded snapshot encodes to the same blob.
+        let mut again = [0u8; BLOB_LEN];
+        assert_eq!(
+            SNAPSHOT_SCHEMA.encode(&decoded, &mut again).unwrap(),
+            &blob[..len]
+        );
+    }
+}
@@ -0,0 +1,89 @@
+#![no_std]
+#![no_main]
+
+use panic_probe as _;
+
+// The MadgwickService is part of the firmware binary rather than the library, so it is built into
+// this test from its source.
+#[allow(dead_code)]
+#[path = "../src/madgwick_service.rs"]
+mod madgwick_service;
+
+#[defmt_test::tests]
+mod tests {
+    use crate::madgwick_service::MadgwickService;
+    use phoenix::{ImuSource, STANDARD_GRAVITY};
+
+    const IMU_PERIOD_US: u32 = 10_000; // 100 Hz
+    const DECIMATION: u16 = 10;
+    const SAMPLES: u32 = 95;
+
+    /// Feeds `SAMPLES` samples of a board at rest and returns a bitmask of those that produced an
+    /// orientation, bit i for the i-th sample.
+    fn kept_samples(madgwick: &mut MadgwickService) -> u128 {
+        let mut kept = 0;
+        for i in 0..SAMPLES {
+            let time_stamp_us = i * IMU_PERIOD_US;
+            let output = madgwick.process_imu_sample(
+                ImuSource::Primary,
+                Some([0.0, 0.0, STANDARD_GRAVITY]),
+                Some([0.0, 0.0, 0.0]),
+                Some(time_stamp_us),
+                (time_stamp_us / 1000) as u64,
+            );
+            if output.is_some() {
+                kept |= 1 << i;
+            }
+        }
+        kept
+    }
+
+    #[test]
+    fn keeps_every_nth_sample() {
+        let mut madgwick = MadgwickService::new();
+        madgwick.set_output_decimation(DECIMATION);
+        let kept = kept_samples(&mut madgwick);
+
+        // The 10th, 20th, ... sample, the partial last group emits nothing.
+        let expected = (0..SAMPLES / DECIMATION as u32).fold(0u128, |mask, n| {
+            mask | 1 << ((n + 1) * DECIMATION as u32 - 1)
+        });
+        assert_eq!(kept, expected);
+        // 100 Hz IMU in, 10 Hz orientation out.
+        let span_us = SAMPLES * IMU_PERIOD_US;
+        assert_eq!(
+            kept.count_ones(),
+            span_us / (IMU_PERIOD_US * DECIMATION as u32)
+        );
+    }
+
+    #[test]
+    fn no_decimation_keeps_every_sample() {
+        let mut madgwick = MadgwickService::new();
+        madgwick.set_output_decimation(0);
+        assert_eq!(madgwick.get_output_decimation(), 1);
+        assert_eq!(kept_samples(&mut madgwick).count_ones(), SAMPLES);
+    }
+
+    #[test]
+    fn reset_restarts_the_count() {
+        let mut madgwick = MadgwickService::new();
+        madgwick.set_output_decimation(DECIMATION);
+        for i in 0..DECIMATION as u32 - 1 {
+            let time_stamp_us = i * IMU_PERIOD_US;
+            assert!(madgwick
+                .process_imu_sample(
+                    ImuSource::Primary,
+                    Some([0.0, 0.0, STANDARD_GRAVITY]),
+                    Some([0.0, 0.0, 0.0]),
+                    Some(time_stamp_us),
+                    (time_stamp_us / 1000) as u64,
+                )
+                .is_none());
+        }
+        madgwick.reset();
+        // A whole group again before the next output.
+        let kept = kept_samples(&mut madgwick);
+        assert_eq!(kept.trailing_zeros(), DECIMATION as u32 - 1);
+    }
+}